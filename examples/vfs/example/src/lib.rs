@@ -85,10 +85,11 @@ const HOST_ENV: VirtualEnvConstState = VirtualEnvConstState {
 
 plug_env!(@const, HostEnvTy, self);
 
-const FILE_COUNT: usize = 10;
+const FILE_COUNT: usize = FilesTy::FLAT_LEN;
 
+#[const_files]
 #[const_struct]
-const FILES: VFSConstNormalFiles<WasiConstFile<&'static str>, { FILE_COUNT }> = ConstFiles!([
+const FILES: VFSConstNormalFiles<WasiConstFile<&'static str>, _> = ConstFiles!([
     ("/root", [("root.txt", WasiConstFile::new("This is root"))]),
     (
         ".",
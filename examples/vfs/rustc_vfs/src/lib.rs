@@ -20,10 +20,11 @@ export!(Main);
 
 import_wasm!(rustc_opt);
 
-const FILE_COUNT: usize = 10;
+const FILE_COUNT: usize = FilesTy::FLAT_LEN;
 
+#[const_files]
 #[const_struct]
-const FILES: VFSConstNormalFiles<WasiConstFile<&'static str>, { FILE_COUNT }> = ConstFiles!([
+const FILES: VFSConstNormalFiles<WasiConstFile<&'static str>, _> = ConstFiles!([
     ("/root", [("root.txt", WasiConstFile::new("This is root"))]),
     (
         ".",
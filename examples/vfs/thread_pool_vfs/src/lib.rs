@@ -1,6 +1,6 @@
 use const_struct::const_struct;
 use wasi_virt_layer::{
-    file::{VFSConstNormalFiles, WasiConstFile},
+    file::{VFSConstNormalFiles, VFSConstNormalFilesLen, WasiConstFile},
     prelude::*,
 };
 
@@ -23,10 +23,11 @@ export!(Starter);
 
 import_wasm!(test_pool_thread);
 
-const FILE_COUNT: usize = 10;
+const FILE_COUNT: usize = FilesTy::FLAT_LEN;
 
+#[const_files]
 #[const_struct]
-const FILES: VFSConstNormalFiles<WasiConstFile<&'static str>, { FILE_COUNT }> = ConstFiles!([
+const FILES: VFSConstNormalFiles<WasiConstFile<&'static str>, _> = ConstFiles!([
     ("/root", [("root.txt", WasiConstFile::new("This is root"))]),
     (
         ".",
@@ -44,13 +44,13 @@ export!(Starter);
 
 import_wasm!(test_threads);
 
-const FILE_COUNT: usize = 5;
+const FILE_COUNT: usize = FilesTy::FLAT_LEN;
 
 type F = WasiConstFile<&'static str>;
-type NormalFILES = VFSConstNormalFiles<F, { FILE_COUNT }>;
 
+#[const_files]
 #[const_struct]
-const FILES: NormalFILES = ConstFiles!([(
+const FILES: VFSConstNormalFiles<F, _> = ConstFiles!([(
     ".",
     [
         ("hey", F::new("Hey!")),
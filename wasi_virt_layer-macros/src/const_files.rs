@@ -0,0 +1,139 @@
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{
+    Expr, GenericArgument, ItemConst, LitStr, PathArguments, Token, Type, parse::Parser,
+    punctuated::Punctuated, spanned::Spanned,
+};
+
+use crate::embed_dir;
+
+fn error<T>(span: Span, message: &str) -> syn::Result<T> {
+    Err(syn::Error::new(span, message))
+}
+
+/// Splits the tokens by the commas which are not in a group.
+fn split_commas(tokens: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut parts = vec![Vec::new()];
+    for token in tokens {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => parts.push(Vec::new()),
+            token => parts.last_mut().unwrap().push(token),
+        }
+    }
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// Number of inodes of one `(name, file_or_dir)` entry,
+/// the same as `ConstFiles!` counts them.
+fn count_entry(entry: &[TokenTree]) -> syn::Result<usize> {
+    let [TokenTree::Group(group)] = entry else {
+        return error(
+            entry[0].span(),
+            "expected `(name, file)`, `(name, [..])` or `(name, @symlink target)`",
+        );
+    };
+    if group.delimiter() != Delimiter::Parenthesis {
+        return error(group.span(), "expected `(name, ..)`");
+    }
+
+    // the name is a literal or an expression before the first comma,
+    // the file may have more commas in it
+    let tokens = group.stream().into_iter().collect::<Vec<_>>();
+    let Some(comma) = tokens
+        .iter()
+        .position(|token| matches!(token, TokenTree::Punct(punct) if punct.as_char() == ','))
+    else {
+        return error(group.span(), "expected `(name, ..)`");
+    };
+
+    match &tokens[comma + 1..] {
+        [TokenTree::Group(dir)] | [TokenTree::Group(dir), TokenTree::Punct(_)]
+            if dir.delimiter() == Delimiter::Bracket =>
+        {
+            count_dir(dir.stream()).map(|count| count + 1)
+        }
+        [] => error(group.span(), "expected a file after the name"),
+        _ => Ok(1),
+    }
+}
+
+/// Number of inodes in the children of a directory.
+fn count_dir(tokens: TokenStream) -> syn::Result<usize> {
+    split_commas(tokens)
+        .iter()
+        .map(|entry| count_entry(entry))
+        .sum()
+}
+
+/// `FLAT_LEN` of a `ConstFiles!([..])` or `embed_dir!("host", "guest")`.
+fn flat_len(expr: &Expr) -> syn::Result<usize> {
+    let Expr::Macro(expr) = expr else {
+        return error(expr.span(), "expected `ConstFiles!` or `embed_dir!`");
+    };
+    let mac = &expr.mac;
+    let name = mac
+        .path
+        .segments
+        .last()
+        .map(|segment| segment.ident.to_string());
+
+    match name.as_deref() {
+        Some("ConstFiles") => match split_commas(mac.tokens.clone()).as_slice() {
+            [root] => match root.as_slice() {
+                [TokenTree::Group(group)] if group.delimiter() == Delimiter::Bracket => {
+                    count_dir(group.stream())
+                }
+                _ => error(mac.span(), "expected `ConstFiles!([..])`"),
+            },
+            _ => error(mac.span(), "expected `ConstFiles!([..])`"),
+        },
+        Some("embed_dir") => {
+            let args =
+                Punctuated::<LitStr, Token![,]>::parse_terminated.parse2(mac.tokens.clone())?;
+            match args.first() {
+                Some(host) if args.len() == 2 => embed_dir::len(host),
+                _ => error(mac.span(), "expected `embed_dir!(\"host\", \"guest\")`"),
+            }
+        }
+        _ => error(mac.span(), "expected `ConstFiles!` or `embed_dir!`"),
+    }
+}
+
+/// Replaces `_` in the type of the const by `FLAT_LEN`.
+pub fn const_files(attr: TokenStream, item: TokenStream) -> syn::Result<TokenStream> {
+    if let Some(token) = attr.into_iter().next() {
+        return error(token.span(), "`#[const_files]` takes no arguments");
+    }
+
+    let mut item = syn::parse2::<ItemConst>(item)?;
+    let len = flat_len(&item.expr)?;
+
+    let Type::Path(ty) = item.ty.as_mut() else {
+        return error(item.ident.span(), "expected `VFSConstNormalFiles<File, _>`");
+    };
+    let Some(PathArguments::AngleBracketed(args)) = ty
+        .path
+        .segments
+        .last_mut()
+        .map(|segment| &mut segment.arguments)
+    else {
+        return error(item.ident.span(), "expected `VFSConstNormalFiles<File, _>`");
+    };
+
+    let mut replaced = false;
+    for arg in args.args.iter_mut() {
+        if let GenericArgument::Type(Type::Infer(_)) = arg {
+            *arg = GenericArgument::Const(syn::parse_quote!(#len));
+            replaced = true;
+        }
+    }
+    if !replaced {
+        return error(
+            item.ident.span(),
+            "write `_` for `FLAT_LEN`, e.g. `VFSConstNormalFiles<File, _>`",
+        );
+    }
+
+    Ok(item.into_token_stream())
+}
@@ -0,0 +1,190 @@
+use std::path::{Path, PathBuf};
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::quote;
+use syn::{LitStr, Token, parse::Parser};
+
+/// A host directory walked at compile time.
+enum Entry {
+    /// name and host path
+    File(String, PathBuf),
+    Dir(String, Vec<Entry>),
+    Symlink(String, String),
+}
+
+impl Entry {
+    /// Number of inodes, the same as `ConstFiles!` counts them.
+    fn count(&self) -> usize {
+        match self {
+            Self::File(..) | Self::Symlink(..) => 1,
+            Self::Dir(_, children) => 1 + children.iter().map(Entry::count).sum::<usize>(),
+        }
+    }
+
+    /// Every file is dated `mtim`, in nanoseconds.
+    fn to_tokens(&self, krate: &TokenStream, mtim: u64) -> TokenStream {
+        match self {
+            Self::File(name, path) => {
+                let path = path.to_string_lossy();
+                quote! {
+                    (#name, {
+                        #krate::file::WasiConstFile::new(include_bytes!(#path) as &'static [u8])
+                            .with_mtim(#mtim)
+                    })
+                }
+            }
+            Self::Dir(name, children) => {
+                let children = children.iter().map(|child| child.to_tokens(krate, mtim));
+                quote! { (#name, [#(#children),*]) }
+            }
+            Self::Symlink(name, target) => quote! { (#name, @symlink #target) },
+        }
+    }
+}
+
+enum Input {
+    Files { host: LitStr, guest: LitStr },
+    Len { host: LitStr },
+}
+
+fn parse(input: TokenStream) -> syn::Result<(TokenStream, Input)> {
+    let parser = |input: syn::parse::ParseStream| {
+        let mut krate = TokenStream::new();
+        while !input.peek(Token![,]) {
+            krate.extend([input.parse::<TokenTree>()?]);
+        }
+        input.parse::<Token![,]>()?;
+
+        let parsed = if input.peek(Token![@]) {
+            input.parse::<Token![@]>()?;
+            let ident = input.parse::<syn::Ident>()?;
+            if ident != "len" {
+                return Err(syn::Error::new(ident.span(), "expected `@len`"));
+            }
+            input.parse::<Token![,]>()?;
+            Input::Len {
+                host: input.parse()?,
+            }
+        } else {
+            let host = input.parse()?;
+            input.parse::<Token![,]>()?;
+            Input::Files {
+                host,
+                guest: input.parse()?,
+            }
+        };
+        input.parse::<Option<Token![,]>>()?;
+
+        Ok((krate, parsed))
+    };
+
+    parser.parse2(input)
+}
+
+/// Resolves the host path relative to the crate using the macro.
+pub fn host_path(host: &LitStr) -> syn::Result<PathBuf> {
+    let path = Path::new(&host.value()).to_path_buf();
+    let path = if path.is_absolute() {
+        path
+    } else {
+        let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+            .map_err(|_| syn::Error::new(host.span(), "CARGO_MANIFEST_DIR is not set"))?;
+        Path::new(&manifest_dir).join(path)
+    };
+
+    path.canonicalize()
+        .map_err(|err| syn::Error::new(host.span(), format!("{}: {err}", path.display())))
+}
+
+/// Walks `path` sorted by name.
+/// Empty directories are skipped, `ConstFiles!` cannot hold them.
+fn walk(path: &Path, span: Span) -> syn::Result<Vec<Entry>> {
+    // the modification time of a directory changes with its entries
+    #[cfg(feature = "tracked_path")]
+    proc_macro::tracked::path(path);
+
+    let error = |err: std::io::Error| syn::Error::new(span, format!("{}: {err}", path.display()));
+
+    let mut entries = std::fs::read_dir(path)
+        .map_err(error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(error)?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut children = Vec::new();
+    for entry in entries {
+        let path = entry.path();
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            return Err(syn::Error::new(
+                span,
+                format!("{}: file name is not UTF-8", path.display()),
+            ));
+        };
+
+        let file_type = entry.file_type().map_err(error)?;
+        if file_type.is_symlink() {
+            let target = std::fs::read_link(&path).map_err(error)?;
+            let Some(target) = target.to_str().map(str::to_string) else {
+                return Err(syn::Error::new(
+                    span,
+                    format!("{}: link target is not UTF-8", path.display()),
+                ));
+            };
+            children.push(Entry::Symlink(name, target));
+        } else if file_type.is_dir() {
+            let dir = walk(&path, span)?;
+            if !dir.is_empty() {
+                children.push(Entry::Dir(name, dir));
+            }
+        } else if file_type.is_file() {
+            children.push(Entry::File(name, path));
+        }
+    }
+
+    Ok(children)
+}
+
+/// Walks the host directory, which must not be empty.
+fn root(host: &LitStr) -> syn::Result<Vec<Entry>> {
+    let children = walk(&host_path(host)?, host.span())?;
+    if children.is_empty() {
+        return Err(syn::Error::new(host.span(), "the directory has no files"));
+    }
+
+    Ok(children)
+}
+
+/// `SOURCE_DATE_EPOCH` in nanoseconds, 0 if it is not set.
+/// The host modification times would make the build unreproducible.
+fn source_date_epoch(span: Span) -> syn::Result<u64> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .and_then(|epoch| epoch.checked_mul(1_000_000_000))
+            .ok_or_else(|| syn::Error::new(span, "SOURCE_DATE_EPOCH is not a number of seconds")),
+        Err(_) => Ok(0),
+    }
+}
+
+/// `FLAT_LEN` of the embedded host directory.
+pub fn len(host: &LitStr) -> syn::Result<usize> {
+    Ok(1 + root(host)?.iter().map(Entry::count).sum::<usize>())
+}
+
+pub fn embed_dir(input: TokenStream) -> syn::Result<TokenStream> {
+    let (krate, input) = parse(input)?;
+
+    Ok(match input {
+        Input::Len { host } => {
+            let len = len(&host)?;
+            quote! { #len }
+        }
+        Input::Files { host, guest } => {
+            let mtim = source_date_epoch(host.span())?;
+            let root = Entry::Dir(guest.value(), root(&host)?).to_tokens(&krate, mtim);
+            quote! { #krate::ConstFiles!([#root]) }
+        }
+    })
+}
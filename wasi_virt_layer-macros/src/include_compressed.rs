@@ -0,0 +1,91 @@
+use proc_macro2::{Literal, TokenStream, TokenTree};
+use quote::quote;
+use syn::{LitInt, LitStr, Token, parse::Parser};
+
+use crate::embed_dir::host_path;
+
+/// Default size of a block before compression.
+/// Reading a byte decompresses the whole block around it.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+fn parse(input: TokenStream) -> syn::Result<(TokenStream, LitStr, Option<LitInt>)> {
+    let parser = |input: syn::parse::ParseStream| {
+        let mut krate = TokenStream::new();
+        while !input.peek(Token![,]) {
+            krate.extend([input.parse::<TokenTree>()?]);
+        }
+        input.parse::<Token![,]>()?;
+
+        let path = input.parse()?;
+        let block_size = if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let block_size = input.parse()?;
+            input.parse::<Option<Token![,]>>()?;
+            Some(block_size)
+        } else {
+            None
+        };
+
+        Ok((krate, path, block_size))
+    };
+
+    parser.parse2(input)
+}
+
+/// Compresses each block by raw deflate.
+/// Returns the blocks and the end of each block,
+/// `None` if the compression does not make the file smaller.
+fn compress(bytes: &[u8], block_size: usize) -> Option<(Vec<u8>, Vec<usize>)> {
+    let mut data = Vec::new();
+    let mut blocks = Vec::new();
+
+    for block in bytes.chunks(block_size) {
+        data.extend(miniz_oxide::deflate::compress_to_vec(block, 9));
+        blocks.push(data.len());
+    }
+
+    (data.len() < bytes.len()).then_some((data, blocks))
+}
+
+pub fn include_compressed(input: TokenStream) -> syn::Result<TokenStream> {
+    let (krate, path, block_size) = parse(input)?;
+
+    let block_size = match block_size {
+        Some(block_size) => match block_size.base10_parse::<usize>()? {
+            0 => {
+                return Err(syn::Error::new(
+                    block_size.span(),
+                    "block size must not be 0",
+                ));
+            }
+            block_size => block_size,
+        },
+        None => BLOCK_SIZE,
+    };
+
+    let host = host_path(&path)?;
+    let bytes = std::fs::read(&host)
+        .map_err(|err| syn::Error::new(path.span(), format!("{}: {err}", host.display())))?;
+    let size = bytes.len();
+
+    // tracks the file to rebuild when it is changed
+    let host = host.to_string_lossy();
+    let file = match compress(&bytes, block_size) {
+        Some((data, blocks)) => {
+            let data = Literal::byte_string(&data);
+            quote! {
+                #krate::file::WasiConstCompressedFile::new(#data, &[#(#blocks),*], #block_size, #size)
+            }
+        }
+        None => {
+            let data = Literal::byte_string(&bytes);
+            quote! { #krate::file::WasiConstCompressedFile::raw(#data) }
+        }
+    };
+
+    Ok(quote! {
+        {
+            const _: &[u8] = include_bytes!(#host);
+            #file
+        }
+    })
+}
@@ -0,0 +1,37 @@
+//! Procedural macros of `wasi_virt_layer`.
+//! Use them through the re-exports of `wasi_virt_layer`,
+//! which pass its `$crate` path as the first argument.
+#![cfg_attr(feature = "tracked_path", feature(proc_macro_tracked_path))]
+
+use proc_macro::TokenStream;
+
+mod const_files;
+mod embed_dir;
+mod include_compressed;
+
+/// `embed_dir!($crate, "host/dir", "/guest/name")`
+/// or `embed_dir!($crate, @len, "host/dir")`
+#[proc_macro]
+pub fn embed_dir(input: TokenStream) -> TokenStream {
+    embed_dir::embed_dir(input.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// `include_compressed!($crate, "host/file")`
+/// or `include_compressed!($crate, "host/file", block_size)`
+#[proc_macro]
+pub fn include_compressed(input: TokenStream) -> TokenStream {
+    include_compressed::include_compressed(input.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// `#[const_files] const FILES: VFSConstNormalFiles<File, _> = ConstFiles!([..]);`
+/// Place it above `#[const_struct]`.
+#[proc_macro_attribute]
+pub fn const_files(attr: TokenStream, item: TokenStream) -> TokenStream {
+    const_files::const_files(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -27,12 +27,12 @@ impl WasmAccess for __self {
     }
 
     #[cfg(not(feature = "multi_memory"))]
-    fn memory_director<T>(ptr: *const T) -> *const T {
+    fn memory_director<T>(ptr: *const T, _len: usize) -> *const T {
         ptr
     }
 
     #[cfg(not(feature = "multi_memory"))]
-    fn memory_director_mut<T>(ptr: *mut T) -> *mut T {
+    fn memory_director_mut<T>(ptr: *mut T, _len: usize) -> *mut T {
         ptr
     }
 
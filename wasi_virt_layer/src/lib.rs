@@ -10,6 +10,8 @@ mod __self;
 mod debug;
 mod initializer;
 pub mod memory;
+#[cfg(any(feature = "mock", all(test, feature = "alloc")))]
+pub mod mock;
 #[cfg(all(
     target_arch = "wasm32",
     feature = "threads",
@@ -27,13 +29,21 @@ pub mod wasip1;
 #[cfg(feature = "alloc")]
 extern crate alloc;
 
+// the tests of the `alloc` only build use the thread local memory of `mock`
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+pub use wasi_virt_layer_macros::const_files;
+
 pub mod prelude {
     pub use crate::memory::WasmAccess;
     #[cfg(feature = "threads")]
     pub use crate::plug_thread;
     pub use crate::wasi::env::{VirtualEnv, VirtualEnvConstState};
     pub use crate::wasi::file::constant::vfs::Wasip1ConstVFS;
-    pub use crate::{ConstFiles, import_wasm, plug_env, plug_fs, plug_process};
+    pub use crate::{
+        ConstFiles, const_files, embed_dir, import_wasm, plug_env, plug_fs, plug_process,
+    };
 }
 
 #[cfg(feature = "threads")]
@@ -46,13 +56,47 @@ pub mod thread {
 pub mod file {
     pub use crate::wasi::file::{
         FilestatWithoutDevice, Wasip1FileSystem, Wasip1FileTrait, Wasip1LFS,
+        clock::{CounterClock, DefaultClock, Wasip1Clock},
         constant::{
-            lfs::VFSConstNormalLFS,
-            lfs_raw::{VFSConstNormalFiles, WasiConstFile},
-            vfs::Wasip1ConstVFS,
+            lfs::{VFSConstNormalInodeKind, VFSConstNormalLFS},
+            lfs_raw::{VFSConstNormalFiles, VFSConstNormalFilesLen, WasiConstFile},
+            vfs::{Wasip1ConstVFS, Wasip1VFS},
+        },
+        dev::lfs::{DefaultRandom, VFSDevLFS, VFSDevNode, Wasip1Random},
+        dirent::{
+            DIRCOOKIE_DOT, DIRCOOKIE_DOTDOT, DIRCOOKIE_ENTRIES, DIRENT_SIZE, DirentReader,
+            DirentWriter,
         },
+        fd_table::{ConstFdTable, FdEntry, FdTable},
+        mount::lfs::VFSMountLFS,
         stdio::DefaultStdIO,
     };
+
+    #[cfg(feature = "compress")]
+    pub use crate::wasi::file::constant::compressed::WasiConstCompressedFile;
+
+    #[cfg(feature = "alloc")]
+    pub use crate::{
+        transporter::Wasip1Transporter,
+        wasi::file::{
+            boxed::lfs::{DynWasmAccess, VFSDynAdapter, VFSDynLFS, Wasip1DynLFS, Wasip1DynMemory},
+            constant::dynamic::{WasiDynamicContent, WasiDynamicFile},
+            fd_table::VecFdTable,
+            heap::lfs::VFSHeapLFS,
+            host::{fs::HostFS, lfs::VFSHostLFS},
+            overlay::lfs::VFSOverlayLFS,
+            quota::lfs::{VFSQuotaLFS, VFSQuotaLimits, VFSQuotaUsage},
+            safe::{
+                fs::{Wasip1DirEntry, Wasip1OpenOptions, Wasip1SafeFS},
+                lfs::VFSSafeLFS,
+            },
+            snapshot::{
+                SNAPSHOT_MAGIC, SNAPSHOT_VERSION, SnapshotReader, SnapshotWriter, Wasip1Snapshot,
+            },
+            tar::lfs::VFSTarLFS,
+            write_back::lfs::{VFSWriteBackLFS, VFSWriteBackOp},
+        },
+    };
 }
 
 pub mod process {
@@ -63,8 +107,11 @@ pub mod __private {
     #[cfg(not(target_os = "wasi"))]
     pub use super::wasip1;
     pub use crate::__self::__self;
+    #[cfg(feature = "alloc")]
+    pub use alloc::{string::String, vec::Vec};
     pub use const_for::const_for;
     pub use paste;
+    pub use wasi_virt_layer_macros::{embed_dir, include_compressed};
     #[cfg(target_os = "wasi")]
     pub use wasip1;
 
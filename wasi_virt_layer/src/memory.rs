@@ -152,7 +152,7 @@ macro_rules! __memory_director_wasm_access {
     ($name:ident) => {
         $crate::__private::paste::paste! {
             #[inline(always)]
-            fn memory_director<T>(ptr: *const T) -> *const T {
+            fn memory_director<T>(ptr: *const T, _len: usize) -> *const T {
                 #[cfg(not(target_os = "wasi"))]
                 unimplemented!("this is not supported on this architecture");
 
@@ -163,7 +163,7 @@ macro_rules! __memory_director_wasm_access {
             }
 
             #[inline(always)]
-            fn memory_director_mut<T>(ptr: *mut T) -> *mut T {
+            fn memory_director_mut<T>(ptr: *mut T, _len: usize) -> *mut T {
                 #[cfg(not(target_os = "wasi"))]
                 unimplemented!("this is not supported on this architecture");
 
@@ -414,11 +414,13 @@ pub trait WasmAccess: Copy {
         buff
     }
 
+    /// The pointer the `len` values at `ptr` in the memory of the guest
+    /// are accessed through.
     #[cfg(not(feature = "multi_memory"))]
-    fn memory_director<T>(ptr: *const T) -> *const T;
+    fn memory_director<T>(ptr: *const T, len: usize) -> *const T;
 
     #[cfg(not(feature = "multi_memory"))]
-    fn memory_director_mut<T>(ptr: *mut T) -> *mut T;
+    fn memory_director_mut<T>(ptr: *mut T, len: usize) -> *mut T;
 
     /// wrapping wasm's _start function
     /// By default in Rust code, when _start is called,
@@ -493,6 +495,12 @@ impl<'a, Wasm: WasmAccess> WasmPathAccess<'a, Wasm> {
         let path = self.path;
         WasmPathComponents { path }
     }
+
+    /// A trailing `/` requires the path to resolve to a directory.
+    #[inline(always)]
+    pub fn ends_with_separator(&self) -> bool {
+        self.path.len > 0 && self.path.get(self.path.len - 1) == b'/'
+    }
 }
 
 pub struct WasmPathComponents<'a, Wasm: WasmAccess> {
@@ -531,6 +539,25 @@ impl<'a, Wasm: WasmAccess> WasmPathComponent<'a, Wasm> {
     }
 }
 
+impl<'a, Wasm: WasmAccess> WasmPathComponents<'a, Wasm> {
+    /// The part of the path which is not iterated yet.
+    #[inline(always)]
+    pub fn as_raw(&self) -> (*const u8, usize) {
+        (self.path.ptr, self.path.len)
+    }
+
+    /// Advances past `from` bytes and the separators after them.
+    fn skip_separators(&mut self, from: usize) {
+        let mut end = from;
+        while end < self.path.len && self.path.get(end) == b'/' {
+            end += 1;
+        }
+
+        self.path.ptr = unsafe { self.path.ptr.add(end) };
+        self.path.len -= end;
+    }
+}
+
 impl<'a, Wasm: WasmAccess> Iterator for WasmPathComponents<'a, Wasm> {
     type Item = WasmPathComponent<'a, Wasm>;
 
@@ -539,81 +566,82 @@ impl<'a, Wasm: WasmAccess> Iterator for WasmPathComponents<'a, Wasm> {
             return None;
         }
 
+        // repeated separators are one separator,
+        // and the ones after a component are skipped with it
         if self.path.get(0) == b'/' {
-            let mut index = 1;
-            while index < self.path.len && self.path.get(index) == b'/' {
-                index += 1;
-            }
-
-            self.path.ptr = unsafe { self.path.ptr.add(index) };
-            self.path.len -= index;
+            self.skip_separators(1);
             return Some(WasmPathComponent::RootDir);
         }
 
-        if self.path.get(0) == b'.' {
-            if self.path.len == 1 {
-                self.path.len = 0;
-                return Some(WasmPathComponent::CurDir);
-            }
+        let mut end = 0;
+        while end < self.path.len && self.path.get(end) != b'/' {
+            end += 1;
+        }
 
-            let second = self.path.get(1);
+        let component = WasmArrayAccess::new(self.path.ptr, end);
+        self.skip_separators(end);
 
-            if second == b'/' {
-                let mut index = 2;
-                while index < self.path.len && self.path.get(index) == b'/' {
-                    index += 1;
-                }
-                self.path.ptr = unsafe { self.path.ptr.add(index) };
-                self.path.len -= index;
-                return Some(WasmPathComponent::CurDir);
-            } else if second == b'.' {
-                if self.path.len == 2 {
-                    self.path.len = 0;
-                    return Some(WasmPathComponent::ParentDir);
-                }
+        Some(match (end, component.get(0), component.get(end - 1)) {
+            (1, b'.', _) => WasmPathComponent::CurDir,
+            (2, b'.', b'.') => WasmPathComponent::ParentDir,
+            _ => WasmPathComponent::Normal(component),
+        })
+    }
+}
 
-                let third = self.path.get(2);
-                if third == b'/' {
-                    let mut index = 3;
-                    while index < self.path.len && self.path.get(index) == b'/' {
-                        index += 1;
-                    }
-                    self.path.ptr = unsafe { self.path.ptr.add(index) };
-                    self.path.len -= index;
-                    return Some(WasmPathComponent::ParentDir);
-                }
+/// [`WasmAccess`] over the memory of this module itself,
+/// where a pointer is an ordinary pointer of ours.
+///
+/// A file system uses it to call another one with buffers it owns,
+/// e.g. a layer reading a file of its lower layer into a `Vec`.
+/// It never touches the memory of a guest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct HostMemory;
 
-                let mut end = 3;
-                while end < self.path.len && self.path.get(end) != b'/' {
-                    end += 1;
-                }
+// the pointers are ours, like the guest ones of the other impls
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+impl WasmAccess for HostMemory {
+    const NAME: &'static str = "HostMemory";
 
-                let component = WasmArrayAccess::new(self.path.ptr, end);
+    fn memcpy<T>(offset: *mut T, data: &[T]) {
+        unsafe {
+            core::ptr::copy_nonoverlapping(data.as_ptr(), offset, data.len());
+        }
+    }
 
-                while end < self.path.len && self.path.get(end) == b'/' {
-                    end += 1;
-                }
-                self.path.ptr = unsafe { self.path.ptr.add(end) };
-                self.path.len -= end;
-                return Some(WasmPathComponent::Normal(component));
-            }
-        } else {
-            let mut end = 0;
-            while end < self.path.len && self.path.get(end) != b'/' {
-                end += 1;
-            }
+    fn store_le<T>(offset: *mut T, value: T) {
+        unsafe { core::ptr::write(offset, value) };
+    }
 
-            let component = WasmArrayAccess::new(self.path.ptr, end);
+    fn load_le<T: core::fmt::Debug + Copy>(offset: *const T) -> T {
+        unsafe { core::ptr::read(offset) }
+    }
 
-            while end < self.path.len && self.path.get(end) == b'/' {
-                end += 1;
-            }
-            self.path.ptr = unsafe { self.path.ptr.add(end) };
-            self.path.len -= end;
-            return Some(WasmPathComponent::Normal(component));
-        }
+    #[inline(always)]
+    fn _main() -> wasip1::Errno {
+        wasip1::ERRNO_SUCCESS
+    }
+
+    #[inline(always)]
+    fn _reset() {}
+
+    #[inline(always)]
+    fn _start() {}
 
-        None
+    #[cfg(not(feature = "multi_memory"))]
+    fn memory_director<T>(ptr: *const T, _len: usize) -> *const T {
+        ptr
+    }
+
+    #[cfg(not(feature = "multi_memory"))]
+    fn memory_director_mut<T>(ptr: *mut T, _len: usize) -> *mut T {
+        ptr
+    }
+
+    fn memcpy_to<T>(offset: &mut [T], src: *const T) {
+        unsafe {
+            core::ptr::copy_nonoverlapping(src, offset.as_mut_ptr(), offset.len());
+        }
     }
 }
 
@@ -623,7 +651,7 @@ mod tests {
 
     #[test]
     fn test_wasm_path_components() {
-        let path = WasmPathAccess::<WasmAccessFaker>::new(b"a/b//c".as_ptr(), 6);
+        let path = WasmPathAccess::<HostMemory>::new(b"a/b//c".as_ptr(), 6);
         let components = path.components();
         let mut iter = components.into_iter();
 
@@ -633,7 +661,7 @@ mod tests {
         assert!(iter.next().is_none());
 
         let path =
-            WasmPathAccess::<WasmAccessFaker>::new(b"virtual-layer/src/wasi/file.rs".as_ptr(), 30);
+            WasmPathAccess::<HostMemory>::new(b"virtual-layer/src/wasi/file.rs".as_ptr(), 30);
         let components = path.components();
         let mut iter = components.into_iter();
 
@@ -643,7 +671,7 @@ mod tests {
         assert!(iter.next().unwrap().eq_str("file.rs"));
         assert!(iter.next().is_none());
 
-        let path = WasmPathAccess::<WasmAccessFaker>::new(b"//bin/lsd.exe".as_ptr(), 13);
+        let path = WasmPathAccess::<HostMemory>::new(b"//bin/lsd.exe".as_ptr(), 13);
         let components = path.components();
         let mut iter = components.into_iter();
 
@@ -652,8 +680,7 @@ mod tests {
         assert!(iter.next().unwrap().eq_str("lsd.exe"));
         assert!(iter.next().is_none());
 
-        let path =
-            WasmPathAccess::<WasmAccessFaker>::new(b"/bin////../bin/explorer.exe".as_ptr(), 27);
+        let path = WasmPathAccess::<HostMemory>::new(b"/bin////../bin/explorer.exe".as_ptr(), 27);
         let components = path.components();
         let mut iter = components.into_iter();
 
@@ -664,53 +691,41 @@ mod tests {
         assert!(iter.next().unwrap().eq_str("explorer.exe"));
         assert!(iter.next().is_none());
     }
-}
 
-#[allow(dead_code)]
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub(crate) struct WasmAccessFaker;
-
-impl WasmAccess for WasmAccessFaker {
-    const NAME: &'static str = "WasmAccessFaker";
-
-    fn memcpy<T>(offset: *mut T, data: &[T]) {
-        unsafe {
-            core::ptr::copy_nonoverlapping(data.as_ptr(), offset, data.len());
+    // compares without allocating, the tests also run without `alloc`
+    fn assert_components(path: &str, expected: &[&str]) {
+        let mut components =
+            WasmPathAccess::<HostMemory>::new(path.as_ptr(), path.len()).components();
+
+        for name in expected {
+            assert!(
+                components
+                    .next()
+                    .is_some_and(|component| component.eq_str(name)),
+                "{path:?} should have {name:?}"
+            );
         }
+        assert!(components.next().is_none(), "{path:?} has more components");
     }
 
-    fn store_le<T>(offset: *mut T, value: T) {
-        unsafe { core::ptr::write(offset, value) };
-    }
-
-    fn load_le<T: core::fmt::Debug + Copy>(offset: *const T) -> T {
-        unsafe { core::ptr::read(offset) }
-    }
-
-    #[inline(always)]
-    fn _main() -> wasip1::Errno {
-        wasip1::ERRNO_SUCCESS
-    }
-
-    #[inline(always)]
-    fn _reset() {}
-
-    #[inline(always)]
-    fn _start() {}
-
-    #[cfg(not(feature = "multi_memory"))]
-    fn memory_director<T>(ptr: *const T) -> *const T {
-        ptr
-    }
-
-    #[cfg(not(feature = "multi_memory"))]
-    fn memory_director_mut<T>(ptr: *mut T) -> *mut T {
-        ptr
-    }
-
-    fn memcpy_to<T>(offset: &mut [T], src: *const T) {
-        unsafe {
-            core::ptr::copy_nonoverlapping(src, offset.as_mut_ptr(), offset.len());
-        }
+    #[test]
+    fn test_wasm_path_normalization() {
+        assert_components("./a/../b", &[".", "a", "..", "b"]);
+        assert_components("a//b///", &["a", "b"]);
+        assert_components("/", &["/"]);
+        assert_components(".", &["."]);
+        assert_components("..", &[".."]);
+        assert_components("", &[]);
+
+        // names only made of or starting with dots
+        assert_components(".hidden/x", &[".hidden", "x"]);
+        assert_components("..a/.../a.", &["..a", "...", "a."]);
+        assert_components("./.././", &[".", "..", "."]);
+
+        let path = |path: &str| WasmPathAccess::<HostMemory>::new(path.as_ptr(), path.len());
+        assert!(path("a/b/").ends_with_separator());
+        assert!(path("/").ends_with_separator());
+        assert!(!path("a/b").ends_with_separator());
+        assert!(!path("").ends_with_separator());
     }
 }
@@ -0,0 +1,545 @@
+use std::{cell::RefCell, string::String, vec, vec::Vec};
+
+use crate::__private::wasip1;
+use crate::memory::WasmAccess;
+use crate::wasi::file::{Wasip1FileSystem, Wasip1LFS};
+
+/// [`WasmAccess`] over a guest memory kept in a `Vec<u8>` of the calling thread,
+/// to test a file system with `cargo test` on the host
+/// instead of building a component and running it.
+///
+/// Pointers given to the file system are offsets in that memory,
+/// like the ones a wasm32 guest passes,
+/// and an access out of it panics where a guest would trap.
+/// Each thread has its own memory, so tests can run in parallel.
+///
+/// Helpers like [`MockWasm::open`] and [`MockWasm::write`] make the common calls,
+/// and under `cargo test` the functions `plug_fs!` exports for `MockWasm`
+/// can be called with the memory too.
+///
+/// ```ignore
+/// let mut vfs = Wasip1ConstVFS::<VFSHeapLFS<DefaultStdIO>, 8>::new(VFSHeapLFS::new("/tmp"));
+///
+/// let (path, path_len) = MockWasm::alloc_path("a.txt");
+/// let fd = MockWasm::alloc_value::<wasip1::Fd>();
+/// Wasip1FileSystem::path_open_raw::<MockWasm>(
+///     &mut vfs, 3, 0, path, path_len, wasip1::OFLAGS_CREAT, wasip1::RIGHTS_FD_WRITE, 0, 0, fd,
+/// );
+///
+/// let (iovs, iovs_len) = MockWasm::alloc_ciovs(&[b"Hello"]);
+/// let nwritten = MockWasm::alloc_value::<wasip1::Size>();
+/// Wasip1FileSystem::fd_write_raw::<MockWasm>(
+///     &mut vfs, MockWasm::load_le(fd), iovs, iovs_len, nwritten,
+/// );
+/// assert_eq!(MockWasm::load_le(nwritten), 5);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MockWasm;
+
+struct MockMemory {
+    data: Vec<u8>,
+    /// where [`MockWasm::alloc`] reserves next
+    top: usize,
+}
+
+/// The first bytes are never reserved, so no reserved pointer is null.
+const RESERVED_START: usize = 16;
+
+impl MockMemory {
+    fn new(size: usize) -> Self {
+        Self {
+            data: vec![0; size],
+            top: RESERVED_START,
+        }
+    }
+
+    fn check(&self, ptr: usize, len: usize) -> usize {
+        match ptr.checked_add(len) {
+            Some(end) if end <= self.data.len() => ptr,
+            _ => panic!(
+                "{}: access of {len} bytes at {ptr:#x} is out of the guest memory of {:#x} bytes",
+                MockWasm::NAME,
+                self.data.len()
+            ),
+        }
+    }
+}
+
+std::thread_local! {
+    static MEMORY: RefCell<MockMemory> = RefCell::new(MockMemory::new(MockWasm::MEMORY_SIZE));
+}
+
+fn with_memory<R>(f: impl FnOnce(&mut MockMemory) -> R) -> R {
+    MEMORY.with(|memory| f(&mut memory.borrow_mut()))
+}
+
+/// Bytes of `len` values of `T`, too many to fit in any memory on overflow.
+#[cfg(not(feature = "multi_memory"))]
+fn array_size<T>(len: usize) -> usize {
+    core::mem::size_of::<T>().saturating_mul(len)
+}
+
+impl MockWasm {
+    /// Size of the memory of a thread until [`MockWasm::reset_memory`], 16 wasm pages.
+    pub const MEMORY_SIZE: usize = 16 * 64 * 1024;
+
+    /// Replaces the memory of the thread with a zeroed one of `size` bytes.
+    /// Pointers into the previous memory must not be used anymore.
+    pub fn reset_memory(size: usize) {
+        with_memory(|memory| *memory = MockMemory::new(size));
+    }
+
+    /// Reserves zeroed bytes, like a guest taking a buffer from its stack or heap.
+    pub fn alloc(len: usize, align: usize) -> *mut u8 {
+        with_memory(|memory| {
+            let ptr = memory.top.next_multiple_of(align);
+            memory.check(ptr, len);
+            memory.top = ptr + len;
+            memory.data[ptr..ptr + len].fill(0);
+            ptr as *mut u8
+        })
+    }
+
+    /// Reserves a zeroed value, e.g. the `Fd`, `Size` or `Filestat` a call returns through.
+    pub fn alloc_value<T>() -> *mut T {
+        Self::alloc(core::mem::size_of::<T>(), core::mem::align_of::<T>()) as *mut T
+    }
+
+    /// Copies the data into the memory.
+    pub fn alloc_bytes(data: &[u8]) -> *mut u8 {
+        let ptr = Self::alloc(data.len(), 1);
+        Self::memcpy(ptr, data);
+        ptr
+    }
+
+    /// Copies the path into the memory, as `path_ptr` and `path_len`.
+    pub fn alloc_path(path: &str) -> (*const u8, usize) {
+        (Self::alloc_bytes(path.as_bytes()), path.len())
+    }
+
+    /// Copies the buffers into the memory with the array of `Ciovec` pointing to them,
+    /// as `iovs_ptr` and `iovs_len` of a write.
+    pub fn alloc_ciovs(bufs: &[&[u8]]) -> (*const wasip1::Ciovec, usize) {
+        let iovs = bufs
+            .iter()
+            .map(|buf| wasip1::Ciovec {
+                buf: Self::alloc_bytes(buf),
+                buf_len: buf.len(),
+            })
+            .collect::<Vec<_>>();
+
+        (Self::alloc_array(&iovs), iovs.len())
+    }
+
+    /// Reserves zeroed buffers of the lengths with the array of `Iovec` pointing to them,
+    /// as `iovs_ptr` and `iovs_len` of a read.
+    pub fn alloc_iovs(lens: &[usize]) -> (*const wasip1::Iovec, usize) {
+        let iovs = lens
+            .iter()
+            .map(|len| wasip1::Iovec {
+                buf: Self::alloc(*len, 1),
+                buf_len: *len,
+            })
+            .collect::<Vec<_>>();
+
+        (Self::alloc_array(&iovs), iovs.len())
+    }
+
+    fn alloc_array<T: Copy>(array: &[T]) -> *const T {
+        let ptr = Self::alloc(core::mem::size_of_val(array), core::mem::align_of::<T>()) as *mut T;
+        Self::memcpy(ptr, array);
+        ptr
+    }
+
+    /// Copies bytes out of the memory.
+    pub fn read_bytes(ptr: *const u8, len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        Self::memcpy_to(&mut data, ptr);
+        data
+    }
+
+    /// Copies the first `len` bytes a read filled out of its `Iovec` buffers.
+    pub fn read_iovs(iovs_ptr: *const wasip1::Iovec, iovs_len: usize, len: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(len);
+
+        for i in 0..iovs_len {
+            let iov = Self::load_le(iovs_ptr.wrapping_add(i));
+            let take = core::cmp::min(iov.buf_len, len - data.len());
+            data.extend(Self::read_bytes(iov.buf, take));
+        }
+
+        data
+    }
+}
+
+/// Calls a guest makes, laid out in the memory the way `plug_fs!` passes them on.
+impl MockWasm {
+    /// What [`MockWasm::open`] opens files for: reading, writing, seeking and telling.
+    pub const RIGHTS: wasip1::Rights = wasip1::RIGHTS_FD_READ
+        | wasip1::RIGHTS_FD_WRITE
+        | wasip1::RIGHTS_FD_SEEK
+        | wasip1::RIGHTS_FD_TELL;
+
+    /// `path_open` of the path under the directory `dir_fd`, with [`MockWasm::RIGHTS`].
+    pub fn open<FS: Wasip1FileSystem>(
+        fs: &mut FS,
+        dir_fd: wasip1::Fd,
+        path: &str,
+        o_flags: wasip1::Oflags,
+    ) -> Result<wasip1::Fd, wasip1::Errno> {
+        Self::open_with(fs, dir_fd, 0, path, o_flags, Self::RIGHTS, 0)
+    }
+
+    /// `path_open` with every argument.
+    pub fn open_with<FS: Wasip1FileSystem>(
+        fs: &mut FS,
+        dir_fd: wasip1::Fd,
+        dir_flags: wasip1::Lookupflags,
+        path: &str,
+        o_flags: wasip1::Oflags,
+        rights: wasip1::Rights,
+        fd_flags: wasip1::Fdflags,
+    ) -> Result<wasip1::Fd, wasip1::Errno> {
+        let (path, path_len) = Self::alloc_path(path);
+        let fd = Self::alloc_value::<wasip1::Fd>();
+        match fs.path_open_raw::<Self>(
+            dir_fd, dir_flags, path, path_len, o_flags, rights, 0, fd_flags, fd,
+        ) {
+            wasip1::ERRNO_SUCCESS => Ok(Self::load_le(fd)),
+            e => Err(e),
+        }
+    }
+
+    /// `fd_read` of up to `len` bytes.
+    pub fn read<FS: Wasip1FileSystem>(
+        fs: &mut FS,
+        fd: wasip1::Fd,
+        len: usize,
+    ) -> Result<Vec<u8>, wasip1::Errno> {
+        let (iovs, iovs_len) = Self::alloc_iovs(&[len]);
+        let nread = Self::alloc_value::<wasip1::Size>();
+        match fs.fd_read_raw::<Self>(fd, iovs as *const _, iovs_len, nread) {
+            wasip1::ERRNO_SUCCESS => Ok(Self::read_iovs(iovs, iovs_len, Self::load_le(nread))),
+            e => Err(e),
+        }
+    }
+
+    /// `fd_write` of the data, returning how many bytes were written.
+    pub fn write<FS: Wasip1FileSystem>(
+        fs: &mut FS,
+        fd: wasip1::Fd,
+        data: &[u8],
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let (iovs, iovs_len) = Self::alloc_ciovs(&[data]);
+        let nwritten = Self::alloc_value::<wasip1::Size>();
+        match fs.fd_write_raw::<Self>(fd, iovs, iovs_len, nwritten) {
+            wasip1::ERRNO_SUCCESS => Ok(Self::load_le(nwritten)),
+            e => Err(e),
+        }
+    }
+
+    /// `fd_prestat_get` and `fd_prestat_dir_name` of the pre-opened directory `fd`.
+    pub fn prestat_name<FS: Wasip1FileSystem>(
+        fs: &mut FS,
+        fd: wasip1::Fd,
+    ) -> Result<String, wasip1::Errno> {
+        let prestat_ptr = Self::alloc_value::<wasip1::Prestat>();
+        match fs.fd_prestat_get_raw::<Self>(fd, prestat_ptr) {
+            wasip1::ERRNO_SUCCESS => {}
+            e => return Err(e),
+        }
+        let mut prestat = [wasip1::Prestat {
+            tag: 0,
+            u: wasip1::PrestatU {
+                dir: wasip1::PrestatDir { pr_name_len: 0 },
+            },
+        }];
+        Self::memcpy_to(&mut prestat, prestat_ptr);
+
+        let len = unsafe { prestat[0].u.dir.pr_name_len };
+        let name = Self::alloc(len, 1);
+        match fs.fd_prestat_dir_name_raw::<Self>(fd, name, len) {
+            wasip1::ERRNO_SUCCESS => Ok(String::from_utf8(Self::read_bytes(name, len)).unwrap()),
+            e => Err(e),
+        }
+    }
+
+    /// [`MockWasm::open`] on a file system of inodes,
+    /// `dir` being one of its pre-opened directories or an inode opened from them.
+    pub fn lfs_open<LFS: Wasip1LFS>(
+        lfs: &mut LFS,
+        dir: LFS::Inode,
+        path: &str,
+        o_flags: wasip1::Oflags,
+    ) -> Result<LFS::Inode, wasip1::Errno> {
+        Self::lfs_open_with(lfs, dir, path, o_flags, Self::RIGHTS)
+    }
+
+    /// [`MockWasm::lfs_open`] with other rights.
+    pub fn lfs_open_with<LFS: Wasip1LFS>(
+        lfs: &mut LFS,
+        dir: LFS::Inode,
+        path: &str,
+        o_flags: wasip1::Oflags,
+        rights: wasip1::Rights,
+    ) -> Result<LFS::Inode, wasip1::Errno> {
+        let (path, path_len) = Self::alloc_path(path);
+        lfs.path_open_raw::<Self>(dir, 0, path, path_len, o_flags, rights, 0, 0)
+    }
+
+    /// Reads up to `len` bytes from the start of the inode.
+    pub fn lfs_read<LFS: Wasip1LFS>(
+        lfs: &mut LFS,
+        inode: LFS::Inode,
+        len: usize,
+    ) -> Result<Vec<u8>, wasip1::Errno> {
+        let buf = Self::alloc(len, 1);
+        let nread = lfs.fd_pread_raw::<Self>(inode, buf, len, 0)?;
+        Ok(Self::read_bytes(buf, nread))
+    }
+
+    /// Writes the data at the start of the inode, returning how many bytes were written.
+    pub fn lfs_write<LFS: Wasip1LFS>(
+        lfs: &mut LFS,
+        inode: LFS::Inode,
+        data: &[u8],
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        lfs.fd_pwrite_raw::<Self>(inode, Self::alloc_bytes(data), data.len(), 0)
+    }
+
+    /// The name of the pre-opened directory `inode`.
+    pub fn lfs_prestat_name<LFS: Wasip1LFS<Inode: Clone>>(
+        lfs: &mut LFS,
+        inode: LFS::Inode,
+    ) -> Result<String, wasip1::Errno> {
+        let prestat = lfs.fd_prestat_get_raw::<Self>(inode.clone())?;
+        let len = unsafe { prestat.u.dir.pr_name_len };
+        let name = Self::alloc(len, 1);
+        lfs.fd_prestat_dir_name_raw::<Self>(inode, name, len)?;
+        Ok(String::from_utf8(Self::read_bytes(name, len)).unwrap())
+    }
+}
+
+impl WasmAccess for MockWasm {
+    const NAME: &'static str = "MockWasm";
+
+    fn memcpy<T>(offset: *mut T, data: &[T]) {
+        let len = core::mem::size_of_val(data);
+        with_memory(|memory| {
+            let ptr = memory.check(offset as usize, len);
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    data.as_ptr() as *const u8,
+                    memory.data.as_mut_ptr().add(ptr),
+                    len,
+                );
+            }
+        });
+    }
+
+    fn memcpy_to<T>(offset: &mut [T], src: *const T) {
+        let len = core::mem::size_of_val(offset);
+        with_memory(|memory| {
+            let ptr = memory.check(src as usize, len);
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    memory.data.as_ptr().add(ptr),
+                    offset.as_mut_ptr() as *mut u8,
+                    len,
+                );
+            }
+        });
+    }
+
+    fn store_le<T>(offset: *mut T, value: T) {
+        with_memory(|memory| {
+            let ptr = memory.check(offset as usize, core::mem::size_of::<T>());
+            unsafe {
+                core::ptr::write_unaligned(memory.data.as_mut_ptr().add(ptr) as *mut T, value);
+            }
+        });
+    }
+
+    fn load_le<T: core::fmt::Debug + Copy>(offset: *const T) -> T {
+        with_memory(|memory| {
+            let ptr = memory.check(offset as usize, core::mem::size_of::<T>());
+            unsafe { core::ptr::read_unaligned(memory.data.as_ptr().add(ptr) as *const T) }
+        })
+    }
+
+    /// The host address of the offset,
+    /// valid until the memory of the thread is reset.
+    #[cfg(not(feature = "multi_memory"))]
+    fn memory_director<T>(ptr: *const T, len: usize) -> *const T {
+        with_memory(|memory| {
+            let ptr = memory.check(ptr as usize, array_size::<T>(len));
+            memory.data.as_ptr().wrapping_add(ptr) as *const T
+        })
+    }
+
+    #[cfg(not(feature = "multi_memory"))]
+    fn memory_director_mut<T>(ptr: *mut T, len: usize) -> *mut T {
+        with_memory(|memory| {
+            let ptr = memory.check(ptr as usize, array_size::<T>(len));
+            memory.data.as_mut_ptr().wrapping_add(ptr) as *mut T
+        })
+    }
+
+    #[inline(always)]
+    fn _main() -> wasip1::Errno {
+        wasip1::ERRNO_SUCCESS
+    }
+
+    /// Zeroes the memory of the thread.
+    fn _reset() {
+        with_memory(|memory| *memory = MockMemory::new(memory.data.len()));
+    }
+
+    #[inline(always)]
+    fn _start() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasi::file::{
+        constant::vfs::Wasip1ConstVFS, heap::lfs::VFSHeapLFS, stdio::DefaultStdIO,
+    };
+
+    type Vfs = Wasip1ConstVFS<VFSHeapLFS<DefaultStdIO>, 8>;
+
+    static mut VIRTUAL_FILE_SYSTEM: Vfs = Wasip1ConstVFS::new(VFSHeapLFS::new("/tmp"));
+
+    #[allow(non_snake_case)]
+    mod plugged {
+        use super::*;
+
+        crate::plug_fs!(@const, {
+            #[allow(static_mut_refs)]
+            unsafe { &mut VIRTUAL_FILE_SYSTEM }
+        }, MockWasm);
+    }
+
+    #[test]
+    fn test_mock_wasm() {
+        MockWasm::reset_memory(4096);
+        let mut vfs = Vfs::new(VFSHeapLFS::new("/tmp"));
+
+        let fd = MockWasm::open(&mut vfs, 3, "a.txt", wasip1::OFLAGS_CREAT).unwrap();
+
+        let (iovs, iovs_len) = MockWasm::alloc_ciovs(&[b"Hel", b"lo"]);
+        let nwritten = MockWasm::alloc_value::<wasip1::Size>();
+        assert_eq!(
+            Wasip1FileSystem::fd_write_raw::<MockWasm>(&mut vfs, fd, iovs, iovs_len, nwritten),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(MockWasm::load_le(nwritten), 5);
+
+        let (iovs, iovs_len) = MockWasm::alloc_iovs(&[2, 8]);
+        let nread = MockWasm::alloc_value::<wasip1::Size>();
+        assert_eq!(
+            Wasip1FileSystem::fd_pread_raw::<MockWasm>(&mut vfs, fd, iovs, iovs_len, 0, nread),
+            wasip1::ERRNO_SUCCESS
+        );
+        let nread = MockWasm::load_le(nread);
+        assert_eq!(MockWasm::read_iovs(iovs, iovs_len, nread), b"Hello");
+
+        let (path, path_len) = MockWasm::alloc_path("a.txt");
+        let filestat = MockWasm::alloc_value::<wasip1::Filestat>();
+        assert_eq!(
+            Wasip1FileSystem::path_filestat_get_raw::<MockWasm>(
+                &mut vfs, 3, 0, path, path_len, filestat
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(MockWasm::load_le(filestat).size, 5);
+        assert_eq!(
+            MockWasm::load_le(filestat).filetype,
+            wasip1::FILETYPE_REGULAR_FILE
+        );
+
+        assert_eq!(MockWasm::prestat_name(&mut vfs, 3).as_deref(), Ok("/tmp"));
+        assert_eq!(MockWasm::prestat_name(&mut vfs, 4), Err(wasip1::ERRNO_BADF));
+    }
+
+    #[test]
+    fn test_plug_fs() {
+        use plugged::*;
+
+        MockWasm::reset_memory(4096);
+
+        let (path, path_len) = MockWasm::alloc_path("a.txt");
+        let fd = MockWasm::alloc_value::<wasip1::Fd>();
+        assert_eq!(
+            unsafe {
+                __wasip1_vfs_MockWasm_path_open(
+                    3,
+                    0,
+                    path,
+                    path_len,
+                    wasip1::OFLAGS_CREAT,
+                    MockWasm::RIGHTS,
+                    0,
+                    0,
+                    fd,
+                )
+            },
+            wasip1::ERRNO_SUCCESS
+        );
+        let fd = MockWasm::load_le(fd);
+
+        let (iovs, iovs_len) = MockWasm::alloc_ciovs(&[b"Hel", b"lo"]);
+        let nwritten = MockWasm::alloc_value::<wasip1::Size>();
+        assert_eq!(
+            unsafe { __wasip1_vfs_MockWasm_fd_write(fd, iovs, iovs_len, nwritten) },
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(MockWasm::load_le(nwritten), 5);
+
+        let newoffset = MockWasm::alloc_value::<wasip1::Filesize>();
+        assert_eq!(
+            unsafe { __wasip1_vfs_MockWasm_fd_seek(fd, 0, wasip1::WHENCE_SET, newoffset) },
+            wasip1::ERRNO_SUCCESS
+        );
+        let (iovs, iovs_len) = MockWasm::alloc_iovs(&[2, 8]);
+        let nread = MockWasm::alloc_value::<wasip1::Size>();
+        assert_eq!(
+            unsafe { __wasip1_vfs_MockWasm_fd_read(fd, iovs as *const _, iovs_len, nread) },
+            wasip1::ERRNO_SUCCESS
+        );
+        let nread = MockWasm::load_le(nread);
+        assert_eq!(MockWasm::read_iovs(iovs, iovs_len, nread), b"Hello");
+
+        let name = MockWasm::alloc(4, 1);
+        assert_eq!(
+            unsafe { __wasip1_vfs_MockWasm_fd_prestat_dir_name(3, name, 4) },
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(MockWasm::read_bytes(name, 4), b"/tmp");
+
+        assert_eq!(
+            unsafe { __wasip1_vfs_MockWasm_fd_close(fd) },
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(
+            unsafe { __wasip1_vfs_MockWasm_fd_close(fd) },
+            wasip1::ERRNO_BADF
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "out of the guest memory")]
+    fn test_mock_wasm_out_of_bounds() {
+        MockWasm::reset_memory(64);
+        MockWasm::load_le(60 as *const u64);
+    }
+
+    #[test]
+    #[cfg(not(feature = "multi_memory"))]
+    #[should_panic(expected = "out of the guest memory")]
+    fn test_mock_wasm_director_out_of_bounds() {
+        MockWasm::reset_memory(64);
+        // starts inside the memory and ends past it
+        MockWasm::memory_director(60 as *const u8, 8);
+    }
+}
@@ -6,6 +6,8 @@ pub struct Wasip1Transporter;
 
 #[cfg(not(feature = "multi_memory"))]
 use crate::prelude::WasmAccess;
+#[cfg(feature = "alloc")]
+use crate::wasi::file::host::fs::HostFS;
 
 unsafe fn non_recursive_fd_read(
     fd: wasip1::Fd,
@@ -69,6 +71,347 @@ unsafe fn non_recursive_proc_exit(rval: wasip1::Exitcode) -> ! {
     unreachable!();
 }
 
+unsafe fn non_recursive_clock_time_get(
+    id: wasip1::Clockid,
+    precision: wasip1::Timestamp,
+) -> Result<wasip1::Timestamp, wasip1::Errno> {
+    let mut rp0 = core::mem::MaybeUninit::<wasip1::Timestamp>::uninit();
+
+    let id = id.raw() as i32;
+    let precision = precision as i64;
+    let rp0_ptr = rp0.as_mut_ptr() as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        clock_time_get(id: i32, precision: i64, rp0_ptr: i32) -> i32
+    );
+
+    errno(ret).map(|()| unsafe { rp0.assume_init() })
+}
+
+unsafe fn non_recursive_random_get(buf: &mut [u8]) -> Result<(), wasip1::Errno> {
+    let buf_ptr = buf.as_mut_ptr() as i32;
+    let buf_len = buf.len() as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        random_get(buf_ptr: i32, buf_len: i32) -> i32
+    );
+
+    errno(ret)
+}
+
+#[inline]
+fn errno(ret: i32) -> Result<(), wasip1::Errno> {
+    match ret {
+        0 => Ok(()),
+        _ => Err(unsafe { core::mem::transmute::<u16, wasip1::Errno>(ret as u16) }),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn non_recursive_path_open(
+    fd: wasip1::Fd,
+    dirflags: wasip1::Lookupflags,
+    path: &str,
+    oflags: wasip1::Oflags,
+    fs_rights_base: wasip1::Rights,
+    fs_rights_inheriting: wasip1::Rights,
+    fdflags: wasip1::Fdflags,
+) -> Result<wasip1::Fd, wasip1::Errno> {
+    let mut rp0 = core::mem::MaybeUninit::<wasip1::Fd>::uninit();
+
+    let fd = fd as i32;
+    let dirflags = dirflags as i32;
+    let path_ptr = path.as_ptr() as i32;
+    let path_len = path.len() as i32;
+    let oflags = oflags as i32;
+    let fs_rights_base = fs_rights_base as i64;
+    let fs_rights_inheriting = fs_rights_inheriting as i64;
+    let fdflags = fdflags as i32;
+    let rp0_ptr = rp0.as_mut_ptr() as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        path_open(
+            fd: i32,
+            dirflags: i32,
+            path_ptr: i32,
+            path_len: i32,
+            oflags: i32,
+            fs_rights_base: i64,
+            fs_rights_inheriting: i64,
+            fdflags: i32,
+            rp0_ptr: i32
+        ) -> i32
+    );
+
+    errno(ret).map(|()| unsafe { rp0.assume_init() })
+}
+
+unsafe fn non_recursive_fd_close(fd: wasip1::Fd) -> Result<(), wasip1::Errno> {
+    let fd = fd as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        fd_close(fd: i32) -> i32
+    );
+
+    errno(ret)
+}
+
+unsafe fn non_recursive_fd_pread(
+    fd: wasip1::Fd,
+    iovs: wasip1::IovecArray<'_>,
+    offset: wasip1::Filesize,
+) -> Result<wasip1::Size, wasip1::Errno> {
+    let mut rp0 = core::mem::MaybeUninit::<wasip1::Size>::uninit();
+
+    let fd = fd as i32;
+    let iovs_ptr = iovs.as_ptr() as i32;
+    let iovs_len = iovs.len() as i32;
+    let offset = offset as i64;
+    let rp0_ptr = rp0.as_mut_ptr() as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        fd_pread(
+            fd: i32,
+            iovs_ptr: i32,
+            iovs_len: i32,
+            offset: i64,
+            rp0_ptr: i32
+        ) -> i32
+    );
+
+    errno(ret).map(|()| unsafe { rp0.assume_init() })
+}
+
+unsafe fn non_recursive_fd_pwrite(
+    fd: wasip1::Fd,
+    iovs: wasip1::CiovecArray<'_>,
+    offset: wasip1::Filesize,
+) -> Result<wasip1::Size, wasip1::Errno> {
+    let mut rp0 = core::mem::MaybeUninit::<wasip1::Size>::uninit();
+
+    let fd = fd as i32;
+    let iovs_ptr = iovs.as_ptr() as i32;
+    let iovs_len = iovs.len() as i32;
+    let offset = offset as i64;
+    let rp0_ptr = rp0.as_mut_ptr() as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        fd_pwrite(
+            fd: i32,
+            iovs_ptr: i32,
+            iovs_len: i32,
+            offset: i64,
+            rp0_ptr: i32
+        ) -> i32
+    );
+
+    errno(ret).map(|()| unsafe { rp0.assume_init() })
+}
+
+unsafe fn non_recursive_fd_readdir(
+    fd: wasip1::Fd,
+    buf: &mut [u8],
+    cookie: wasip1::Dircookie,
+) -> Result<wasip1::Size, wasip1::Errno> {
+    let mut rp0 = core::mem::MaybeUninit::<wasip1::Size>::uninit();
+
+    let fd = fd as i32;
+    let buf_ptr = buf.as_mut_ptr() as i32;
+    let buf_len = buf.len() as i32;
+    let cookie = cookie as i64;
+    let rp0_ptr = rp0.as_mut_ptr() as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        fd_readdir(
+            fd: i32,
+            buf_ptr: i32,
+            buf_len: i32,
+            cookie: i64,
+            rp0_ptr: i32
+        ) -> i32
+    );
+
+    errno(ret).map(|()| unsafe { rp0.assume_init() })
+}
+
+unsafe fn non_recursive_path_filestat_get(
+    fd: wasip1::Fd,
+    flags: wasip1::Lookupflags,
+    path: &str,
+) -> Result<wasip1::Filestat, wasip1::Errno> {
+    let mut rp0 = core::mem::MaybeUninit::<wasip1::Filestat>::uninit();
+
+    let fd = fd as i32;
+    let flags = flags as i32;
+    let path_ptr = path.as_ptr() as i32;
+    let path_len = path.len() as i32;
+    let rp0_ptr = rp0.as_mut_ptr() as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        path_filestat_get(
+            fd: i32,
+            flags: i32,
+            path_ptr: i32,
+            path_len: i32,
+            rp0_ptr: i32
+        ) -> i32
+    );
+
+    errno(ret).map(|()| unsafe { rp0.assume_init() })
+}
+
+unsafe fn non_recursive_path_filestat_set_times(
+    fd: wasip1::Fd,
+    flags: wasip1::Lookupflags,
+    path: &str,
+    atim: wasip1::Timestamp,
+    mtim: wasip1::Timestamp,
+    fst_flags: wasip1::Fstflags,
+) -> Result<(), wasip1::Errno> {
+    let fd = fd as i32;
+    let flags = flags as i32;
+    let path_ptr = path.as_ptr() as i32;
+    let path_len = path.len() as i32;
+    let atim = atim as i64;
+    let mtim = mtim as i64;
+    let fst_flags = fst_flags as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        path_filestat_set_times(
+            fd: i32,
+            flags: i32,
+            path_ptr: i32,
+            path_len: i32,
+            atim: i64,
+            mtim: i64,
+            fst_flags: i32
+        ) -> i32
+    );
+
+    errno(ret)
+}
+
+unsafe fn non_recursive_path_readlink(
+    fd: wasip1::Fd,
+    path: &str,
+    buf: &mut [u8],
+) -> Result<wasip1::Size, wasip1::Errno> {
+    let mut rp0 = core::mem::MaybeUninit::<wasip1::Size>::uninit();
+
+    let fd = fd as i32;
+    let path_ptr = path.as_ptr() as i32;
+    let path_len = path.len() as i32;
+    let buf_ptr = buf.as_mut_ptr() as i32;
+    let buf_len = buf.len() as i32;
+    let rp0_ptr = rp0.as_mut_ptr() as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        path_readlink(
+            fd: i32,
+            path_ptr: i32,
+            path_len: i32,
+            buf_ptr: i32,
+            buf_len: i32,
+            rp0_ptr: i32
+        ) -> i32
+    );
+
+    errno(ret).map(|()| unsafe { rp0.assume_init() })
+}
+
+unsafe fn non_recursive_path_create_directory(
+    fd: wasip1::Fd,
+    path: &str,
+) -> Result<(), wasip1::Errno> {
+    let fd = fd as i32;
+    let path_ptr = path.as_ptr() as i32;
+    let path_len = path.len() as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        path_create_directory(fd: i32, path_ptr: i32, path_len: i32) -> i32
+    );
+
+    errno(ret)
+}
+
+unsafe fn non_recursive_path_remove_directory(
+    fd: wasip1::Fd,
+    path: &str,
+) -> Result<(), wasip1::Errno> {
+    let fd = fd as i32;
+    let path_ptr = path.as_ptr() as i32;
+    let path_len = path.len() as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        path_remove_directory(fd: i32, path_ptr: i32, path_len: i32) -> i32
+    );
+
+    errno(ret)
+}
+
+unsafe fn non_recursive_path_unlink_file(fd: wasip1::Fd, path: &str) -> Result<(), wasip1::Errno> {
+    let fd = fd as i32;
+    let path_ptr = path.as_ptr() as i32;
+    let path_len = path.len() as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        path_unlink_file(fd: i32, path_ptr: i32, path_len: i32) -> i32
+    );
+
+    errno(ret)
+}
+
+unsafe fn non_recursive_path_rename(
+    old_fd: wasip1::Fd,
+    old_path: &str,
+    new_fd: wasip1::Fd,
+    new_path: &str,
+) -> Result<(), wasip1::Errno> {
+    let old_fd = old_fd as i32;
+    let old_path_ptr = old_path.as_ptr() as i32;
+    let old_path_len = old_path.len() as i32;
+    let new_fd = new_fd as i32;
+    let new_path_ptr = new_path.as_ptr() as i32;
+    let new_path_len = new_path.len() as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        path_rename(
+            old_fd: i32,
+            old_path_ptr: i32,
+            old_path_len: i32,
+            new_fd: i32,
+            new_path_ptr: i32,
+            new_path_len: i32
+        ) -> i32
+    );
+
+    errno(ret)
+}
+
+unsafe fn non_recursive_path_symlink(
+    old_path: &str,
+    fd: wasip1::Fd,
+    new_path: &str,
+) -> Result<(), wasip1::Errno> {
+    let old_path_ptr = old_path.as_ptr() as i32;
+    let old_path_len = old_path.len() as i32;
+    let fd = fd as i32;
+    let new_path_ptr = new_path.as_ptr() as i32;
+    let new_path_len = new_path.len() as i32;
+
+    let ret = crate::non_recursive_wasi_snapshot_preview1!(
+        path_symlink(
+            old_path_ptr: i32,
+            old_path_len: i32,
+            fd: i32,
+            new_path_ptr: i32,
+            new_path_len: i32
+        ) -> i32
+    );
+
+    errno(ret)
+}
+
 impl Wasip1Transporter {
     #[allow(unused_variables)]
     pub fn read_from_stdin(buf: &mut [u8]) -> Result<wasip1::Size, wasip1::Errno> {
@@ -97,7 +440,7 @@ impl Wasip1Transporter {
         #[cfg(target_os = "wasi")]
         {
             let iovec_arr = [wasip1::Iovec {
-                buf: Wasm::memory_director_mut(buf),
+                buf: Wasm::memory_director_mut(buf, len),
                 buf_len: len,
             }];
 
@@ -135,7 +478,7 @@ impl Wasip1Transporter {
         len: usize,
     ) -> Result<wasip1::Size, wasip1::Errno> {
         let ciovec_arr = [wasip1::Ciovec {
-            buf: Wasm::memory_director(buf),
+            buf: Wasm::memory_director(buf, len),
             buf_len: len,
         }];
 
@@ -177,7 +520,7 @@ impl Wasip1Transporter {
         #[cfg(target_os = "wasi")]
         {
             let ciovec_arr = [wasip1::Ciovec {
-                buf: Wasm::memory_director(buf),
+                buf: Wasm::memory_director(buf, len),
                 buf_len: len,
             }];
 
@@ -190,6 +533,35 @@ impl Wasip1Transporter {
         }
     }
 
+    #[allow(unused_variables)]
+    pub fn clock_time_get(
+        id: wasip1::Clockid,
+        precision: wasip1::Timestamp,
+    ) -> Result<wasip1::Timestamp, wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        unsafe {
+            non_recursive_clock_time_get(id, precision)
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        {
+            unimplemented!("this is not supported on this architecture");
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub fn random_get(buf: &mut [u8]) -> Result<(), wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        unsafe {
+            non_recursive_random_get(buf)
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        {
+            unimplemented!("this is not supported on this architecture");
+        }
+    }
+
     #[allow(unused_variables)]
     pub fn process_abort(rval: wasip1::Exitcode) -> ! {
         #[cfg(not(target_os = "wasi"))]
@@ -204,3 +576,197 @@ impl Wasip1Transporter {
         }
     }
 }
+
+/// Forwards to the host WASI.
+/// Not supported when not targeting wasi.
+#[cfg(feature = "alloc")]
+#[allow(unused_variables)]
+impl HostFS for Wasip1Transporter {
+    fn path_open(
+        fd: wasip1::Fd,
+        dir_flags: wasip1::Lookupflags,
+        path: &str,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        fs_rights_inheriting: wasip1::Rights,
+        fd_flags: wasip1::Fdflags,
+    ) -> Result<wasip1::Fd, wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        unsafe {
+            non_recursive_path_open(
+                fd,
+                dir_flags,
+                path,
+                o_flags,
+                fs_rights_base,
+                fs_rights_inheriting,
+                fd_flags,
+            )
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        unimplemented!("this is not supported on this architecture");
+    }
+
+    fn fd_close(fd: wasip1::Fd) -> Result<(), wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        unsafe {
+            non_recursive_fd_close(fd)
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        unimplemented!("this is not supported on this architecture");
+    }
+
+    fn fd_pread(
+        fd: wasip1::Fd,
+        buf: &mut [u8],
+        offset: wasip1::Filesize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        {
+            let iovec_arr = [wasip1::Iovec {
+                buf: buf.as_mut_ptr(),
+                buf_len: buf.len(),
+            }];
+
+            unsafe { non_recursive_fd_pread(fd, &iovec_arr, offset) }
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        unimplemented!("this is not supported on this architecture");
+    }
+
+    fn fd_pwrite(
+        fd: wasip1::Fd,
+        data: &[u8],
+        offset: wasip1::Filesize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        {
+            let ciovec_arr = [wasip1::Ciovec {
+                buf: data.as_ptr(),
+                buf_len: data.len(),
+            }];
+
+            unsafe { non_recursive_fd_pwrite(fd, &ciovec_arr, offset) }
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        unimplemented!("this is not supported on this architecture");
+    }
+
+    fn fd_readdir(
+        fd: wasip1::Fd,
+        buf: &mut [u8],
+        cookie: wasip1::Dircookie,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        unsafe {
+            non_recursive_fd_readdir(fd, buf, cookie)
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        unimplemented!("this is not supported on this architecture");
+    }
+
+    fn path_filestat_get(
+        fd: wasip1::Fd,
+        flags: wasip1::Lookupflags,
+        path: &str,
+    ) -> Result<wasip1::Filestat, wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        unsafe {
+            non_recursive_path_filestat_get(fd, flags, path)
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        unimplemented!("this is not supported on this architecture");
+    }
+
+    fn path_filestat_set_times(
+        fd: wasip1::Fd,
+        flags: wasip1::Lookupflags,
+        path: &str,
+        atim: wasip1::Timestamp,
+        mtim: wasip1::Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        unsafe {
+            non_recursive_path_filestat_set_times(fd, flags, path, atim, mtim, fst_flags)
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        unimplemented!("this is not supported on this architecture");
+    }
+
+    fn path_readlink(
+        fd: wasip1::Fd,
+        path: &str,
+        buf: &mut [u8],
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        unsafe {
+            non_recursive_path_readlink(fd, path, buf)
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        unimplemented!("this is not supported on this architecture");
+    }
+
+    fn path_create_directory(fd: wasip1::Fd, path: &str) -> Result<(), wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        unsafe {
+            non_recursive_path_create_directory(fd, path)
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        unimplemented!("this is not supported on this architecture");
+    }
+
+    fn path_remove_directory(fd: wasip1::Fd, path: &str) -> Result<(), wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        unsafe {
+            non_recursive_path_remove_directory(fd, path)
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        unimplemented!("this is not supported on this architecture");
+    }
+
+    fn path_unlink_file(fd: wasip1::Fd, path: &str) -> Result<(), wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        unsafe {
+            non_recursive_path_unlink_file(fd, path)
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        unimplemented!("this is not supported on this architecture");
+    }
+
+    fn path_rename(
+        old_fd: wasip1::Fd,
+        old_path: &str,
+        new_fd: wasip1::Fd,
+        new_path: &str,
+    ) -> Result<(), wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        unsafe {
+            non_recursive_path_rename(old_fd, old_path, new_fd, new_path)
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        unimplemented!("this is not supported on this architecture");
+    }
+
+    fn path_symlink(old_path: &str, fd: wasip1::Fd, new_path: &str) -> Result<(), wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        unsafe {
+            non_recursive_path_symlink(old_path, fd, new_path)
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        unimplemented!("this is not supported on this architecture");
+    }
+}
@@ -0,0 +1,540 @@
+use alloc::{boxed::Box, collections::BTreeMap, vec, vec::Vec};
+
+use crate::__private::wasip1;
+use crate::__private::wasip1::{Dircookie, Size, Timestamp};
+use crate::{
+    memory::WasmAccess,
+    wasi::file::{FilestatWithoutDevice, Wasip1LFS, seek_cursor},
+};
+
+/// Memory of the guest behind a `&dyn`,
+/// what [`WasmAccess`] is to [`Wasip1LFS`].
+pub trait Wasip1DynMemory {
+    /// Copies the data to the offset in the guest.
+    fn memcpy(&self, offset: *mut u8, data: &[u8]);
+
+    /// Fills the slice from the offset in the guest.
+    fn memcpy_to(&self, offset: &mut [u8], src: *const u8);
+
+    fn get_array(&self, ptr: *const u8, len: usize) -> Vec<u8> {
+        let mut array = vec![0; len];
+        self.memcpy_to(&mut array, ptr);
+        array
+    }
+}
+
+/// [`Wasip1DynMemory`] of a [`WasmAccess`].
+pub struct DynWasmAccess<Wasm: WasmAccess> {
+    __marker: core::marker::PhantomData<Wasm>,
+}
+
+impl<Wasm: WasmAccess> DynWasmAccess<Wasm> {
+    pub const fn new() -> Self {
+        Self {
+            __marker: core::marker::PhantomData,
+        }
+    }
+}
+
+impl<Wasm: WasmAccess> Default for DynWasmAccess<Wasm> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Wasm: WasmAccess> Wasip1DynMemory for DynWasmAccess<Wasm> {
+    fn memcpy(&self, offset: *mut u8, data: &[u8]) {
+        Wasm::memcpy(offset, data);
+    }
+
+    fn memcpy_to(&self, offset: &mut [u8], src: *const u8) {
+        Wasm::memcpy_to(offset, src);
+    }
+}
+
+/// [`Wasip1LFS`] which can be used as `dyn`,
+/// to pick file systems at runtime, e.g. from the configuration of the host.
+///
+/// Inodes are opaque `u64`, the pre-opened directories are inodes 0, 1, ... in order.
+/// The methods are the ones of [`Wasip1LFS`]
+/// with the memory of the guest passed as `mem`.
+///
+/// [`VFSDynLFS`] serves a `Box<dyn Wasip1DynLFS>` as a [`Wasip1LFS`],
+/// [`VFSDynAdapter`] turns a [`Wasip1LFS`] into one.
+pub trait Wasip1DynLFS {
+    /// How many pre-opened directories there are.
+    fn pre_open_len(&self) -> usize;
+
+    fn fd_pwrite_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+        data: *const u8,
+        data_len: usize,
+        offset: usize,
+    ) -> Result<Size, wasip1::Errno>;
+
+    fn fd_write_stdout_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<Size, wasip1::Errno>;
+
+    fn fd_write_stderr_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<Size, wasip1::Errno>;
+
+    fn is_dir(&self, inode: u64) -> bool;
+
+    fn fd_readdir_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+        buf: *mut u8,
+        buf_len: usize,
+        cookie: Dircookie,
+    ) -> Result<(Size, Dircookie), wasip1::Errno>;
+
+    fn path_filestat_get_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno>;
+
+    fn fd_prestat_get_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+    ) -> Result<wasip1::Prestat, wasip1::Errno>;
+
+    fn fd_prestat_dir_name_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+        dir_path_ptr: *mut u8,
+        dir_path_len: usize,
+    ) -> Result<(), wasip1::Errno>;
+
+    fn fd_filestat_get_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno>;
+
+    #[allow(unused_variables)]
+    fn fd_close_raw(&mut self, mem: &dyn Wasip1DynMemory, inode: u64) {}
+
+    #[allow(unused_variables)]
+    fn fd_sync_raw(&mut self, mem: &dyn Wasip1DynMemory, inode: u64) -> Result<(), wasip1::Errno> {
+        Ok(())
+    }
+
+    fn fd_seek_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+        cursor: usize,
+        offset: wasip1::Filedelta,
+        whence: wasip1::Whence,
+    ) -> Result<usize, wasip1::Errno> {
+        let filestat = self.fd_filestat_get_raw(mem, inode)?;
+
+        seek_cursor(&filestat, cursor, offset, whence)
+    }
+
+    #[allow(unused_variables)]
+    fn fd_tell_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+        cursor: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        Ok(cursor)
+    }
+
+    #[allow(unused_variables)]
+    fn fd_filestat_set_times_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+        atim: Timestamp,
+        mtim: Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        Err(wasip1::ERRNO_PERM)
+    }
+
+    fn fd_pread_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+        buf: *mut u8,
+        buf_len: usize,
+        offset: usize,
+    ) -> Result<Size, wasip1::Errno>;
+
+    fn fd_read_stdin_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<Size, wasip1::Errno>;
+
+    #[allow(clippy::too_many_arguments)]
+    fn path_open_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        dir_ino: u64,
+        dir_flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        fs_rights_inheriting: wasip1::Rights,
+        fd_flags: wasip1::Fdflags,
+    ) -> Result<u64, wasip1::Errno>;
+
+    fn path_create_directory_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        dir_ino: u64,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno>;
+
+    fn path_remove_directory_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        dir_ino: u64,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno>;
+
+    fn path_unlink_file_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        dir_ino: u64,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno>;
+
+    #[allow(clippy::too_many_arguments)]
+    fn path_rename_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        old_dir_ino: u64,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_dir_ino: u64,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno>;
+
+    fn path_readlink_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        dir_ino: u64,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<Size, wasip1::Errno>;
+
+    fn path_symlink_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        dir_ino: u64,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno>;
+}
+
+/// Numbers inodes of another type in the order they are opened,
+/// a pre-opened directory is numbered by its position in the pre-opened ones,
+/// the first `RESERVED` numbers are kept for them.
+/// A number is freed when the last descriptor of its inode is closed
+/// and given to the next inode opened.
+pub(crate) struct InodeTable<Inode: Copy + Ord> {
+    reserved: usize,
+    /// inode `n` and how many times it is open are stored at `inodes[n - reserved]`,
+    /// `None` for a free number
+    inodes: Vec<Option<(Inode, usize)>>,
+    numbers: BTreeMap<Inode, usize>,
+    /// free indices of `inodes`
+    free: Vec<usize>,
+}
+
+impl<Inode: Copy + Ord> InodeTable<Inode> {
+    /// `reserved` is at least the number of pre-opened directories.
+    pub const fn new(reserved: usize) -> Self {
+        Self {
+            reserved,
+            inodes: Vec::new(),
+            numbers: BTreeMap::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, number: usize, pre_open: &[Inode]) -> Result<Inode, wasip1::Errno> {
+        if number < self.reserved {
+            return pre_open.get(number).copied().ok_or(wasip1::ERRNO_BADF);
+        }
+
+        match self.inodes.get(number - self.reserved) {
+            Some(Some((inode, _))) => Ok(*inode),
+            _ => Err(wasip1::ERRNO_BADF),
+        }
+    }
+
+    /// Numbers the inode for one more descriptor.
+    pub fn opened(&mut self, inode: Inode, pre_open: &[Inode]) -> usize {
+        if let Some(number) = pre_open.iter().position(|pre_open| *pre_open == inode) {
+            return number;
+        }
+
+        if let Some(number) = self.numbers.get(&inode) {
+            if let Some((_, opens)) = &mut self.inodes[number - self.reserved] {
+                *opens += 1;
+            }
+            return *number;
+        }
+
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.inodes[index] = Some((inode, 1));
+                index
+            }
+            None => {
+                self.inodes.push(Some((inode, 1)));
+                self.inodes.len() - 1
+            }
+        };
+
+        let number = self.reserved + index;
+        self.numbers.insert(inode, number);
+        number
+    }
+
+    /// One descriptor of the number is closed.
+    pub fn closed(&mut self, number: usize) {
+        let Some(index) = number.checked_sub(self.reserved) else {
+            return;
+        };
+
+        if let Some(Some((inode, opens))) = self.inodes.get_mut(index) {
+            *opens -= 1;
+            if *opens == 0 {
+                self.numbers.remove(inode);
+                self.inodes[index] = None;
+                self.free.push(index);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.inodes.clear();
+        self.numbers.clear();
+        self.free.clear();
+    }
+}
+
+/// Type erased file system
+/// which serves a [`Wasip1DynLFS`] chosen at runtime.
+///
+/// Its inodes are `usize` like the bundled LFS,
+/// so it can be mounted in a [`VFSMountLFS`](crate::wasi::file::mount::lfs::VFSMountLFS)
+/// and wrapped by the other layers.
+/// `PRE_OPENS` pre-opened directories are served,
+/// inodes 0 to `PRE_OPENS - 1` of the file system,
+/// a file system with fewer fails with `ERRNO_BADF` on the missing ones
+/// and the ones past `PRE_OPENS` are not seen.
+/// Until a file system is set, every call fails with `ERRNO_NOENT`,
+/// and a guest which started before does not see the pre-opened directories.
+///
+/// ```ignore
+/// static mut VIRTUAL_FILE_SYSTEM: Wasip1ConstVFS<VFSDynLFS, FLAT_LEN> =
+///     Wasip1ConstVFS::new(VFSDynLFS::new());
+///
+/// let lfs: Box<dyn Wasip1DynLFS> = match config.kind {
+///     Kind::Memory => VFSDynAdapter::boxed(VFSHeapLFS::<DefaultStdIO>::new("/data")),
+///     Kind::Host => VFSDynAdapter::boxed(VFSHostLFS::<DefaultStdIO>::new("/data", 3)),
+/// };
+/// unsafe { VIRTUAL_FILE_SYSTEM.lfs_mut() }.set(lfs);
+/// ```
+pub struct VFSDynLFS<const PRE_OPENS: usize = 1> {
+    lfs: Option<Box<dyn Wasip1DynLFS>>,
+    inodes: InodeTable<u64>,
+}
+
+impl<const PRE_OPENS: usize> VFSDynLFS<PRE_OPENS> {
+    /// Inodes of the pre-opened directories, here and in the file system.
+    pub(crate) const PRE_OPEN_INODES: [usize; PRE_OPENS] = {
+        let mut inodes = [0; PRE_OPENS];
+        let mut i = 0;
+        while i < PRE_OPENS {
+            inodes[i] = i;
+            i += 1;
+        }
+        inodes
+    };
+
+    const PRE_OPEN_INOS: [u64; PRE_OPENS] = {
+        let mut inos = [0; PRE_OPENS];
+        let mut i = 0;
+        while i < PRE_OPENS {
+            inos[i] = i as u64;
+            i += 1;
+        }
+        inos
+    };
+
+    pub const fn new() -> Self {
+        Self {
+            lfs: None,
+            inodes: InodeTable::new(PRE_OPENS),
+        }
+    }
+
+    pub fn with(lfs: Box<dyn Wasip1DynLFS>) -> Self {
+        let mut this = Self::new();
+        this.set(lfs);
+        this
+    }
+
+    /// Replaces the file system and returns the previous one.
+    /// Descriptors opened on the previous one are left dangling.
+    pub fn set(&mut self, lfs: Box<dyn Wasip1DynLFS>) -> Option<Box<dyn Wasip1DynLFS>> {
+        self.inodes.clear();
+        self.lfs.replace(lfs)
+    }
+
+    pub fn take(&mut self) -> Option<Box<dyn Wasip1DynLFS>> {
+        self.inodes.clear();
+        self.lfs.take()
+    }
+
+    #[inline]
+    pub fn get(&self) -> Option<&dyn Wasip1DynLFS> {
+        self.lfs.as_deref()
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut (dyn Wasip1DynLFS + 'static)> {
+        self.lfs.as_deref_mut()
+    }
+
+    /// The file system and the inode it knows the given one by.
+    pub(crate) fn lfs_for(
+        &mut self,
+        inode: usize,
+    ) -> Result<(&mut (dyn Wasip1DynLFS + 'static), u64), wasip1::Errno> {
+        let ino = self.ino(inode)?;
+        let lfs = self.lfs.as_deref_mut().ok_or(wasip1::ERRNO_NOENT)?;
+
+        Ok((lfs, ino))
+    }
+
+    pub(crate) fn lfs(&mut self) -> Result<&mut (dyn Wasip1DynLFS + 'static), wasip1::Errno> {
+        self.lfs.as_deref_mut().ok_or(wasip1::ERRNO_NOENT)
+    }
+
+    /// The pre-opened directories the file system has of the served ones,
+    /// its other inodes are its files. Without one, they are all empty.
+    fn pre_open_inos(&self) -> &'static [u64] {
+        let len = self
+            .lfs
+            .as_ref()
+            .map_or(PRE_OPENS, |lfs| lfs.pre_open_len());
+        &Self::PRE_OPEN_INOS[..core::cmp::min(len, PRE_OPENS)]
+    }
+
+    /// The inode the file system knows the given one by.
+    pub(crate) fn ino(&self, inode: usize) -> Result<u64, wasip1::Errno> {
+        self.inodes.get(inode, self.pre_open_inos())
+    }
+
+    pub(crate) fn opened(&mut self, ino: u64) -> usize {
+        let pre_open = self.pre_open_inos();
+        self.inodes.opened(ino, pre_open)
+    }
+
+    #[inline]
+    pub(crate) fn closed(&mut self, inode: usize) {
+        self.inodes.closed(inode);
+    }
+}
+
+impl<const PRE_OPENS: usize> Default for VFSDynLFS<PRE_OPENS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// [`Wasip1DynLFS`] serving a [`Wasip1LFS`],
+/// e.g. to put a heap LFS behind a `Box<dyn Wasip1DynLFS>`.
+///
+/// The pre-opened directories of `Inner` are inodes 0, 1, ... in order.
+/// Guest memory is copied through buffers of the layer,
+/// since `Inner` is called with the memory of the layer itself.
+pub struct VFSDynAdapter<Inner: Wasip1LFS>
+where
+    Inner::Inode: Copy + Ord,
+{
+    inner: Inner,
+    inodes: InodeTable<Inner::Inode>,
+}
+
+impl<Inner: Wasip1LFS> VFSDynAdapter<Inner>
+where
+    Inner::Inode: Copy + Ord,
+{
+    pub const fn new(inner: Inner) -> Self {
+        Self {
+            inner,
+            inodes: InodeTable::new(Inner::PRE_OPEN.len()),
+        }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &Inner {
+        &self.inner
+    }
+
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut Inner {
+        &mut self.inner
+    }
+
+    pub(crate) fn inode(&self, ino: u64) -> Result<Inner::Inode, wasip1::Errno> {
+        let number = usize::try_from(ino).map_err(|_| wasip1::ERRNO_BADF)?;
+        self.inodes.get(number, Inner::PRE_OPEN)
+    }
+
+    pub(crate) fn opened(&mut self, inode: Inner::Inode) -> u64 {
+        self.inodes.opened(inode, Inner::PRE_OPEN) as u64
+    }
+
+    pub(crate) fn closed(&mut self, ino: u64) {
+        if let Ok(number) = usize::try_from(ino) {
+            self.inodes.closed(number);
+        }
+    }
+}
+
+impl<Inner: Wasip1LFS + 'static> VFSDynAdapter<Inner>
+where
+    Inner::Inode: Copy + Ord,
+{
+    pub fn boxed(inner: Inner) -> Box<dyn Wasip1DynLFS> {
+        Box::new(Self::new(inner))
+    }
+}
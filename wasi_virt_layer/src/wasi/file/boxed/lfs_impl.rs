@@ -0,0 +1,821 @@
+use alloc::vec;
+
+use crate::__private::wasip1;
+use crate::__private::wasip1::{Dircookie, Size, Timestamp};
+use crate::{
+    memory::{HostMemory, WasmAccess},
+    wasi::file::{
+        FilestatWithoutDevice, Wasip1LFS,
+        boxed::lfs::{DynWasmAccess, VFSDynAdapter, VFSDynLFS, Wasip1DynLFS, Wasip1DynMemory},
+    },
+};
+
+impl<const PRE_OPENS: usize> Wasip1LFS for VFSDynLFS<PRE_OPENS> {
+    type Inode = usize;
+    const PRE_OPEN: &'static [Self::Inode] = &Self::PRE_OPEN_INODES;
+
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        data: *const u8,
+        data_len: usize,
+        offset: usize,
+    ) -> Result<Size, wasip1::Errno> {
+        let (lfs, ino) = self.lfs_for(inode)?;
+        lfs.fd_pwrite_raw(&DynWasmAccess::<Wasm>::new(), ino, data, data_len, offset)
+    }
+
+    fn fd_write_stdout_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<Size, wasip1::Errno> {
+        self.lfs()?
+            .fd_write_stdout_raw(&DynWasmAccess::<Wasm>::new(), data, data_len)
+    }
+
+    fn fd_write_stderr_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<Size, wasip1::Errno> {
+        self.lfs()?
+            .fd_write_stderr_raw(&DynWasmAccess::<Wasm>::new(), data, data_len)
+    }
+
+    fn is_dir(&self, inode: Self::Inode) -> bool {
+        match (self.get(), self.ino(inode)) {
+            (Some(lfs), Ok(ino)) => lfs.is_dir(ino),
+            _ => false,
+        }
+    }
+
+    fn fd_readdir_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        cookie: Dircookie,
+    ) -> Result<(Size, Dircookie), wasip1::Errno> {
+        let (lfs, ino) = self.lfs_for(inode)?;
+        lfs.fd_readdir_raw(&DynWasmAccess::<Wasm>::new(), ino, buf, buf_len, cookie)
+    }
+
+    fn path_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let (lfs, ino) = self.lfs_for(inode)?;
+        lfs.path_filestat_get_raw(
+            &DynWasmAccess::<Wasm>::new(),
+            ino,
+            flags,
+            path_ptr,
+            path_len,
+        )
+    }
+
+    fn fd_prestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<wasip1::Prestat, wasip1::Errno> {
+        let (lfs, ino) = self.lfs_for(inode)?;
+        lfs.fd_prestat_get_raw(&DynWasmAccess::<Wasm>::new(), ino)
+    }
+
+    fn fd_prestat_dir_name_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        dir_path_ptr: *mut u8,
+        dir_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (lfs, ino) = self.lfs_for(inode)?;
+        lfs.fd_prestat_dir_name_raw(
+            &DynWasmAccess::<Wasm>::new(),
+            ino,
+            dir_path_ptr,
+            dir_path_len,
+        )
+    }
+
+    fn fd_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let (lfs, ino) = self.lfs_for(inode)?;
+        lfs.fd_filestat_get_raw(&DynWasmAccess::<Wasm>::new(), ino)
+    }
+
+    fn fd_close_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) {
+        if let Ok((lfs, ino)) = self.lfs_for(inode) {
+            lfs.fd_close_raw(&DynWasmAccess::<Wasm>::new(), ino);
+            self.closed(inode);
+        }
+    }
+
+    fn fd_sync_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) -> Result<(), wasip1::Errno> {
+        let (lfs, ino) = self.lfs_for(inode)?;
+        lfs.fd_sync_raw(&DynWasmAccess::<Wasm>::new(), ino)
+    }
+
+    fn fd_seek_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        cursor: usize,
+        offset: wasip1::Filedelta,
+        whence: wasip1::Whence,
+    ) -> Result<usize, wasip1::Errno> {
+        let (lfs, ino) = self.lfs_for(inode)?;
+        lfs.fd_seek_raw(&DynWasmAccess::<Wasm>::new(), ino, cursor, offset, whence)
+    }
+
+    fn fd_tell_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        cursor: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        let (lfs, ino) = self.lfs_for(inode)?;
+        lfs.fd_tell_raw(&DynWasmAccess::<Wasm>::new(), ino, cursor)
+    }
+
+    fn fd_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        atim: Timestamp,
+        mtim: Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        let (lfs, ino) = self.lfs_for(inode)?;
+        lfs.fd_filestat_set_times_raw(&DynWasmAccess::<Wasm>::new(), ino, atim, mtim, fst_flags)
+    }
+
+    fn fd_pread_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        offset: usize,
+    ) -> Result<Size, wasip1::Errno> {
+        let (lfs, ino) = self.lfs_for(inode)?;
+        lfs.fd_pread_raw(&DynWasmAccess::<Wasm>::new(), ino, buf, buf_len, offset)
+    }
+
+    fn fd_read_stdin_raw<Wasm: WasmAccess>(
+        &mut self,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<Size, wasip1::Errno> {
+        self.lfs()?
+            .fd_read_stdin_raw(&DynWasmAccess::<Wasm>::new(), buf, buf_len)
+    }
+
+    fn path_open_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        dir_flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        fs_rights_inheriting: wasip1::Rights,
+        fd_flags: wasip1::Fdflags,
+    ) -> Result<Self::Inode, wasip1::Errno> {
+        let (lfs, dir) = self.lfs_for(dir_ino)?;
+        let ino = lfs.path_open_raw(
+            &DynWasmAccess::<Wasm>::new(),
+            dir,
+            dir_flags,
+            path_ptr,
+            path_len,
+            o_flags,
+            fs_rights_base,
+            fs_rights_inheriting,
+            fd_flags,
+        )?;
+
+        Ok(self.opened(ino))
+    }
+
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (lfs, dir) = self.lfs_for(dir_ino)?;
+        lfs.path_create_directory_raw(&DynWasmAccess::<Wasm>::new(), dir, path_ptr, path_len)
+    }
+
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (lfs, dir) = self.lfs_for(dir_ino)?;
+        lfs.path_remove_directory_raw(&DynWasmAccess::<Wasm>::new(), dir, path_ptr, path_len)
+    }
+
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (lfs, dir) = self.lfs_for(dir_ino)?;
+        lfs.path_unlink_file_raw(&DynWasmAccess::<Wasm>::new(), dir, path_ptr, path_len)
+    }
+
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_dir_ino: Self::Inode,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_dir_ino: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let new_dir = self.ino(new_dir_ino)?;
+        let (lfs, old_dir) = self.lfs_for(old_dir_ino)?;
+        lfs.path_rename_raw(
+            &DynWasmAccess::<Wasm>::new(),
+            old_dir,
+            old_path_ptr,
+            old_path_len,
+            new_dir,
+            new_path_ptr,
+            new_path_len,
+        )
+    }
+
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<Size, wasip1::Errno> {
+        let (lfs, dir) = self.lfs_for(dir_ino)?;
+        lfs.path_readlink_raw(
+            &DynWasmAccess::<Wasm>::new(),
+            dir,
+            path_ptr,
+            path_len,
+            buf,
+            buf_len,
+        )
+    }
+
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        dir_ino: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (lfs, dir) = self.lfs_for(dir_ino)?;
+        lfs.path_symlink_raw(
+            &DynWasmAccess::<Wasm>::new(),
+            old_path_ptr,
+            old_path_len,
+            dir,
+            new_path_ptr,
+            new_path_len,
+        )
+    }
+}
+
+/// `Inner` reads and writes buffers of this layer through `HostMemory`,
+/// which are copied from and to the guest with `mem`.
+impl<Inner: Wasip1LFS> Wasip1DynLFS for VFSDynAdapter<Inner>
+where
+    Inner::Inode: Copy + Ord,
+{
+    fn fd_pwrite_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+        data: *const u8,
+        data_len: usize,
+        offset: usize,
+    ) -> Result<Size, wasip1::Errno> {
+        let inode = self.inode(inode)?;
+        let data = mem.get_array(data, data_len);
+
+        self.inner_mut()
+            .fd_pwrite_raw::<HostMemory>(inode, data.as_ptr(), data.len(), offset)
+    }
+
+    fn fd_write_stdout_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<Size, wasip1::Errno> {
+        let data = mem.get_array(data, data_len);
+
+        self.inner_mut()
+            .fd_write_stdout_raw::<HostMemory>(data.as_ptr(), data.len())
+    }
+
+    fn fd_write_stderr_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<Size, wasip1::Errno> {
+        let data = mem.get_array(data, data_len);
+
+        self.inner_mut()
+            .fd_write_stderr_raw::<HostMemory>(data.as_ptr(), data.len())
+    }
+
+    fn pre_open_len(&self) -> usize {
+        Inner::PRE_OPEN.len()
+    }
+
+    fn is_dir(&self, inode: u64) -> bool {
+        self.inode(inode)
+            .is_ok_and(|inode| self.inner().is_dir(inode))
+    }
+
+    fn fd_readdir_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+        buf: *mut u8,
+        buf_len: usize,
+        cookie: Dircookie,
+    ) -> Result<(Size, Dircookie), wasip1::Errno> {
+        let inode = self.inode(inode)?;
+        let mut entries = vec![0u8; buf_len];
+
+        let (used, next) = self.inner_mut().fd_readdir_raw::<HostMemory>(
+            inode,
+            entries.as_mut_ptr(),
+            buf_len,
+            cookie,
+        )?;
+        mem.memcpy(buf, &entries[..used]);
+
+        Ok((used, next))
+    }
+
+    fn path_filestat_get_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let inode = self.inode(inode)?;
+        let path = mem.get_array(path_ptr, path_len);
+
+        self.inner_mut().path_filestat_get_raw::<HostMemory>(
+            inode,
+            flags,
+            path.as_ptr(),
+            path.len(),
+        )
+    }
+
+    fn fd_prestat_get_raw(
+        &mut self,
+        _: &dyn Wasip1DynMemory,
+        inode: u64,
+    ) -> Result<wasip1::Prestat, wasip1::Errno> {
+        let inode = self.inode(inode)?;
+
+        self.inner_mut().fd_prestat_get_raw::<HostMemory>(inode)
+    }
+
+    fn fd_prestat_dir_name_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+        dir_path_ptr: *mut u8,
+        dir_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let inode = self.inode(inode)?;
+        let mut name = vec![0u8; dir_path_len];
+
+        self.inner_mut().fd_prestat_dir_name_raw::<HostMemory>(
+            inode,
+            name.as_mut_ptr(),
+            dir_path_len,
+        )?;
+        mem.memcpy(dir_path_ptr, &name);
+
+        Ok(())
+    }
+
+    fn fd_filestat_get_raw(
+        &mut self,
+        _: &dyn Wasip1DynMemory,
+        inode: u64,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let inode = self.inode(inode)?;
+
+        self.inner_mut().fd_filestat_get_raw::<HostMemory>(inode)
+    }
+
+    fn fd_close_raw(&mut self, _: &dyn Wasip1DynMemory, inode: u64) {
+        if let Ok(inner) = self.inode(inode) {
+            self.inner_mut().fd_close_raw::<HostMemory>(inner);
+            self.closed(inode);
+        }
+    }
+
+    fn fd_sync_raw(&mut self, _: &dyn Wasip1DynMemory, inode: u64) -> Result<(), wasip1::Errno> {
+        let inode = self.inode(inode)?;
+
+        self.inner_mut().fd_sync_raw::<HostMemory>(inode)
+    }
+
+    fn fd_seek_raw(
+        &mut self,
+        _: &dyn Wasip1DynMemory,
+        inode: u64,
+        cursor: usize,
+        offset: wasip1::Filedelta,
+        whence: wasip1::Whence,
+    ) -> Result<usize, wasip1::Errno> {
+        let inode = self.inode(inode)?;
+
+        self.inner_mut()
+            .fd_seek_raw::<HostMemory>(inode, cursor, offset, whence)
+    }
+
+    fn fd_tell_raw(
+        &mut self,
+        _: &dyn Wasip1DynMemory,
+        inode: u64,
+        cursor: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        let inode = self.inode(inode)?;
+
+        self.inner_mut().fd_tell_raw::<HostMemory>(inode, cursor)
+    }
+
+    fn fd_filestat_set_times_raw(
+        &mut self,
+        _: &dyn Wasip1DynMemory,
+        inode: u64,
+        atim: Timestamp,
+        mtim: Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        let inode = self.inode(inode)?;
+
+        self.inner_mut()
+            .fd_filestat_set_times_raw::<HostMemory>(inode, atim, mtim, fst_flags)
+    }
+
+    fn fd_pread_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        inode: u64,
+        buf: *mut u8,
+        buf_len: usize,
+        offset: usize,
+    ) -> Result<Size, wasip1::Errno> {
+        let inode = self.inode(inode)?;
+        let mut data = vec![0u8; buf_len];
+
+        let read = self.inner_mut().fd_pread_raw::<HostMemory>(
+            inode,
+            data.as_mut_ptr(),
+            buf_len,
+            offset,
+        )?;
+        mem.memcpy(buf, &data[..read]);
+
+        Ok(read)
+    }
+
+    fn fd_read_stdin_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<Size, wasip1::Errno> {
+        let mut data = vec![0u8; buf_len];
+
+        let read = self
+            .inner_mut()
+            .fd_read_stdin_raw::<HostMemory>(data.as_mut_ptr(), buf_len)?;
+        mem.memcpy(buf, &data[..read]);
+
+        Ok(read)
+    }
+
+    fn path_open_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        dir_ino: u64,
+        dir_flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        fs_rights_inheriting: wasip1::Rights,
+        fd_flags: wasip1::Fdflags,
+    ) -> Result<u64, wasip1::Errno> {
+        let dir = self.inode(dir_ino)?;
+        let path = mem.get_array(path_ptr, path_len);
+
+        let inode = self.inner_mut().path_open_raw::<HostMemory>(
+            dir,
+            dir_flags,
+            path.as_ptr(),
+            path.len(),
+            o_flags,
+            fs_rights_base,
+            fs_rights_inheriting,
+            fd_flags,
+        )?;
+
+        Ok(self.opened(inode))
+    }
+
+    fn path_create_directory_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        dir_ino: u64,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let dir = self.inode(dir_ino)?;
+        let path = mem.get_array(path_ptr, path_len);
+
+        self.inner_mut()
+            .path_create_directory_raw::<HostMemory>(dir, path.as_ptr(), path.len())
+    }
+
+    fn path_remove_directory_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        dir_ino: u64,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let dir = self.inode(dir_ino)?;
+        let path = mem.get_array(path_ptr, path_len);
+
+        self.inner_mut()
+            .path_remove_directory_raw::<HostMemory>(dir, path.as_ptr(), path.len())
+    }
+
+    fn path_unlink_file_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        dir_ino: u64,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let dir = self.inode(dir_ino)?;
+        let path = mem.get_array(path_ptr, path_len);
+
+        self.inner_mut()
+            .path_unlink_file_raw::<HostMemory>(dir, path.as_ptr(), path.len())
+    }
+
+    fn path_rename_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        old_dir_ino: u64,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_dir_ino: u64,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let old_dir = self.inode(old_dir_ino)?;
+        let new_dir = self.inode(new_dir_ino)?;
+        let old_path = mem.get_array(old_path_ptr, old_path_len);
+        let new_path = mem.get_array(new_path_ptr, new_path_len);
+
+        self.inner_mut().path_rename_raw::<HostMemory>(
+            old_dir,
+            old_path.as_ptr(),
+            old_path.len(),
+            new_dir,
+            new_path.as_ptr(),
+            new_path.len(),
+        )
+    }
+
+    fn path_readlink_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        dir_ino: u64,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<Size, wasip1::Errno> {
+        let dir = self.inode(dir_ino)?;
+        let path = mem.get_array(path_ptr, path_len);
+        let mut target = vec![0u8; buf_len];
+
+        let read = self.inner_mut().path_readlink_raw::<HostMemory>(
+            dir,
+            path.as_ptr(),
+            path.len(),
+            target.as_mut_ptr(),
+            buf_len,
+        )?;
+        mem.memcpy(buf, &target[..read]);
+
+        Ok(read)
+    }
+
+    fn path_symlink_raw(
+        &mut self,
+        mem: &dyn Wasip1DynMemory,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        dir_ino: u64,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let dir = self.inode(dir_ino)?;
+        let target = mem.get_array(old_path_ptr, old_path_len);
+        let path = mem.get_array(new_path_ptr, new_path_len);
+
+        self.inner_mut().path_symlink_raw::<HostMemory>(
+            target.as_ptr(),
+            target.len(),
+            dir,
+            path.as_ptr(),
+            path.len(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, vec::Vec};
+
+    use super::*;
+    use crate::{
+        mock::MockWasm,
+        wasi::file::{
+            dirent::DirentReader,
+            heap::lfs::VFSHeapLFS,
+            mount::lfs::VFSMountLFS,
+            quota::lfs::{VFSQuotaLFS, VFSQuotaLimits},
+            stdio::DefaultStdIO,
+        },
+    };
+
+    fn heap(name: &'static str) -> Box<dyn Wasip1DynLFS> {
+        VFSDynAdapter::boxed(VFSHeapLFS::<DefaultStdIO>::new(name))
+    }
+
+    #[test]
+    fn test_dyn_lfs() {
+        let mut lfs = VFSDynLFS::<1>::new();
+        assert_eq!(
+            MockWasm::lfs_open(&mut lfs, 0, "a.txt", 0),
+            Err(wasip1::ERRNO_NOENT)
+        );
+
+        lfs.set(heap("/data"));
+        assert_eq!(
+            MockWasm::lfs_prestat_name(&mut lfs, 0).as_deref(),
+            Ok("/data")
+        );
+
+        let inode = MockWasm::lfs_open(&mut lfs, 0, "a.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(MockWasm::lfs_write(&mut lfs, inode, b"Hello"), Ok(5));
+        assert_eq!(MockWasm::lfs_read(&mut lfs, inode, 64).unwrap(), b"Hello");
+        assert_eq!(MockWasm::lfs_open(&mut lfs, 0, "a.txt", 0), Ok(inode));
+
+        let mut buf = [0u8; 256];
+        let (used, _) = lfs
+            .fd_readdir_raw::<HostMemory>(0, buf.as_mut_ptr(), buf.len(), 0)
+            .unwrap();
+        let names = DirentReader::new(&buf[..used])
+            .map(|(_, _, _, name)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, [&b"."[..], b"..", b"a.txt"]);
+
+        // replaced at runtime, the old inodes are gone
+        lfs.set(heap("/other"));
+        assert_eq!(
+            MockWasm::lfs_prestat_name(&mut lfs, 0).as_deref(),
+            Ok("/other")
+        );
+        assert_eq!(
+            lfs.fd_filestat_get_raw::<HostMemory>(inode).err(),
+            Some(wasip1::ERRNO_BADF)
+        );
+    }
+
+    #[test]
+    fn test_dyn_lfs_stacked() {
+        // a quota over a boxed heap LFS, boxed again
+        let quota = VFSQuotaLFS::new(
+            VFSDynLFS::<1>::with(heap("/tmp")),
+            VFSQuotaLimits {
+                file_bytes: 3,
+                ..VFSQuotaLimits::UNLIMITED
+            },
+        );
+
+        let mut mounts = VFSMountLFS::<(VFSDynLFS, VFSDynLFS)>::new(
+            (VFSDynLFS::with(heap("/data")), VFSDynLFS::new()),
+            &["/data", "/tmp"],
+        );
+        mounts.mounts_mut().1.set(VFSDynAdapter::boxed(quota));
+
+        let pre_open = VFSMountLFS::<(VFSDynLFS, VFSDynLFS)>::PRE_OPEN;
+        assert_eq!(
+            MockWasm::lfs_prestat_name(&mut mounts, pre_open[0]).as_deref(),
+            Ok("/data")
+        );
+        assert_eq!(
+            MockWasm::lfs_prestat_name(&mut mounts, pre_open[1]).as_deref(),
+            Ok("/tmp")
+        );
+
+        let inode =
+            MockWasm::lfs_open(&mut mounts, pre_open[1], "a.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(MockWasm::lfs_write(&mut mounts, inode, b"Hello"), Ok(3));
+        assert_eq!(MockWasm::lfs_read(&mut mounts, inode, 64).unwrap(), b"Hel");
+
+        let inode =
+            MockWasm::lfs_open(&mut mounts, pre_open[0], "a.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(MockWasm::lfs_write(&mut mounts, inode, b"Hello"), Ok(5));
+    }
+
+    #[test]
+    fn test_dyn_lfs_inode_reuse() {
+        let mut lfs = VFSDynLFS::<1>::with(heap("/data"));
+
+        let a = MockWasm::lfs_open(&mut lfs, 0, "a.txt", wasip1::OFLAGS_CREAT).unwrap();
+        let b = MockWasm::lfs_open(&mut lfs, 0, "b.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(MockWasm::lfs_open(&mut lfs, 0, "a.txt", 0), Ok(a));
+        MockWasm::lfs_write(&mut lfs, a, b"a").unwrap();
+
+        // the number stays until the last descriptor is closed
+        lfs.fd_close_raw::<HostMemory>(a);
+        assert_eq!(MockWasm::lfs_read(&mut lfs, a, 64).unwrap(), b"a");
+        lfs.fd_close_raw::<HostMemory>(a);
+        assert_eq!(
+            lfs.fd_filestat_get_raw::<HostMemory>(a).err(),
+            Some(wasip1::ERRNO_BADF)
+        );
+
+        let c = MockWasm::lfs_open(&mut lfs, 0, "c.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(c, a);
+        assert_ne!(c, b);
+        assert_eq!(MockWasm::lfs_read(&mut lfs, c, 64).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_dyn_lfs_pre_opens() {
+        type Mounts = VFSMountLFS<(VFSHeapLFS<DefaultStdIO>, VFSHeapLFS<DefaultStdIO>)>;
+        let mounts = Mounts::new(
+            (VFSHeapLFS::new("/data"), VFSHeapLFS::new("/tmp")),
+            &["/data", "/tmp"],
+        );
+
+        let mut lfs = VFSDynLFS::<2>::with(VFSDynAdapter::boxed(mounts));
+        assert_eq!(VFSDynLFS::<2>::PRE_OPEN, [0, 1]);
+        assert_eq!(
+            MockWasm::lfs_prestat_name(&mut lfs, 0).as_deref(),
+            Ok("/data")
+        );
+        assert_eq!(
+            MockWasm::lfs_prestat_name(&mut lfs, 1).as_deref(),
+            Ok("/tmp")
+        );
+
+        let inode = MockWasm::lfs_open(&mut lfs, 1, "a.txt", wasip1::OFLAGS_CREAT).unwrap();
+        MockWasm::lfs_write(&mut lfs, inode, b"tmp").unwrap();
+        assert_eq!(
+            MockWasm::lfs_open(&mut lfs, 0, "a.txt", 0),
+            Err(wasip1::ERRNO_NOENT)
+        );
+
+        // a third one is not in the boxed file system, even once a file took its number
+        let mut lfs = VFSDynLFS::<3>::with(VFSDynAdapter::boxed(Mounts::new(
+            (VFSHeapLFS::new("/data"), VFSHeapLFS::new("/tmp")),
+            &["/data", "/tmp"],
+        )));
+        let inode = MockWasm::lfs_open(&mut lfs, 0, "a.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(inode, 3);
+        assert_eq!(
+            lfs.fd_prestat_get_raw::<HostMemory>(2).err(),
+            Some(wasip1::ERRNO_BADF)
+        );
+    }
+}
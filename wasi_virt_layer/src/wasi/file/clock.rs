@@ -0,0 +1,71 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::__private::wasip1;
+use crate::__private::wasip1::Timestamp;
+
+/// Where the file systems take the time from
+/// when a read updates the access time or a write the modification time,
+/// and for `FSTFLAGS_ATIM_NOW` / `FSTFLAGS_MTIM_NOW`.
+pub trait Wasip1Clock {
+    /// Nanoseconds since 1970-01-01T00:00:00Z.
+    fn now() -> Timestamp;
+}
+
+/// The realtime clock of the host.
+pub struct DefaultClock;
+
+impl Wasip1Clock for DefaultClock {
+    fn now() -> Timestamp {
+        #[cfg(target_os = "wasi")]
+        {
+            use crate::transporter::Wasip1Transporter;
+
+            Wasip1Transporter::clock_time_get(wasip1::CLOCKID_REALTIME, 1).unwrap_or(0)
+        }
+
+        #[cfg(all(not(target_os = "wasi"), feature = "std"))]
+        {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |time| time.as_nanos() as Timestamp)
+        }
+
+        #[cfg(all(not(target_os = "wasi"), not(feature = "std")))]
+        {
+            0
+        }
+    }
+}
+
+/// A virtual clock which moves one nanosecond each time it is read,
+/// so the timestamps only tell the order of the accesses
+/// and the same run gives the same timestamps.
+pub struct CounterClock;
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl Wasip1Clock for CounterClock {
+    fn now() -> Timestamp {
+        COUNTER.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+/// The times `fd_filestat_set_times` asks for, `None` keeps the current one.
+/// `ERRNO_INVAL` if a time is both given and asked to be now.
+pub fn times_to_set<Clock: Wasip1Clock>(
+    atim: Timestamp,
+    mtim: Timestamp,
+    fst_flags: wasip1::Fstflags,
+) -> Result<(Option<Timestamp>, Option<Timestamp>), wasip1::Errno> {
+    let time = |given, now, time| match (fst_flags & given != 0, fst_flags & now != 0) {
+        (true, true) => Err(wasip1::ERRNO_INVAL),
+        (true, false) => Ok(Some(time)),
+        (false, true) => Ok(Some(Clock::now())),
+        (false, false) => Ok(None),
+    };
+
+    Ok((
+        time(wasip1::FSTFLAGS_ATIM, wasip1::FSTFLAGS_ATIM_NOW, atim)?,
+        time(wasip1::FSTFLAGS_MTIM, wasip1::FSTFLAGS_MTIM_NOW, mtim)?,
+    ))
+}
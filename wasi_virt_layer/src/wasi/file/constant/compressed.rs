@@ -0,0 +1,275 @@
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use alloc::vec::Vec;
+
+use crate::__private::wasip1;
+use crate::{memory::WasmAccess, wasi::file::Wasip1FileTrait};
+
+/// A const file compressed at build time by [`include_compressed!`](crate::include_compressed)
+/// or the `@compressed` entry of [`ConstFiles!`](crate::ConstFiles).
+///
+/// The contents are split into blocks deflated one by one,
+/// so a read decompresses only the blocks it touches.
+///
+/// The last decompressed block is cached in one slot
+/// shared by every compressed file of the program.
+/// Reads alternating between two files decompress on each read,
+/// and a read while another thread holds the slot
+/// decompresses its block again without caching it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WasiConstCompressedFile {
+    /// deflated blocks, or the contents if `blocks` is empty
+    data: &'static [u8],
+    /// end of each block in `data`
+    blocks: &'static [usize],
+    /// size of a block before compression
+    block_size: usize,
+    size: usize,
+}
+
+/// The last decompressed block of any compressed file.
+/// A read finding it locked does not wait,
+/// it decompresses the block into a buffer of its own.
+struct BlockCache {
+    lock: AtomicBool,
+    /// data pointer, block index and the decompressed block
+    block: UnsafeCell<(usize, usize, Vec<u8>)>,
+}
+
+unsafe impl Sync for BlockCache {}
+
+static CACHE: BlockCache = BlockCache {
+    lock: AtomicBool::new(false),
+    block: UnsafeCell::new((0, usize::MAX, Vec::new())),
+};
+
+impl WasiConstCompressedFile {
+    pub const fn new(
+        data: &'static [u8],
+        blocks: &'static [usize],
+        block_size: usize,
+        size: usize,
+    ) -> Self {
+        Self {
+            data,
+            blocks,
+            block_size,
+            size,
+        }
+    }
+
+    /// Keeps the contents as is,
+    /// for files which compression does not make smaller.
+    pub const fn raw(data: &'static [u8]) -> Self {
+        Self {
+            data,
+            blocks: &[],
+            block_size: data.len(),
+            size: data.len(),
+        }
+    }
+
+    #[inline]
+    pub const fn is_compressed(&self) -> bool {
+        !self.blocks.is_empty()
+    }
+
+    fn decompress(&self, index: usize, out: &mut Vec<u8>) -> Result<(), wasip1::Errno> {
+        let start = match index {
+            0 => 0,
+            _ => self.blocks[index - 1],
+        };
+        let len = core::cmp::min(self.block_size, self.size - index * self.block_size);
+
+        out.clear();
+        out.resize(len, 0);
+
+        match miniz_oxide::inflate::decompress_slice_iter_to_slice(
+            out,
+            core::iter::once(&self.data[start..self.blocks[index]]),
+            false,
+            true,
+        ) {
+            Ok(nread) if nread == len => Ok(()),
+            _ => Err(wasip1::ERRNO_IO),
+        }
+    }
+
+    /// Calls `f` with the decompressed block.
+    fn with_block<R>(&self, index: usize, f: impl FnOnce(&[u8]) -> R) -> Result<R, wasip1::Errno> {
+        if CACHE
+            .lock
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            let mut block = Vec::new();
+            self.decompress(index, &mut block)?;
+            return Ok(f(&block));
+        }
+
+        let cache = unsafe { &mut *CACHE.block.get() };
+        let key = (self.data.as_ptr() as usize, index);
+
+        let result = if (cache.0, cache.1) == key {
+            Ok(f(&cache.2))
+        } else {
+            cache.1 = usize::MAX;
+            self.decompress(index, &mut cache.2).map(|()| {
+                (cache.0, cache.1) = key;
+                f(&cache.2)
+            })
+        };
+
+        CACHE.lock.store(false, Ordering::Release);
+
+        result
+    }
+
+    /// Calls `copy` with the position in the buffer and the contents at it,
+    /// from `offset` to `offset + len` or the end of the file.
+    fn read_with(
+        &self,
+        offset: usize,
+        len: usize,
+        mut copy: impl FnMut(usize, &[u8]),
+    ) -> Result<usize, wasip1::Errno> {
+        let end = core::cmp::min(offset.saturating_add(len), self.size);
+        if offset >= end {
+            return Ok(0);
+        }
+
+        if !self.is_compressed() {
+            copy(0, &self.data[offset..end]);
+            return Ok(end - offset);
+        }
+
+        let mut pos = offset;
+        while pos < end {
+            let index = pos / self.block_size;
+            let from = pos - index * self.block_size;
+
+            pos += self.with_block(index, |block| {
+                let n = core::cmp::min(block.len() - from, end - pos);
+                copy(pos - offset, &block[from..from + n]);
+                n
+            })?;
+        }
+
+        Ok(end - offset)
+    }
+}
+
+impl Wasip1FileTrait for WasiConstCompressedFile {
+    #[inline]
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn pread(&self, buf: &mut [u8], offset: usize) -> Result<usize, wasip1::Errno> {
+        self.read_with(offset, buf.len(), |pos, bytes| {
+            buf[pos..pos + bytes.len()].copy_from_slice(bytes)
+        })
+    }
+
+    fn pread_raw<Wasm: WasmAccess>(
+        &self,
+        buf_ptr: *mut u8,
+        buf_len: usize,
+        offset: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        self.read_with(offset, buf_len, |pos, bytes| {
+            Wasm::memcpy(buf_ptr.wrapping_add(pos), bytes)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use const_struct::const_struct;
+
+    use super::*;
+    use crate::{
+        ConstFiles, const_files,
+        memory::HostMemory,
+        wasi::file::{
+            Wasip1LFS,
+            constant::{
+                lfs::VFSConstNormalLFS,
+                lfs_raw::{VFSConstNormalFiles, VFSConstNormalFilesLen},
+            },
+            stdio::DefaultStdIO,
+        },
+    };
+
+    const LINES: &str = include_str!("../../../../test_assets/lines.txt");
+
+    #[const_files]
+    #[const_struct]
+    const FILES: VFSConstNormalFiles<WasiConstCompressedFile, _> = ConstFiles!([(
+        ".",
+        [
+            ("lines.txt", @compressed "test_assets/lines.txt", 1000),
+            ("short.txt", @compressed "test_assets/embed/hello.txt"),
+        ]
+    )]);
+
+    type Lfs =
+        VFSConstNormalLFS<FilesTy, WasiConstCompressedFile, { FilesTy::FLAT_LEN }, DefaultStdIO>;
+
+    #[test]
+    fn test_compressed_pread() {
+        let file = crate::include_compressed!("test_assets/lines.txt", 1000);
+        assert!(file.is_compressed());
+        assert_eq!(file.size(), LINES.len());
+
+        // across the blocks
+        let mut buf = [0u8; 2500];
+        assert_eq!(file.pread(&mut buf, 900), Ok(2500));
+        assert_eq!(&buf[..], &LINES.as_bytes()[900..3400]);
+
+        // the tail of the last block
+        let mut buf = [0u8; 64];
+        let tail = LINES.len() - 10;
+        assert_eq!(file.pread(&mut buf, tail), Ok(10));
+        assert_eq!(&buf[..10], &LINES.as_bytes()[tail..]);
+        assert_eq!(file.pread(&mut buf, LINES.len()), Ok(0));
+
+        // too short to be compressed
+        let short = crate::include_compressed!("test_assets/embed/hello.txt");
+        assert!(!short.is_compressed());
+        assert_eq!(short.pread(&mut buf, 1), Ok(5));
+        assert_eq!(&buf[..5], b"ello!");
+    }
+
+    #[test]
+    fn test_compressed_lfs() {
+        let mut lfs = Lfs::new();
+        let root = Lfs::PRE_OPEN[0];
+
+        let path = "lines.txt";
+        let inode = lfs
+            .path_open_raw::<HostMemory>(
+                root,
+                0,
+                path.as_ptr(),
+                path.len(),
+                0,
+                wasip1::RIGHTS_FD_READ,
+                0,
+                0,
+            )
+            .unwrap();
+
+        let stat = lfs.fd_filestat_get_raw::<HostMemory>(inode).unwrap();
+        assert_eq!(stat.size as usize, LINES.len());
+
+        let mut buf = [0u8; 1500];
+        let nread = lfs
+            .fd_pread_raw::<HostMemory>(inode, buf.as_mut_ptr(), buf.len(), 3000)
+            .unwrap();
+        assert_eq!(&buf[..nread], &LINES.as_bytes()[3000..4500]);
+    }
+}
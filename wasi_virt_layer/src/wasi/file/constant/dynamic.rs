@@ -0,0 +1,193 @@
+use alloc::vec::Vec;
+
+use crate::__private::wasip1;
+use crate::wasi::file::Wasip1FileTrait;
+
+/// Produces the contents of a [`WasiDynamicFile`],
+/// e.g. a report of the process or the virtual environment.
+/// Any `Fn() -> Vec<u8>` is one.
+pub trait WasiDynamicContent: Sync {
+    fn generate(&self) -> Vec<u8>;
+}
+
+impl<F: Fn() -> Vec<u8> + Sync> WasiDynamicContent for F {
+    fn generate(&self) -> Vec<u8> {
+        self()
+    }
+}
+
+/// A file whose contents are computed when it is read,
+/// like the files of procfs.
+///
+/// In [`ConstFiles!`](crate::ConstFiles) it is the `@dynamic` entry,
+/// which can stand next to any other file:
+///
+/// ```ignore
+/// ("status", @dynamic &|| format!("pid: {}\n", 1).into_bytes()),
+/// ```
+///
+/// [`VFSConstNormalLFS`](crate::file::VFSConstNormalLFS) generates the contents
+/// once on open and keeps them until the descriptor is closed,
+/// so reads through one descriptor see the same contents.
+/// Used on its own, the contents are generated on every call.
+///
+/// Like in procfs, the size is 0 until the file is opened,
+/// so listing and stating it do not run the generator.
+#[derive(Clone, Copy)]
+pub struct WasiDynamicFile {
+    content: &'static dyn WasiDynamicContent,
+}
+
+impl WasiDynamicFile {
+    pub const fn new(content: &'static dyn WasiDynamicContent) -> Self {
+        Self { content }
+    }
+
+    #[inline]
+    pub fn generate(&self) -> Vec<u8> {
+        self.content.generate()
+    }
+}
+
+impl core::fmt::Debug for WasiDynamicFile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("WasiDynamicFile").finish_non_exhaustive()
+    }
+}
+
+impl Wasip1FileTrait for WasiDynamicFile {
+    fn size(&self) -> usize {
+        0
+    }
+
+    fn pread(&self, buf: &mut [u8], offset: usize) -> Result<usize, wasip1::Errno> {
+        let data = self.generate();
+
+        if offset >= data.len() {
+            return Ok(0);
+        }
+
+        let len = core::cmp::min(buf.len(), data.len() - offset);
+        buf[..len].copy_from_slice(&data[offset..offset + len]);
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use const_struct::const_struct;
+
+    use super::*;
+    use crate::{
+        ConstFiles, const_files,
+        memory::HostMemory,
+        mock::MockWasm,
+        wasi::file::{
+            Wasip1LFS,
+            constant::{
+                lfs::{VFSConstNormalInodeKind, VFSConstNormalLFS},
+                lfs_raw::{VFSConstNormalFiles, VFSConstNormalFilesLen, WasiConstFile},
+            },
+            stdio::DefaultStdIO,
+        },
+    };
+
+    static OPENED: AtomicUsize = AtomicUsize::new(0);
+
+    #[const_files]
+    #[const_struct]
+    const FILES: VFSConstNormalFiles<WasiConstFile<&'static str>, _> = ConstFiles!([(
+        ".",
+        [
+            ("hey", WasiConstFile::new("Hey!")),
+            (
+                "status",
+                @dynamic &|| {
+                    let n = OPENED.fetch_add(1, Ordering::Relaxed);
+                    alloc::format!("opened: {}\n", "#".repeat(n)).into_bytes()
+                }
+            ),
+        ]
+    )]);
+
+    type Lfs = VFSConstNormalLFS<
+        FilesTy,
+        WasiConstFile<&'static str>,
+        { FilesTy::FLAT_LEN },
+        DefaultStdIO,
+    >;
+
+    #[test]
+    fn test_dynamic_snapshot() {
+        let mut lfs = Lfs::new();
+
+        // stating the unopened file does not generate it
+        let path = "status";
+        let before = OPENED.load(Ordering::Relaxed);
+        let stat = lfs
+            .path_filestat_get_raw::<HostMemory>(Lfs::PRE_OPEN[0], 0, path.as_ptr(), path.len())
+            .unwrap();
+        assert_eq!(stat.size, 0);
+        assert_eq!(OPENED.load(Ordering::Relaxed), before);
+
+        let first = MockWasm::lfs_open_with(
+            &mut lfs,
+            Lfs::PRE_OPEN[0],
+            "status",
+            0,
+            wasip1::RIGHTS_FD_READ,
+        )
+        .unwrap();
+        let second = MockWasm::lfs_open_with(
+            &mut lfs,
+            Lfs::PRE_OPEN[0],
+            "status",
+            0,
+            wasip1::RIGHTS_FD_READ,
+        )
+        .unwrap();
+        assert_ne!(first, second);
+
+        // each descriptor keeps the contents generated on its open
+        let contents = MockWasm::lfs_read(&mut lfs, first, 64).unwrap();
+        assert_eq!(MockWasm::lfs_read(&mut lfs, first, 64).unwrap(), contents);
+        assert_ne!(MockWasm::lfs_read(&mut lfs, second, 64).unwrap(), contents);
+
+        let stat = lfs.fd_filestat_get_raw::<HostMemory>(first).unwrap();
+        assert_eq!(stat.size as usize, contents.len());
+        let stat = lfs.fd_filestat_get_raw::<HostMemory>(second).unwrap();
+        assert_eq!(
+            stat.ino,
+            lfs.fd_filestat_get_raw::<HostMemory>(first).unwrap().ino
+        );
+
+        assert_eq!(lfs.kind(first), VFSConstNormalInodeKind::Opened(0));
+        assert_eq!(
+            lfs.kind(stat.ino as usize),
+            VFSConstNormalInodeKind::Tree(stat.ino as usize)
+        );
+
+        // the slot of a closed descriptor is reused
+        lfs.fd_close_raw::<HostMemory>(first);
+        assert_eq!(lfs.snapshot(first), None);
+        assert_eq!(
+            MockWasm::lfs_open_with(
+                &mut lfs,
+                Lfs::PRE_OPEN[0],
+                "status",
+                0,
+                wasip1::RIGHTS_FD_READ
+            )
+            .unwrap(),
+            first
+        );
+
+        // the other files are untouched
+        let hey =
+            MockWasm::lfs_open_with(&mut lfs, Lfs::PRE_OPEN[0], "hey", 0, wasip1::RIGHTS_FD_READ)
+                .unwrap();
+        assert_eq!(MockWasm::lfs_read(&mut lfs, hey, 64).unwrap(), b"Hey!");
+    }
+}
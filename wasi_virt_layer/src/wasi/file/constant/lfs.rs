@@ -1,8 +1,14 @@
 use crate::__private::wasip1;
+#[cfg(feature = "alloc")]
+use crate::wasi::file::{
+    constant::dynamic::WasiDynamicFile,
+    snapshot::{SnapshotReader, SnapshotWriter, Wasip1Snapshot},
+};
 use crate::{
     memory::{WasmAccess, WasmPathAccess, WasmPathComponent},
     wasi::file::{
-        FilestatWithoutDevice, Wasip1FileTrait,
+        FilestatWithoutDevice, SYMLINK_MAX_DEPTH, Wasip1FileTrait,
+        clock::{DefaultClock, Wasip1Clock},
         constant::{
             lfs_impl::VFSConstNormalAddInfo,
             lfs_raw::{VFSConstNormalFilesTy, VFSConstNormalInode},
@@ -11,14 +17,39 @@ use crate::{
     },
 };
 
+enum PathPart<Eq: Fn(&str) -> bool> {
+    Root,
+    Cur,
+    Parent,
+    Normal(Eq),
+}
+
+/// What an inode of [`VFSConstNormalLFS`] refers to.
+///
+/// The inodes below `FLAT_LEN` are the entries of the tree,
+/// the ones from `FLAT_LEN` on are the descriptors of dynamic files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VFSConstNormalInodeKind {
+    /// the entry of the tree at this index
+    Tree(usize),
+    /// an opened dynamic file, with its contents in this slot
+    #[cfg(feature = "alloc")]
+    Opened(usize),
+}
+
 pub struct VFSConstNormalLFS<
     ConstRoot: VFSConstNormalFilesTy<File, FLAT_LEN>,
     File: Wasip1FileTrait + 'static + Copy,
     const FLAT_LEN: usize,
     StdIo: StdIO + 'static,
+    Clock: Wasip1Clock = DefaultClock,
 > {
     add_info: [VFSConstNormalAddInfo; FLAT_LEN],
-    __marker: core::marker::PhantomData<(ConstRoot, File, StdIo)>,
+    /// inode in the tree and contents of the opened dynamic files,
+    /// see [`VFSConstNormalInodeKind::Opened`], `None` for a free slot
+    #[cfg(feature = "alloc")]
+    snapshots: alloc::vec::Vec<(usize, Option<alloc::vec::Vec<u8>>)>,
+    __marker: core::marker::PhantomData<(ConstRoot, File, StdIo, Clock)>,
 }
 
 impl<
@@ -26,99 +57,504 @@ impl<
     File: Wasip1FileTrait + 'static + Copy,
     const FLAT_LEN: usize,
     StdIo: StdIO + 'static,
-> VFSConstNormalLFS<ConstRoot, File, FLAT_LEN, StdIo>
+    Clock: Wasip1Clock,
+> VFSConstNormalLFS<ConstRoot, File, FLAT_LEN, StdIo, Clock>
 {
     pub const fn new() -> Self {
         Self {
             add_info: [VFSConstNormalAddInfo::new(); FLAT_LEN],
+            #[cfg(feature = "alloc")]
+            snapshots: alloc::vec::Vec::new(),
             __marker: core::marker::PhantomData,
         }
     }
 
+    /// What the inode refers to.
     #[inline]
-    pub const fn update_access_time(&mut self, inode: usize, atime: usize) {
-        let add_info = &mut self.add_info[inode];
-        add_info.set_access_time(atime);
+    pub fn kind(&self, inode: usize) -> VFSConstNormalInodeKind {
+        match inode.checked_sub(FLAT_LEN) {
+            #[cfg(feature = "alloc")]
+            Some(slot) => VFSConstNormalInodeKind::Opened(slot),
+            _ => VFSConstNormalInodeKind::Tree(inode),
+        }
     }
 
+    /// The inode in the tree,
+    /// which differs only for an opened dynamic file.
     #[inline]
-    pub const fn is_dir(&self, inode: usize) -> bool {
-        let (_, file_or_dir) = ConstRoot::FILES[inode];
+    pub fn tree_inode(&self, inode: usize) -> usize {
+        match self.kind(inode) {
+            VFSConstNormalInodeKind::Tree(inode) => inode,
+            #[cfg(feature = "alloc")]
+            VFSConstNormalInodeKind::Opened(slot) => self.snapshots[slot].0,
+        }
+    }
+
+    #[inline]
+    pub fn update_access_time(&mut self, inode: usize, atim: wasip1::Timestamp) {
+        let inode = self.tree_inode(inode);
+        self.add_info[inode].set_access_time(atim);
+    }
+
+    #[inline]
+    pub fn update_modification_time(&mut self, inode: usize, mtim: wasip1::Timestamp) {
+        let inode = self.tree_inode(inode);
+        self.add_info[inode].set_modification_time(mtim);
+    }
+
+    /// Generates the contents of the dynamic file for a new descriptor
+    /// and returns the inode it is opened as.
+    #[cfg(feature = "alloc")]
+    pub fn open_dynamic(&mut self, inode: usize, file: WasiDynamicFile) -> usize {
+        let data = (self.tree_inode(inode), Some(file.generate()));
+
+        let slot = match self.snapshots.iter().position(|(_, data)| data.is_none()) {
+            Some(slot) => {
+                self.snapshots[slot] = data;
+                slot
+            }
+            None => {
+                self.snapshots.push(data);
+                self.snapshots.len() - 1
+            }
+        };
+
+        FLAT_LEN + slot
+    }
+
+    /// The contents generated when the inode was opened,
+    /// `None` if it is not an opened dynamic file.
+    #[cfg(feature = "alloc")]
+    pub fn snapshot(&self, inode: usize) -> Option<&[u8]> {
+        match self.kind(inode) {
+            VFSConstNormalInodeKind::Opened(slot) => self.snapshots.get(slot)?.1.as_deref(),
+            VFSConstNormalInodeKind::Tree(_) => None,
+        }
+    }
+
+    /// Drops the contents of the opened dynamic file.
+    #[cfg(feature = "alloc")]
+    pub fn close_dynamic(&mut self, inode: usize) {
+        if let VFSConstNormalInodeKind::Opened(slot) = self.kind(inode)
+            && let Some((_, data)) = self.snapshots.get_mut(slot)
+        {
+            *data = None;
+        }
+    }
+
+    #[inline]
+    pub fn is_dir(&self, inode: usize) -> bool {
+        let (_, file_or_dir) = ConstRoot::FILES[self.tree_inode(inode)];
         match file_or_dir {
             VFSConstNormalInode::Dir(..) => true,
-            VFSConstNormalInode::File(..) => false,
+            VFSConstNormalInode::File(..) | VFSConstNormalInode::Symlink(..) => false,
+            #[cfg(feature = "alloc")]
+            VFSConstNormalInode::Dynamic(..) => false,
         }
     }
 
     #[inline]
-    pub const fn parent_inode(&self, inode: usize) -> Option<usize> {
-        let (_, file_or_dir) = ConstRoot::FILES[inode];
+    pub fn is_symlink(&self, inode: usize) -> bool {
+        matches!(
+            ConstRoot::FILES[self.tree_inode(inode)].1,
+            VFSConstNormalInode::Symlink(..)
+        )
+    }
+
+    #[inline]
+    pub fn parent_inode(&self, inode: usize) -> Option<usize> {
+        let (_, file_or_dir) = ConstRoot::FILES[self.tree_inode(inode)];
         match file_or_dir {
             VFSConstNormalInode::Dir(_, parent, ..) => parent,
             VFSConstNormalInode::File(_, parent, ..) => Some(parent),
+            VFSConstNormalInode::Symlink(_, parent) => Some(parent),
+            #[cfg(feature = "alloc")]
+            VFSConstNormalInode::Dynamic(_, parent) => Some(parent),
         }
     }
 
-    pub fn get_inode_for_path<Wasm: WasmAccess>(
-        &self,
-        inode: usize,
-        path_ptr: *const u8,
-        path_len: usize,
-    ) -> Option<usize> {
-        let path = WasmPathAccess::<Wasm>::new(path_ptr, path_len);
+    /// The top directory of the tree the inode lives in.
+    /// Absolute paths are resolved from here.
+    pub fn root_inode(&self, mut inode: usize) -> usize {
+        while let Some(parent) = self.parent_inode(inode) {
+            inode = parent;
+        }
+        inode
+    }
+
+    fn child(&self, dir: usize, eq: impl Fn(&str) -> bool) -> Result<usize, wasip1::Errno> {
+        let (start, end) = match ConstRoot::FILES[dir] {
+            (_, VFSConstNormalInode::Dir(range, ..)) => range,
+            _ => return Err(wasip1::ERRNO_NOTDIR),
+        };
+
+        ConstRoot::FILES[start..end]
+            .iter()
+            .position(|(name, _)| eq(name))
+            .map(|i| start + i)
+            .ok_or(wasip1::ERRNO_NOENT)
+    }
+
+    /// If the inode is a symbolic link, returns the inode it points to.
+    fn follow(&self, inode: usize, depth: &mut usize) -> Result<usize, wasip1::Errno> {
+        match ConstRoot::FILES[inode].1 {
+            VFSConstNormalInode::Symlink(target, parent) => {
+                *depth += 1;
+                if *depth > SYMLINK_MAX_DEPTH {
+                    return Err(wasip1::ERRNO_LOOP);
+                }
+
+                if target.is_empty() {
+                    return Err(wasip1::ERRNO_NOENT);
+                }
+
+                let parts = target.split('/').map(|part| match part {
+                    "" => PathPart::Root,
+                    "." => PathPart::Cur,
+                    ".." => PathPart::Parent,
+                    name => PathPart::Normal(move |n: &str| n == name),
+                });
 
-        let path_parts = path.components();
+                self.walk(parent, parts, true, depth)
+            }
+            _ => Ok(inode),
+        }
+    }
 
+    fn walk<Eq: Fn(&str) -> bool>(
+        &self,
+        inode: usize,
+        parts: impl Iterator<Item = PathPart<Eq>>,
+        follow_last: bool,
+        depth: &mut usize,
+    ) -> Result<usize, wasip1::Errno> {
         let mut current_inode = inode;
 
-        for part in path_parts {
-            // Resolve each part of the path
+        for (i, part) in parts.enumerate() {
+            // every component before this one must be a directory
+            current_inode = self.follow(current_inode, depth)?;
+
             match part {
-                WasmPathComponent::RootDir => unreachable!(),
-                WasmPathComponent::CurDir => {
-                    // Stay in the current directory
-                }
-                WasmPathComponent::ParentDir => {
-                    current_inode = self.parent_inode(current_inode)?;
+                // `split('/')` yields an empty part for every repeated slash
+                PathPart::Root if i == 0 => current_inode = self.root_inode(current_inode),
+                PathPart::Root | PathPart::Cur => {
+                    if !self.is_dir(current_inode) {
+                        return Err(wasip1::ERRNO_NOTDIR);
+                    }
                 }
-                WasmPathComponent::Normal(wasm_array_access) => {
-                    let (start, end) = match ConstRoot::FILES[current_inode] {
-                        (_, VFSConstNormalInode::Dir(range, ..)) => range,
-                        _ => return None, // Not a directory
-                    };
-
-                    if let Some(i) = ConstRoot::FILES[start..end].iter().position(|(name, _)| {
-                        name.len() == wasm_array_access.len()
-                            && name
-                                .as_bytes()
-                                .iter()
-                                .zip(wasm_array_access.iter())
-                                .all(|(a, b)| *a == b)
-                    }) {
-                        current_inode = start + i;
-                    } else {
-                        return None; // Not found
+                PathPart::Parent => {
+                    if !self.is_dir(current_inode) {
+                        return Err(wasip1::ERRNO_NOTDIR);
+                    }
+                    // `..` of the root is the root itself
+                    if let Some(parent) = self.parent_inode(current_inode) {
+                        current_inode = parent;
                     }
                 }
+                PathPart::Normal(eq) => current_inode = self.child(current_inode, eq)?,
             }
         }
 
-        Some(current_inode)
+        if follow_last {
+            current_inode = self.follow(current_inode, depth)?;
+        }
+
+        Ok(current_inode)
+    }
+
+    /// Resolves the path relative to the inode like POSIX does.
+    /// Symbolic links in the middle of the path are always followed,
+    /// the last one only when `LOOKUPFLAGS_SYMLINK_FOLLOW` is set
+    /// or the path ends with `/`, which also requires a directory.
+    /// `..` stops at the root.
+    pub fn get_inode_for_path<Wasm: WasmAccess>(
+        &self,
+        inode: usize,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        if path_len == 0 {
+            return Err(wasip1::ERRNO_NOENT);
+        }
+
+        let path = WasmPathAccess::<Wasm>::new(path_ptr, path_len);
+
+        let parts = path.components().map(|part| match part {
+            WasmPathComponent::RootDir => PathPart::Root,
+            WasmPathComponent::CurDir => PathPart::Cur,
+            WasmPathComponent::ParentDir => PathPart::Parent,
+            WasmPathComponent::Normal(wasm_array_access) => PathPart::Normal(move |name: &str| {
+                name.len() == wasm_array_access.len()
+                    && name
+                        .as_bytes()
+                        .iter()
+                        .zip(wasm_array_access.iter())
+                        .all(|(a, b)| *a == b)
+            }),
+        });
+
+        let must_be_dir = path.ends_with_separator();
+        let follow_last = must_be_dir
+            || flags & wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW == wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW;
+
+        let inode = self.walk(self.tree_inode(inode), parts, follow_last, &mut 0)?;
+
+        if must_be_dir && !self.is_dir(inode) {
+            return Err(wasip1::ERRNO_NOTDIR);
+        }
+
+        Ok(inode)
     }
 
+    /// The time set or the build-time one of the file,
+    /// 0 for directories, symbolic links and dynamic files.
+    pub fn modification_time(&self, inode: usize) -> wasip1::Timestamp {
+        let inode = self.tree_inode(inode);
+
+        self.add_info[inode].modification_time().unwrap_or_else(|| {
+            match ConstRoot::FILES[inode].1 {
+                VFSConstNormalInode::File(file, _) => file.mtim(),
+                _ => 0,
+            }
+        })
+    }
+
+    /// The time of the last read, the modification time before it.
     pub fn access_time(&self, inode: usize) -> wasip1::Timestamp {
-        self.add_info[inode].access_time() as wasip1::Timestamp
+        self.add_info[self.tree_inode(inode)]
+            .access_time()
+            .unwrap_or_else(|| self.modification_time(inode))
     }
 
     pub fn filestat_from_inode(&self, inode: usize) -> FilestatWithoutDevice {
+        let mtim = self.modification_time(inode);
+
+        #[cfg(feature = "alloc")]
+        let size = match self.snapshot(inode) {
+            Some(data) => data.len(),
+            None => ConstRoot::FILES[self.tree_inode(inode)].1.size(),
+        };
+        #[cfg(not(feature = "alloc"))]
+        let size = ConstRoot::FILES[inode].1.size();
+
+        let inode = self.tree_inode(inode);
+
         FilestatWithoutDevice {
             ino: inode as _,
             filetype: ConstRoot::FILES[inode].1.filetype(),
             nlink: 1,
-            size: ConstRoot::FILES[inode].1.size() as _,
+            size: size as _,
             atim: self.access_time(inode),
-            mtim: 0,
-            ctim: 0,
+            mtim,
+            ctim: mtim,
+        }
+    }
+}
+
+/// The times the guest set or the reads and writes changed,
+/// the files themselves cannot change.
+#[cfg(feature = "alloc")]
+impl<
+    ConstRoot: VFSConstNormalFilesTy<File, FLAT_LEN>,
+    File: Wasip1FileTrait + 'static + Copy,
+    const FLAT_LEN: usize,
+    StdIo: StdIO + 'static,
+    Clock: Wasip1Clock,
+> Wasip1Snapshot for VFSConstNormalLFS<ConstRoot, File, FLAT_LEN, StdIo, Clock>
+{
+    fn snapshot_to(&self, out: &mut SnapshotWriter) {
+        out.usize(FLAT_LEN);
+        for add_info in &self.add_info {
+            out.option_u64(add_info.access_time());
+            out.option_u64(add_info.modification_time());
+        }
+    }
+
+    fn restore_from(&mut self, input: &mut SnapshotReader<'_>) -> Result<(), wasip1::Errno> {
+        // a snapshot of another tree
+        if input.usize()? != FLAT_LEN {
+            return Err(wasip1::ERRNO_INVAL);
+        }
+
+        let mut add_info = [VFSConstNormalAddInfo::new(); FLAT_LEN];
+        for add_info in &mut add_info {
+            if let Some(atim) = input.option_u64()? {
+                add_info.set_access_time(atim);
+            }
+            if let Some(mtim) = input.option_u64()? {
+                add_info.set_modification_time(mtim);
+            }
         }
+        self.add_info = add_info;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use const_struct::const_struct;
+
+    use super::*;
+    use crate::{
+        ConstFiles, const_files,
+        memory::HostMemory,
+        wasi::file::{
+            Wasip1LFS,
+            constant::lfs_raw::{VFSConstNormalFiles, VFSConstNormalFilesLen, WasiConstFile},
+            stdio::DefaultStdIO,
+        },
+    };
+
+    #[const_files]
+    #[const_struct]
+    const FILES: VFSConstNormalFiles<WasiConstFile<&'static str>, _> = ConstFiles!([(
+        ".",
+        [
+            ("usr", [("bin", [("clang", WasiConstFile::new("clang"))])]),
+            (
+                "bin",
+                [
+                    ("cc", @symlink "../usr/bin/clang"),
+                    ("tools", @symlink "/usr/bin"),
+                ]
+            ),
+            ("loop", @symlink "loop"),
+            (".hidden", WasiConstFile::new("hidden").with_mtim(1_600_000_000_000_000_000)),
+        ]
+    )]);
+
+    /// A fixed time after the build time, the host clock reads 0 without `std`.
+    struct Clock;
+
+    impl Wasip1Clock for Clock {
+        fn now() -> wasip1::Timestamp {
+            1_700_000_000_000_000_000
+        }
+    }
+
+    type Lfs = VFSConstNormalLFS<
+        FilesTy,
+        WasiConstFile<&'static str>,
+        { FilesTy::FLAT_LEN },
+        DefaultStdIO,
+        Clock,
+    >;
+
+    fn resolve(lfs: &Lfs, path: &str, flags: wasip1::Lookupflags) -> Result<usize, wasip1::Errno> {
+        lfs.get_inode_for_path::<HostMemory>(Lfs::PRE_OPEN[0], flags, path.as_ptr(), path.len())
+    }
+
+    #[test]
+    fn test_const_symlink() {
+        let mut lfs = Lfs::new();
+        let follow = wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW;
+
+        let clang = resolve(&lfs, "usr/bin/clang", 0).unwrap();
+
+        let cc = resolve(&lfs, "bin/cc", 0).unwrap();
+        assert!(lfs.is_symlink(cc));
+        assert_eq!(resolve(&lfs, "bin/cc", follow), Ok(clang));
+        assert_eq!(resolve(&lfs, "bin/tools/clang", 0), Ok(clang));
+        assert_eq!(resolve(&lfs, "bin/tools/../bin/clang", 0), Ok(clang));
+
+        assert!(resolve(&lfs, "loop", 0).is_ok());
+        assert_eq!(resolve(&lfs, "loop", follow), Err(wasip1::ERRNO_LOOP));
+        assert_eq!(resolve(&lfs, "loop/a", 0), Err(wasip1::ERRNO_LOOP));
+
+        let mut buf = [0u8; 32];
+        let len = lfs
+            .path_readlink_raw::<HostMemory>(
+                Lfs::PRE_OPEN[0],
+                "bin/cc".as_ptr(),
+                6,
+                buf.as_mut_ptr(),
+                buf.len(),
+            )
+            .unwrap();
+        assert_eq!(&buf[..len], b"../usr/bin/clang");
+        assert_eq!(
+            lfs.path_readlink_raw::<HostMemory>(
+                Lfs::PRE_OPEN[0],
+                "usr".as_ptr(),
+                3,
+                buf.as_mut_ptr(),
+                buf.len(),
+            ),
+            Err(wasip1::ERRNO_INVAL)
+        );
+
+        let open = |lfs: &mut Lfs, flags| {
+            lfs.path_open_raw::<HostMemory>(
+                Lfs::PRE_OPEN[0],
+                flags,
+                "bin/cc".as_ptr(),
+                6,
+                0,
+                wasip1::RIGHTS_FD_READ,
+                0,
+                0,
+            )
+        };
+        assert_eq!(open(&mut lfs, 0), Err(wasip1::ERRNO_LOOP));
+        assert_eq!(open(&mut lfs, follow), Ok(clang));
+    }
+
+    #[test]
+    fn test_const_path_normalization() {
+        let lfs = Lfs::new();
+        let root = Lfs::PRE_OPEN[0];
+
+        let clang = resolve(&lfs, "usr/bin/clang", 0).unwrap();
+        let bin = resolve(&lfs, "usr/bin", 0).unwrap();
+
+        assert_eq!(resolve(&lfs, "./usr/../usr/bin/clang", 0), Ok(clang));
+        assert_eq!(resolve(&lfs, "usr//bin///clang", 0), Ok(clang));
+        assert_eq!(resolve(&lfs, "/usr/bin/clang", 0), Ok(clang));
+
+        // `..` stops at the root
+        assert_eq!(resolve(&lfs, "../../usr/bin/clang", 0), Ok(clang));
+        assert_eq!(resolve(&lfs, "/..", 0), Ok(root));
+        assert_eq!(resolve(&lfs, ".", 0), Ok(root));
+
+        // a trailing slash needs a directory and follows the last link
+        assert_eq!(resolve(&lfs, "usr/bin/", 0), Ok(bin));
+        assert_eq!(
+            resolve(&lfs, "usr/bin/clang/", 0),
+            Err(wasip1::ERRNO_NOTDIR)
+        );
+        assert_eq!(resolve(&lfs, "bin/tools/", 0), Ok(bin));
+        assert_eq!(resolve(&lfs, "bin/cc/", 0), Err(wasip1::ERRNO_NOTDIR));
+
+        assert!(resolve(&lfs, ".hidden", 0).is_ok());
+        assert_eq!(resolve(&lfs, "", 0), Err(wasip1::ERRNO_NOENT));
+    }
+
+    #[test]
+    fn test_const_times() {
+        let mut lfs = Lfs::new();
+        let hidden = resolve(&lfs, ".hidden", 0).unwrap();
+
+        // the build-time time until something changes it
+        let filestat = lfs.fd_filestat_get_raw::<HostMemory>(hidden).unwrap();
+        assert_eq!(filestat.mtim, 1_600_000_000_000_000_000);
+        assert_eq!(filestat.atim, filestat.mtim);
+
+        let mut buf = [0u8; 8];
+        lfs.fd_pread_raw::<HostMemory>(hidden, buf.as_mut_ptr(), buf.len(), 0)
+            .unwrap();
+        let filestat = lfs.fd_filestat_get_raw::<HostMemory>(hidden).unwrap();
+        assert!(filestat.atim > filestat.mtim);
+
+        lfs.fd_filestat_set_times_raw::<HostMemory>(
+            hidden,
+            10,
+            20,
+            wasip1::FSTFLAGS_ATIM | wasip1::FSTFLAGS_MTIM,
+        )
+        .unwrap();
+        let filestat = lfs.fd_filestat_get_raw::<HostMemory>(hidden).unwrap();
+        assert_eq!((filestat.atim, filestat.mtim), (10, 20));
     }
 }
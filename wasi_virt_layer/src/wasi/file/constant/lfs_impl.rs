@@ -5,11 +5,13 @@ use crate::{
     memory::WasmAccess,
     wasi::file::{
         FilestatWithoutDevice, Wasip1FileTrait, Wasip1LFS,
+        clock::{Wasip1Clock, times_to_set},
         constant::{
             lfs::VFSConstNormalLFS,
             lfs_raw::{VFSConstNormalFilesTy, VFSConstNormalInode},
         },
-        stdio::StdIO,
+        dirent::{DIRCOOKIE_ENTRIES, DirentWriter},
+        stdio::{self, StdIO},
     },
 };
 
@@ -18,18 +20,20 @@ impl<
     File: Wasip1FileTrait + 'static + Copy,
     const FLAT_LEN: usize,
     StdIo: StdIO + 'static,
-> Wasip1LFS for VFSConstNormalLFS<ROOT, File, FLAT_LEN, StdIo>
+    Clock: Wasip1Clock,
+> Wasip1LFS for VFSConstNormalLFS<ROOT, File, FLAT_LEN, StdIo, Clock>
 {
     type Inode = usize;
     const PRE_OPEN: &'static [Self::Inode] = ROOT::PRE_OPEN;
 
-    fn fd_write_raw<Wasm: WasmAccess>(
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
         &mut self,
         _: Self::Inode,
         _: *const u8,
         _: usize,
+        _: usize,
     ) -> Result<wasip1::Size, wasip1::Errno> {
-        Err(wasip1::ERRNO_PERM)
+        Err(wasip1::ERRNO_ROFS)
     }
 
     fn fd_write_stdout_raw<Wasm: WasmAccess>(
@@ -37,18 +41,7 @@ impl<
         data: *const u8,
         data_len: usize,
     ) -> Result<wasip1::Size, wasip1::Errno> {
-        #[cfg(not(feature = "multi_memory"))]
-        {
-            StdIo::write_direct::<Wasm>(data, data_len)
-        }
-        #[cfg(feature = "multi_memory")]
-        {
-            let (buf, _) = unsafe {
-                use crate::utils::alloc_buff;
-                alloc_buff(data_len, |buf| Wasm::memcpy_to(buf, data))
-            };
-            StdIo::write(&buf)
-        }
+        stdio::write_stdout_raw::<StdIo, Wasm>(data, data_len)
     }
 
     fn fd_write_stderr_raw<Wasm: WasmAccess>(
@@ -56,18 +49,7 @@ impl<
         data: *const u8,
         data_len: usize,
     ) -> Result<wasip1::Size, wasip1::Errno> {
-        #[cfg(not(feature = "multi_memory"))]
-        {
-            StdIo::ewrite_direct::<Wasm>(data, data_len)
-        }
-        #[cfg(feature = "multi_memory")]
-        {
-            let (buf, _) = unsafe {
-                use crate::utils::alloc_buff;
-                alloc_buff(data_len, |buf| Wasm::memcpy_to(buf, data))
-            };
-            StdIo::ewrite(&buf)
-        }
+        stdio::write_stderr_raw::<StdIo, Wasm>(data, data_len)
     }
 
     fn is_dir(&self, inode: Self::Inode) -> bool {
@@ -83,132 +65,39 @@ impl<
     ) -> Result<(wasip1::Size, Dircookie), wasip1::Errno> {
         let (_, dir) = ROOT::FILES[inode];
 
-        // . (current directory)
-        if cookie == 0 {
-            let next_cookie = if dir.parent().is_some() { 1 } else { 2 };
-            let entry = wasip1::Dirent {
-                d_next: next_cookie,
-                d_ino: inode as _,
-                d_namlen: 1,
-                d_type: dir.filetype(),
-            };
-            let entry_buf = unsafe {
-                core::slice::from_raw_parts(
-                    &entry as *const _ as *const u8,
-                    core::cmp::min(core::mem::size_of::<wasip1::Dirent>(), buf_len),
-                )
-            };
-            Wasm::memcpy(buf, entry_buf);
-
-            if buf_len < core::mem::size_of::<wasip1::Dirent>() {
-                return Ok((buf_len, cookie));
-            }
-
-            Wasm::memcpy(
-                unsafe { buf.add(core::mem::size_of::<wasip1::Dirent>()) },
-                b".",
-            );
-
-            return Ok((core::mem::size_of::<wasip1::Dirent>() + 1, next_cookie));
-        }
-
-        // .. (parent directory)
-        if cookie == 1 {
-            let parent = dir.parent().unwrap();
-            let entry = wasip1::Dirent {
-                d_next: 2,
-                d_ino: parent as _,
-                d_namlen: 2,
-                d_type: ROOT::FILES[parent].1.filetype(),
-            };
-            let entry_buf = unsafe {
-                core::slice::from_raw_parts(
-                    &entry as *const _ as *const u8,
-                    core::cmp::min(core::mem::size_of::<wasip1::Dirent>(), buf_len),
-                )
-            };
-            Wasm::memcpy(buf, entry_buf);
-
-            if buf_len < core::mem::size_of::<wasip1::Dirent>() {
-                return Ok((buf_len, cookie));
-            }
-
-            Wasm::memcpy(
-                unsafe { buf.add(core::mem::size_of::<wasip1::Dirent>()) },
-                b"..",
-            );
-
-            return Ok((core::mem::size_of::<wasip1::Dirent>() + 2, 2));
-        }
-
         let (start, end) = match dir {
             VFSConstNormalInode::Dir(range, ..) => range,
-            _ => unreachable!(),
-        };
-
-        let index = start + cookie as usize - 2;
-        if index >= end {
-            return Ok((0, cookie)); // No more entries
-        }
-
-        let (name, file_or_dir) = ROOT::FILES[index];
-
-        let next_cookie = cookie + 1;
-
-        let name_len = name.len();
-
-        let entry = wasip1::Dirent {
-            d_next: if (next_cookie as usize) < end {
-                next_cookie
-            } else {
-                0
-            },
-            d_ino: index as _,
-            d_namlen: name_len as _,
-            d_type: file_or_dir.filetype(),
-        };
-
-        let entry_buf = unsafe {
-            core::slice::from_raw_parts(
-                &entry as *const _ as *const u8,
-                core::cmp::min(core::mem::size_of::<wasip1::Dirent>(), buf_len),
-            )
+            _ => return Err(wasip1::ERRNO_NOTDIR),
         };
 
-        Wasm::memcpy(buf, entry_buf);
+        let mut writer = DirentWriter::<Wasm>::new(buf, buf_len, cookie);
 
-        if buf_len < core::mem::size_of::<wasip1::Dirent>() {
-            return Ok((buf_len, cookie));
+        if !writer.dots(inode as _, dir.parent().unwrap_or(inode) as _) {
+            return Ok(writer.finish());
         }
 
-        let name_bytes = unsafe {
-            core::slice::from_raw_parts(
-                name.as_ptr(),
-                core::cmp::min(name_len, buf_len - core::mem::size_of::<wasip1::Dirent>()),
-            )
-        };
+        // the tree never changes, so the position is a stable cookie
+        let skipped = cookie.saturating_sub(DIRCOOKIE_ENTRIES) as usize;
+        for index in core::cmp::min(start + skipped, end)..end {
+            let (name, file_or_dir) = ROOT::FILES[index];
+            let cookie = DIRCOOKIE_ENTRIES + (index - start) as Dircookie;
 
-        Wasm::memcpy(
-            unsafe { buf.add(core::mem::size_of::<wasip1::Dirent>()) },
-            name_bytes,
-        );
+            if !writer.entry(cookie, index as _, file_or_dir.filetype(), name.as_bytes()) {
+                break;
+            }
+        }
 
-        Ok((
-            core::mem::size_of::<wasip1::Dirent>() + name_bytes.len(),
-            next_cookie,
-        ))
+        Ok(writer.finish())
     }
 
     fn path_filestat_get_raw<Wasm: WasmAccess>(
         &mut self,
         inode: Self::Inode,
-        _: wasip1::Lookupflags,
+        flags: wasip1::Lookupflags,
         path_ptr: *const u8,
         path_len: usize,
     ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
-        let inode = self
-            .get_inode_for_path::<Wasm>(inode, path_ptr, path_len)
-            .ok_or(wasip1::ERRNO_NOENT)?;
+        let inode = self.get_inode_for_path::<Wasm>(inode, flags, path_ptr, path_len)?;
 
         Ok(self.filestat_from_inode(inode))
     }
@@ -261,6 +150,32 @@ impl<
         Ok(self.filestat_from_inode(inode))
     }
 
+    fn fd_close_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) {
+        #[cfg(feature = "alloc")]
+        self.close_dynamic(inode);
+        #[cfg(not(feature = "alloc"))]
+        let _ = inode;
+    }
+
+    fn fd_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        atim: wasip1::Timestamp,
+        mtim: wasip1::Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        let (atim, mtim) = times_to_set::<Clock>(atim, mtim, fst_flags)?;
+
+        if let Some(atim) = atim {
+            self.update_access_time(inode, atim);
+        }
+        if let Some(mtim) = mtim {
+            self.update_modification_time(inode, mtim);
+        }
+
+        Ok(())
+    }
+
     fn fd_pread_raw<Wasm: WasmAccess>(
         &mut self,
         inode: Self::Inode,
@@ -268,20 +183,40 @@ impl<
         buf_len: usize,
         offset: usize,
     ) -> Result<wasip1::Size, wasip1::Errno> {
-        let (_, file_or_dir) = ROOT::FILES[inode];
-
-        if let VFSConstNormalInode::File(file, _) = file_or_dir {
-            if offset >= file.size() {
+        #[cfg(feature = "alloc")]
+        if let Some(data) = self.snapshot(inode) {
+            if offset >= data.len() {
                 return Ok(0); // No data to read
             }
 
-            let buf_len = core::cmp::min(buf_len, file.size() - offset);
-            let nread = file.pread_raw::<Wasm>(buf, buf_len, offset)?;
+            let buf_len = core::cmp::min(buf_len, data.len() - offset);
+            Wasm::memcpy(buf, &data[offset..offset + buf_len]);
+            self.update_access_time(inode, Clock::now());
 
-            Ok(nread)
-        } else {
-            unreachable!();
+            return Ok(buf_len);
         }
+
+        let (_, file_or_dir) = ROOT::FILES[self.tree_inode(inode)];
+
+        let nread = match file_or_dir {
+            VFSConstNormalInode::File(file, _) => {
+                if offset >= file.size() {
+                    return Ok(0); // No data to read
+                }
+
+                let buf_len = core::cmp::min(buf_len, file.size() - offset);
+                file.pread_raw::<Wasm>(buf, buf_len, offset)?
+            }
+            // not opened through this file system
+            #[cfg(feature = "alloc")]
+            VFSConstNormalInode::Dynamic(file, _) => {
+                file.pread_raw::<Wasm>(buf, buf_len, offset)?
+            }
+            _ => unreachable!(),
+        };
+        self.update_access_time(inode, Clock::now());
+
+        Ok(nread)
     }
 
     fn fd_read_stdin_raw<Wasm: WasmAccess>(
@@ -289,25 +224,13 @@ impl<
         buf: *mut u8,
         buf_len: usize,
     ) -> Result<wasip1::Size, wasip1::Errno> {
-        #[cfg(not(feature = "multi_memory"))]
-        {
-            StdIo::read_direct::<Wasm>(buf, buf_len)
-        }
-
-        #[cfg(feature = "multi_memory")]
-        {
-            use crate::__private::utils;
-
-            let (buf_vec, read) = unsafe { utils::alloc_buff(buf_len, |buf| StdIo::read(buf)) };
-            Wasm::memcpy(buf, &buf_vec);
-            Ok(read?)
-        }
+        stdio::read_stdin_raw::<StdIo, Wasm>(buf, buf_len)
     }
 
     fn path_open_raw<Wasm: WasmAccess>(
         &mut self,
         dir_inode: Self::Inode,
-        _: wasip1::Fdflags,
+        dir_flags: wasip1::Lookupflags,
         path_ptr: *const u8,
         path_len: usize,
         o_flags: wasip1::Oflags,
@@ -315,50 +238,166 @@ impl<
         _: wasip1::Rights,
         _: wasip1::Fdflags,
     ) -> Result<Self::Inode, wasip1::Errno> {
-        if let Some(inode) = self.get_inode_for_path::<Wasm>(dir_inode, path_ptr, path_len) {
-            if o_flags & wasip1::OFLAGS_EXCL == wasip1::OFLAGS_EXCL {
-                return Err(wasip1::ERRNO_EXIST);
+        match self.get_inode_for_path::<Wasm>(dir_inode, dir_flags, path_ptr, path_len) {
+            Ok(inode) => {
+                if o_flags & wasip1::OFLAGS_EXCL == wasip1::OFLAGS_EXCL {
+                    return Err(wasip1::ERRNO_EXIST);
+                }
+
+                // the link was not followed
+                if self.is_symlink(inode) {
+                    return Err(wasip1::ERRNO_LOOP);
+                }
+
+                if o_flags & wasip1::OFLAGS_DIRECTORY == wasip1::OFLAGS_DIRECTORY
+                    && !self.is_dir(inode)
+                {
+                    return Err(wasip1::ERRNO_NOTDIR);
+                }
+
+                if fs_rights_base & wasip1::RIGHTS_FD_WRITE == wasip1::RIGHTS_FD_WRITE {
+                    return Err(wasip1::ERRNO_ROFS);
+                }
+
+                if o_flags & wasip1::OFLAGS_TRUNC == wasip1::OFLAGS_TRUNC {
+                    return Err(wasip1::ERRNO_ROFS);
+                }
+
+                #[cfg(feature = "alloc")]
+                if let VFSConstNormalInode::Dynamic(file, _) = ROOT::FILES[inode].1 {
+                    return Ok(self.open_dynamic(inode, file));
+                }
+
+                Ok(inode)
             }
-
-            if o_flags & wasip1::OFLAGS_DIRECTORY == wasip1::OFLAGS_DIRECTORY && !self.is_dir(inode)
-            {
-                return Err(wasip1::ERRNO_NOTDIR);
+            Err(wasip1::ERRNO_NOENT) if o_flags & wasip1::OFLAGS_CREAT == wasip1::OFLAGS_CREAT => {
+                Err(wasip1::ERRNO_ROFS)
             }
+            Err(e) => Err(e),
+        }
+    }
 
-            if fs_rights_base & wasip1::RIGHTS_FD_WRITE == wasip1::RIGHTS_FD_WRITE {
-                return Err(wasip1::ERRNO_PERM);
-            }
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        match self.get_inode_for_path::<Wasm>(dir_inode, 0, path_ptr, path_len) {
+            Ok(_) => Err(wasip1::ERRNO_EXIST),
+            Err(wasip1::ERRNO_NOENT) => Err(wasip1::ERRNO_ROFS),
+            Err(e) => Err(e),
+        }
+    }
 
-            if o_flags & wasip1::OFLAGS_TRUNC == wasip1::OFLAGS_TRUNC {
-                return Err(wasip1::ERRNO_PERM);
-            }
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let inode = self.get_inode_for_path::<Wasm>(dir_inode, 0, path_ptr, path_len)?;
+
+        if !self.is_dir(inode) {
+            return Err(wasip1::ERRNO_NOTDIR);
+        }
+
+        Err(wasip1::ERRNO_ROFS)
+    }
+
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let inode = self.get_inode_for_path::<Wasm>(dir_inode, 0, path_ptr, path_len)?;
+
+        if self.is_dir(inode) {
+            return Err(wasip1::ERRNO_ISDIR);
+        }
+
+        Err(wasip1::ERRNO_ROFS)
+    }
+
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_dir_inode: Self::Inode,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        _: Self::Inode,
+        _: *const u8,
+        _: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.get_inode_for_path::<Wasm>(old_dir_inode, 0, old_path_ptr, old_path_len)?;
 
-            Ok(inode)
-        } else {
-            if o_flags & wasip1::OFLAGS_CREAT == wasip1::OFLAGS_CREAT {
-                return Err(wasip1::ERRNO_PERM);
+        Err(wasip1::ERRNO_ROFS)
+    }
+
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let inode = self.get_inode_for_path::<Wasm>(dir_inode, 0, path_ptr, path_len)?;
+
+        match ROOT::FILES[inode].1 {
+            VFSConstNormalInode::Symlink(target, _) => {
+                let len = core::cmp::min(target.len(), buf_len);
+                Wasm::memcpy(buf, &target.as_bytes()[..len]);
+                Ok(len)
             }
+            _ => Err(wasip1::ERRNO_INVAL),
+        }
+    }
 
-            Err(wasip1::ERRNO_NOENT)
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        _: *const u8,
+        _: usize,
+        dir_inode: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        match self.get_inode_for_path::<Wasm>(dir_inode, 0, new_path_ptr, new_path_len) {
+            Ok(_) => Err(wasip1::ERRNO_EXIST),
+            Err(wasip1::ERRNO_NOENT) => Err(wasip1::ERRNO_ROFS),
+            Err(e) => Err(e),
         }
     }
 }
 
+/// Times changed at runtime, `None` until then.
 #[derive(Copy, Clone, Debug)]
 pub struct VFSConstNormalAddInfo {
-    atime: usize,
+    atim: Option<wasip1::Timestamp>,
+    mtim: Option<wasip1::Timestamp>,
 }
 
 impl VFSConstNormalAddInfo {
     pub const fn new() -> Self {
-        Self { atime: 0 }
+        Self {
+            atim: None,
+            mtim: None,
+        }
+    }
+
+    pub const fn access_time(&self) -> Option<wasip1::Timestamp> {
+        self.atim
+    }
+
+    pub const fn set_access_time(&mut self, atim: wasip1::Timestamp) {
+        self.atim = Some(atim);
     }
 
-    pub const fn access_time(&self) -> usize {
-        self.atime
+    pub const fn modification_time(&self) -> Option<wasip1::Timestamp> {
+        self.mtim
     }
 
-    pub const fn set_access_time(&mut self, atime: usize) {
-        self.atime = atime;
+    pub const fn set_modification_time(&mut self, mtim: wasip1::Timestamp) {
+        self.mtim = Some(mtim);
     }
 }
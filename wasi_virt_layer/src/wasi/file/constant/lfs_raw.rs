@@ -1,6 +1,8 @@
 use crate::__private::wasip1;
-use const_struct::ConstStruct;
+use const_struct::{ConstStruct, PrimitiveTraits};
 
+#[cfg(feature = "alloc")]
+use crate::wasi::file::constant::dynamic::WasiDynamicFile;
 use crate::{memory::WasmAccess, wasi::file::Wasip1FileTrait};
 
 /// A constant file system root that can be used in a WASI component.
@@ -26,12 +28,32 @@ impl<File: Wasip1FileTrait + 'static + Copy, const FLAT_LEN: usize>
     }
 }
 
+/// `FLAT_LEN` of a `#[const_struct]` files,
+/// e.g. `VFSConstNormalLFS<FilesTy, File, { FilesTy::FLAT_LEN }, StdIo>`.
+pub trait VFSConstNormalFilesLen {
+    const FLAT_LEN: usize;
+}
+
+impl<
+    T: PrimitiveTraits<DATATYPE = VFSConstNormalFiles<File, FLAT_LEN>>,
+    File: Wasip1FileTrait + 'static + Copy,
+    const FLAT_LEN: usize,
+> VFSConstNormalFilesLen for T
+{
+    const FLAT_LEN: usize = FLAT_LEN;
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum VFSConstNormalInode<File: Wasip1FileTrait + 'static + Copy> {
     /// file, parent
     File(File, usize),
     /// (first index..last index), parent
     Dir((usize, usize), Option<usize>),
+    /// target path, parent
+    Symlink(&'static str, usize),
+    /// file, parent
+    #[cfg(feature = "alloc")]
+    Dynamic(WasiDynamicFile, usize),
 }
 
 impl<File: Wasip1FileTrait + 'static + Copy> VFSConstNormalInode<File> {
@@ -39,6 +61,9 @@ impl<File: Wasip1FileTrait + 'static + Copy> VFSConstNormalInode<File> {
         match self {
             Self::File(..) => wasip1::FILETYPE_REGULAR_FILE,
             Self::Dir(..) => wasip1::FILETYPE_DIRECTORY,
+            Self::Symlink(..) => wasip1::FILETYPE_SYMBOLIC_LINK,
+            #[cfg(feature = "alloc")]
+            Self::Dynamic(..) => wasip1::FILETYPE_REGULAR_FILE,
         }
     }
 
@@ -46,6 +71,9 @@ impl<File: Wasip1FileTrait + 'static + Copy> VFSConstNormalInode<File> {
         match self {
             Self::File(file, _) => file.size(),
             Self::Dir(..) => core::mem::size_of::<((usize, usize), Option<usize>)>(), // directory size is just the size of the inode
+            Self::Symlink(target, _) => target.len(),
+            #[cfg(feature = "alloc")]
+            Self::Dynamic(file, _) => file.size(),
         }
     }
 
@@ -53,10 +81,26 @@ impl<File: Wasip1FileTrait + 'static + Copy> VFSConstNormalInode<File> {
         match self {
             Self::File(_, parent) => Some(*parent),
             Self::Dir(_, parent) => *parent,
+            Self::Symlink(_, parent) => Some(*parent),
+            #[cfg(feature = "alloc")]
+            Self::Dynamic(_, parent) => Some(*parent),
         }
     }
 }
 
+/// Builds [`VFSConstNormalFiles`] from a tree of files.
+/// Write `_` for `FLAT_LEN` and put `#[const_files]` above `#[const_struct]`
+/// to count the files and dirs automatically.
+///
+/// ```ignore
+/// #[const_files]
+/// #[const_struct]
+/// const FILES: VFSConstNormalFiles<WasiConstFile<&'static str>, _> = ConstFiles!([
+///     (".", [("hey", WasiConstFile::new("Hey!"))]),
+/// ]);
+///
+/// type LFS = VFSConstNormalLFS<FilesTy, WasiConstFile<&'static str>, { FilesTy::FLAT_LEN }, DefaultStdIO>;
+/// ```
 #[macro_export]
 macro_rules! ConstFiles {
     (
@@ -320,6 +364,24 @@ macro_rules! ConstFiles {
         $count += 1;
     };
 
+    (@counter2, $count:ident,
+        ($file_or_dir_name:tt, @symlink $target:expr $(,)?)
+    ) => {
+        $count += 1;
+    };
+
+    (@counter2, $count:ident,
+        ($file_or_dir_name:tt, @compressed $($path:expr),+ $(,)?)
+    ) => {
+        $count += 1;
+    };
+
+    (@counter2, $count:ident,
+        ($file_or_dir_name:tt, @dynamic $content:expr $(,)?)
+    ) => {
+        $count += 1;
+    };
+
     (@counter2, $count:ident,
         ($file_or_dir_name:tt, $file_or_dir:tt $(,)?)
     ) => {
@@ -358,6 +420,24 @@ macro_rules! ConstFiles {
         $empty_arr.push(($depth, $parent_name));
     };
 
+    (@empty2, $depth:expr, $empty_arr:ident, [$parent_name:expr],
+        ($file_or_dir_name:tt, @symlink $target:expr $(,)?)
+    ) => {
+        $empty_arr.push(($depth + 1, concat!($parent_name, "/", $file_or_dir_name)));
+    };
+
+    (@empty2, $depth:expr, $empty_arr:ident, [$parent_name:expr],
+        ($file_or_dir_name:tt, @compressed $($path:expr),+ $(,)?)
+    ) => {
+        $empty_arr.push(($depth + 1, concat!($parent_name, "/", $file_or_dir_name)));
+    };
+
+    (@empty2, $depth:expr, $empty_arr:ident, [$parent_name:expr],
+        ($file_or_dir_name:tt, @dynamic $content:expr $(,)?)
+    ) => {
+        $empty_arr.push(($depth + 1, concat!($parent_name, "/", $file_or_dir_name)));
+    };
+
     (@empty2, $depth:expr, $empty_arr:ident, [$parent_name:expr],
         ($file_or_dir_name:tt, $file_or_dir:tt $(,)?)
     ) => {
@@ -384,7 +464,7 @@ macro_rules! ConstFiles {
         $static_array.push(($depth, (
             $parent_path,
             $name,
-            $crate::wasi::file::constant::lfs_raw::VFSConstNormalInode::Dir(
+            $crate::__private::inner::fs::VFSConstNormalInode::Dir(
                 get_child_range(
                     $empty,
                     $parent_path,
@@ -415,6 +495,51 @@ macro_rules! ConstFiles {
         )));
     };
 
+    (@next2, $depth:expr, $static_array:ident, [$empty:expr], [$parent_path:expr], [$name:expr],
+        ($file_or_dir_name:tt, @symlink $target:expr $(,)?)
+    ) => {
+        $static_array.push((
+            $depth + 1,
+            (
+                concat!($parent_path, "/", $file_or_dir_name),
+                $file_or_dir_name,
+                $crate::__private::inner::fs::VFSConstNormalInode::Symlink(
+                    $target,
+                    get_parent($empty, concat!($parent_path, "/", $file_or_dir_name), &$static_array).unwrap()
+                )
+        )));
+    };
+
+    (@next2, $depth:expr, $static_array:ident, [$empty:expr], [$parent_path:expr], [$name:expr],
+        ($file_or_dir_name:tt, @compressed $($path:expr),+ $(,)?)
+    ) => {
+        $static_array.push((
+            $depth + 1,
+            (
+                concat!($parent_path, "/", $file_or_dir_name),
+                $file_or_dir_name,
+                $crate::__private::inner::fs::VFSConstNormalInode::File(
+                    $crate::include_compressed!($($path),+),
+                    get_parent($empty, concat!($parent_path, "/", $file_or_dir_name), &$static_array).unwrap()
+                )
+        )));
+    };
+
+    (@next2, $depth:expr, $static_array:ident, [$empty:expr], [$parent_path:expr], [$name:expr],
+        ($file_or_dir_name:tt, @dynamic $content:expr $(,)?)
+    ) => {
+        $static_array.push((
+            $depth + 1,
+            (
+                concat!($parent_path, "/", $file_or_dir_name),
+                $file_or_dir_name,
+                $crate::__private::inner::fs::VFSConstNormalInode::Dynamic(
+                    $crate::file::WasiDynamicFile::new($content),
+                    get_parent($empty, concat!($parent_path, "/", $file_or_dir_name), &$static_array).unwrap()
+                )
+        )));
+    };
+
     (@next2, $depth:expr, $static_array:ident, [$empty:expr], [$parent_path:expr], [$name:expr],
         ($file_or_dir_name:tt, $file_or_dir:tt $(,)?)
     ) => {
@@ -438,14 +563,70 @@ macro_rules! ConstFiles {
     };
 }
 
+/// Embeds a host directory as [`VFSConstNormalFiles`] of
+/// `WasiConstFile<&'static [u8]>` at compile time.
+///
+/// The host path is relative to the `Cargo.toml` of the crate using the macro,
+/// the second path is the name of the pre-opened directory.
+/// The files are dated `SOURCE_DATE_EPOCH` if it is set and 0 if not,
+/// so the build does not depend on when the files were touched.
+/// `embed_dir!(@len, ...)` is the number of files and dirs,
+/// or use `#[const_files]` like [`ConstFiles!`].
+/// Symbolic links are kept as links, empty directories are skipped.
+///
+/// ```ignore
+/// const FILE_COUNT: usize = embed_dir!(@len, "assets");
+///
+/// #[const_struct]
+/// const FILES: VFSConstNormalFiles<WasiConstFile<&'static [u8]>, FILE_COUNT> =
+///     embed_dir!("assets", "/usr/share");
+/// ```
+///
+/// Contents of the files are tracked by `include_bytes!`.
+/// The directories are tracked with the `tracked_path` feature,
+/// which needs a nightly compiler,
+/// so adding or removing a file rebuilds the crate.
+/// Without it touch the source file using the macro.
+#[macro_export]
+macro_rules! embed_dir {
+    ($($input:tt)*) => {
+        $crate::__private::embed_dir!($crate, $($input)*)
+    };
+}
+
+/// Compresses a host file as `WasiConstCompressedFile` at compile time.
+/// The path is relative to the `Cargo.toml` of the crate using the macro,
+/// an optional second argument is the size of the blocks before compression.
+/// `("name", @compressed "path")` is the same in [`ConstFiles!`].
+///
+/// ```ignore
+/// const FILE: WasiConstCompressedFile = include_compressed!("assets/libstd.rlib", 4096);
+/// ```
+#[cfg(feature = "compress")]
+#[macro_export]
+macro_rules! include_compressed {
+    ($($input:tt)*) => {
+        $crate::__private::include_compressed!($crate, $($input)*)
+    };
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct WasiConstFile<File: WasiConstPrimitiveFile> {
     pub file: File,
+    /// modification time in nanoseconds, 0 if unknown
+    mtim: wasip1::Timestamp,
 }
 
 impl<File: WasiConstPrimitiveFile> WasiConstFile<File> {
     pub const fn new(file: File) -> Self {
-        Self { file }
+        Self { file, mtim: 0 }
+    }
+
+    /// Sets the modification time reported for the file,
+    /// `embed_dir!` sets `SOURCE_DATE_EPOCH` if it is given.
+    pub const fn with_mtim(mut self, mtim: wasip1::Timestamp) -> Self {
+        self.mtim = mtim;
+        self
     }
 }
 
@@ -478,11 +659,34 @@ impl<'a> WasiConstPrimitiveFile for &'a str {
     }
 }
 
+impl WasiConstPrimitiveFile for &[u8] {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    #[inline(always)]
+    fn pread_raw<Wasm: WasmAccess>(
+        &self,
+        buf_ptr: *mut u8,
+        buf_len: usize,
+        offset: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        let buf_len = core::cmp::min(buf_len, self.len() - offset);
+        Wasm::memcpy(buf_ptr, &self[offset..offset + buf_len]);
+        Ok(buf_len)
+    }
+}
+
 impl<File: WasiConstPrimitiveFile> Wasip1FileTrait for WasiConstFile<File> {
     fn size(&self) -> usize {
         self.file.len()
     }
 
+    fn mtim(&self) -> wasip1::Timestamp {
+        self.mtim
+    }
+
     fn pread_raw<Wasm: WasmAccess>(
         &self,
         buf_ptr: *mut u8,
@@ -532,4 +736,62 @@ mod tests {
         #[cfg(feature = "std")]
         println!("Files: {:#?}", FILES);
     }
+
+    #[test]
+    fn test_const_files_infer_len() {
+        #[crate::const_files]
+        const FILES: VFSConstNormalFiles<WasiConstFile<&'static str>, _> = ConstFiles!([
+            ("/root", [("root.txt", WasiConstFile::new("This is root"))]),
+            (
+                ".",
+                [
+                    ("hey", WasiConstFile::new("Hey!")),
+                    ("hello", [("world", WasiConstFile::new("Hello, world!"))])
+                ]
+            )
+        ]);
+
+        assert_eq!(FILES.files.len(), 6);
+        assert_eq!(FILES.pre_open.len(), 2);
+    }
+
+    #[test]
+    fn test_embed_dir() {
+        const FILE_COUNT: usize = crate::embed_dir!(@len, "test_assets/embed");
+        const FILES: VFSConstNormalFiles<WasiConstFile<&'static [u8]>, FILE_COUNT> =
+            crate::embed_dir!("test_assets/embed", "/usr/share");
+
+        assert_eq!(FILE_COUNT, 5);
+        assert_eq!(FILES.files[0].0, "/usr/share");
+        assert_eq!(FILES.pre_open, &[0]);
+
+        let find = |name: &str| {
+            FILES
+                .files
+                .iter()
+                .find(|(path, _)| *path == name)
+                .map(|(_, inode)| *inode)
+        };
+
+        match find("hello.txt") {
+            Some(VFSConstNormalInode::File(file, 0)) => {
+                assert_eq!(file.file, b"Hello!");
+                let epoch = option_env!("SOURCE_DATE_EPOCH")
+                    .map_or(0, |epoch| epoch.parse::<u64>().unwrap() * 1_000_000_000);
+                assert_eq!(file.mtim(), epoch);
+            }
+            inode => panic!("unexpected {inode:?}"),
+        }
+        match find("world.txt") {
+            Some(VFSConstNormalInode::File(file, parent)) => {
+                assert_eq!(file.file, b"Hello, world!");
+                assert_eq!(FILES.files[parent].0, "dir");
+            }
+            inode => panic!("unexpected {inode:?}"),
+        }
+        assert!(matches!(
+            find("link"),
+            Some(VFSConstNormalInode::Symlink("hello.txt", 0))
+        ));
+    }
 }
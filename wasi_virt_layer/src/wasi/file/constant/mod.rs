@@ -1,3 +1,7 @@
+#[cfg(feature = "compress")]
+pub mod compressed;
+#[cfg(feature = "alloc")]
+pub mod dynamic;
 pub mod lfs;
 pub mod lfs_impl;
 pub mod lfs_raw;
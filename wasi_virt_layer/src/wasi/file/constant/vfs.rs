@@ -1,109 +1,154 @@
 use crate::__private::wasip1;
-use crate::__private::wasip1::{Ciovec, Dircookie, Fd, Size};
-#[cfg(feature = "threads")]
-use parking_lot::RwLock;
-
-use crate::{memory::WasmAccess, wasi::file::Wasip1LFS};
+use crate::__private::wasip1::{Ciovec, Dircookie, Fd, Iovec, Size};
+
+#[cfg(feature = "alloc")]
+use crate::wasi::file::snapshot::{SnapshotReader, SnapshotWriter, Wasip1Snapshot};
+use crate::{
+    memory::WasmAccess,
+    wasi::file::{
+        Wasip1LFS,
+        fd_table::{ConstFdTable, FdEntry, FdTable},
+    },
+};
+
+/// [`Wasip1VFS`] with a [`ConstFdTable`] of `FD_LEN` descriptors besides stdio.
+/// `FD_LEN` is independent of the number of inodes in the LFS.
+pub type Wasip1ConstVFS<LFS, const FD_LEN: usize> =
+    Wasip1VFS<LFS, ConstFdTable<<LFS as Wasip1LFS>::Inode, FD_LEN>>;
 
 /// small posix like virtual file system
 /// but inode has some metadata
-pub struct Wasip1ConstVFS<LFS: Wasip1LFS + Sync, const FLAT_LEN: usize>
+///
+/// The descriptors are kept in `Table`,
+/// see [`Wasip1ConstVFS`] for the fixed size one
+/// and [`Wasip1VFS::with_fd_table`] for others,
+/// e.g. the growable `VecFdTable` under `alloc`.
+pub struct Wasip1VFS<LFS: Wasip1LFS + Sync, Table: FdTable<LFS::Inode>>
 where
     LFS::Inode: Copy,
 {
     lfs: LFS,
-    // (inode, cursor)
-    #[cfg(feature = "threads")]
-    map: [RwLock<Option<(LFS::Inode, usize)>>; FLAT_LEN],
-    #[cfg(not(feature = "threads"))]
-    map: [Option<(LFS::Inode, usize)>; FLAT_LEN],
+    table: Table,
 }
 
-impl<LFS: Wasip1LFS + Sync, const FLAT_LEN: usize> Wasip1ConstVFS<LFS, FLAT_LEN>
+impl<LFS: Wasip1LFS + Sync, const FD_LEN: usize> Wasip1VFS<LFS, ConstFdTable<LFS::Inode, FD_LEN>>
 where
     LFS::Inode: Copy,
 {
-    #[cfg(feature = "threads")]
     pub const fn new(lfs: LFS) -> Self {
-        let mut map: [RwLock<Option<(LFS::Inode, usize)>>; FLAT_LEN] =
-            [const { RwLock::new(None) }; FLAT_LEN];
+        Self {
+            lfs,
+            table: ConstFdTable::new(LFS::PRE_OPEN),
+        }
+    }
+}
+
+impl<LFS: Wasip1LFS + Sync, Table: FdTable<LFS::Inode>> Wasip1VFS<LFS, Table>
+where
+    LFS::Inode: Copy,
+{
+    /// The table must already contain `LFS::PRE_OPEN`.
+    pub const fn with_fd_table(lfs: LFS, table: Table) -> Self {
+        Self { lfs, table }
+    }
 
-        use const_for::const_for;
+    #[inline]
+    pub const fn lfs(&self) -> &LFS {
+        &self.lfs
+    }
 
-        const_for!(i in 0..LFS::PRE_OPEN.len() => {
-            map[i] = RwLock::new(Some((LFS::PRE_OPEN[i], i)));
-        });
+    #[inline]
+    pub const fn lfs_mut(&mut self) -> &mut LFS {
+        &mut self.lfs
+    }
 
-        Self { lfs, map }
+    #[inline]
+    pub const fn fd_table(&self) -> &Table {
+        &self.table
     }
 
-    #[cfg(not(feature = "threads"))]
-    pub const fn new(lfs: LFS) -> Self {
-        let map: [Option<(LFS::Inode, usize)>; FLAT_LEN] = [const { None }; FLAT_LEN];
+    #[inline]
+    pub const fn fd_table_mut(&mut self) -> &mut Table {
+        &mut self.table
+    }
+
+    /// Saves the state of the file systems, including the files the guest created,
+    /// in the versioned format of [`SnapshotWriter`].
+    /// The descriptors are not saved.
+    #[cfg(feature = "alloc")]
+    pub fn snapshot(&self) -> alloc::vec::Vec<u8>
+    where
+        LFS: Wasip1Snapshot,
+    {
+        let mut out = SnapshotWriter::new();
+        self.lfs.snapshot_to(&mut out);
+        out.into_bytes()
+    }
 
-        Self { lfs, map }
+    /// Loads a snapshot made by [`Wasip1VFS::snapshot`]
+    /// of the same file systems, meant for a fresh instance.
+    /// `ERRNO_INVAL` if it is broken,
+    /// `ERRNO_NOTSUP` if it was written by another version.
+    #[cfg(feature = "alloc")]
+    pub fn restore(&mut self, data: &[u8]) -> Result<(), wasip1::Errno>
+    where
+        LFS: Wasip1Snapshot,
+    {
+        let mut input = SnapshotReader::new(data)?;
+        self.lfs.restore_from(&mut input)?;
+        input.finish()
     }
 
     #[inline]
-    pub fn get_inode(&self, fd: Fd) -> Option<LFS::Inode> {
-        #[cfg(feature = "threads")]
-        {
-            self.map
-                .get(fd as usize - 3)?
-                .read()
-                .map(|(inode, _)| inode)
-        }
+    pub fn get_entry(&self, fd: Fd) -> Option<FdEntry<LFS::Inode>> {
+        self.table.get(fd)
+    }
 
-        #[cfg(not(feature = "threads"))]
-        {
-            self.map.get(fd as usize - 3)?.map(|(inode, _)| inode)
-        }
+    #[inline]
+    pub fn update_entry<R>(
+        &mut self,
+        fd: Fd,
+        f: impl FnOnce(&mut FdEntry<LFS::Inode>) -> R,
+    ) -> Option<R> {
+        self.table.update(fd, f)
+    }
+
+    #[inline]
+    pub fn get_inode(&self, fd: Fd) -> Option<LFS::Inode> {
+        self.get_entry(fd).map(|entry| entry.inode)
     }
 
     #[inline]
     pub fn remove_inode(&mut self, fd: Fd) -> Option<LFS::Inode> {
-        #[cfg(feature = "threads")]
-        {
-            self.map
-                .get_mut(fd as usize - 3)?
-                .write()
-                .take()
-                .map(|(inode, _)| inode)
-        }
+        self.table.remove(fd).map(|entry| entry.inode)
+    }
 
-        #[cfg(not(feature = "threads"))]
-        {
-            self.map
-                .get_mut(fd as usize - 3)?
-                .take()
-                .map(|(inode, _)| inode)
-        }
+    #[inline]
+    pub fn push_inode(&mut self, entry: FdEntry<LFS::Inode>) -> Result<Fd, wasip1::Errno> {
+        self.table.push(entry)
     }
 
+    /// Whether the fd is the stdio of the host, not redirected by `fd_renumber`.
     #[inline]
-    pub fn push_inode(&mut self, inode: LFS::Inode) -> Fd {
-        #[cfg(feature = "threads")]
-        {
-            for (i, slot) in self.map.iter_mut().enumerate() {
-                let mut slot = slot.write();
-                if slot.is_none() {
-                    *slot = Some((inode, 0));
-                    return (i + 3) as Fd;
-                }
-            }
-        }
+    pub fn is_stdio(&self, fd: Fd) -> bool {
+        fd < 3 && self.table.get(fd).is_none()
+    }
 
-        #[cfg(not(feature = "threads"))]
-        {
-            for (i, slot) in self.map.iter_mut().enumerate() {
-                if slot.is_none() {
-                    *slot = Some((inode, 0));
-                    return (i + 3) as Fd;
-                }
-            }
+    /// Returns the entry if it has all of the rights,
+    /// `ERRNO_NOTCAPABLE` otherwise.
+    #[inline]
+    pub(crate) fn entry_with_rights(
+        &self,
+        fd: Fd,
+        rights: wasip1::Rights,
+    ) -> Result<FdEntry<LFS::Inode>, wasip1::Errno> {
+        let entry = self.get_entry(fd).ok_or(wasip1::ERRNO_BADF)?;
+
+        if !entry.has_rights(rights) {
+            return Err(wasip1::ERRNO_NOTCAPABLE);
         }
 
-        unreachable!();
+        Ok(entry)
     }
 
     #[inline]
@@ -114,29 +159,21 @@ where
     pub(crate) fn fd_readdir_raw<Wasm: WasmAccess>(
         &mut self,
         fd: Fd,
-        mut buf: *mut u8,
-        mut buf_len: usize,
-        mut cookie: Dircookie,
+        buf: *mut u8,
+        buf_len: usize,
+        cookie: Dircookie,
     ) -> Result<Size, wasip1::Errno> {
-        let (inode, lfs) = self.get_inode_and_lfs(fd).ok_or(wasip1::ERRNO_BADF)?;
+        let inode = self.entry_with_rights(fd, wasip1::RIGHTS_FD_READDIR)?.inode;
+        let lfs = &mut self.lfs;
 
         // check is this a directory
         if !lfs.is_dir(inode) {
             return Err(wasip1::ERRNO_NOTDIR);
         }
 
-        let mut read = 0;
+        let (read, _) = lfs.fd_readdir_raw::<Wasm>(inode, buf, buf_len, cookie)?;
 
-        loop {
-            let (n, next_cookie) = lfs.fd_readdir_raw::<Wasm>(inode, buf, buf_len, cookie)?;
-            if n == 0 {
-                return Ok(read);
-            }
-            read += n;
-            buf = unsafe { buf.add(n) };
-            buf_len -= n;
-            cookie = next_cookie;
-        }
+        Ok(read)
     }
 
     pub(crate) fn fd_write_raw<Wasm: WasmAccess>(
@@ -146,8 +183,8 @@ where
         iovs_len: usize,
     ) -> Result<Size, wasip1::Errno> {
         match fd {
-            0 => return Err(wasip1::ERRNO_BADF),
-            1 | 2 => {
+            0 if self.is_stdio(fd) => Err(wasip1::ERRNO_BADF),
+            1 | 2 if self.is_stdio(fd) => {
                 // stdout
                 let lfs = &mut self.lfs;
 
@@ -169,7 +206,21 @@ where
                 Ok(written)
             }
             fd => {
-                let (inode, lfs) = self.get_inode_and_lfs(fd).ok_or(wasip1::ERRNO_BADF)?;
+                let FdEntry {
+                    inode,
+                    mut cursor,
+                    fd_flags,
+                    ..
+                } = self.entry_with_rights(fd, wasip1::RIGHTS_FD_WRITE)?;
+                let lfs = &mut self.lfs;
+
+                if lfs.is_dir(inode) {
+                    return Err(wasip1::ERRNO_ISDIR);
+                }
+
+                if fd_flags & wasip1::FDFLAGS_APPEND == wasip1::FDFLAGS_APPEND {
+                    cursor = lfs.fd_filestat_get_raw::<Wasm>(inode)?.size as usize;
+                }
 
                 let iovs_vec = Wasm::as_array(iovs_ptr, iovs_len);
 
@@ -179,9 +230,13 @@ where
                     let buf_len = iovs.buf_len;
                     let buf_ptr = iovs.buf;
 
-                    written += lfs.fd_write_raw::<Wasm>(inode, buf_ptr, buf_len)?;
+                    let nwritten = lfs.fd_pwrite_raw::<Wasm>(inode, buf_ptr, buf_len, cursor)?;
+                    written += nwritten;
+                    cursor += nwritten;
                 }
 
+                self.set_cursor(fd, cursor)?;
+
                 Ok(written)
             }
         }
@@ -252,55 +307,187 @@ where
         })
     }
 
+    pub(crate) fn fd_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        atim: wasip1::Timestamp,
+        mtim: wasip1::Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        let entry = self.entry_with_rights(fd, wasip1::RIGHTS_FD_FILESTAT_SET_TIMES)?;
+
+        self.lfs
+            .fd_filestat_set_times_raw::<Wasm>(entry.inode, atim, mtim, fst_flags)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn path_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        atim: wasip1::Timestamp,
+        mtim: wasip1::Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        let dir = self.entry_with_rights(fd, wasip1::RIGHTS_PATH_FILESTAT_SET_TIMES)?;
+
+        // opening without flags only looks the path up
+        let inode = self
+            .lfs
+            .path_open_raw::<Wasm>(dir.inode, flags, path_ptr, path_len, 0, 0, 0, 0)?;
+
+        let result = self
+            .lfs
+            .fd_filestat_set_times_raw::<Wasm>(inode, atim, mtim, fst_flags);
+        self.lfs.fd_close_raw::<Wasm>(inode);
+
+        result
+    }
+
     pub(crate) fn fd_close_raw<Wasm: WasmAccess>(&mut self, fd: Fd) -> Result<(), wasip1::Errno> {
-        if self.remove_inode(fd).is_none() {
-            return Err(wasip1::ERRNO_BADF);
-        }
+        let inode = self.remove_inode(fd).ok_or(wasip1::ERRNO_BADF)?;
+        self.lfs.fd_close_raw::<Wasm>(inode);
         Ok(())
     }
 
-    pub(crate) fn get_cursor(&mut self, fd: Fd) -> Result<usize, wasip1::Errno> {
-        #[cfg(feature = "threads")]
-        {
-            self.map
-                .get(fd as usize - 3)
-                .ok_or(wasip1::ERRNO_BADF)?
-                .read()
-                .map(|(_, cursor)| cursor)
-                .ok_or(wasip1::ERRNO_BADF)
+    pub(crate) fn fd_sync_raw<Wasm: WasmAccess>(&mut self, fd: Fd) -> Result<(), wasip1::Errno> {
+        // the stdio of the host is not a file to sync
+        if self.is_stdio(fd) {
+            return Err(wasip1::ERRNO_INVAL);
         }
 
-        #[cfg(not(feature = "threads"))]
-        {
-            self.map
-                .get(fd as usize - 3)
-                .ok_or(wasip1::ERRNO_BADF)?
-                .map(|(_, cursor)| cursor)
-                .ok_or(wasip1::ERRNO_BADF)
+        let entry = self.entry_with_rights(fd, wasip1::RIGHTS_FD_SYNC)?;
+
+        self.lfs.fd_sync_raw::<Wasm>(entry.inode)
+    }
+
+    /// Same as `fd_sync`, file systems here do not keep metadata apart from data.
+    pub(crate) fn fd_datasync_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+    ) -> Result<(), wasip1::Errno> {
+        if self.is_stdio(fd) {
+            return Err(wasip1::ERRNO_INVAL);
+        }
+
+        let entry = self.entry_with_rights(fd, wasip1::RIGHTS_FD_DATASYNC)?;
+
+        self.lfs.fd_sync_raw::<Wasm>(entry.inode)
+    }
+
+    /// Moves `fd` to `to` and closes what `to` was.
+    /// `to` may be stdio, which redirects it to the file
+    /// until it is closed again.
+    pub(crate) fn fd_renumber_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        to: Fd,
+    ) -> Result<(), wasip1::Errno> {
+        // the stdio of the host has no entry to move
+        if self.is_stdio(fd) {
+            return Err(wasip1::ERRNO_NOTSUP);
+        }
+
+        let entry = self.get_entry(fd).ok_or(wasip1::ERRNO_BADF)?;
+
+        if !self.is_stdio(to) && self.get_entry(to).is_none() {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        if fd == to {
+            return Ok(());
+        }
+
+        let closed = self.get_inode(to);
+
+        self.table.replace(to, entry)?;
+        self.table.remove(fd);
+
+        if let Some(closed) = closed {
+            self.lfs.fd_close_raw::<Wasm>(closed);
         }
+
+        Ok(())
     }
 
     pub(crate) fn set_cursor(&mut self, fd: Fd, cursor: usize) -> Result<(), wasip1::Errno> {
-        #[cfg(feature = "threads")]
-        {
-            self.map
-                .get(fd as usize - 3)
-                .ok_or(wasip1::ERRNO_BADF)?
-                .write()
-                .as_mut()
-                .map(|(_, cur)| *cur = cursor)
-                .ok_or(wasip1::ERRNO_BADF)
+        self.update_entry(fd, |entry| entry.cursor = cursor)
+            .ok_or(wasip1::ERRNO_BADF)
+    }
+
+    pub(crate) fn fd_fdstat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+    ) -> Result<wasip1::Fdstat, wasip1::Errno> {
+        let rights_base = match fd {
+            0 if self.is_stdio(fd) => wasip1::RIGHTS_FD_READ | wasip1::RIGHTS_POLL_FD_READWRITE,
+            1 | 2 if self.is_stdio(fd) => {
+                wasip1::RIGHTS_FD_WRITE | wasip1::RIGHTS_POLL_FD_READWRITE
+            }
+            fd => {
+                let entry = self.get_entry(fd).ok_or(wasip1::ERRNO_BADF)?;
+
+                let filestat = self.lfs.fd_filestat_get_raw::<Wasm>(entry.inode)?;
+
+                return Ok(wasip1::Fdstat {
+                    fs_filetype: filestat.filetype,
+                    fs_flags: entry.fd_flags,
+                    fs_rights_base: entry.rights_base,
+                    fs_rights_inheriting: entry.rights_inheriting,
+                });
+            }
+        };
+
+        // stdio is a tty without seek and tell
+        Ok(wasip1::Fdstat {
+            fs_filetype: wasip1::FILETYPE_CHARACTER_DEVICE,
+            fs_flags: 0,
+            fs_rights_base: rights_base,
+            fs_rights_inheriting: 0,
+        })
+    }
+
+    pub(crate) fn fd_fdstat_set_flags_raw(
+        &mut self,
+        fd: Fd,
+        flags: wasip1::Fdflags,
+    ) -> Result<(), wasip1::Errno> {
+        if self.is_stdio(fd) {
+            return Err(wasip1::ERRNO_NOTSUP);
         }
 
-        #[cfg(not(feature = "threads"))]
+        self.entry_with_rights(fd, wasip1::RIGHTS_FD_FDSTAT_SET_FLAGS)?;
+
+        self.update_entry(fd, |entry| entry.fd_flags = flags)
+            .ok_or(wasip1::ERRNO_BADF)
+    }
+
+    /// Rights can only be dropped, never added.
+    pub(crate) fn fd_fdstat_set_rights_raw(
+        &mut self,
+        fd: Fd,
+        fs_rights_base: wasip1::Rights,
+        fs_rights_inheriting: wasip1::Rights,
+    ) -> Result<(), wasip1::Errno> {
+        if self.is_stdio(fd) {
+            return Err(wasip1::ERRNO_NOTSUP);
+        }
+
+        let entry = self.get_entry(fd).ok_or(wasip1::ERRNO_BADF)?;
+
+        if fs_rights_base & !entry.rights_base != 0
+            || fs_rights_inheriting & !entry.rights_inheriting != 0
         {
-            self.map
-                .get_mut(fd as usize - 3)
-                .ok_or(wasip1::ERRNO_BADF)?
-                .as_mut()
-                .map(|(_, cur)| *cur = cursor)
-                .ok_or(wasip1::ERRNO_BADF)
+            return Err(wasip1::ERRNO_NOTCAPABLE);
         }
+
+        self.update_entry(fd, |entry| {
+            entry.rights_base = fs_rights_base;
+            entry.rights_inheriting = fs_rights_inheriting;
+        })
+        .ok_or(wasip1::ERRNO_BADF)
     }
 
     pub(crate) fn fd_read_raw<Wasm: WasmAccess>(
@@ -310,7 +497,7 @@ where
         iovs_len: usize,
     ) -> Result<Size, wasip1::Errno> {
         match fd {
-            0 => {
+            0 if self.is_stdio(fd) => {
                 let lfs = &mut self.lfs;
 
                 let iovs_vec = Wasm::as_array(iovs_ptr, iovs_len);
@@ -323,11 +510,12 @@ where
 
                 Ok(read)
             }
-            1 | 2 => return Err(wasip1::ERRNO_BADF),
+            1 | 2 if self.is_stdio(fd) => Err(wasip1::ERRNO_BADF),
             fd => {
-                let mut cursor = self.get_cursor(fd)?;
-
-                let (inode, lfs) = self.get_inode_and_lfs(fd).ok_or(wasip1::ERRNO_BADF)?;
+                let FdEntry {
+                    inode, mut cursor, ..
+                } = self.entry_with_rights(fd, wasip1::RIGHTS_FD_READ)?;
+                let lfs = &mut self.lfs;
 
                 if lfs.is_dir(inode) {
                     return Err(wasip1::ERRNO_ISDIR);
@@ -351,10 +539,128 @@ where
         }
     }
 
+    pub(crate) fn fd_pread_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        iovs_ptr: *const Iovec,
+        iovs_len: usize,
+        offset: wasip1::Filesize,
+    ) -> Result<Size, wasip1::Errno> {
+        if self.is_stdio(fd) {
+            return Err(wasip1::ERRNO_SPIPE);
+        }
+
+        let inode = self
+            .entry_with_rights(fd, wasip1::RIGHTS_FD_READ | wasip1::RIGHTS_FD_SEEK)?
+            .inode;
+        let lfs = &mut self.lfs;
+
+        if lfs.is_dir(inode) {
+            return Err(wasip1::ERRNO_ISDIR);
+        }
+
+        let mut offset = offset as usize;
+
+        let iovs_vec = Wasm::as_array(iovs_ptr, iovs_len);
+
+        let mut read = 0;
+
+        for iovs in iovs_vec {
+            let nread = lfs.fd_pread_raw::<Wasm>(inode, iovs.buf, iovs.buf_len, offset)?;
+            read += nread;
+            offset += nread;
+
+            if nread < iovs.buf_len {
+                break;
+            }
+        }
+
+        Ok(read)
+    }
+
+    pub(crate) fn fd_pwrite_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        iovs_ptr: *const Ciovec,
+        iovs_len: usize,
+        offset: wasip1::Filesize,
+    ) -> Result<Size, wasip1::Errno> {
+        if self.is_stdio(fd) {
+            return Err(wasip1::ERRNO_SPIPE);
+        }
+
+        let inode = self
+            .entry_with_rights(fd, wasip1::RIGHTS_FD_WRITE | wasip1::RIGHTS_FD_SEEK)?
+            .inode;
+        let lfs = &mut self.lfs;
+
+        if lfs.is_dir(inode) {
+            return Err(wasip1::ERRNO_ISDIR);
+        }
+
+        let mut offset = offset as usize;
+
+        let iovs_vec = Wasm::as_array(iovs_ptr, iovs_len);
+
+        let mut written = 0;
+
+        for iovs in iovs_vec {
+            let nwritten = lfs.fd_pwrite_raw::<Wasm>(inode, iovs.buf, iovs.buf_len, offset)?;
+            written += nwritten;
+            offset += nwritten;
+        }
+
+        Ok(written)
+    }
+
+    pub(crate) fn fd_seek_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        offset: wasip1::Filedelta,
+        whence: wasip1::Whence,
+    ) -> Result<wasip1::Filesize, wasip1::Errno> {
+        if self.is_stdio(fd) {
+            return Err(wasip1::ERRNO_SPIPE);
+        }
+
+        let entry = self.get_entry(fd).ok_or(wasip1::ERRNO_BADF)?;
+
+        // asking only the current offset is allowed with `RIGHTS_FD_TELL`
+        let is_tell = whence == wasip1::WHENCE_CUR && offset == 0;
+        let allowed = entry.has_rights(wasip1::RIGHTS_FD_SEEK)
+            || (is_tell && entry.has_rights(wasip1::RIGHTS_FD_TELL));
+        if !allowed {
+            return Err(wasip1::ERRNO_NOTCAPABLE);
+        }
+
+        let FdEntry { inode, cursor, .. } = entry;
+
+        let new_cursor = self
+            .lfs
+            .fd_seek_raw::<Wasm>(inode, cursor, offset, whence)?;
+
+        self.set_cursor(fd, new_cursor)?;
+
+        Ok(new_cursor as wasip1::Filesize)
+    }
+
+    pub(crate) fn fd_tell_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+    ) -> Result<wasip1::Filesize, wasip1::Errno> {
+        if self.is_stdio(fd) {
+            return Err(wasip1::ERRNO_SPIPE);
+        }
+
+        let FdEntry { inode, cursor, .. } = self.entry_with_rights(fd, wasip1::RIGHTS_FD_TELL)?;
+
+        Ok(self.lfs.fd_tell_raw::<Wasm>(inode, cursor)? as wasip1::Filesize)
+    }
+
     pub(crate) fn path_open_raw<Wasm: WasmAccess>(
         &mut self,
         dir_fd: Fd,
-        dir_flags: wasip1::Fdflags,
+        dir_flags: wasip1::Lookupflags,
         path_ptr: *const u8,
         path_len: usize,
         o_flags: wasip1::Oflags,
@@ -362,7 +668,17 @@ where
         fs_rights_inheriting: wasip1::Rights,
         fd_flags: wasip1::Fdflags,
     ) -> Result<Fd, wasip1::Errno> {
-        let (inode, lfs) = self.get_inode_and_lfs(dir_fd).ok_or(wasip1::ERRNO_BADF)?;
+        let dir = self.entry_with_rights(dir_fd, wasip1::RIGHTS_PATH_OPEN)?;
+
+        // the new descriptor cannot have more rights than the directory hands out
+        if fs_rights_base & !dir.rights_inheriting != 0
+            || fs_rights_inheriting & !dir.rights_inheriting != 0
+        {
+            return Err(wasip1::ERRNO_NOTCAPABLE);
+        }
+
+        let inode = dir.inode;
+        let lfs = &mut self.lfs;
 
         let new_inode = lfs.path_open_raw::<Wasm>(
             inode,
@@ -375,6 +691,107 @@ where
             fd_flags,
         )?;
 
-        Ok(self.push_inode(new_inode))
+        match self.push_inode(FdEntry {
+            inode: new_inode,
+            cursor: 0,
+            fd_flags,
+            rights_base: fs_rights_base,
+            rights_inheriting: fs_rights_inheriting,
+        }) {
+            Ok(fd) => Ok(fd),
+            Err(e) => {
+                // the table is full, the inode opened above has no descriptor to close it
+                self.lfs.fd_close_raw::<Wasm>(new_inode);
+                Err(e)
+            }
+        }
+    }
+
+    pub(crate) fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (inode, lfs) = self.get_inode_and_lfs(fd).ok_or(wasip1::ERRNO_BADF)?;
+
+        lfs.path_create_directory_raw::<Wasm>(inode, path_ptr, path_len)
+    }
+
+    pub(crate) fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (inode, lfs) = self.get_inode_and_lfs(fd).ok_or(wasip1::ERRNO_BADF)?;
+
+        lfs.path_remove_directory_raw::<Wasm>(inode, path_ptr, path_len)
+    }
+
+    pub(crate) fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (inode, lfs) = self.get_inode_and_lfs(fd).ok_or(wasip1::ERRNO_BADF)?;
+
+        lfs.path_unlink_file_raw::<Wasm>(inode, path_ptr, path_len)
+    }
+
+    /// Both descriptors share the same LFS, so `ERRNO_XDEV` never happens here.
+    pub(crate) fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_fd: Fd,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_fd: Fd,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let old_inode = self.get_inode(old_fd).ok_or(wasip1::ERRNO_BADF)?;
+        let new_inode = self.get_inode(new_fd).ok_or(wasip1::ERRNO_BADF)?;
+
+        self.lfs_mut().path_rename_raw::<Wasm>(
+            old_inode,
+            old_path_ptr,
+            old_path_len,
+            new_inode,
+            new_path_ptr,
+            new_path_len,
+        )
+    }
+
+    pub(crate) fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<Size, wasip1::Errno> {
+        let (inode, lfs) = self.get_inode_and_lfs(fd).ok_or(wasip1::ERRNO_BADF)?;
+
+        lfs.path_readlink_raw::<Wasm>(inode, path_ptr, path_len, buf, buf_len)
+    }
+
+    pub(crate) fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        fd: Fd,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (inode, lfs) = self.get_inode_and_lfs(fd).ok_or(wasip1::ERRNO_BADF)?;
+
+        lfs.path_symlink_raw::<Wasm>(
+            old_path_ptr,
+            old_path_len,
+            inode,
+            new_path_ptr,
+            new_path_len,
+        )
     }
 }
@@ -1,13 +1,12 @@
 use crate::__private::wasip1;
-use crate::__private::wasip1::{Ciovec, Dircookie, Fd, Size};
+use crate::__private::wasip1::{Ciovec, Dircookie, Fd, Iovec, Size};
 
 use crate::{
     memory::WasmAccess,
-    wasi::file::{Wasip1FileSystem, Wasip1LFS, constant::vfs::Wasip1ConstVFS},
+    wasi::file::{Wasip1FileSystem, Wasip1LFS, constant::vfs::Wasip1VFS, fd_table::FdTable},
 };
 
-impl<LFS: Wasip1LFS + Sync, const FLAT_LEN: usize> Wasip1FileSystem
-    for Wasip1ConstVFS<LFS, FLAT_LEN>
+impl<LFS: Wasip1LFS + Sync, Table: FdTable<LFS::Inode>> Wasip1FileSystem for Wasip1VFS<LFS, Table>
 where
     LFS::Inode: Copy,
 {
@@ -94,6 +93,20 @@ where
         }
     }
 
+    fn fd_sync_raw<Wasm: WasmAccess>(&mut self, fd: Fd) -> wasip1::Errno {
+        match self.fd_sync_raw::<Wasm>(fd) {
+            Ok(()) => wasip1::ERRNO_SUCCESS,
+            Err(e) => e,
+        }
+    }
+
+    fn fd_datasync_raw<Wasm: WasmAccess>(&mut self, fd: Fd) -> wasip1::Errno {
+        match self.fd_datasync_raw::<Wasm>(fd) {
+            Ok(()) => wasip1::ERRNO_SUCCESS,
+            Err(e) => e,
+        }
+    }
+
     fn fd_filestat_get_raw<Wasm: WasmAccess>(
         &mut self,
         fd: Fd,
@@ -108,6 +121,37 @@ where
         }
     }
 
+    fn fd_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        atim: wasip1::Timestamp,
+        mtim: wasip1::Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> wasip1::Errno {
+        match self.fd_filestat_set_times_raw::<Wasm>(fd, atim, mtim, fst_flags) {
+            Ok(()) => wasip1::ERRNO_SUCCESS,
+            Err(e) => e,
+        }
+    }
+
+    fn path_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        atim: wasip1::Timestamp,
+        mtim: wasip1::Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> wasip1::Errno {
+        match self.path_filestat_set_times_raw::<Wasm>(
+            fd, flags, path_ptr, path_len, atim, mtim, fst_flags,
+        ) {
+            Ok(()) => wasip1::ERRNO_SUCCESS,
+            Err(e) => e,
+        }
+    }
+
     fn fd_read_raw<Wasm: WasmAccess>(
         &mut self,
         fd: Fd,
@@ -124,10 +168,74 @@ where
         }
     }
 
+    fn fd_pread_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        iovs_ptr: *const Iovec,
+        iovs_len: usize,
+        offset: wasip1::Filesize,
+        nread: *mut Size,
+    ) -> wasip1::Errno {
+        match self.fd_pread_raw::<Wasm>(fd, iovs_ptr, iovs_len, offset) {
+            Ok(n) => {
+                Wasm::store_le(nread, n);
+                wasip1::ERRNO_SUCCESS
+            }
+            Err(e) => e,
+        }
+    }
+
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        iovs_ptr: *const Ciovec,
+        iovs_len: usize,
+        offset: wasip1::Filesize,
+        nwritten: *mut Size,
+    ) -> wasip1::Errno {
+        match self.fd_pwrite_raw::<Wasm>(fd, iovs_ptr, iovs_len, offset) {
+            Ok(n) => {
+                Wasm::store_le(nwritten, n);
+                wasip1::ERRNO_SUCCESS
+            }
+            Err(e) => e,
+        }
+    }
+
+    fn fd_seek_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        offset: wasip1::Filedelta,
+        whence: wasip1::Whence,
+        newoffset: *mut wasip1::Filesize,
+    ) -> wasip1::Errno {
+        match self.fd_seek_raw::<Wasm>(fd, offset, whence) {
+            Ok(n) => {
+                Wasm::store_le(newoffset, n);
+                wasip1::ERRNO_SUCCESS
+            }
+            Err(e) => e,
+        }
+    }
+
+    fn fd_tell_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        offset: *mut wasip1::Filesize,
+    ) -> wasip1::Errno {
+        match self.fd_tell_raw::<Wasm>(fd) {
+            Ok(n) => {
+                Wasm::store_le(offset, n);
+                wasip1::ERRNO_SUCCESS
+            }
+            Err(e) => e,
+        }
+    }
+
     fn path_open_raw<Wasm: WasmAccess>(
         &mut self,
         dir_fd: Fd,
-        dir_flags: wasip1::Fdflags,
+        dir_flags: wasip1::Lookupflags,
         path_ptr: *const u8,
         path_len: usize,
         o_flags: wasip1::Oflags,
@@ -153,4 +261,144 @@ where
             Err(e) => e,
         }
     }
+
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> wasip1::Errno {
+        match self.path_create_directory_raw::<Wasm>(fd, path_ptr, path_len) {
+            Ok(()) => wasip1::ERRNO_SUCCESS,
+            Err(e) => e,
+        }
+    }
+
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> wasip1::Errno {
+        match self.path_remove_directory_raw::<Wasm>(fd, path_ptr, path_len) {
+            Ok(()) => wasip1::ERRNO_SUCCESS,
+            Err(e) => e,
+        }
+    }
+
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> wasip1::Errno {
+        match self.path_unlink_file_raw::<Wasm>(fd, path_ptr, path_len) {
+            Ok(()) => wasip1::ERRNO_SUCCESS,
+            Err(e) => e,
+        }
+    }
+
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_fd: Fd,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_fd: Fd,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> wasip1::Errno {
+        match self.path_rename_raw::<Wasm>(
+            old_fd,
+            old_path_ptr,
+            old_path_len,
+            new_fd,
+            new_path_ptr,
+            new_path_len,
+        ) {
+            Ok(()) => wasip1::ERRNO_SUCCESS,
+            Err(e) => e,
+        }
+    }
+
+    fn fd_renumber_raw<Wasm: WasmAccess>(&mut self, fd: Fd, to: Fd) -> wasip1::Errno {
+        match self.fd_renumber_raw::<Wasm>(fd, to) {
+            Ok(()) => wasip1::ERRNO_SUCCESS,
+            Err(e) => e,
+        }
+    }
+
+    fn fd_fdstat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        fdstat: *mut wasip1::Fdstat,
+    ) -> wasip1::Errno {
+        match self.fd_fdstat_get_raw::<Wasm>(fd) {
+            Ok(stat) => {
+                Wasm::store_le(fdstat, stat);
+                wasip1::ERRNO_SUCCESS
+            }
+            Err(e) => e,
+        }
+    }
+
+    fn fd_fdstat_set_flags_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        flags: wasip1::Fdflags,
+    ) -> wasip1::Errno {
+        match self.fd_fdstat_set_flags_raw(fd, flags) {
+            Ok(()) => wasip1::ERRNO_SUCCESS,
+            Err(e) => e,
+        }
+    }
+
+    fn fd_fdstat_set_rights_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        fs_rights_base: wasip1::Rights,
+        fs_rights_inheriting: wasip1::Rights,
+    ) -> wasip1::Errno {
+        match self.fd_fdstat_set_rights_raw(fd, fs_rights_base, fs_rights_inheriting) {
+            Ok(()) => wasip1::ERRNO_SUCCESS,
+            Err(e) => e,
+        }
+    }
+
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+        nread: *mut Size,
+    ) -> wasip1::Errno {
+        match self.path_readlink_raw::<Wasm>(fd, path_ptr, path_len, buf, buf_len) {
+            Ok(len) => {
+                Wasm::store_le(nread, len);
+                wasip1::ERRNO_SUCCESS
+            }
+            Err(e) => e,
+        }
+    }
+
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        fd: Fd,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> wasip1::Errno {
+        match self.path_symlink_raw::<Wasm>(
+            old_path_ptr,
+            old_path_len,
+            fd,
+            new_path_ptr,
+            new_path_len,
+        ) {
+            Ok(()) => wasip1::ERRNO_SUCCESS,
+            Err(e) => e,
+        }
+    }
 }
@@ -0,0 +1,174 @@
+use crate::__private::wasip1;
+#[cfg(feature = "alloc")]
+use crate::wasi::file::snapshot::{SnapshotReader, SnapshotWriter, Wasip1Snapshot};
+use crate::{
+    memory::{WasmAccess, WasmPathAccess, WasmPathComponent},
+    wasi::file::{FilestatWithoutDevice, stdio::StdIO},
+};
+
+/// Where `/dev/urandom` takes its bytes from.
+pub trait Wasip1Random {
+    fn fill(buf: &mut [u8]) -> Result<(), wasip1::Errno>;
+}
+
+/// `random_get` of the host.
+pub struct DefaultRandom;
+
+impl Wasip1Random for DefaultRandom {
+    #[allow(unused_variables)]
+    fn fill(buf: &mut [u8]) -> Result<(), wasip1::Errno> {
+        #[cfg(target_os = "wasi")]
+        {
+            use crate::transporter::Wasip1Transporter;
+
+            Wasip1Transporter::random_get(buf)
+        }
+
+        #[cfg(not(target_os = "wasi"))]
+        {
+            Err(wasip1::ERRNO_NOSYS)
+        }
+    }
+}
+
+/// One device of [`VFSDevLFS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VFSDevNode {
+    /// Reads nothing, discards writes.
+    Null,
+    /// Reads zeros, discards writes.
+    Zero,
+    /// Reads random bytes, discards writes.
+    Urandom,
+    /// The terminal, reads stdin and writes stdout of the `StdIO`.
+    Tty,
+}
+
+/// The devices by inode, inode 0 is the directory itself.
+pub const DEVICES: [(&str, VFSDevNode); 4] = [
+    ("null", VFSDevNode::Null),
+    ("zero", VFSDevNode::Zero),
+    ("urandom", VFSDevNode::Urandom),
+    ("tty", VFSDevNode::Tty),
+];
+
+/// Device directory
+/// which serves the usual character devices,
+/// `null`, `zero`, `urandom` and `tty`, to be mounted at `/dev`.
+/// The devices have no size and ignore the offset,
+/// and no file can be created next to them.
+pub struct VFSDevLFS<StdIo: StdIO + 'static, Random: Wasip1Random = DefaultRandom> {
+    name: &'static str,
+    __marker: core::marker::PhantomData<(StdIo, Random)>,
+}
+
+impl<StdIo: StdIO + 'static, Random: Wasip1Random> VFSDevLFS<StdIo, Random> {
+    /// `name` is the name of the pre-opened directory, e.g. `/dev`.
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            __marker: core::marker::PhantomData,
+        }
+    }
+
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// `None` for the directory.
+    #[inline]
+    pub fn device(&self, inode: usize) -> Option<VFSDevNode> {
+        DEVICES
+            .get(inode.checked_sub(1)?)
+            .map(|(_, device)| *device)
+    }
+
+    #[inline]
+    pub fn is_valid(&self, inode: usize) -> bool {
+        inode <= DEVICES.len()
+    }
+
+    pub fn lookup<Wasm: WasmAccess>(
+        &self,
+        dir: usize,
+        name: &WasmPathComponent<'_, Wasm>,
+    ) -> Result<usize, wasip1::Errno> {
+        if dir != 0 {
+            return Err(wasip1::ERRNO_NOTDIR);
+        }
+
+        DEVICES
+            .iter()
+            .position(|(device, _)| name.eq_str(device))
+            .map(|index| index + 1)
+            .ok_or(wasip1::ERRNO_NOENT)
+    }
+
+    /// There is no symbolic link, so the lookup flags do not matter.
+    pub fn get_inode_for_path<Wasm: WasmAccess>(
+        &self,
+        inode: usize,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        if !self.is_valid(inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        if path_len == 0 {
+            return Err(wasip1::ERRNO_NOENT);
+        }
+
+        let path = WasmPathAccess::<Wasm>::new(path_ptr, path_len);
+
+        let mut current = inode;
+        for component in path.components() {
+            current = match component {
+                // the devices are the whole tree
+                WasmPathComponent::RootDir | WasmPathComponent::ParentDir if current == 0 => 0,
+                WasmPathComponent::CurDir if current == 0 => 0,
+                WasmPathComponent::Normal(..) => self.lookup::<Wasm>(current, &component)?,
+                _ => return Err(wasip1::ERRNO_NOTDIR),
+            };
+        }
+
+        if path.ends_with_separator() && current != 0 {
+            return Err(wasip1::ERRNO_NOTDIR);
+        }
+
+        Ok(current)
+    }
+
+    pub fn filestat_from_inode(
+        &self,
+        inode: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        if !self.is_valid(inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        Ok(FilestatWithoutDevice {
+            ino: inode as _,
+            filetype: match self.device(inode) {
+                Some(_) => wasip1::FILETYPE_CHARACTER_DEVICE,
+                None => wasip1::FILETYPE_DIRECTORY,
+            },
+            nlink: 1,
+            size: 0,
+            atim: 0,
+            mtim: 0,
+            ctim: 0,
+        })
+    }
+}
+
+/// Nothing to save, the devices have no state.
+#[cfg(feature = "alloc")]
+impl<StdIo: StdIO + 'static, Random: Wasip1Random> Wasip1Snapshot for VFSDevLFS<StdIo, Random> {
+    fn snapshot_to(&self, _: &mut SnapshotWriter) {}
+
+    fn restore_from(&mut self, _: &mut SnapshotReader<'_>) -> Result<(), wasip1::Errno> {
+        Ok(())
+    }
+}
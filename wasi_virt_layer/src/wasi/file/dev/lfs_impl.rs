@@ -0,0 +1,502 @@
+use crate::__private::wasip1;
+use crate::__private::wasip1::Dircookie;
+
+use crate::{
+    memory::WasmAccess,
+    wasi::file::{
+        FilestatWithoutDevice, Wasip1LFS,
+        dev::lfs::{DEVICES, VFSDevLFS, VFSDevNode, Wasip1Random},
+        dirent::{DIRCOOKIE_ENTRIES, DirentWriter},
+        stdio::{self, StdIO},
+    },
+};
+
+/// Size of the stack buffer `/dev/zero` and `/dev/urandom` are read through.
+const CHUNK: usize = 256;
+
+impl<StdIo: StdIO + 'static, Random: Wasip1Random> Wasip1LFS for VFSDevLFS<StdIo, Random> {
+    type Inode = usize;
+    const PRE_OPEN: &'static [Self::Inode] = &[0];
+
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        data: *const u8,
+        data_len: usize,
+        _: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        match self.device(inode) {
+            Some(VFSDevNode::Tty) => self.fd_write_stdout_raw::<Wasm>(data, data_len),
+            Some(_) => Ok(data_len),
+            None if self.is_valid(inode) => Err(wasip1::ERRNO_ISDIR),
+            None => Err(wasip1::ERRNO_BADF),
+        }
+    }
+
+    fn fd_write_stdout_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::write_stdout_raw::<StdIo, Wasm>(data, data_len)
+    }
+
+    fn fd_write_stderr_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::write_stderr_raw::<StdIo, Wasm>(data, data_len)
+    }
+
+    fn is_dir(&self, inode: Self::Inode) -> bool {
+        inode == 0
+    }
+
+    fn fd_readdir_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        cookie: Dircookie,
+    ) -> Result<(wasip1::Size, Dircookie), wasip1::Errno> {
+        if inode != 0 {
+            return Err(if self.is_valid(inode) {
+                wasip1::ERRNO_NOTDIR
+            } else {
+                wasip1::ERRNO_BADF
+            });
+        }
+
+        let mut writer = DirentWriter::<Wasm>::new(buf, buf_len, cookie);
+
+        // there is nothing above the devices
+        if !writer.dots(0, 0) {
+            return Ok(writer.finish());
+        }
+
+        for (index, (name, _)) in DEVICES.iter().enumerate() {
+            let ino = index + 1;
+            let filetype = self.filestat_from_inode(ino)?.filetype;
+
+            if !writer.entry(
+                DIRCOOKIE_ENTRIES + index as Dircookie,
+                ino as _,
+                filetype,
+                name.as_bytes(),
+            ) {
+                break;
+            }
+        }
+
+        Ok(writer.finish())
+    }
+
+    fn path_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        _: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let inode = self.get_inode_for_path::<Wasm>(inode, path_ptr, path_len)?;
+
+        self.filestat_from_inode(inode)
+    }
+
+    fn fd_prestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<wasip1::Prestat, wasip1::Errno> {
+        if !Self::PRE_OPEN.contains(&inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        Ok(wasip1::Prestat {
+            tag: 0, // prestat is enum but variant is only 0
+            // union type but we only have one variant
+            u: wasip1::PrestatU {
+                dir: wasip1::PrestatDir {
+                    pr_name_len: self.name().len() as _,
+                },
+            },
+        })
+    }
+
+    fn fd_prestat_dir_name_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        dir_path_ptr: *mut u8,
+        dir_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        if !Self::PRE_OPEN.contains(&inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        let name = self.name();
+
+        Wasm::memcpy(
+            dir_path_ptr,
+            &name.as_bytes()[..core::cmp::min(name.len(), dir_path_len)],
+        );
+
+        Ok(())
+    }
+
+    fn fd_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        self.filestat_from_inode(inode)
+    }
+
+    fn fd_pread_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        _: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let device = match self.device(inode) {
+            Some(VFSDevNode::Null) => return Ok(0),
+            Some(VFSDevNode::Tty) => return self.fd_read_stdin_raw::<Wasm>(buf, buf_len),
+            Some(device) => device,
+            None if self.is_valid(inode) => return Err(wasip1::ERRNO_ISDIR),
+            None => return Err(wasip1::ERRNO_BADF),
+        };
+
+        let mut chunk = [0u8; CHUNK];
+        let mut read = 0;
+
+        while read < buf_len {
+            let len = core::cmp::min(CHUNK, buf_len - read);
+
+            if device == VFSDevNode::Urandom {
+                Random::fill(&mut chunk[..len])?;
+            }
+
+            Wasm::memcpy(buf.wrapping_add(read), &chunk[..len]);
+            read += len;
+        }
+
+        Ok(read)
+    }
+
+    /// Devices keep no offset, as on Linux every seek lands on 0.
+    fn fd_seek_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        _: usize,
+        _: wasip1::Filedelta,
+        _: wasip1::Whence,
+    ) -> Result<usize, wasip1::Errno> {
+        match self.device(inode) {
+            Some(_) => Ok(0),
+            None if self.is_valid(inode) => Err(wasip1::ERRNO_ISDIR),
+            None => Err(wasip1::ERRNO_BADF),
+        }
+    }
+
+    fn fd_tell_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        cursor: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        match self.device(inode) {
+            Some(_) => Ok(0),
+            None => Ok(cursor),
+        }
+    }
+
+    fn fd_read_stdin_raw<Wasm: WasmAccess>(
+        &mut self,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::read_stdin_raw::<StdIo, Wasm>(buf, buf_len)
+    }
+
+    fn path_open_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        _: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        _: wasip1::Rights,
+        _: wasip1::Fdflags,
+    ) -> Result<Self::Inode, wasip1::Errno> {
+        match self.get_inode_for_path::<Wasm>(dir_inode, path_ptr, path_len) {
+            Ok(inode) => {
+                if o_flags & (wasip1::OFLAGS_CREAT | wasip1::OFLAGS_EXCL)
+                    == (wasip1::OFLAGS_CREAT | wasip1::OFLAGS_EXCL)
+                {
+                    return Err(wasip1::ERRNO_EXIST);
+                }
+
+                if inode == 0 {
+                    if fs_rights_base & wasip1::RIGHTS_FD_WRITE == wasip1::RIGHTS_FD_WRITE {
+                        return Err(wasip1::ERRNO_ISDIR);
+                    }
+                } else if o_flags & wasip1::OFLAGS_DIRECTORY == wasip1::OFLAGS_DIRECTORY {
+                    return Err(wasip1::ERRNO_NOTDIR);
+                }
+
+                // truncating a device does nothing
+                Ok(inode)
+            }
+            Err(wasip1::ERRNO_NOENT) if o_flags & wasip1::OFLAGS_CREAT == wasip1::OFLAGS_CREAT => {
+                Err(wasip1::ERRNO_PERM)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        match self.get_inode_for_path::<Wasm>(dir_inode, path_ptr, path_len) {
+            Ok(_) => Err(wasip1::ERRNO_EXIST),
+            Err(wasip1::ERRNO_NOENT) => Err(wasip1::ERRNO_PERM),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let inode = self.get_inode_for_path::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        if !self.is_dir(inode) {
+            return Err(wasip1::ERRNO_NOTDIR);
+        }
+
+        Err(wasip1::ERRNO_PERM)
+    }
+
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let inode = self.get_inode_for_path::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        if self.is_dir(inode) {
+            return Err(wasip1::ERRNO_ISDIR);
+        }
+
+        Err(wasip1::ERRNO_PERM)
+    }
+
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_dir_inode: Self::Inode,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        _: Self::Inode,
+        _: *const u8,
+        _: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.get_inode_for_path::<Wasm>(old_dir_inode, old_path_ptr, old_path_len)?;
+
+        Err(wasip1::ERRNO_PERM)
+    }
+
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+        _: *mut u8,
+        _: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.get_inode_for_path::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        Err(wasip1::ERRNO_INVAL)
+    }
+
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        _: *const u8,
+        _: usize,
+        dir_inode: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        match self.get_inode_for_path::<Wasm>(dir_inode, new_path_ptr, new_path_len) {
+            Ok(_) => Err(wasip1::ERRNO_EXIST),
+            Err(wasip1::ERRNO_NOENT) => Err(wasip1::ERRNO_PERM),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        memory::{HostMemory, WasmAccess},
+        mock::MockWasm,
+        wasi::file::{
+            Wasip1FileSystem, constant::vfs::Wasip1ConstVFS, heap::lfs::VFSHeapLFS,
+            mount::lfs::VFSMountLFS, stdio::DefaultStdIO,
+        },
+    };
+
+    static TTY: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+    struct TestIO;
+
+    impl StdIO for TestIO {
+        fn read(buf: &mut [u8]) -> Result<wasip1::Size, wasip1::Errno> {
+            let len = core::cmp::min(buf.len(), 5);
+            buf[..len].copy_from_slice(&b"typed"[..len]);
+            Ok(len)
+        }
+
+        fn write(buf: &[u8]) -> Result<wasip1::Size, wasip1::Errno> {
+            TTY.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    struct TestRandom;
+
+    impl Wasip1Random for TestRandom {
+        fn fill(buf: &mut [u8]) -> Result<(), wasip1::Errno> {
+            buf.fill(0xA5);
+            Ok(())
+        }
+    }
+
+    type Dev = VFSDevLFS<TestIO, TestRandom>;
+    type Vfs = Wasip1ConstVFS<Dev, 8>;
+
+    #[test]
+    fn test_dev_devices() {
+        let mut vfs = Vfs::new(Dev::new("/dev"));
+
+        let null = MockWasm::open(&mut vfs, 3, "null", 0).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, null, b"gone"), Ok(4));
+        assert_eq!(MockWasm::read(&mut vfs, null, 16).unwrap(), b"");
+
+        let zero = MockWasm::open(&mut vfs, 3, "/zero", wasip1::OFLAGS_TRUNC).unwrap();
+        // over a buffer of garbage, to see the device fill it
+        let (iovs, iovs_len) = MockWasm::alloc_iovs(&[600]);
+        MockWasm::memcpy(MockWasm::load_le(iovs).buf, &[0x11u8; 600]);
+        let nread = MockWasm::alloc_value::<wasip1::Size>();
+        assert_eq!(
+            Wasip1FileSystem::fd_read_raw::<MockWasm>(
+                &mut vfs,
+                zero,
+                iovs as *const _,
+                iovs_len,
+                nread
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(
+            MockWasm::read_iovs(iovs, iovs_len, MockWasm::load_le(nread)),
+            vec![0u8; 600]
+        );
+
+        // devices keep no offset
+        let mut offset = 0;
+        assert_eq!(
+            Wasip1FileSystem::fd_tell_raw::<HostMemory>(&mut vfs, zero, &mut offset),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(offset, 0);
+        assert_eq!(
+            Wasip1FileSystem::fd_seek_raw::<HostMemory>(
+                &mut vfs,
+                zero,
+                10,
+                wasip1::WHENCE_END,
+                &mut offset
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(offset, 0);
+
+        let urandom = MockWasm::open(&mut vfs, 3, "./urandom", 0).unwrap();
+        assert_eq!(
+            MockWasm::read(&mut vfs, urandom, 300).unwrap(),
+            vec![0xA5u8; 300]
+        );
+
+        let tty = MockWasm::open(&mut vfs, 3, "tty", 0).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, tty, b"prompt> "), Ok(8));
+        assert_eq!(TTY.lock().unwrap().as_slice(), b"prompt> ");
+        assert_eq!(MockWasm::read(&mut vfs, tty, 16).unwrap(), b"typed");
+
+        let mut filestat = core::mem::MaybeUninit::<wasip1::Filestat>::uninit();
+        assert_eq!(
+            Wasip1FileSystem::fd_filestat_get_raw::<HostMemory>(
+                &mut vfs,
+                null,
+                filestat.as_mut_ptr()
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        let filestat = unsafe { filestat.assume_init() };
+        assert_eq!(filestat.filetype, wasip1::FILETYPE_CHARACTER_DEVICE);
+
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "sda", 0),
+            Err(wasip1::ERRNO_NOENT)
+        );
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "sda", wasip1::OFLAGS_CREAT),
+            Err(wasip1::ERRNO_PERM)
+        );
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "null/", 0),
+            Err(wasip1::ERRNO_NOTDIR)
+        );
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "null", wasip1::OFLAGS_DIRECTORY),
+            Err(wasip1::ERRNO_NOTDIR)
+        );
+
+        // `O_EXCL` only means something with `O_CREAT`
+        assert!(MockWasm::open(&mut vfs, 3, "null", wasip1::OFLAGS_EXCL).is_ok());
+        assert_eq!(
+            MockWasm::open(
+                &mut vfs,
+                3,
+                "null",
+                wasip1::OFLAGS_CREAT | wasip1::OFLAGS_EXCL
+            ),
+            Err(wasip1::ERRNO_EXIST)
+        );
+    }
+
+    #[test]
+    fn test_dev_mount() {
+        type Tmp = VFSHeapLFS<DefaultStdIO>;
+        let mut vfs = Wasip1ConstVFS::<VFSMountLFS<(Tmp, Dev)>, 8>::new(VFSMountLFS::new(
+            (Tmp::new(""), Dev::new("")),
+            &["/", "/dev"],
+        ));
+
+        let path = "/dev/null";
+        assert!(MockWasm::open_with(&mut vfs, 3, 0, path, 0, wasip1::RIGHTS_FD_WRITE, 0).is_ok());
+        assert_eq!(
+            vfs.lfs_mut()
+                .route::<HostMemory>((0, 0), path.as_ptr(), path.len())
+                .map(|route| route.inode),
+            Ok((1, 0))
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::__private::wasip1;
+use crate::__private::wasip1::{Dircookie, Size};
+use crate::memory::WasmAccess;
+
+/// Size of the header before the name of each entry.
+pub const DIRENT_SIZE: usize = core::mem::size_of::<wasip1::Dirent>();
+
+/// Cookie of `.`, listed first in every directory.
+pub const DIRCOOKIE_DOT: Dircookie = 0;
+/// Cookie of `..`, listed second.
+pub const DIRCOOKIE_DOTDOT: Dircookie = 1;
+/// The other entries have cookies from here.
+pub const DIRCOOKIE_ENTRIES: Dircookie = 2;
+
+/// Encodes the entries of `fd_readdir` into the buffer of the guest,
+/// shared by every [`Wasip1LFS`](super::Wasip1LFS).
+///
+/// An entry listed from `cookie` has `d_next` of `cookie + 1`,
+/// so a file system gives each entry a cookie which does not move
+/// when other entries are added or removed, e.g. its inode,
+/// and lists them in the order of their cookies.
+///
+/// Entries are written while they fit.
+/// The first one which does not is cut at the end of the buffer,
+/// so the guest sees `bufused == buf_len` and reads again
+/// from the last `d_next` it got whole, with a larger buffer if needed.
+pub struct DirentWriter<Wasm: WasmAccess> {
+    buf: *mut u8,
+    buf_len: usize,
+    used: usize,
+    /// entries with a smaller cookie were listed before
+    start: Dircookie,
+    /// `d_next` of the last entry written whole
+    next: Dircookie,
+    __marker: core::marker::PhantomData<Wasm>,
+}
+
+impl<Wasm: WasmAccess> DirentWriter<Wasm> {
+    pub const fn new(buf: *mut u8, buf_len: usize, cookie: Dircookie) -> Self {
+        Self {
+            buf,
+            buf_len,
+            used: 0,
+            start: cookie,
+            next: cookie,
+            __marker: core::marker::PhantomData,
+        }
+    }
+
+    #[inline]
+    pub const fn is_full(&self) -> bool {
+        self.used == self.buf_len
+    }
+
+    /// Writes the entry with the given `d_next` as is.
+    /// Returns false once the buffer is full, the caller stops then.
+    pub fn push(
+        &mut self,
+        d_next: Dircookie,
+        d_ino: wasip1::Inode,
+        d_type: wasip1::Filetype,
+        name: &[u8],
+    ) -> bool {
+        if self.is_full() {
+            return false;
+        }
+
+        let mut header = [0u8; DIRENT_SIZE];
+        header[0..8].copy_from_slice(&d_next.to_le_bytes());
+        header[8..16].copy_from_slice(&d_ino.to_le_bytes());
+        header[16..20].copy_from_slice(&(name.len() as wasip1::Dirnamlen).to_le_bytes());
+        header[20] = d_type.raw();
+
+        let room = self.buf_len - self.used;
+        let at = self.buf.wrapping_add(self.used);
+
+        Wasm::memcpy(at, &header[..core::cmp::min(DIRENT_SIZE, room)]);
+        if room > DIRENT_SIZE {
+            let name_len = core::cmp::min(name.len(), room - DIRENT_SIZE);
+            Wasm::memcpy(at.wrapping_add(DIRENT_SIZE), &name[..name_len]);
+        }
+
+        let len = DIRENT_SIZE + name.len();
+        if len > room {
+            self.used = self.buf_len;
+            return false;
+        }
+
+        self.used += len;
+        self.next = d_next;
+        true
+    }
+
+    /// Writes the entry of `cookie`, skipped if it was listed before.
+    /// Returns false once the buffer is full, the caller stops then.
+    pub fn entry(
+        &mut self,
+        cookie: Dircookie,
+        d_ino: wasip1::Inode,
+        d_type: wasip1::Filetype,
+        name: &[u8],
+    ) -> bool {
+        if cookie < self.start {
+            return true;
+        }
+
+        self.push(cookie + 1, d_ino, d_type, name)
+    }
+
+    /// Writes `.` and `..`.
+    /// The parent of a root directory is itself.
+    pub fn dots(&mut self, ino: wasip1::Inode, parent: wasip1::Inode) -> bool {
+        self.entry(DIRCOOKIE_DOT, ino, wasip1::FILETYPE_DIRECTORY, b".")
+            && self.entry(DIRCOOKIE_DOTDOT, parent, wasip1::FILETYPE_DIRECTORY, b"..")
+    }
+
+    /// The bytes written and the cookie to list the rest from.
+    #[inline]
+    pub const fn finish(self) -> (Size, Dircookie) {
+        (self.used, self.next)
+    }
+}
+
+/// Reads the entries a [`DirentWriter`] or the host wrote,
+/// as `(d_next, d_ino, d_type, name)`.
+/// A cut entry at the end is not returned.
+pub struct DirentReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> DirentReader<'a> {
+    #[inline]
+    pub const fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    /// `d_namlen` of the entry at the start of the buffer,
+    /// how large a buffer the cut entry needs.
+    pub fn needed(&self) -> Option<usize> {
+        if self.buf.len() < DIRENT_SIZE {
+            return None;
+        }
+
+        let dirent =
+            unsafe { core::ptr::read_unaligned(self.buf.as_ptr() as *const wasip1::Dirent) };
+        Some(DIRENT_SIZE + dirent.d_namlen as usize)
+    }
+}
+
+impl<'a> Iterator for DirentReader<'a> {
+    type Item = (Dircookie, wasip1::Inode, wasip1::Filetype, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.needed()?;
+        if self.buf.len() < len {
+            return None;
+        }
+
+        let dirent =
+            unsafe { core::ptr::read_unaligned(self.buf.as_ptr() as *const wasip1::Dirent) };
+        let name = &self.buf[DIRENT_SIZE..len];
+        self.buf = &self.buf[len..];
+
+        Some((dirent.d_next, dirent.d_ino, dirent.d_type, name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::HostMemory;
+
+    fn write(buf: &mut [u8], cookie: Dircookie) -> (Size, Dircookie) {
+        let mut writer = DirentWriter::<HostMemory>::new(buf.as_mut_ptr(), buf.len(), cookie);
+        let _ = writer.dots(0, 0)
+            && writer.entry(5, 3, wasip1::FILETYPE_REGULAR_FILE, b"a.txt")
+            && writer.entry(9, 7, wasip1::FILETYPE_DIRECTORY, b"dir");
+        writer.finish()
+    }
+
+    // compares without allocating, the tests also run without `alloc`
+    fn assert_names(buf: &[u8], expected: &[(Dircookie, &[u8])]) {
+        assert!(
+            DirentReader::new(buf)
+                .map(|(d_next, _, _, name)| (d_next, name))
+                .eq(expected.iter().copied()),
+            "{expected:?}"
+        );
+    }
+
+    #[test]
+    fn test_dirent_writer() {
+        let mut buf = [0u8; 256];
+        let (used, next) = write(&mut buf, 0);
+        assert_eq!(used, 4 * DIRENT_SIZE + 1 + 2 + 5 + 3);
+        assert_eq!(next, 10);
+        assert_names(
+            &buf[..used],
+            &[(1, &b"."[..]), (2, b".."), (6, b"a.txt"), (10, b"dir")],
+        );
+
+        // the cut entry fills the buffer and is read again from the cookie before it
+        let mut small = [0u8; 2 * DIRENT_SIZE + 3 + DIRENT_SIZE + 2];
+        let (used, next) = write(&mut small, 0);
+        assert_eq!(used, small.len());
+        assert_eq!(next, 2);
+        let mut reader = DirentReader::new(&small);
+        assert_eq!(reader.by_ref().count(), 2);
+        assert_eq!(reader.needed(), Some(DIRENT_SIZE + 5));
+
+        let (used, next) = write(&mut buf, next);
+        assert_names(&buf[..used], &[(6, &b"a.txt"[..]), (10, b"dir")]);
+        assert_eq!(next, 10);
+
+        // entries removed before the cookie do not move the rest
+        let (used, _) = write(&mut buf, 7);
+        assert_names(&buf[..used], &[(10, &b"dir"[..])]);
+
+        // the end of the listing
+        assert_eq!(write(&mut buf, 10), (0, 10));
+    }
+}
@@ -0,0 +1,333 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::__private::wasip1;
+use crate::__private::wasip1::Fd;
+#[cfg(feature = "threads")]
+use parking_lot::RwLock;
+
+/// An open file descriptor.
+#[derive(Clone, Copy, Debug)]
+pub struct FdEntry<Inode: Copy> {
+    pub inode: Inode,
+    pub cursor: usize,
+    pub fd_flags: wasip1::Fdflags,
+    pub rights_base: wasip1::Rights,
+    pub rights_inheriting: wasip1::Rights,
+}
+
+impl<Inode: Copy> FdEntry<Inode> {
+    /// Pre-opened directories have every right.
+    pub const fn pre_open(inode: Inode) -> Self {
+        Self {
+            inode,
+            cursor: 0,
+            fd_flags: 0,
+            rights_base: wasip1::Rights::MAX,
+            rights_inheriting: wasip1::Rights::MAX,
+        }
+    }
+
+    #[inline]
+    pub const fn has_rights(&self, rights: wasip1::Rights) -> bool {
+        self.rights_base & rights == rights
+    }
+}
+
+/// Maps file descriptors to open files.
+/// Pre-opened directories start at fd 3.
+/// Stdio (0, 1, 2) has an entry only while it is redirected by `fd_renumber`.
+pub trait FdTable<Inode: Copy> {
+    fn get(&self, fd: Fd) -> Option<FdEntry<Inode>>;
+
+    fn update<R>(&mut self, fd: Fd, f: impl FnOnce(&mut FdEntry<Inode>) -> R) -> Option<R>;
+
+    fn remove(&mut self, fd: Fd) -> Option<FdEntry<Inode>>;
+
+    /// Stores the entry under the lowest free descriptor.
+    /// `ERRNO_MFILE` if the table is full.
+    fn push(&mut self, entry: FdEntry<Inode>) -> Result<Fd, wasip1::Errno>;
+
+    /// Stores the entry under the descriptor and returns the old one.
+    /// `ERRNO_BADF` if the descriptor is out of the table.
+    fn replace(
+        &mut self,
+        fd: Fd,
+        entry: FdEntry<Inode>,
+    ) -> Result<Option<FdEntry<Inode>>, wasip1::Errno>;
+}
+
+#[cfg(feature = "threads")]
+type Slot<Inode> = RwLock<Option<FdEntry<Inode>>>;
+#[cfg(not(feature = "threads"))]
+type Slot<Inode> = Option<FdEntry<Inode>>;
+
+/// fd table with `LEN` slots after stdio, no allocation.
+pub struct ConstFdTable<Inode: Copy, const LEN: usize> {
+    stdio: [Slot<Inode>; 3],
+    slots: [Slot<Inode>; LEN],
+}
+
+impl<Inode: Copy, const LEN: usize> ConstFdTable<Inode, LEN> {
+    pub const fn new(pre_open: &[Inode]) -> Self {
+        #[cfg(feature = "threads")]
+        let (stdio, mut slots) = (
+            [const { RwLock::new(None) }; 3],
+            [const { RwLock::new(None) }; LEN],
+        );
+        #[cfg(not(feature = "threads"))]
+        let (stdio, mut slots) = ([const { None }; 3], [const { None }; LEN]);
+
+        use const_for::const_for;
+
+        const_for!(i in 0..pre_open.len() => {
+            #[cfg(feature = "threads")]
+            {
+                slots[i] = RwLock::new(Some(FdEntry::pre_open(pre_open[i])));
+            }
+            #[cfg(not(feature = "threads"))]
+            {
+                slots[i] = Some(FdEntry::pre_open(pre_open[i]));
+            }
+        });
+
+        Self { stdio, slots }
+    }
+
+    #[inline]
+    fn slot(&self, fd: Fd) -> Option<&Slot<Inode>> {
+        match fd {
+            0..3 => self.stdio.get(fd as usize),
+            fd => self.slots.get(fd as usize - 3),
+        }
+    }
+
+    #[inline]
+    fn slot_mut(&mut self, fd: Fd) -> Option<&mut Option<FdEntry<Inode>>> {
+        let slot = match fd {
+            0..3 => self.stdio.get_mut(fd as usize),
+            fd => self.slots.get_mut(fd as usize - 3),
+        }?;
+
+        #[cfg(feature = "threads")]
+        {
+            Some(slot.get_mut())
+        }
+
+        #[cfg(not(feature = "threads"))]
+        {
+            Some(slot)
+        }
+    }
+}
+
+impl<Inode: Copy, const LEN: usize> FdTable<Inode> for ConstFdTable<Inode, LEN> {
+    #[inline]
+    fn get(&self, fd: Fd) -> Option<FdEntry<Inode>> {
+        let slot = self.slot(fd)?;
+
+        #[cfg(feature = "threads")]
+        {
+            *slot.read()
+        }
+
+        #[cfg(not(feature = "threads"))]
+        {
+            *slot
+        }
+    }
+
+    #[inline]
+    fn update<R>(&mut self, fd: Fd, f: impl FnOnce(&mut FdEntry<Inode>) -> R) -> Option<R> {
+        self.slot_mut(fd)?.as_mut().map(f)
+    }
+
+    #[inline]
+    fn remove(&mut self, fd: Fd) -> Option<FdEntry<Inode>> {
+        self.slot_mut(fd)?.take()
+    }
+
+    fn push(&mut self, entry: FdEntry<Inode>) -> Result<Fd, wasip1::Errno> {
+        for fd in 3..(LEN + 3) as Fd {
+            let slot = self.slot_mut(fd).unwrap();
+            if slot.is_none() {
+                *slot = Some(entry);
+                return Ok(fd);
+            }
+        }
+
+        Err(wasip1::ERRNO_MFILE)
+    }
+
+    fn replace(
+        &mut self,
+        fd: Fd,
+        entry: FdEntry<Inode>,
+    ) -> Result<Option<FdEntry<Inode>>, wasip1::Errno> {
+        let slot = self.slot_mut(fd).ok_or(wasip1::ERRNO_BADF)?;
+        Ok(slot.replace(entry))
+    }
+}
+
+/// fd table which grows on demand
+/// up to `limit` descriptors after stdio.
+#[cfg(feature = "alloc")]
+pub struct VecFdTable<Inode: Copy + 'static> {
+    pre_open: &'static [Inode],
+    limit: usize,
+    /// indexed by fd, empty until first written
+    #[cfg(feature = "threads")]
+    entries: RwLock<Vec<Option<FdEntry<Inode>>>>,
+    #[cfg(not(feature = "threads"))]
+    entries: Vec<Option<FdEntry<Inode>>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<Inode: Copy + 'static> VecFdTable<Inode> {
+    pub const fn new(pre_open: &'static [Inode], limit: usize) -> Self {
+        Self {
+            pre_open,
+            limit,
+            #[cfg(feature = "threads")]
+            entries: RwLock::new(Vec::new()),
+            #[cfg(not(feature = "threads"))]
+            entries: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub const fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Changes the limit. Already open descriptors stay open.
+    #[inline]
+    pub const fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    fn entries_mut(&mut self) -> &mut Vec<Option<FdEntry<Inode>>> {
+        #[cfg(feature = "threads")]
+        let entries = self.entries.get_mut();
+        #[cfg(not(feature = "threads"))]
+        let entries = &mut self.entries;
+
+        // the pre-opens are registered lazily so that `new` stays const
+        if entries.is_empty() {
+            entries.extend([None, None, None]);
+            entries.extend(
+                self.pre_open
+                    .iter()
+                    .map(|inode| Some(FdEntry::pre_open(*inode))),
+            );
+        }
+
+        entries
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Inode: Copy + 'static> FdTable<Inode> for VecFdTable<Inode> {
+    fn get(&self, fd: Fd) -> Option<FdEntry<Inode>> {
+        #[cfg(feature = "threads")]
+        let entries = self.entries.read();
+        #[cfg(not(feature = "threads"))]
+        let entries = &self.entries;
+
+        if entries.is_empty() {
+            return match fd {
+                0..3 => None,
+                fd => self
+                    .pre_open
+                    .get(fd as usize - 3)
+                    .map(|inode| FdEntry::pre_open(*inode)),
+            };
+        }
+
+        *entries.get(fd as usize)?
+    }
+
+    fn update<R>(&mut self, fd: Fd, f: impl FnOnce(&mut FdEntry<Inode>) -> R) -> Option<R> {
+        self.entries_mut().get_mut(fd as usize)?.as_mut().map(f)
+    }
+
+    fn remove(&mut self, fd: Fd) -> Option<FdEntry<Inode>> {
+        self.entries_mut().get_mut(fd as usize)?.take()
+    }
+
+    fn push(&mut self, entry: FdEntry<Inode>) -> Result<Fd, wasip1::Errno> {
+        let limit = self.limit;
+        let entries = self.entries_mut();
+
+        if let Some(fd) = entries.iter().skip(3).position(Option::is_none) {
+            entries[fd + 3] = Some(entry);
+            return Ok((fd + 3) as Fd);
+        }
+
+        if entries.len() - 3 >= limit {
+            return Err(wasip1::ERRNO_MFILE);
+        }
+
+        entries.push(Some(entry));
+
+        Ok((entries.len() - 1) as Fd)
+    }
+
+    fn replace(
+        &mut self,
+        fd: Fd,
+        entry: FdEntry<Inode>,
+    ) -> Result<Option<FdEntry<Inode>>, wasip1::Errno> {
+        let slot = self
+            .entries_mut()
+            .get_mut(fd as usize)
+            .ok_or(wasip1::ERRNO_BADF)?;
+        Ok(slot.replace(entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_const_fd_table() {
+        let mut table = ConstFdTable::<usize, 2>::new(&[0]);
+
+        assert_eq!(table.get(3).map(|entry| entry.inode), Some(0));
+        assert!(table.get(1).is_none());
+
+        assert_eq!(table.push(FdEntry::pre_open(1)), Ok(4));
+        assert_eq!(table.push(FdEntry::pre_open(2)), Err(wasip1::ERRNO_MFILE));
+
+        assert!(table.remove(3).is_some());
+        assert_eq!(table.push(FdEntry::pre_open(2)), Ok(3));
+
+        assert!(table.replace(1, FdEntry::pre_open(5)).unwrap().is_none());
+        assert_eq!(table.get(1).map(|entry| entry.inode), Some(5));
+        assert_eq!(
+            table.replace(5, FdEntry::pre_open(5)).map(|_| ()),
+            Err(wasip1::ERRNO_BADF)
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_vec_fd_table() {
+        let mut table = VecFdTable::<usize>::new(&[0, 1], 3);
+
+        // pre-opens are visible before the first write
+        assert_eq!(table.get(4).map(|entry| entry.inode), Some(1));
+        assert!(table.get(5).is_none());
+
+        assert_eq!(table.push(FdEntry::pre_open(2)), Ok(5));
+        assert_eq!(table.push(FdEntry::pre_open(3)), Err(wasip1::ERRNO_MFILE));
+
+        table.set_limit(4);
+        assert_eq!(table.push(FdEntry::pre_open(3)), Ok(6));
+
+        assert!(table.remove(4).is_some());
+        assert_eq!(table.push(FdEntry::pre_open(4)), Ok(4));
+        assert_eq!(table.get(4).map(|entry| entry.inode), Some(4));
+    }
+}
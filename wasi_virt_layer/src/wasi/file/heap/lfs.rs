@@ -0,0 +1,733 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet, btree_map::Entry},
+    string::String,
+    vec,
+    vec::Vec,
+};
+
+use crate::__private::wasip1;
+use crate::{
+    memory::{WasmAccess, WasmPathAccess, WasmPathComponent},
+    wasi::file::{
+        FilestatWithoutDevice, SYMLINK_MAX_DEPTH,
+        clock::{DefaultClock, Wasip1Clock},
+        snapshot::{SnapshotReader, SnapshotWriter, Wasip1Snapshot},
+        stdio::StdIO,
+    },
+};
+
+/// Writable in-memory file system
+/// which keeps every file on the heap,
+/// so the wasm can create, write and truncate files.
+/// The host can read the written files back with [`VFSHeapLFS::get_file`].
+/// Access and modification times are taken from `Clock`.
+pub struct VFSHeapLFS<StdIo: StdIO + 'static, Clock: Wasip1Clock = DefaultClock> {
+    name: &'static str,
+    root: VFSHeapInode,
+    /// inode `n` is stored at `inodes[n - 1]`, root is inode 0
+    inodes: Vec<Option<VFSHeapInode>>,
+    /// freed inode numbers, handed out again by [`VFSHeapLFS::insert`]
+    free_inodes: Vec<usize>,
+    /// number of descriptors of each opened inode
+    opened: BTreeMap<usize, usize>,
+    /// inodes removed while opened, freed on their last close
+    unlinked: BTreeSet<usize>,
+    file_size_max: usize,
+    __marker: core::marker::PhantomData<(StdIo, Clock)>,
+}
+
+#[derive(Debug)]
+pub struct VFSHeapInode {
+    pub parent: usize,
+    pub node: VFSHeapNode,
+    pub atim: wasip1::Timestamp,
+    pub mtim: wasip1::Timestamp,
+    pub ctim: wasip1::Timestamp,
+}
+
+impl VFSHeapInode {
+    pub const fn new(parent: usize, node: VFSHeapNode, now: wasip1::Timestamp) -> Self {
+        Self {
+            parent,
+            node,
+            atim: now,
+            mtim: now,
+            ctim: now,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum VFSHeapNode {
+    File(Vec<u8>),
+    /// name, inode
+    Dir(BTreeMap<String, usize>),
+    /// target path
+    Symlink(String),
+}
+
+impl VFSHeapNode {
+    pub const fn filetype(&self) -> wasip1::Filetype {
+        match self {
+            Self::File(..) => wasip1::FILETYPE_REGULAR_FILE,
+            Self::Dir(..) => wasip1::FILETYPE_DIRECTORY,
+            Self::Symlink(..) => wasip1::FILETYPE_SYMBOLIC_LINK,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Self::File(data) => data.len(),
+            Self::Dir(entries) => entries.len(),
+            Self::Symlink(target) => target.len(),
+        }
+    }
+}
+
+impl<StdIo: StdIO + 'static, Clock: Wasip1Clock> VFSHeapLFS<StdIo, Clock> {
+    /// `name` is the name of the pre-opened root directory,
+    /// e.g. "." or "/tmp".
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            root: VFSHeapInode::new(0, VFSHeapNode::Dir(BTreeMap::new()), 0),
+            inodes: Vec::new(),
+            free_inodes: Vec::new(),
+            opened: BTreeMap::new(),
+            unlinked: BTreeSet::new(),
+            file_size_max: Self::DEFAULT_FILE_SIZE_MAX,
+            __marker: core::marker::PhantomData,
+        }
+    }
+
+    /// Largest file the wasm can write by default, 1 GiB.
+    pub const DEFAULT_FILE_SIZE_MAX: usize = 1 << 30;
+
+    /// Limits the size of every file; writes past it fail with `ERRNO_FBIG`.
+    pub const fn with_file_size_max(mut self, max: usize) -> Self {
+        self.file_size_max = max;
+        self
+    }
+
+    #[inline]
+    pub const fn file_size_max(&self) -> usize {
+        self.file_size_max
+    }
+
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    pub fn get(&self, inode: usize) -> Option<&VFSHeapInode> {
+        match inode {
+            0 => Some(&self.root),
+            inode => self.inodes.get(inode - 1)?.as_ref(),
+        }
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, inode: usize) -> Option<&mut VFSHeapInode> {
+        match inode {
+            0 => Some(&mut self.root),
+            inode => self.inodes.get_mut(inode - 1)?.as_mut(),
+        }
+    }
+
+    /// Marks the inode as read now.
+    pub fn accessed(&mut self, inode: usize) {
+        if let Some(node) = self.get_mut(inode) {
+            node.atim = Clock::now();
+        }
+    }
+
+    /// Marks the inode as written now.
+    pub fn modified(&mut self, inode: usize) {
+        if let Some(node) = self.get_mut(inode) {
+            let now = Clock::now();
+            node.mtim = now;
+            node.ctim = now;
+        }
+    }
+
+    #[inline]
+    pub fn is_dir(&self, inode: usize) -> bool {
+        matches!(
+            self.get(inode),
+            Some(VFSHeapInode {
+                node: VFSHeapNode::Dir(..),
+                ..
+            })
+        )
+    }
+
+    /// `None` for a removed directory kept open,
+    /// whose parent may be gone.
+    #[inline]
+    pub fn parent_inode(&self, inode: usize) -> Option<usize> {
+        if self.unlinked.contains(&inode) {
+            return None;
+        }
+        self.get(inode).map(|inode| inode.parent)
+    }
+
+    pub fn lookup(&self, dir: usize, name: &str) -> Result<usize, wasip1::Errno> {
+        match self.get(dir) {
+            Some(VFSHeapInode {
+                node: VFSHeapNode::Dir(entries),
+                ..
+            }) => entries.get(name).copied().ok_or(wasip1::ERRNO_NOENT),
+            Some(_) => Err(wasip1::ERRNO_NOTDIR),
+            None => Err(wasip1::ERRNO_BADF),
+        }
+    }
+
+    /// Creates a new inode under `dir` and returns its number.
+    /// The number of a freed inode is reused,
+    /// which is safe since an inode is only freed after its last close.
+    pub fn insert(
+        &mut self,
+        dir: usize,
+        name: String,
+        node: VFSHeapNode,
+    ) -> Result<usize, wasip1::Errno> {
+        let inode = match self.free_inodes.last() {
+            Some(&inode) => inode,
+            None => self.inodes.len() + 1,
+        };
+
+        self.link(dir, name, inode)?;
+
+        let node = Some(VFSHeapInode::new(dir, node, Clock::now()));
+        match self.inodes.get_mut(inode - 1) {
+            Some(slot) => {
+                self.free_inodes.pop();
+                *slot = node;
+            }
+            None => self.inodes.push(node),
+        }
+
+        Ok(inode)
+    }
+
+    /// Adds the entry `name` pointing to `inode` into `dir`.
+    /// A removed directory kept open takes no new entry.
+    pub fn link(&mut self, dir: usize, name: String, inode: usize) -> Result<(), wasip1::Errno> {
+        if self.unlinked.contains(&dir) {
+            return Err(wasip1::ERRNO_NOENT);
+        }
+
+        match self.get_mut(dir) {
+            Some(VFSHeapInode {
+                node: VFSHeapNode::Dir(entries),
+                ..
+            }) => {
+                if entries.contains_key(&name) {
+                    return Err(wasip1::ERRNO_EXIST);
+                }
+                entries.insert(name, inode);
+                self.modified(dir);
+                Ok(())
+            }
+            Some(_) => Err(wasip1::ERRNO_NOTDIR),
+            None => Err(wasip1::ERRNO_BADF),
+        }
+    }
+
+    /// Removes the entry `name` from `dir` and returns the inode it pointed to.
+    /// The inode itself is kept, see [`VFSHeapLFS::free`].
+    pub fn unlink(&mut self, dir: usize, name: &str) -> Result<usize, wasip1::Errno> {
+        match self.get_mut(dir) {
+            Some(VFSHeapInode {
+                node: VFSHeapNode::Dir(entries),
+                ..
+            }) => {
+                let inode = entries.remove(name).ok_or(wasip1::ERRNO_NOENT)?;
+                self.modified(dir);
+                Ok(inode)
+            }
+            Some(_) => Err(wasip1::ERRNO_NOTDIR),
+            None => Err(wasip1::ERRNO_BADF),
+        }
+    }
+
+    /// Drops the inode and its data,
+    /// or on the last close if a descriptor still has it open,
+    /// so the descriptor keeps reading and writing the removed file.
+    /// The root directory is never freed.
+    pub fn free(&mut self, inode: usize) {
+        if self.opened.contains_key(&inode) {
+            self.unlinked.insert(inode);
+            return;
+        }
+
+        if let Some(slot) = inode.checked_sub(1).and_then(|i| self.inodes.get_mut(i))
+            && slot.take().is_some()
+        {
+            self.free_inodes.push(inode);
+        }
+    }
+
+    /// Counts a new descriptor of the inode.
+    pub fn opened(&mut self, inode: usize) {
+        *self.opened.entry(inode).or_default() += 1;
+    }
+
+    /// Counts a closed descriptor of the inode,
+    /// freeing it if it was removed and this was the last one.
+    pub fn closed(&mut self, inode: usize) {
+        if let Entry::Occupied(mut count) = self.opened.entry(inode) {
+            *count.get_mut() -= 1;
+            if *count.get() == 0 {
+                count.remove();
+                if self.unlinked.remove(&inode) {
+                    self.free(inode);
+                }
+            }
+        }
+    }
+
+    /// Whether `ancestor` is `inode` or one of its parents.
+    pub fn is_ancestor(&self, ancestor: usize, mut inode: usize) -> bool {
+        loop {
+            if inode == ancestor {
+                return true;
+            }
+            if inode == 0 {
+                return false;
+            }
+            match self.parent_inode(inode) {
+                Some(parent) => inode = parent,
+                None => return false,
+            }
+        }
+    }
+
+    fn is_empty_dir(&self, inode: usize) -> bool {
+        matches!(
+            self.get(inode),
+            Some(VFSHeapInode {
+                node: VFSHeapNode::Dir(entries),
+                ..
+            }) if entries.is_empty()
+        )
+    }
+
+    pub fn create_directory(
+        &mut self,
+        dir: usize,
+        name: Option<String>,
+    ) -> Result<(), wasip1::Errno> {
+        let name = name.ok_or(wasip1::ERRNO_EXIST)?;
+
+        self.insert(dir, name, VFSHeapNode::Dir(BTreeMap::new()))?;
+
+        Ok(())
+    }
+
+    pub fn remove_directory(
+        &mut self,
+        dir: usize,
+        name: Option<String>,
+    ) -> Result<(), wasip1::Errno> {
+        // `.` and `..` cannot be removed
+        let name = name.ok_or(wasip1::ERRNO_INVAL)?;
+
+        let inode = self.lookup(dir, &name)?;
+
+        if !self.is_dir(inode) {
+            return Err(wasip1::ERRNO_NOTDIR);
+        }
+
+        if !self.is_empty_dir(inode) {
+            return Err(wasip1::ERRNO_NOTEMPTY);
+        }
+
+        self.unlink(dir, &name)?;
+        self.free(inode);
+
+        Ok(())
+    }
+
+    pub fn unlink_file(&mut self, dir: usize, name: Option<String>) -> Result<(), wasip1::Errno> {
+        let name = name.ok_or(wasip1::ERRNO_ISDIR)?;
+
+        let inode = self.lookup(dir, &name)?;
+
+        if self.is_dir(inode) {
+            return Err(wasip1::ERRNO_ISDIR);
+        }
+
+        self.unlink(dir, &name)?;
+        self.free(inode);
+
+        Ok(())
+    }
+
+    /// Moves `old_dir/old_name` to `new_dir/new_name`,
+    /// replacing the target if it is a file or an empty directory.
+    pub fn rename(
+        &mut self,
+        old_dir: usize,
+        old_name: Option<String>,
+        new_dir: usize,
+        new_name: Option<String>,
+    ) -> Result<(), wasip1::Errno> {
+        // `.` and `..` cannot be renamed
+        let old_name = old_name.ok_or(wasip1::ERRNO_INVAL)?;
+        let new_name = new_name.ok_or(wasip1::ERRNO_INVAL)?;
+
+        let inode = self.lookup(old_dir, &old_name)?;
+
+        match self.get(new_dir) {
+            Some(node) if node.node.filetype() != wasip1::FILETYPE_DIRECTORY => {
+                return Err(wasip1::ERRNO_NOTDIR);
+            }
+            Some(_) => {}
+            None => return Err(wasip1::ERRNO_BADF),
+        }
+        // a removed directory kept open takes no new entry,
+        // checked before anything is unlinked so the inode never loses its name
+        if self.unlinked.contains(&new_dir) {
+            return Err(wasip1::ERRNO_NOENT);
+        }
+
+        if self.is_dir(inode) && self.is_ancestor(inode, new_dir) {
+            // cannot move a directory into itself
+            return Err(wasip1::ERRNO_INVAL);
+        }
+
+        match self.lookup(new_dir, &new_name) {
+            Ok(target) if target == inode => return Ok(()),
+            Ok(target) => {
+                match (self.is_dir(inode), self.is_dir(target)) {
+                    (true, false) => return Err(wasip1::ERRNO_NOTDIR),
+                    (false, true) => return Err(wasip1::ERRNO_ISDIR),
+                    (true, true) if !self.is_empty_dir(target) => {
+                        return Err(wasip1::ERRNO_NOTEMPTY);
+                    }
+                    _ => {}
+                }
+
+                self.unlink(new_dir, &new_name)?;
+                self.free(target);
+            }
+            Err(wasip1::ERRNO_NOENT) => {}
+            Err(e) => return Err(e),
+        }
+
+        self.unlink(old_dir, &old_name)?;
+        self.link(new_dir, new_name, inode)?;
+        if let Some(node) = self.get_mut(inode) {
+            node.parent = new_dir;
+        }
+
+        Ok(())
+    }
+
+    fn component_name<Wasm: WasmAccess>(
+        component: &WasmPathComponent<'_, Wasm>,
+    ) -> Result<String, wasip1::Errno> {
+        match component {
+            WasmPathComponent::Normal(name) => {
+                String::from_utf8(name.iter().collect()).map_err(|_| wasip1::ERRNO_ILSEQ)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn step<Wasm: WasmAccess>(
+        &self,
+        current: usize,
+        component: &WasmPathComponent<'_, Wasm>,
+    ) -> Result<usize, wasip1::Errno> {
+        match component {
+            // paths are resolved relative to the pre-opened directory
+            WasmPathComponent::RootDir => Ok(0),
+            WasmPathComponent::CurDir => Ok(current),
+            WasmPathComponent::ParentDir => self.parent_inode(current).ok_or(wasip1::ERRNO_NOENT),
+            WasmPathComponent::Normal(..) => {
+                self.lookup(current, &Self::component_name::<Wasm>(component)?)
+            }
+        }
+    }
+
+    /// If the inode is a symbolic link, returns the inode it points to.
+    pub fn follow(&self, inode: usize, depth: &mut usize) -> Result<usize, wasip1::Errno> {
+        match self.get(inode) {
+            Some(VFSHeapInode {
+                parent,
+                node: VFSHeapNode::Symlink(target),
+                ..
+            }) => {
+                *depth += 1;
+                if *depth > SYMLINK_MAX_DEPTH {
+                    return Err(wasip1::ERRNO_LOOP);
+                }
+
+                if target.is_empty() {
+                    return Err(wasip1::ERRNO_NOENT);
+                }
+
+                let mut current_inode = *parent;
+
+                for (i, part) in target.split('/').enumerate() {
+                    current_inode = self.follow(current_inode, depth)?;
+
+                    current_inode = match part {
+                        "" if i == 0 => 0,
+                        "" | "." => current_inode,
+                        ".." => self
+                            .parent_inode(current_inode)
+                            .ok_or(wasip1::ERRNO_NOENT)?,
+                        name => self.lookup(current_inode, name)?,
+                    };
+                }
+
+                self.follow(current_inode, depth)
+            }
+            _ => Ok(inode),
+        }
+    }
+
+    fn resolve_parent<Wasm: WasmAccess>(
+        &self,
+        inode: usize,
+        path_ptr: *const u8,
+        path_len: usize,
+        depth: &mut usize,
+    ) -> Result<(usize, Option<String>), wasip1::Errno> {
+        let path = WasmPathAccess::<Wasm>::new(path_ptr, path_len);
+
+        let mut components = path.components().peekable();
+
+        let mut current_inode = inode;
+
+        while let Some(part) = components.next() {
+            // every component before the last one must be a directory
+            current_inode = self.follow(current_inode, depth)?;
+
+            if components.peek().is_none()
+                && let WasmPathComponent::Normal(..) = part
+            {
+                if !self.is_dir(current_inode) {
+                    return Err(wasip1::ERRNO_NOTDIR);
+                }
+                return Ok((current_inode, Some(Self::component_name(&part)?)));
+            }
+
+            current_inode = self.step(current_inode, &part)?;
+        }
+
+        Ok((current_inode, None))
+    }
+
+    /// Resolves every component except the last one.
+    /// Returns the directory the path lives in
+    /// and the name of the last component, if it is a normal one.
+    /// Symbolic links in the middle of the path are followed.
+    pub fn get_parent_for_path<Wasm: WasmAccess>(
+        &self,
+        inode: usize,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(usize, Option<String>), wasip1::Errno> {
+        self.resolve_parent::<Wasm>(inode, path_ptr, path_len, &mut 0)
+    }
+
+    /// The last symbolic link is followed
+    /// only when `LOOKUPFLAGS_SYMLINK_FOLLOW` is set.
+    pub fn get_inode_for_path<Wasm: WasmAccess>(
+        &self,
+        inode: usize,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        let mut depth = 0;
+
+        let inode = match self.resolve_parent::<Wasm>(inode, path_ptr, path_len, &mut depth)? {
+            (dir, Some(name)) => self.lookup(dir, &name)?,
+            (inode, None) => inode,
+        };
+
+        if flags & wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW == wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW {
+            self.follow(inode, &mut depth)
+        } else {
+            Ok(inode)
+        }
+    }
+
+    /// Returns the content of the file at `path`.
+    /// `path` is relative to the pre-opened directory and separated by `/`.
+    pub fn get_file(&self, path: &str) -> Option<&[u8]> {
+        let mut current_inode = 0;
+
+        for part in path.split('/') {
+            current_inode = match part {
+                "" | "." => current_inode,
+                ".." => self.parent_inode(current_inode)?,
+                name => self.lookup(current_inode, name).ok()?,
+            };
+        }
+
+        match &self.get(current_inode)?.node {
+            VFSHeapNode::File(data) => Some(data),
+            VFSHeapNode::Dir(..) | VFSHeapNode::Symlink(..) => None,
+        }
+    }
+
+    pub fn filestat_from_inode(
+        &self,
+        inode: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let node = self.get(inode).ok_or(wasip1::ERRNO_BADF)?;
+
+        Ok(FilestatWithoutDevice {
+            ino: inode as _,
+            filetype: node.node.filetype(),
+            nlink: 1,
+            size: node.node.size() as _,
+            atim: node.atim,
+            mtim: node.mtim,
+            ctim: node.ctim,
+        })
+    }
+}
+
+impl VFSHeapInode {
+    fn snapshot_to(&self, out: &mut SnapshotWriter) {
+        out.usize(self.parent);
+        out.u64(self.atim);
+        out.u64(self.mtim);
+        out.u64(self.ctim);
+
+        match &self.node {
+            VFSHeapNode::File(data) => {
+                out.u8(0);
+                out.bytes(data);
+            }
+            VFSHeapNode::Dir(entries) => {
+                out.u8(1);
+                out.usize(entries.len());
+                for (name, inode) in entries {
+                    out.str(name);
+                    out.usize(*inode);
+                }
+            }
+            VFSHeapNode::Symlink(target) => {
+                out.u8(2);
+                out.str(target);
+            }
+        }
+    }
+
+    fn restore_from(input: &mut SnapshotReader<'_>) -> Result<Self, wasip1::Errno> {
+        let parent = input.usize()?;
+        let atim = input.u64()?;
+        let mtim = input.u64()?;
+        let ctim = input.u64()?;
+
+        let node = match input.u8()? {
+            0 => VFSHeapNode::File(input.bytes()?.to_vec()),
+            1 => {
+                let mut entries = BTreeMap::new();
+                for _ in 0..input.usize()? {
+                    let name = String::from(input.str()?);
+                    entries.insert(name, input.usize()?);
+                }
+                VFSHeapNode::Dir(entries)
+            }
+            2 => VFSHeapNode::Symlink(String::from(input.str()?)),
+            _ => return Err(wasip1::ERRNO_INVAL),
+        };
+
+        Ok(Self {
+            parent,
+            node,
+            atim,
+            mtim,
+            ctim,
+        })
+    }
+}
+
+/// The whole tree, with the inode numbers kept,
+/// so removed inodes are saved as holes,
+/// as are the removed ones still open since descriptors are not saved.
+impl<StdIo: StdIO + 'static, Clock: Wasip1Clock> Wasip1Snapshot for VFSHeapLFS<StdIo, Clock> {
+    fn snapshot_to(&self, out: &mut SnapshotWriter) {
+        self.root.snapshot_to(out);
+
+        out.usize(self.inodes.len());
+        for (i, inode) in self.inodes.iter().enumerate() {
+            match inode {
+                Some(inode) if !self.unlinked.contains(&(i + 1)) => {
+                    out.u8(1);
+                    inode.snapshot_to(out);
+                }
+                _ => out.u8(0),
+            }
+        }
+    }
+
+    fn restore_from(&mut self, input: &mut SnapshotReader<'_>) -> Result<(), wasip1::Errno> {
+        let root = VFSHeapInode::restore_from(input)?;
+
+        let len = input.usize()?;
+        let mut inodes = Vec::new();
+        for _ in 0..len {
+            inodes.push(match input.u8()? {
+                0 => None,
+                1 => Some(VFSHeapInode::restore_from(input)?),
+                _ => return Err(wasip1::ERRNO_INVAL),
+            });
+        }
+
+        // the tree from the root has to reach every live inode once,
+        // from the directory its parent names, so lookups and `is_ancestor` end
+        if root.parent != 0 || !matches!(root.node, VFSHeapNode::Dir(..)) {
+            return Err(wasip1::ERRNO_INVAL);
+        }
+        let mut reached = vec![false; inodes.len()];
+        let mut dirs = vec![0];
+        while let Some(dir) = dirs.pop() {
+            let node = match dir {
+                0 => &root,
+                dir => inodes[dir - 1].as_ref().unwrap(),
+            };
+            let VFSHeapNode::Dir(entries) = &node.node else {
+                continue;
+            };
+
+            for &inode in entries.values() {
+                let Some(Some(entry)) = inode.checked_sub(1).and_then(|i| inodes.get(i)) else {
+                    return Err(wasip1::ERRNO_INVAL);
+                };
+                if entry.parent != dir || core::mem::replace(&mut reached[inode - 1], true) {
+                    return Err(wasip1::ERRNO_INVAL);
+                }
+                dirs.push(inode);
+            }
+        }
+        if inodes
+            .iter()
+            .zip(&reached)
+            .any(|(inode, reached)| inode.is_some() && !reached)
+        {
+            return Err(wasip1::ERRNO_INVAL);
+        }
+
+        self.root = root;
+        self.free_inodes = (1..=inodes.len())
+            .rev()
+            .filter(|inode| inodes[inode - 1].is_none())
+            .collect();
+        self.inodes = inodes;
+        self.opened.clear();
+        self.unlinked.clear();
+
+        Ok(())
+    }
+}
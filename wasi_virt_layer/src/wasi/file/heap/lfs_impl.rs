@@ -0,0 +1,1356 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::__private::wasip1;
+use crate::__private::wasip1::Dircookie;
+
+use crate::{
+    memory::{WasmAccess, WasmArrayAccess},
+    wasi::file::{
+        FilestatWithoutDevice, Wasip1LFS,
+        clock::{Wasip1Clock, times_to_set},
+        dirent::{DIRCOOKIE_ENTRIES, DirentWriter},
+        heap::lfs::{VFSHeapInode, VFSHeapLFS, VFSHeapNode},
+        stdio::{self, StdIO},
+    },
+};
+
+impl<StdIo: StdIO + 'static, Clock: Wasip1Clock> Wasip1LFS for VFSHeapLFS<StdIo, Clock> {
+    type Inode = usize;
+    const PRE_OPEN: &'static [Self::Inode] = &[0];
+
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        data: *const u8,
+        data_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let file_size_max = self.file_size_max();
+        match self.get_mut(inode) {
+            Some(VFSHeapInode {
+                node: VFSHeapNode::File(file),
+                ..
+            }) => {
+                let end = offset.checked_add(data_len).ok_or(wasip1::ERRNO_FBIG)?;
+                if end > file_size_max {
+                    return Err(wasip1::ERRNO_FBIG);
+                }
+                if file.len() < end {
+                    file.try_reserve(end - file.len())
+                        .map_err(|_| wasip1::ERRNO_NOSPC)?;
+                    file.resize(end, 0);
+                }
+                Wasm::memcpy_to(&mut file[offset..end], data);
+
+                self.modified(inode);
+                Ok(data_len)
+            }
+            Some(_) => Err(wasip1::ERRNO_ISDIR),
+            None => Err(wasip1::ERRNO_BADF),
+        }
+    }
+
+    fn fd_write_stdout_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::write_stdout_raw::<StdIo, Wasm>(data, data_len)
+    }
+
+    fn fd_write_stderr_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::write_stderr_raw::<StdIo, Wasm>(data, data_len)
+    }
+
+    fn is_dir(&self, inode: Self::Inode) -> bool {
+        self.is_dir(inode)
+    }
+
+    fn fd_readdir_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        cookie: Dircookie,
+    ) -> Result<(wasip1::Size, Dircookie), wasip1::Errno> {
+        let entries = match self.get(inode) {
+            Some(VFSHeapInode {
+                node: VFSHeapNode::Dir(entries),
+                ..
+            }) => entries,
+            Some(_) => return Err(wasip1::ERRNO_NOTDIR),
+            None => return Err(wasip1::ERRNO_BADF),
+        };
+
+        let mut writer = DirentWriter::<Wasm>::new(buf, buf_len, cookie);
+
+        // a removed directory kept open is its own parent
+        let parent = self.parent_inode(inode).unwrap_or(inode);
+        if !writer.dots(inode as _, parent as _) {
+            return Ok(writer.finish());
+        }
+
+        // listed by inode number, which stays a valid cookie when entries are removed;
+        // an entry created meanwhile under a reused number may be missed
+        let mut entries = entries
+            .iter()
+            .map(|(name, ino)| (*ino, name.as_str()))
+            .collect::<Vec<_>>();
+        entries.sort_unstable();
+
+        for (ino, name) in entries {
+            let filetype = self.get(ino).unwrap().node.filetype();
+
+            if !writer.entry(
+                DIRCOOKIE_ENTRIES + ino as Dircookie,
+                ino as _,
+                filetype,
+                name.as_bytes(),
+            ) {
+                break;
+            }
+        }
+
+        Ok(writer.finish())
+    }
+
+    fn path_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let inode = self.get_inode_for_path::<Wasm>(inode, flags, path_ptr, path_len)?;
+
+        self.filestat_from_inode(inode)
+    }
+
+    fn fd_prestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<wasip1::Prestat, wasip1::Errno> {
+        if !Self::PRE_OPEN.contains(&inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        Ok(wasip1::Prestat {
+            tag: 0, // prestat is enum but variant is only 0
+            // union type but we only have one variant
+            u: wasip1::PrestatU {
+                dir: wasip1::PrestatDir {
+                    pr_name_len: self.name().len() as _,
+                },
+            },
+        })
+    }
+
+    fn fd_prestat_dir_name_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        dir_path_ptr: *mut u8,
+        dir_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        if !Self::PRE_OPEN.contains(&inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        let name = self.name();
+
+        Wasm::memcpy(
+            dir_path_ptr,
+            &name.as_bytes()[..core::cmp::min(name.len(), dir_path_len)],
+        );
+
+        Ok(())
+    }
+
+    fn fd_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        self.filestat_from_inode(inode)
+    }
+
+    fn fd_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        atim: wasip1::Timestamp,
+        mtim: wasip1::Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        let (atim, mtim) = times_to_set::<Clock>(atim, mtim, fst_flags)?;
+
+        let node = self.get_mut(inode).ok_or(wasip1::ERRNO_BADF)?;
+        if let Some(atim) = atim {
+            node.atim = atim;
+        }
+        if let Some(mtim) = mtim {
+            node.mtim = mtim;
+        }
+        node.ctim = Clock::now();
+
+        Ok(())
+    }
+
+    fn fd_pread_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        match self.get(inode) {
+            Some(VFSHeapInode {
+                node: VFSHeapNode::File(file),
+                ..
+            }) => {
+                if offset >= file.len() {
+                    return Ok(0); // No data to read
+                }
+
+                let buf_len = core::cmp::min(buf_len, file.len() - offset);
+                Wasm::memcpy(buf, &file[offset..offset + buf_len]);
+
+                self.accessed(inode);
+                Ok(buf_len)
+            }
+            Some(_) => Err(wasip1::ERRNO_ISDIR),
+            None => Err(wasip1::ERRNO_BADF),
+        }
+    }
+
+    fn fd_read_stdin_raw<Wasm: WasmAccess>(
+        &mut self,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::read_stdin_raw::<StdIo, Wasm>(buf, buf_len)
+    }
+
+    fn path_open_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        dir_flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        _: wasip1::Rights,
+        _: wasip1::Fdflags,
+    ) -> Result<Self::Inode, wasip1::Errno> {
+        let (dir, name) = self.get_parent_for_path::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        let inode = match name {
+            Some(name) => match self.lookup(dir, &name) {
+                Ok(inode) => {
+                    if o_flags & (wasip1::OFLAGS_CREAT | wasip1::OFLAGS_EXCL)
+                        == (wasip1::OFLAGS_CREAT | wasip1::OFLAGS_EXCL)
+                    {
+                        return Err(wasip1::ERRNO_EXIST);
+                    }
+
+                    if !matches!(
+                        self.get(inode),
+                        Some(VFSHeapInode {
+                            node: VFSHeapNode::Symlink(..),
+                            ..
+                        })
+                    ) {
+                        inode
+                    } else if dir_flags & wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW
+                        == wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW
+                    {
+                        self.follow(inode, &mut 0)?
+                    } else {
+                        return Err(wasip1::ERRNO_LOOP);
+                    }
+                }
+                Err(wasip1::ERRNO_NOENT)
+                    if o_flags & wasip1::OFLAGS_CREAT == wasip1::OFLAGS_CREAT =>
+                {
+                    if o_flags & wasip1::OFLAGS_DIRECTORY == wasip1::OFLAGS_DIRECTORY {
+                        return Err(wasip1::ERRNO_INVAL);
+                    }
+
+                    let inode = self.insert(dir, name, VFSHeapNode::File(Vec::new()))?;
+                    self.opened(inode);
+                    return Ok(inode);
+                }
+                Err(e) => return Err(e),
+            },
+            None => dir,
+        };
+
+        let is_dir = self.is_dir(inode);
+
+        if o_flags & wasip1::OFLAGS_DIRECTORY == wasip1::OFLAGS_DIRECTORY && !is_dir {
+            return Err(wasip1::ERRNO_NOTDIR);
+        }
+
+        if is_dir
+            && (fs_rights_base & wasip1::RIGHTS_FD_WRITE == wasip1::RIGHTS_FD_WRITE
+                || o_flags & wasip1::OFLAGS_TRUNC == wasip1::OFLAGS_TRUNC)
+        {
+            return Err(wasip1::ERRNO_ISDIR);
+        }
+
+        if o_flags & wasip1::OFLAGS_TRUNC == wasip1::OFLAGS_TRUNC
+            && let Some(VFSHeapInode {
+                node: VFSHeapNode::File(file),
+                ..
+            }) = self.get_mut(inode)
+        {
+            file.clear();
+            self.modified(inode);
+        }
+
+        self.opened(inode);
+        Ok(inode)
+    }
+
+    fn fd_close_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) {
+        self.closed(inode);
+    }
+
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (dir, name) = self.get_parent_for_path::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        self.create_directory(dir, name)
+    }
+
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (dir, name) = self.get_parent_for_path::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        self.remove_directory(dir, name)
+    }
+
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (dir, name) = self.get_parent_for_path::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        self.unlink_file(dir, name)
+    }
+
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_dir_inode: Self::Inode,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_dir_inode: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (old_dir, old_name) =
+            self.get_parent_for_path::<Wasm>(old_dir_inode, old_path_ptr, old_path_len)?;
+        let (new_dir, new_name) =
+            self.get_parent_for_path::<Wasm>(new_dir_inode, new_path_ptr, new_path_len)?;
+
+        self.rename(old_dir, old_name, new_dir, new_name)
+    }
+
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let inode = self.get_inode_for_path::<Wasm>(dir_inode, 0, path_ptr, path_len)?;
+
+        match self.get(inode) {
+            Some(VFSHeapInode {
+                node: VFSHeapNode::Symlink(target),
+                ..
+            }) => {
+                let len = core::cmp::min(target.len(), buf_len);
+                Wasm::memcpy(buf, &target.as_bytes()[..len]);
+                Ok(len)
+            }
+            _ => Err(wasip1::ERRNO_INVAL),
+        }
+    }
+
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        dir_inode: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let target = String::from_utf8(
+            WasmArrayAccess::<u8, Wasm>::new(old_path_ptr, old_path_len)
+                .iter()
+                .collect(),
+        )
+        .map_err(|_| wasip1::ERRNO_ILSEQ)?;
+
+        let (dir, name) =
+            self.get_parent_for_path::<Wasm>(dir_inode, new_path_ptr, new_path_len)?;
+        let name = name.ok_or(wasip1::ERRNO_EXIST)?;
+
+        self.insert(dir, name, VFSHeapNode::Symlink(target))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        memory::HostMemory,
+        mock::MockWasm,
+        wasi::file::{
+            Wasip1FileSystem,
+            clock::CounterClock,
+            constant::vfs::{Wasip1ConstVFS, Wasip1VFS},
+            fd_table::VecFdTable,
+            snapshot::SnapshotWriter,
+            stdio::DefaultStdIO,
+        },
+    };
+
+    fn seek(
+        vfs: &mut Wasip1ConstVFS<VFSHeapLFS<DefaultStdIO>, 8>,
+        fd: wasip1::Fd,
+        offset: wasip1::Filedelta,
+        whence: wasip1::Whence,
+    ) -> Result<wasip1::Filesize, wasip1::Errno> {
+        let mut newoffset = 0;
+        match Wasip1FileSystem::fd_seek_raw::<HostMemory>(vfs, fd, offset, whence, &mut newoffset) {
+            wasip1::ERRNO_SUCCESS => Ok(newoffset),
+            e => Err(e),
+        }
+    }
+
+    #[test]
+    fn test_heap_create_write_truncate_append() {
+        let mut vfs = Wasip1ConstVFS::<_, 8>::new(VFSHeapLFS::<DefaultStdIO>::new("."));
+
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "out.txt", 0),
+            Err(wasip1::ERRNO_NOENT)
+        );
+
+        let fd = MockWasm::open(&mut vfs, 3, "out.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"Hello, "), Ok(7));
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"world!"), Ok(6));
+        assert_eq!(vfs.lfs().get_file("out.txt"), Some(&b"Hello, world!"[..]));
+
+        let fd = MockWasm::open_with(
+            &mut vfs,
+            3,
+            0,
+            "out.txt",
+            0,
+            MockWasm::RIGHTS,
+            wasip1::FDFLAGS_APPEND,
+        )
+        .unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"!!"), Ok(2));
+        assert_eq!(
+            vfs.lfs().get_file("./out.txt"),
+            Some(&b"Hello, world!!!"[..])
+        );
+
+        let fd = MockWasm::open(&mut vfs, 3, "out.txt", wasip1::OFLAGS_TRUNC).unwrap();
+        assert_eq!(vfs.lfs().get_file("out.txt"), Some(&b""[..]));
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"Bye"), Ok(3));
+        assert_eq!(vfs.lfs().get_file("out.txt"), Some(&b"Bye"[..]));
+
+        assert_eq!(
+            MockWasm::open(
+                &mut vfs,
+                3,
+                "out.txt",
+                wasip1::OFLAGS_CREAT | wasip1::OFLAGS_EXCL
+            ),
+            Err(wasip1::ERRNO_EXIST)
+        );
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "out.txt/a", wasip1::OFLAGS_CREAT),
+            Err(wasip1::ERRNO_NOTDIR)
+        );
+    }
+
+    #[test]
+    fn test_heap_seek_tell() {
+        let mut vfs = Wasip1ConstVFS::<_, 8>::new(VFSHeapLFS::<DefaultStdIO>::new("."));
+
+        let fd = MockWasm::open(&mut vfs, 3, "seek.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"0123456789"), Ok(10));
+
+        assert_eq!(seek(&mut vfs, fd, 2, wasip1::WHENCE_SET), Ok(2));
+        assert_eq!(MockWasm::read(&mut vfs, fd, 3).as_deref(), Ok(&b"234"[..]));
+
+        assert_eq!(seek(&mut vfs, fd, 1, wasip1::WHENCE_CUR), Ok(6));
+        assert_eq!(MockWasm::read(&mut vfs, fd, 3).as_deref(), Ok(&b"678"[..]));
+
+        assert_eq!(seek(&mut vfs, fd, -2, wasip1::WHENCE_END), Ok(8));
+        assert_eq!(MockWasm::read(&mut vfs, fd, 3).as_deref(), Ok(&b"89"[..]));
+
+        let mut offset = 0;
+        assert_eq!(
+            Wasip1FileSystem::fd_tell_raw::<HostMemory>(&mut vfs, fd, &mut offset),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(offset, 10);
+
+        assert_eq!(
+            seek(&mut vfs, fd, -11, wasip1::WHENCE_END),
+            Err(wasip1::ERRNO_INVAL)
+        );
+
+        assert_eq!(seek(&mut vfs, fd, 12, wasip1::WHENCE_SET), Ok(12));
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"!"), Ok(1));
+        assert_eq!(
+            vfs.lfs().get_file("seek.txt"),
+            Some(&b"0123456789\0\0!"[..])
+        );
+
+        assert_eq!(
+            seek(&mut vfs, 1, 0, wasip1::WHENCE_SET),
+            Err(wasip1::ERRNO_SPIPE)
+        );
+    }
+
+    #[test]
+    fn test_heap_pread_pwrite() {
+        let mut vfs = Wasip1ConstVFS::<_, 8>::new(VFSHeapLFS::<DefaultStdIO>::new("."));
+
+        let fd = MockWasm::open(&mut vfs, 3, "page.db", wasip1::OFLAGS_CREAT).unwrap();
+
+        let (a, b) = (b"abcd", b"efgh");
+        let iovs = [
+            wasip1::Ciovec {
+                buf: a.as_ptr(),
+                buf_len: a.len(),
+            },
+            wasip1::Ciovec {
+                buf: b.as_ptr(),
+                buf_len: b.len(),
+            },
+        ];
+        let mut nwritten = 0;
+        assert_eq!(
+            Wasip1FileSystem::fd_pwrite_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                iovs.as_ptr(),
+                iovs.len(),
+                4,
+                &mut nwritten
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(nwritten, 8);
+        assert_eq!(
+            vfs.lfs().get_file("page.db"),
+            Some(&b"\0\0\0\0abcdefgh"[..])
+        );
+
+        let (mut a, mut b) = ([0u8; 3], [0u8; 8]);
+        let iovs = [
+            wasip1::Iovec {
+                buf: a.as_mut_ptr(),
+                buf_len: a.len(),
+            },
+            wasip1::Iovec {
+                buf: b.as_mut_ptr(),
+                buf_len: b.len(),
+            },
+        ];
+        let mut nread = 0;
+        assert_eq!(
+            Wasip1FileSystem::fd_pread_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                iovs.as_ptr(),
+                iovs.len(),
+                5,
+                &mut nread
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(nread, 7);
+        assert_eq!(&a, b"bcd");
+        assert_eq!(&b[..4], b"efgh");
+
+        // positional I/O does not move the cursor
+        assert_eq!(seek(&mut vfs, fd, 0, wasip1::WHENCE_CUR), Ok(0));
+    }
+
+    #[test]
+    fn test_heap_file_size_max() {
+        let mut lfs = VFSHeapLFS::<DefaultStdIO>::new(".").with_file_size_max(8);
+        let inode = lfs
+            .insert(0, "small.txt".into(), VFSHeapNode::File(Vec::new()))
+            .unwrap();
+        let data = b"abcd";
+
+        let pwrite = |lfs: &mut VFSHeapLFS<DefaultStdIO>, offset| {
+            Wasip1LFS::fd_pwrite_raw::<HostMemory>(lfs, inode, data.as_ptr(), data.len(), offset)
+        };
+
+        assert_eq!(pwrite(&mut lfs, 4), Ok(4));
+        assert_eq!(pwrite(&mut lfs, 5), Err(wasip1::ERRNO_FBIG));
+        assert_eq!(pwrite(&mut lfs, usize::MAX), Err(wasip1::ERRNO_FBIG));
+        assert_eq!(lfs.get_file("small.txt"), Some(&b"\0\0\0\0abcd"[..]));
+    }
+
+    fn path_op(
+        vfs: &mut Wasip1ConstVFS<VFSHeapLFS<DefaultStdIO>, 8>,
+        op: fn(
+            &mut Wasip1ConstVFS<VFSHeapLFS<DefaultStdIO>, 8>,
+            wasip1::Fd,
+            *const u8,
+            usize,
+        ) -> wasip1::Errno,
+        path: &str,
+    ) -> wasip1::Errno {
+        op(vfs, 3, path.as_ptr(), path.len())
+    }
+
+    fn rename(
+        vfs: &mut Wasip1ConstVFS<VFSHeapLFS<DefaultStdIO>, 8>,
+        old: &str,
+        new: &str,
+    ) -> wasip1::Errno {
+        Wasip1FileSystem::path_rename_raw::<HostMemory>(
+            vfs,
+            3,
+            old.as_ptr(),
+            old.len(),
+            3,
+            new.as_ptr(),
+            new.len(),
+        )
+    }
+
+    #[test]
+    fn test_heap_mkdir_rmdir_unlink_rename() {
+        type Vfs = Wasip1ConstVFS<VFSHeapLFS<DefaultStdIO>, 8>;
+        let mkdir = <Vfs as Wasip1FileSystem>::path_create_directory_raw::<HostMemory>;
+        let rmdir = <Vfs as Wasip1FileSystem>::path_remove_directory_raw::<HostMemory>;
+        let unlink = <Vfs as Wasip1FileSystem>::path_unlink_file_raw::<HostMemory>;
+
+        let mut vfs = Vfs::new(VFSHeapLFS::<DefaultStdIO>::new("."));
+
+        assert_eq!(path_op(&mut vfs, mkdir, "dir"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(path_op(&mut vfs, mkdir, "dir"), wasip1::ERRNO_EXIST);
+        assert_eq!(path_op(&mut vfs, mkdir, "."), wasip1::ERRNO_EXIST);
+        assert_eq!(path_op(&mut vfs, mkdir, "none/sub"), wasip1::ERRNO_NOENT);
+
+        let fd = MockWasm::open(&mut vfs, 3, "dir/a.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"a"), Ok(1));
+
+        assert_eq!(path_op(&mut vfs, rmdir, "dir"), wasip1::ERRNO_NOTEMPTY);
+        assert_eq!(path_op(&mut vfs, rmdir, "dir/a.txt"), wasip1::ERRNO_NOTDIR);
+        assert_eq!(path_op(&mut vfs, unlink, "dir"), wasip1::ERRNO_ISDIR);
+
+        assert_eq!(
+            rename(&mut vfs, "dir/a.txt", "b.txt"),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(vfs.lfs().get_file("b.txt"), Some(&b"a"[..]));
+        assert_eq!(vfs.lfs().get_file("dir/a.txt"), None);
+
+        assert_eq!(rename(&mut vfs, "dir", "dir/sub"), wasip1::ERRNO_INVAL);
+        assert_eq!(rename(&mut vfs, "b.txt", "dir"), wasip1::ERRNO_ISDIR);
+        assert_eq!(rename(&mut vfs, "dir", "b.txt"), wasip1::ERRNO_NOTDIR);
+        assert_eq!(rename(&mut vfs, "none", "c.txt"), wasip1::ERRNO_NOENT);
+
+        assert_eq!(rename(&mut vfs, "dir", "moved"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(
+            rename(&mut vfs, "b.txt", "moved/b.txt"),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(vfs.lfs().get_file("moved/../moved/b.txt"), Some(&b"a"[..]));
+
+        assert_eq!(
+            path_op(&mut vfs, unlink, "moved/b.txt"),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(
+            path_op(&mut vfs, unlink, "moved/b.txt"),
+            wasip1::ERRNO_NOENT
+        );
+        // the descriptor keeps the unlinked file
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"b"), Ok(1));
+
+        assert_eq!(path_op(&mut vfs, rmdir, "moved"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(path_op(&mut vfs, rmdir, "moved"), wasip1::ERRNO_NOENT);
+
+        // nothing moves into a removed directory kept open
+        assert_eq!(path_op(&mut vfs, mkdir, "gone"), wasip1::ERRNO_SUCCESS);
+        let gone = MockWasm::open_with(
+            &mut vfs,
+            3,
+            0,
+            "gone",
+            wasip1::OFLAGS_DIRECTORY,
+            wasip1::RIGHTS_FD_READDIR,
+            0,
+        )
+        .unwrap();
+        assert_eq!(path_op(&mut vfs, rmdir, "gone"), wasip1::ERRNO_SUCCESS);
+        let fd = MockWasm::open(&mut vfs, 3, "c.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"c"), Ok(1));
+        let (old, new) = ("c.txt", "c.txt");
+        assert_eq!(
+            Wasip1FileSystem::path_rename_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                old.as_ptr(),
+                old.len(),
+                gone,
+                new.as_ptr(),
+                new.len()
+            ),
+            wasip1::ERRNO_NOENT
+        );
+        assert_eq!(vfs.lfs().get_file("c.txt"), Some(&b"c"[..]));
+    }
+
+    #[test]
+    fn test_heap_unlink_while_open() {
+        type Vfs = Wasip1ConstVFS<VFSHeapLFS<DefaultStdIO>, 8>;
+        let unlink = <Vfs as Wasip1FileSystem>::path_unlink_file_raw::<HostMemory>;
+        let ino = |vfs: &mut Vfs, fd| {
+            let mut filestat = core::mem::MaybeUninit::<wasip1::Filestat>::uninit();
+            assert_eq!(
+                Wasip1FileSystem::fd_filestat_get_raw::<HostMemory>(vfs, fd, filestat.as_mut_ptr()),
+                wasip1::ERRNO_SUCCESS
+            );
+            unsafe { filestat.assume_init() }.ino
+        };
+
+        let mut vfs = Vfs::new(VFSHeapLFS::<DefaultStdIO>::new("."));
+
+        let fd = MockWasm::open(&mut vfs, 3, "a.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"kept"), Ok(4));
+        assert_eq!(path_op(&mut vfs, unlink, "a.txt"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(vfs.lfs().get_file("a.txt"), None);
+
+        // the data stays until the last close, and its number is not handed out
+        let other = MockWasm::open(&mut vfs, 3, "b.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_ne!(ino(&mut vfs, other), ino(&mut vfs, fd));
+        assert_eq!(seek(&mut vfs, fd, 0, wasip1::WHENCE_SET), Ok(0));
+        assert_eq!(MockWasm::read(&mut vfs, fd, 8).as_deref(), Ok(&b"kept"[..]));
+
+        let freed = ino(&mut vfs, fd);
+        assert_eq!(
+            Wasip1FileSystem::fd_close_raw::<HostMemory>(&mut vfs, fd),
+            wasip1::ERRNO_SUCCESS
+        );
+
+        let fd = MockWasm::open(&mut vfs, 3, "c.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(ino(&mut vfs, fd), freed);
+        assert_eq!(MockWasm::read(&mut vfs, fd, 8).as_deref(), Ok(&b""[..]));
+    }
+
+    #[test]
+    fn test_heap_symlink() {
+        let mut vfs = Wasip1ConstVFS::<_, 8>::new(VFSHeapLFS::<DefaultStdIO>::new("."));
+
+        let symlink =
+            |vfs: &mut Wasip1ConstVFS<VFSHeapLFS<DefaultStdIO>, 8>, target: &str, path: &str| {
+                Wasip1FileSystem::path_symlink_raw::<HostMemory>(
+                    vfs,
+                    target.as_ptr(),
+                    target.len(),
+                    3,
+                    path.as_ptr(),
+                    path.len(),
+                )
+            };
+
+        assert_eq!(
+            Wasip1FileSystem::path_create_directory_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                "usr".as_ptr(),
+                3
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        let fd = MockWasm::open(&mut vfs, 3, "usr/clang", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"clang"), Ok(5));
+
+        assert_eq!(symlink(&mut vfs, "usr/clang", "cc"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(symlink(&mut vfs, "/usr", "lib"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(symlink(&mut vfs, "loop", "loop"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(symlink(&mut vfs, "x", "cc"), wasip1::ERRNO_EXIST);
+
+        let mut buf = [0u8; 16];
+        let mut nread = 0;
+        assert_eq!(
+            Wasip1FileSystem::path_readlink_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                "cc".as_ptr(),
+                2,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut nread
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(&buf[..nread], b"usr/clang");
+
+        // path_open only follows the last link with LOOKUPFLAGS_SYMLINK_FOLLOW
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "cc", 0),
+            Err(wasip1::ERRNO_LOOP)
+        );
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "loop", 0),
+            Err(wasip1::ERRNO_LOOP)
+        );
+
+        let mut stat = unsafe { core::mem::zeroed::<wasip1::Filestat>() };
+        let filestat = |vfs: &mut Wasip1ConstVFS<VFSHeapLFS<DefaultStdIO>, 8>,
+                        flags,
+                        path: &str,
+                        stat: &mut wasip1::Filestat| {
+            Wasip1FileSystem::path_filestat_get_raw::<HostMemory>(
+                vfs,
+                3,
+                flags,
+                path.as_ptr(),
+                path.len(),
+                stat,
+            )
+        };
+        assert_eq!(
+            filestat(&mut vfs, 0, "cc", &mut stat),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(stat.filetype, wasip1::FILETYPE_SYMBOLIC_LINK);
+        assert_eq!(
+            filestat(
+                &mut vfs,
+                wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+                "cc",
+                &mut stat
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(stat.filetype, wasip1::FILETYPE_REGULAR_FILE);
+        assert_eq!(stat.size, 5);
+
+        // links in the middle of the path are always followed
+        assert_eq!(
+            filestat(&mut vfs, 0, "lib/clang", &mut stat),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(stat.filetype, wasip1::FILETYPE_REGULAR_FILE);
+        assert_eq!(
+            filestat(
+                &mut vfs,
+                wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+                "loop",
+                &mut stat
+            ),
+            wasip1::ERRNO_LOOP
+        );
+        assert_eq!(
+            filestat(&mut vfs, 0, "loop/a", &mut stat),
+            wasip1::ERRNO_LOOP
+        );
+
+        // unlink removes the link, not the target
+        assert_eq!(
+            Wasip1FileSystem::path_unlink_file_raw::<HostMemory>(&mut vfs, 3, "lib".as_ptr(), 3),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(vfs.lfs().get_file("usr/clang"), Some(&b"clang"[..]));
+    }
+
+    #[test]
+    fn test_heap_fdstat_rights() {
+        let mut vfs = Wasip1ConstVFS::<_, 8>::new(VFSHeapLFS::<DefaultStdIO>::new("."));
+
+        let fdstat = |vfs: &mut Wasip1ConstVFS<VFSHeapLFS<DefaultStdIO>, 8>, fd| {
+            let mut stat = unsafe { core::mem::zeroed::<wasip1::Fdstat>() };
+            match Wasip1FileSystem::fd_fdstat_get_raw::<HostMemory>(vfs, fd, &mut stat) {
+                wasip1::ERRNO_SUCCESS => Ok(stat),
+                e => Err(e),
+            }
+        };
+
+        let stdout = fdstat(&mut vfs, 1).unwrap();
+        assert_eq!(stdout.fs_filetype, wasip1::FILETYPE_CHARACTER_DEVICE);
+        assert_eq!(stdout.fs_rights_base & wasip1::RIGHTS_FD_SEEK, 0);
+
+        let dir = fdstat(&mut vfs, 3).unwrap();
+        assert_eq!(dir.fs_filetype, wasip1::FILETYPE_DIRECTORY);
+
+        let fd = MockWasm::open(&mut vfs, 3, "log", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"one"), Ok(3));
+
+        let stat = fdstat(&mut vfs, fd).unwrap();
+        assert_eq!(stat.fs_filetype, wasip1::FILETYPE_REGULAR_FILE);
+        assert_eq!(stat.fs_flags, 0);
+        assert!(stat.fs_rights_base & wasip1::RIGHTS_FD_WRITE != 0);
+
+        // fd_fdstat_set_flags needs its own right
+        assert_eq!(
+            Wasip1FileSystem::fd_fdstat_set_flags_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                wasip1::FDFLAGS_APPEND
+            ),
+            wasip1::ERRNO_NOTCAPABLE
+        );
+
+        let fd = MockWasm::open_with(
+            &mut vfs,
+            3,
+            0,
+            "log",
+            0,
+            wasip1::RIGHTS_FD_WRITE | wasip1::RIGHTS_FD_FDSTAT_SET_FLAGS,
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            Wasip1FileSystem::fd_fdstat_set_flags_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                wasip1::FDFLAGS_APPEND
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(
+            fdstat(&mut vfs, fd).unwrap().fs_flags,
+            wasip1::FDFLAGS_APPEND
+        );
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"two"), Ok(3));
+        assert_eq!(vfs.lfs().get_file("log"), Some(&b"onetwo"[..]));
+
+        // rights are enforced
+        let mut buf = [0u8; 8];
+        let iovs = [wasip1::Ciovec {
+            buf: buf.as_mut_ptr(),
+            buf_len: buf.len(),
+        }];
+        let mut n = 0;
+        assert_eq!(
+            Wasip1FileSystem::fd_read_raw::<HostMemory>(&mut vfs, fd, iovs.as_ptr(), 1, &mut n),
+            wasip1::ERRNO_NOTCAPABLE
+        );
+        assert_eq!(
+            seek(&mut vfs, fd, 0, wasip1::WHENCE_SET),
+            Err(wasip1::ERRNO_NOTCAPABLE)
+        );
+
+        // rights can be dropped but not added back
+        assert_eq!(
+            Wasip1FileSystem::fd_fdstat_set_rights_raw::<HostMemory>(&mut vfs, fd, 0, 0),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(
+            Wasip1FileSystem::fd_fdstat_set_rights_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                wasip1::RIGHTS_FD_WRITE,
+                0
+            ),
+            wasip1::ERRNO_NOTCAPABLE
+        );
+        let iovs = [wasip1::Ciovec {
+            buf: b"x".as_ptr(),
+            buf_len: 1,
+        }];
+        assert_eq!(
+            Wasip1FileSystem::fd_write_raw::<HostMemory>(&mut vfs, fd, iovs.as_ptr(), 1, &mut n),
+            wasip1::ERRNO_NOTCAPABLE
+        );
+
+        // a directory opened without RIGHTS_FD_READDIR cannot be listed
+        let dir = MockWasm::open_with(&mut vfs, 3, 0, ".", wasip1::OFLAGS_DIRECTORY, 0, 0).unwrap();
+        assert_eq!(
+            Wasip1FileSystem::fd_readdir_raw::<HostMemory>(
+                &mut vfs,
+                dir,
+                buf.as_mut_ptr(),
+                buf.len(),
+                0,
+                &mut n
+            ),
+            wasip1::ERRNO_NOTCAPABLE
+        );
+        // and hands out no rights to the files opened through it
+        let mut new_fd = 0;
+        assert_eq!(
+            Wasip1FileSystem::path_open_raw::<HostMemory>(
+                &mut vfs,
+                dir,
+                0,
+                "log".as_ptr(),
+                3,
+                0,
+                wasip1::RIGHTS_FD_READ,
+                0,
+                0,
+                &mut new_fd,
+            ),
+            wasip1::ERRNO_NOTCAPABLE
+        );
+    }
+
+    #[test]
+    fn test_heap_fd_limit_renumber() {
+        let mut vfs = Wasip1ConstVFS::<_, 8>::new(VFSHeapLFS::<DefaultStdIO>::new("."));
+
+        // fd 3 is the pre-opened directory
+        for fd in 4..11 {
+            assert_eq!(
+                MockWasm::open(&mut vfs, 3, "a", wasip1::OFLAGS_CREAT),
+                Ok(fd)
+            );
+        }
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "a", 0),
+            Err(wasip1::ERRNO_MFILE)
+        );
+
+        for fd in 6..11 {
+            assert_eq!(
+                Wasip1FileSystem::fd_close_raw::<HostMemory>(&mut vfs, fd),
+                wasip1::ERRNO_SUCCESS
+            );
+        }
+        let (a, b) = (4, 5);
+
+        // the target must be open
+        assert_eq!(
+            Wasip1FileSystem::fd_renumber_raw::<HostMemory>(&mut vfs, a, 6),
+            wasip1::ERRNO_BADF
+        );
+        assert_eq!(
+            Wasip1FileSystem::fd_renumber_raw::<HostMemory>(&mut vfs, a, b),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(MockWasm::write(&mut vfs, b, b"to a"), Ok(4));
+        assert_eq!(vfs.lfs().get_file("a"), Some(&b"to a"[..]));
+        assert!(vfs.get_entry(a).is_none());
+
+        // redirect stdout to the file
+        assert_eq!(
+            Wasip1FileSystem::fd_renumber_raw::<HostMemory>(&mut vfs, b, 1),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(MockWasm::write(&mut vfs, 1, b"!"), Ok(1));
+        assert_eq!(vfs.lfs().get_file("a"), Some(&b"to a!"[..]));
+        assert_eq!(
+            Wasip1FileSystem::fd_renumber_raw::<HostMemory>(&mut vfs, 2, 4),
+            wasip1::ERRNO_NOTSUP
+        );
+    }
+
+    #[test]
+    fn test_heap_fd_limit_closes_inode() {
+        // fd 3 is the pre-opened directory, fd 4 the only free one
+        let mut vfs = Wasip1ConstVFS::<_, 2>::new(VFSHeapLFS::<DefaultStdIO>::new("."));
+        let ino = |vfs: &mut Wasip1ConstVFS<VFSHeapLFS<DefaultStdIO>, 2>, fd| {
+            let mut filestat = core::mem::MaybeUninit::<wasip1::Filestat>::uninit();
+            assert_eq!(
+                Wasip1FileSystem::fd_filestat_get_raw::<HostMemory>(vfs, fd, filestat.as_mut_ptr()),
+                wasip1::ERRNO_SUCCESS
+            );
+            unsafe { filestat.assume_init() }.ino
+        };
+
+        let fd = MockWasm::open(&mut vfs, 3, "a.txt", wasip1::OFLAGS_CREAT).unwrap();
+        let freed = ino(&mut vfs, fd);
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "a.txt", 0),
+            Err(wasip1::ERRNO_MFILE)
+        );
+
+        // the failed open holds no count, so the inode is freed on the last real close
+        assert_eq!(
+            Wasip1FileSystem::path_unlink_file_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                "a.txt".as_ptr(),
+                "a.txt".len()
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(
+            Wasip1FileSystem::fd_close_raw::<HostMemory>(&mut vfs, fd),
+            wasip1::ERRNO_SUCCESS
+        );
+        let fd = MockWasm::open(&mut vfs, 3, "b.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(ino(&mut vfs, fd), freed);
+    }
+
+    #[test]
+    fn test_heap_vec_fd_table() {
+        let mut vfs = Wasip1VFS::with_fd_table(
+            VFSHeapLFS::<DefaultStdIO>::new("."),
+            VecFdTable::new(VFSHeapLFS::<DefaultStdIO>::PRE_OPEN, 2),
+        );
+
+        let mut open = |path: &str| {
+            let mut fd = 0;
+            match Wasip1FileSystem::path_open_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                0,
+                path.as_ptr(),
+                path.len(),
+                wasip1::OFLAGS_CREAT,
+                wasip1::RIGHTS_FD_WRITE,
+                0,
+                0,
+                &mut fd,
+            ) {
+                wasip1::ERRNO_SUCCESS => Ok(fd),
+                e => Err(e),
+            }
+        };
+
+        // the pre-opened directory takes one of the two descriptors
+        assert_eq!(open("a"), Ok(4));
+        assert_eq!(open("b"), Err(wasip1::ERRNO_MFILE));
+    }
+
+    #[test]
+    fn test_heap_times() {
+        type Vfs = Wasip1ConstVFS<VFSHeapLFS<DefaultStdIO, CounterClock>, 8>;
+        let mut vfs = Vfs::new(VFSHeapLFS::new("."));
+
+        let filestat = |vfs: &mut Vfs, fd| {
+            let mut stat = unsafe { core::mem::zeroed::<wasip1::Filestat>() };
+            assert_eq!(
+                Wasip1FileSystem::fd_filestat_get_raw::<HostMemory>(vfs, fd, &mut stat),
+                wasip1::ERRNO_SUCCESS
+            );
+            stat
+        };
+
+        let path = "out.txt";
+        let mut fd = 0;
+        assert_eq!(
+            Wasip1FileSystem::path_open_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                0,
+                path.as_ptr(),
+                path.len(),
+                wasip1::OFLAGS_CREAT,
+                wasip1::RIGHTS_FD_READ
+                    | wasip1::RIGHTS_FD_WRITE
+                    | wasip1::RIGHTS_FD_SEEK
+                    | wasip1::RIGHTS_FD_FILESTAT_SET_TIMES,
+                0,
+                0,
+                &mut fd,
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        let created = filestat(&mut vfs, fd);
+        assert_eq!(created.atim, created.mtim);
+
+        // a write moves the modification time only
+        let data = b"Hello";
+        let iovs = [wasip1::Ciovec {
+            buf: data.as_ptr(),
+            buf_len: data.len(),
+        }];
+        let mut nwritten = 0;
+        assert_eq!(
+            Wasip1FileSystem::fd_pwrite_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                iovs.as_ptr(),
+                iovs.len(),
+                0,
+                &mut nwritten,
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        let written = filestat(&mut vfs, fd);
+        assert!(written.mtim > created.mtim);
+        assert_eq!(written.atim, created.atim);
+
+        // a read moves the access time only
+        let mut buf = [0u8; 8];
+        let iovs = [wasip1::Iovec {
+            buf: buf.as_mut_ptr(),
+            buf_len: buf.len(),
+        }];
+        let mut nread = 0;
+        assert_eq!(
+            Wasip1FileSystem::fd_pread_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                iovs.as_ptr(),
+                iovs.len(),
+                0,
+                &mut nread,
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        let read = filestat(&mut vfs, fd);
+        assert!(read.atim > written.mtim);
+        assert_eq!(read.mtim, written.mtim);
+
+        // the root starts at 0 and was modified by the creation
+        assert_ne!(filestat(&mut vfs, 3).mtim, 0);
+
+        assert_eq!(
+            Wasip1FileSystem::fd_filestat_set_times_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                10,
+                20,
+                wasip1::FSTFLAGS_ATIM | wasip1::FSTFLAGS_MTIM
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        let set = filestat(&mut vfs, fd);
+        assert_eq!((set.atim, set.mtim), (10, 20));
+
+        assert_eq!(
+            Wasip1FileSystem::path_filestat_set_times_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                0,
+                path.as_ptr(),
+                path.len(),
+                0,
+                30,
+                wasip1::FSTFLAGS_ATIM_NOW | wasip1::FSTFLAGS_MTIM
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        let set = filestat(&mut vfs, fd);
+        assert!(set.atim > read.atim);
+        assert_eq!(set.mtim, 30);
+
+        // a time cannot be both given and now
+        assert_eq!(
+            Wasip1FileSystem::fd_filestat_set_times_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                0,
+                0,
+                wasip1::FSTFLAGS_MTIM | wasip1::FSTFLAGS_MTIM_NOW
+            ),
+            wasip1::ERRNO_INVAL
+        );
+    }
+
+    type Entries<'a> = Option<&'a [(&'a str, usize)]>;
+
+    /// A snapshot of a heap LFS of the root and the inodes after it,
+    /// each a parent and the entries of a directory, `None` for a file `x`.
+    fn heap_snapshot(root: Entries, inodes: &[Option<(usize, Entries)>]) -> Vec<u8> {
+        fn inode(out: &mut SnapshotWriter, parent: usize, entries: Entries) {
+            out.usize(parent);
+            for _ in 0..3 {
+                out.u64(0);
+            }
+            match entries {
+                Some(entries) => {
+                    out.u8(1);
+                    out.usize(entries.len());
+                    for (name, inode) in entries {
+                        out.str(name);
+                        out.usize(*inode);
+                    }
+                }
+                None => {
+                    out.u8(0);
+                    out.bytes(b"x");
+                }
+            }
+        }
+
+        let mut out = SnapshotWriter::new();
+        inode(&mut out, 0, root);
+        out.usize(inodes.len());
+        for slot in inodes {
+            out.u8(slot.is_some() as u8);
+            if let Some((parent, entries)) = slot {
+                inode(&mut out, *parent, *entries);
+            }
+        }
+        out.into_bytes()
+    }
+
+    #[test]
+    fn test_heap_restore_tree() {
+        let mut vfs = Wasip1ConstVFS::<_, 8>::new(VFSHeapLFS::<DefaultStdIO>::new("."));
+        let mut restore = |root, inodes| vfs.restore(&heap_snapshot(root, inodes));
+
+        // `a` with a hole after it
+        assert_eq!(restore(Some(&[("a", 1)]), &[Some((0, None)), None]), Ok(()));
+
+        // directories whose parents point at each other, out of the tree
+        assert_eq!(
+            restore(Some(&[]), &[Some((2, Some(&[]))), Some((1, Some(&[])))]),
+            Err(wasip1::ERRNO_INVAL)
+        );
+        // an entry whose parent is another directory
+        assert_eq!(
+            restore(
+                Some(&[("a", 1), ("b", 2)]),
+                &[Some((0, Some(&[]))), Some((1, None))]
+            ),
+            Err(wasip1::ERRNO_INVAL)
+        );
+        // a directory listed twice
+        assert_eq!(
+            restore(Some(&[("a", 1), ("b", 1)]), &[Some((0, Some(&[])))]),
+            Err(wasip1::ERRNO_INVAL)
+        );
+        // the root as an entry, an entry in a hole, and a root which is a file
+        assert_eq!(restore(Some(&[("a", 0)]), &[]), Err(wasip1::ERRNO_INVAL));
+        assert_eq!(
+            restore(Some(&[("a", 1)]), &[None]),
+            Err(wasip1::ERRNO_INVAL)
+        );
+        assert_eq!(restore(None, &[]), Err(wasip1::ERRNO_INVAL));
+
+        // the refused ones left the first one
+        assert_eq!(vfs.lfs().get_file("a"), Some(&b"x"[..]));
+        let fd = MockWasm::open(&mut vfs, 3, "b", wasip1::OFLAGS_CREAT).unwrap();
+        let mut filestat = core::mem::MaybeUninit::<wasip1::Filestat>::uninit();
+        assert_eq!(
+            Wasip1FileSystem::fd_filestat_get_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                filestat.as_mut_ptr()
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(unsafe { filestat.assume_init() }.ino, 2);
+    }
+}
@@ -0,0 +1,64 @@
+use crate::__private::wasip1;
+use crate::__private::wasip1::{Dircookie, Fd, Filesize, Size, Timestamp};
+
+/// The real file system of the host,
+/// called by [`VFSHostLFS`](super::lfs::VFSHostLFS)
+/// with paths already checked not to leave its directory.
+///
+/// [`Wasip1Transporter`](crate::file::Wasip1Transporter) calls the host WASI
+/// without being virtualized again.
+pub trait HostFS {
+    #[allow(clippy::too_many_arguments)]
+    fn path_open(
+        fd: Fd,
+        dir_flags: wasip1::Lookupflags,
+        path: &str,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        fs_rights_inheriting: wasip1::Rights,
+        fd_flags: wasip1::Fdflags,
+    ) -> Result<Fd, wasip1::Errno>;
+
+    fn fd_close(fd: Fd) -> Result<(), wasip1::Errno>;
+
+    fn fd_pread(fd: Fd, buf: &mut [u8], offset: Filesize) -> Result<Size, wasip1::Errno>;
+
+    fn fd_pwrite(fd: Fd, data: &[u8], offset: Filesize) -> Result<Size, wasip1::Errno>;
+
+    /// Fills the buffer with as many entries as fit, like `fd_readdir`.
+    fn fd_readdir(fd: Fd, buf: &mut [u8], cookie: Dircookie) -> Result<Size, wasip1::Errno>;
+
+    fn path_filestat_get(
+        fd: Fd,
+        flags: wasip1::Lookupflags,
+        path: &str,
+    ) -> Result<wasip1::Filestat, wasip1::Errno>;
+
+    /// `fst_flags` is passed as is,
+    /// so `FSTFLAGS_ATIM_NOW` and `FSTFLAGS_MTIM_NOW` use the clock of the host.
+    fn path_filestat_set_times(
+        fd: Fd,
+        flags: wasip1::Lookupflags,
+        path: &str,
+        atim: Timestamp,
+        mtim: Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno>;
+
+    fn path_readlink(fd: Fd, path: &str, buf: &mut [u8]) -> Result<Size, wasip1::Errno>;
+
+    fn path_create_directory(fd: Fd, path: &str) -> Result<(), wasip1::Errno>;
+
+    fn path_remove_directory(fd: Fd, path: &str) -> Result<(), wasip1::Errno>;
+
+    fn path_unlink_file(fd: Fd, path: &str) -> Result<(), wasip1::Errno>;
+
+    fn path_rename(
+        old_fd: Fd,
+        old_path: &str,
+        new_fd: Fd,
+        new_path: &str,
+    ) -> Result<(), wasip1::Errno>;
+
+    fn path_symlink(old_path: &str, fd: Fd, new_path: &str) -> Result<(), wasip1::Errno>;
+}
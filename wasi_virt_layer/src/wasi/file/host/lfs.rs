@@ -0,0 +1,378 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet, btree_map::Entry},
+    string::String,
+    vec::Vec,
+};
+
+use crate::__private::wasip1;
+use crate::{
+    memory::WasmAccess,
+    transporter::Wasip1Transporter,
+    wasi::file::{
+        FilestatWithoutDevice, SYMLINK_MAX_DEPTH,
+        host::fs::HostFS,
+        snapshot::{SnapshotReader, SnapshotWriter, Wasip1Snapshot},
+        stdio::StdIO,
+    },
+};
+
+/// Pass-through file system
+/// which passes through to a directory pre-opened by the host,
+/// e.g. the guest `/work` served by the host preopen fd 3.
+///
+/// Paths are resolved here component by component,
+/// so `..` above the directory and symbolic links pointing out of it
+/// (or to an absolute path) are refused with `ERRNO_PERM`,
+/// and the host only sees paths without any symbolic link in them.
+/// The path of an opened inode is resolved again on every access,
+/// as a directory on the way may have been replaced by a link since.
+///
+/// The host file is opened for each read or write.
+/// An inode removed while opened fails with `ERRNO_NOENT`
+/// and its number is reused after the last close.
+pub struct VFSHostLFS<StdIo: StdIO + 'static, Host: HostFS = Wasip1Transporter> {
+    /// guest path of the directory
+    name: &'static str,
+    /// pre-opened directory of the host
+    host_fd: wasip1::Fd,
+    /// directory under `host_fd` served as the root, `""` for `host_fd` itself
+    host_dir: &'static str,
+    /// path of inode `n` relative to the root is stored at `paths[n - 1]`,
+    /// root is inode 0
+    paths: Vec<Option<String>>,
+    /// path -> inode
+    inodes: BTreeMap<String, usize>,
+    free_inodes: Vec<usize>,
+    /// number of descriptors of each opened inode
+    opened: BTreeMap<usize, usize>,
+    /// inodes removed while opened, freed on their last close
+    unlinked: BTreeSet<usize>,
+    __marker: core::marker::PhantomData<(StdIo, Host)>,
+}
+
+impl<StdIo: StdIO + 'static, Host: HostFS> VFSHostLFS<StdIo, Host> {
+    pub const fn new(name: &'static str, host_fd: wasip1::Fd) -> Self {
+        Self::with_dir(name, host_fd, "")
+    }
+
+    /// Serves `host_dir` under the pre-opened `host_fd` instead of `host_fd` itself.
+    pub const fn with_dir(name: &'static str, host_fd: wasip1::Fd, host_dir: &'static str) -> Self {
+        Self {
+            name,
+            host_fd,
+            host_dir,
+            paths: Vec::new(),
+            inodes: BTreeMap::new(),
+            free_inodes: Vec::new(),
+            opened: BTreeMap::new(),
+            unlinked: BTreeSet::new(),
+            __marker: core::marker::PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    pub fn host_fd(&self) -> wasip1::Fd {
+        self.host_fd
+    }
+
+    /// Path of the inode relative to the root,
+    /// as it was resolved when the inode was opened.
+    pub fn path(&self, inode: usize) -> Result<&str, wasip1::Errno> {
+        if self.unlinked.contains(&inode) {
+            return Err(wasip1::ERRNO_NOENT);
+        }
+
+        match inode {
+            0 => Ok(""),
+            inode => self
+                .paths
+                .get(inode - 1)
+                .and_then(Option::as_deref)
+                .ok_or(wasip1::ERRNO_BADF),
+        }
+    }
+
+    /// Path under `host_fd` of the inode,
+    /// resolved again so no symbolic link is on the way.
+    pub fn checked_host_path(&self, inode: usize) -> Result<String, wasip1::Errno> {
+        let path = self.resolve(0, self.path(inode)?, false)?;
+        Ok(self.host_path(&path))
+    }
+
+    /// Inode of the path relative to the root,
+    /// which is numbered on the first time.
+    pub fn inode(&mut self, path: String) -> usize {
+        if path.is_empty() {
+            return 0;
+        }
+
+        if let Some(inode) = self.inodes.get(&path) {
+            return *inode;
+        }
+
+        let inode = match self.free_inodes.pop() {
+            Some(inode) => {
+                self.paths[inode - 1] = Some(path.clone());
+                inode
+            }
+            None => {
+                self.paths.push(Some(path.clone()));
+                self.paths.len()
+            }
+        };
+        self.inodes.insert(path, inode);
+
+        inode
+    }
+
+    /// Counts a new descriptor of the inode.
+    pub fn opened(&mut self, inode: usize) {
+        *self.opened.entry(inode).or_default() += 1;
+    }
+
+    /// Counts a closed descriptor of the inode,
+    /// freeing it if it was removed and this was the last one.
+    pub fn closed(&mut self, inode: usize) {
+        if let Entry::Occupied(mut count) = self.opened.entry(inode) {
+            *count.get_mut() -= 1;
+            if *count.get() == 0 {
+                count.remove();
+                if self.unlinked.remove(&inode) {
+                    self.free(inode);
+                }
+            }
+        }
+    }
+
+    fn free(&mut self, inode: usize) {
+        if let Some(slot) = inode.checked_sub(1).and_then(|i| self.paths.get_mut(i))
+            && slot.take().is_some()
+        {
+            self.free_inodes.push(inode);
+        }
+    }
+
+    /// Drops the inodes of the path and of everything under it after a removal.
+    /// The opened ones are freed on their last close.
+    pub fn removed(&mut self, path: &str) {
+        let under = |other: &str| {
+            other
+                .strip_prefix(path)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        };
+
+        let removed = self
+            .inodes
+            .iter()
+            .filter(|(other, _)| under(other))
+            .map(|(_, inode)| *inode)
+            .collect::<Vec<_>>();
+        self.inodes.retain(|other, _| !under(other));
+
+        for inode in removed {
+            if self.opened.contains_key(&inode) {
+                self.unlinked.insert(inode);
+            } else {
+                self.free(inode);
+            }
+        }
+    }
+
+    /// Path under `host_fd` of the path relative to the root.
+    pub fn host_path(&self, path: &str) -> String {
+        match (self.host_dir, path) {
+            ("", "") => String::from("."),
+            ("", path) => String::from(path),
+            (dir, "") => String::from(dir),
+            (dir, path) => alloc::format!("{dir}/{path}"),
+        }
+    }
+
+    /// Moves the inodes under the old path to the new path after a rename,
+    /// so the opened ones keep pointing to the same entry.
+    pub fn renamed(&mut self, old: &str, new: &str) {
+        for (inode, path) in self.paths.iter_mut().enumerate() {
+            let Some(path) = path else {
+                continue;
+            };
+            if self.unlinked.contains(&(inode + 1)) {
+                continue;
+            }
+
+            let rest = match path.strip_prefix(old) {
+                Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+                _ => continue,
+            };
+
+            let moved = alloc::format!("{new}{rest}");
+            self.inodes.remove(path.as_str());
+            self.inodes.insert(moved.clone(), inode + 1);
+            *path = moved;
+        }
+    }
+
+    pub fn filestat(
+        &self,
+        path: &str,
+        flags: wasip1::Lookupflags,
+    ) -> Result<wasip1::Filestat, wasip1::Errno> {
+        Host::path_filestat_get(self.host_fd, flags, &self.host_path(path))
+    }
+
+    /// Resolves the path from the directory
+    /// to a path relative to the root without symbolic links,
+    /// except the last component when `follow` is false.
+    /// The last component does not have to exist.
+    pub fn resolve(&self, dir: usize, path: &str, follow: bool) -> Result<String, wasip1::Errno> {
+        let mut resolved = match path.starts_with('/') {
+            true => Vec::new(),
+            false => self
+                .path(dir)?
+                .split('/')
+                .filter(|part| !part.is_empty())
+                .map(String::from)
+                .collect(),
+        };
+
+        // the components left, in reverse order
+        let mut rest = path.split('/').rev().map(String::from).collect::<Vec<_>>();
+        let mut depth = 0;
+
+        while let Some(part) = rest.pop() {
+            match part.as_str() {
+                "" | "." => continue,
+                ".." => {
+                    resolved.pop().ok_or(wasip1::ERRNO_PERM)?;
+                    continue;
+                }
+                _ => resolved.push(part),
+            }
+
+            let last = rest.iter().all(|part| part.is_empty() || part == ".");
+            if last && !follow {
+                break;
+            }
+
+            let current = resolved.join("/");
+            let filestat = match self.filestat(&current, 0) {
+                Ok(filestat) => filestat,
+                Err(wasip1::ERRNO_NOENT) if last => break,
+                Err(e) => return Err(e),
+            };
+
+            if filestat.filetype != wasip1::FILETYPE_SYMBOLIC_LINK {
+                continue;
+            }
+
+            depth += 1;
+            if depth > SYMLINK_MAX_DEPTH {
+                return Err(wasip1::ERRNO_LOOP);
+            }
+
+            // some hosts report no size for symbolic links
+            let mut target = alloc::vec![0u8; core::cmp::max(filestat.size as usize, 256)];
+            let len = Host::path_readlink(self.host_fd, &self.host_path(&current), &mut target)?;
+            target.truncate(len);
+            let target = String::from_utf8(target).map_err(|_| wasip1::ERRNO_ILSEQ)?;
+
+            if target.starts_with('/') {
+                return Err(wasip1::ERRNO_PERM);
+            }
+
+            resolved.pop();
+            rest.extend(target.split('/').rev().map(String::from));
+        }
+
+        Ok(resolved.join("/"))
+    }
+
+    /// [`Self::resolve`] with the path in the memory of the guest.
+    pub fn resolve_raw<Wasm: WasmAccess>(
+        &self,
+        dir: usize,
+        path_ptr: *const u8,
+        path_len: usize,
+        follow: bool,
+    ) -> Result<String, wasip1::Errno> {
+        let path = Wasm::get_array(path_ptr, path_len);
+        let path = core::str::from_utf8(&path).map_err(|_| wasip1::ERRNO_ILSEQ)?;
+
+        self.resolve(dir, path, follow)
+    }
+
+    /// Opens the host file of the inode for the call.
+    /// A symbolic link in place of the file itself is not followed.
+    pub fn with_host_fd<R>(
+        &self,
+        inode: usize,
+        o_flags: wasip1::Oflags,
+        rights: wasip1::Rights,
+        f: impl FnOnce(wasip1::Fd) -> Result<R, wasip1::Errno>,
+    ) -> Result<R, wasip1::Errno> {
+        let path = self.checked_host_path(inode)?;
+        let fd = Host::path_open(self.host_fd, 0, &path, o_flags, rights, 0, 0)?;
+
+        let result = f(fd);
+        Host::fd_close(fd)?;
+        result
+    }
+
+    pub fn filestat_from_inode(
+        &self,
+        inode: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let path = self.checked_host_path(inode)?;
+        let filestat = Host::path_filestat_get(self.host_fd, 0, &path)?;
+
+        Ok(Self::filestat_without_device(filestat))
+    }
+
+    pub fn filestat_without_device(filestat: wasip1::Filestat) -> FilestatWithoutDevice {
+        FilestatWithoutDevice {
+            ino: filestat.ino,
+            filetype: filestat.filetype,
+            nlink: filestat.nlink,
+            size: filestat.size,
+            atim: filestat.atim,
+            mtim: filestat.mtim,
+            ctim: filestat.ctim,
+        }
+    }
+
+    /// Checks that the target of a new symbolic link at `path`
+    /// stays in the directory, so it is not absolute
+    /// and its `..` do not climb above the root.
+    pub fn check_link_target(path: &str, target: &str) -> Result<(), wasip1::Errno> {
+        if target.starts_with('/') {
+            return Err(wasip1::ERRNO_PERM);
+        }
+
+        // the link is resolved from the directory it is in
+        let mut depth = path.split('/').filter(|part| !part.is_empty()).count();
+        depth = depth.saturating_sub(1);
+
+        for part in target.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => depth = depth.checked_sub(1).ok_or(wasip1::ERRNO_PERM)?,
+                _ => depth += 1,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Nothing to save, the files are kept by the host.
+impl<StdIo: StdIO + 'static, Host: HostFS> Wasip1Snapshot for VFSHostLFS<StdIo, Host> {
+    fn snapshot_to(&self, _: &mut SnapshotWriter) {}
+
+    fn restore_from(&mut self, _: &mut SnapshotReader<'_>) -> Result<(), wasip1::Errno> {
+        Ok(())
+    }
+}
@@ -0,0 +1,1040 @@
+use alloc::vec;
+
+use crate::__private::wasip1;
+use crate::__private::wasip1::Dircookie;
+
+use crate::{
+    memory::WasmAccess,
+    wasi::file::{
+        FilestatWithoutDevice, Wasip1LFS,
+        dirent::{DIRENT_SIZE, DirentReader, DirentWriter},
+        host::{fs::HostFS, lfs::VFSHostLFS},
+        stdio::{self, StdIO},
+    },
+};
+
+impl<StdIo: StdIO + 'static, Host: HostFS> Wasip1LFS for VFSHostLFS<StdIo, Host> {
+    type Inode = usize;
+    const PRE_OPEN: &'static [Self::Inode] = &[0];
+
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        data: *const u8,
+        data_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.with_host_fd(inode, 0, wasip1::RIGHTS_FD_WRITE, |fd| {
+            #[cfg(not(feature = "multi_memory"))]
+            {
+                let data = unsafe {
+                    core::slice::from_raw_parts(Wasm::memory_director(data, data_len), data_len)
+                };
+                Host::fd_pwrite(fd, data, offset as _)
+            }
+            #[cfg(feature = "multi_memory")]
+            {
+                Host::fd_pwrite(fd, &Wasm::get_array(data, data_len), offset as _)
+            }
+        })
+    }
+
+    fn fd_write_stdout_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::write_stdout_raw::<StdIo, Wasm>(data, data_len)
+    }
+
+    fn fd_write_stderr_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::write_stderr_raw::<StdIo, Wasm>(data, data_len)
+    }
+
+    fn is_dir(&self, inode: Self::Inode) -> bool {
+        self.filestat_from_inode(inode)
+            .is_ok_and(|filestat| filestat.filetype == wasip1::FILETYPE_DIRECTORY)
+    }
+
+    fn fd_readdir_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        cookie: Dircookie,
+    ) -> Result<(wasip1::Size, Dircookie), wasip1::Errno> {
+        let mut writer = DirentWriter::<Wasm>::new(buf, buf_len, cookie);
+
+        // the cookies of the host are passed through,
+        // it may give fewer entries than fit, so it is asked until it gives none
+        self.with_host_fd(
+            inode,
+            wasip1::OFLAGS_DIRECTORY,
+            wasip1::RIGHTS_FD_READDIR,
+            |fd| {
+                let mut entries = vec![0u8; core::cmp::max(buf_len, DIRENT_SIZE + 256)];
+                let mut cookie = cookie;
+
+                loop {
+                    let size = Host::fd_readdir(fd, &mut entries, cookie)?;
+                    if size == 0 {
+                        return Ok(());
+                    }
+
+                    let mut reader = DirentReader::new(&entries[..size]);
+                    let mut read = 0;
+                    for (d_next, d_ino, d_type, name) in reader.by_ref() {
+                        if !writer.push(d_next, d_ino, d_type, name) {
+                            return Ok(());
+                        }
+                        cookie = d_next;
+                        read += 1;
+                    }
+
+                    if read == 0 {
+                        // the name did not fit, read the entry again with a larger buffer
+                        match reader.needed() {
+                            Some(len) if len > entries.len() => entries.resize(len, 0),
+                            _ => return Err(wasip1::ERRNO_IO),
+                        }
+                    }
+                }
+            },
+        )?;
+
+        Ok(writer.finish())
+    }
+
+    fn path_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let follow =
+            flags & wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW == wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW;
+        let path = self.resolve_raw::<Wasm>(inode, path_ptr, path_len, follow)?;
+
+        Ok(Self::filestat_without_device(self.filestat(&path, 0)?))
+    }
+
+    fn fd_prestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<wasip1::Prestat, wasip1::Errno> {
+        if !Self::PRE_OPEN.contains(&inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        Ok(wasip1::Prestat {
+            tag: 0, // prestat is enum but variant is only 0
+            // union type but we only have one variant
+            u: wasip1::PrestatU {
+                dir: wasip1::PrestatDir {
+                    pr_name_len: self.name().len() as _,
+                },
+            },
+        })
+    }
+
+    fn fd_prestat_dir_name_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        dir_path_ptr: *mut u8,
+        dir_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        if !Self::PRE_OPEN.contains(&inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        let name = self.name();
+
+        Wasm::memcpy(
+            dir_path_ptr,
+            &name.as_bytes()[..core::cmp::min(name.len(), dir_path_len)],
+        );
+
+        Ok(())
+    }
+
+    fn fd_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        self.filestat_from_inode(inode)
+    }
+
+    fn fd_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        atim: wasip1::Timestamp,
+        mtim: wasip1::Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        // the path has no symbolic link left to follow
+        let path = self.checked_host_path(inode)?;
+
+        Host::path_filestat_set_times(self.host_fd(), 0, &path, atim, mtim, fst_flags)
+    }
+
+    fn fd_pread_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.with_host_fd(inode, 0, wasip1::RIGHTS_FD_READ, |fd| {
+            #[cfg(not(feature = "multi_memory"))]
+            {
+                let buf = unsafe {
+                    core::slice::from_raw_parts_mut(
+                        Wasm::memory_director_mut(buf, buf_len),
+                        buf_len,
+                    )
+                };
+                Host::fd_pread(fd, buf, offset as _)
+            }
+            #[cfg(feature = "multi_memory")]
+            {
+                use crate::__private::utils;
+
+                let (buf_vec, read) =
+                    unsafe { utils::alloc_buff(buf_len, |b| Host::fd_pread(fd, b, offset as _)) };
+                let read = read?;
+                Wasm::memcpy(buf, &buf_vec[..read]);
+                Ok(read)
+            }
+        })
+    }
+
+    fn fd_read_stdin_raw<Wasm: WasmAccess>(
+        &mut self,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::read_stdin_raw::<StdIo, Wasm>(buf, buf_len)
+    }
+
+    fn path_open_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        dir_flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        fs_rights_inheriting: wasip1::Rights,
+        fd_flags: wasip1::Fdflags,
+    ) -> Result<Self::Inode, wasip1::Errno> {
+        let follow =
+            dir_flags & wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW == wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW;
+        let path = self.resolve_raw::<Wasm>(dir_inode, path_ptr, path_len, follow)?;
+
+        // creates, truncates or checks the file on the host,
+        // the descriptor itself is not kept
+        let fd = Host::path_open(
+            self.host_fd(),
+            0,
+            &self.host_path(&path),
+            o_flags,
+            fs_rights_base,
+            fs_rights_inheriting,
+            fd_flags,
+        )?;
+        Host::fd_close(fd)?;
+
+        let inode = self.inode(path);
+        self.opened(inode);
+
+        Ok(inode)
+    }
+
+    fn fd_close_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) {
+        self.closed(inode);
+    }
+
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let path = self.resolve_raw::<Wasm>(dir_inode, path_ptr, path_len, false)?;
+
+        Host::path_create_directory(self.host_fd(), &self.host_path(&path))
+    }
+
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let path = self.resolve_raw::<Wasm>(dir_inode, path_ptr, path_len, false)?;
+
+        // the root itself is not removed
+        if path.is_empty() {
+            return Err(wasip1::ERRNO_PERM);
+        }
+
+        Host::path_remove_directory(self.host_fd(), &self.host_path(&path))?;
+        self.removed(&path);
+
+        Ok(())
+    }
+
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let path = self.resolve_raw::<Wasm>(dir_inode, path_ptr, path_len, false)?;
+
+        Host::path_unlink_file(self.host_fd(), &self.host_path(&path))?;
+        self.removed(&path);
+
+        Ok(())
+    }
+
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_dir_inode: Self::Inode,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_dir_inode: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let old_path =
+            self.resolve_raw::<Wasm>(old_dir_inode, old_path_ptr, old_path_len, false)?;
+        let new_path =
+            self.resolve_raw::<Wasm>(new_dir_inode, new_path_ptr, new_path_len, false)?;
+
+        if old_path.is_empty() || new_path.is_empty() {
+            return Err(wasip1::ERRNO_PERM);
+        }
+
+        Host::path_rename(
+            self.host_fd(),
+            &self.host_path(&old_path),
+            self.host_fd(),
+            &self.host_path(&new_path),
+        )?;
+
+        // the replaced entry is gone
+        if old_path != new_path {
+            self.removed(&new_path);
+            self.renamed(&old_path, &new_path);
+        }
+
+        Ok(())
+    }
+
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let path = self.resolve_raw::<Wasm>(dir_inode, path_ptr, path_len, false)?;
+        let path = self.host_path(&path);
+
+        #[cfg(not(feature = "multi_memory"))]
+        {
+            let buf = unsafe {
+                core::slice::from_raw_parts_mut(Wasm::memory_director_mut(buf, buf_len), buf_len)
+            };
+            Host::path_readlink(self.host_fd(), &path, buf)
+        }
+        #[cfg(feature = "multi_memory")]
+        {
+            use crate::__private::utils;
+
+            let (buf_vec, read) = unsafe {
+                utils::alloc_buff(buf_len, |b| Host::path_readlink(self.host_fd(), &path, b))
+            };
+            let read = read?;
+            Wasm::memcpy(buf, &buf_vec[..read]);
+            Ok(read)
+        }
+    }
+
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        dir_inode: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let target = Wasm::get_array(old_path_ptr, old_path_len);
+        let target = core::str::from_utf8(&target).map_err(|_| wasip1::ERRNO_ILSEQ)?;
+        let path = self.resolve_raw::<Wasm>(dir_inode, new_path_ptr, new_path_len, false)?;
+
+        // links made by the guest never point out,
+        // the ones made by the host are checked when followed
+        Self::check_link_target(&path, target)?;
+        Host::path_symlink(target, self.host_fd(), &self.host_path(&path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::String, vec::Vec};
+    use std::{
+        fs,
+        os::unix::fs::{FileExt, MetadataExt, symlink},
+        path::PathBuf,
+        sync::Mutex,
+    };
+
+    use super::*;
+    use crate::{
+        memory::{HostMemory, WasmAccess},
+        mock::MockWasm,
+        wasi::file::{Wasip1FileSystem, constant::vfs::Wasip1ConstVFS, stdio::DefaultStdIO},
+    };
+
+    /// opened host paths, the index is the fd
+    static FDS: Mutex<Vec<Option<PathBuf>>> = Mutex::new(Vec::new());
+
+    fn host(fd: wasip1::Fd, path: &str) -> PathBuf {
+        let base = FDS.lock().unwrap()[fd as usize].clone().unwrap();
+        match path {
+            "" => base,
+            path => base.join(path),
+        }
+    }
+
+    fn errno(e: std::io::Error) -> wasip1::Errno {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => wasip1::ERRNO_NOENT,
+            std::io::ErrorKind::AlreadyExists => wasip1::ERRNO_EXIST,
+            _ => wasip1::ERRNO_IO,
+        }
+    }
+
+    /// [`HostFS`] over `std::fs`, for the tests.
+    struct StdHost;
+
+    impl HostFS for StdHost {
+        fn path_open(
+            fd: wasip1::Fd,
+            _: wasip1::Lookupflags,
+            path: &str,
+            o_flags: wasip1::Oflags,
+            _: wasip1::Rights,
+            _: wasip1::Rights,
+            _: wasip1::Fdflags,
+        ) -> Result<wasip1::Fd, wasip1::Errno> {
+            let path = host(fd, path);
+            if o_flags & wasip1::OFLAGS_CREAT == wasip1::OFLAGS_CREAT && !path.exists() {
+                fs::write(&path, b"").map_err(errno)?;
+            }
+            fs::symlink_metadata(&path).map_err(errno)?;
+
+            let mut fds = FDS.lock().unwrap();
+            fds.push(Some(path));
+            Ok(fds.len() as wasip1::Fd - 1)
+        }
+
+        fn fd_close(fd: wasip1::Fd) -> Result<(), wasip1::Errno> {
+            FDS.lock().unwrap()[fd as usize] = None;
+            Ok(())
+        }
+
+        fn fd_pread(
+            fd: wasip1::Fd,
+            buf: &mut [u8],
+            offset: wasip1::Filesize,
+        ) -> Result<wasip1::Size, wasip1::Errno> {
+            let file = fs::File::open(host(fd, "")).map_err(errno)?;
+            file.read_at(buf, offset).map_err(errno)
+        }
+
+        fn fd_pwrite(
+            fd: wasip1::Fd,
+            data: &[u8],
+            offset: wasip1::Filesize,
+        ) -> Result<wasip1::Size, wasip1::Errno> {
+            let file = fs::OpenOptions::new()
+                .write(true)
+                .open(host(fd, ""))
+                .map_err(errno)?;
+            file.write_at(data, offset).map_err(errno)
+        }
+
+        fn fd_readdir(
+            fd: wasip1::Fd,
+            buf: &mut [u8],
+            cookie: wasip1::Dircookie,
+        ) -> Result<wasip1::Size, wasip1::Errno> {
+            let mut names = fs::read_dir(host(fd, ""))
+                .map_err(errno)?
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect::<Vec<_>>();
+            names.sort();
+
+            let Some(name) = names.get(cookie as usize) else {
+                return Ok(0);
+            };
+            let entry = wasip1::Dirent {
+                d_next: cookie + 1,
+                d_ino: 0,
+                d_namlen: name.len() as _,
+                d_type: wasip1::FILETYPE_UNKNOWN,
+            };
+            let entry = unsafe {
+                core::slice::from_raw_parts(&entry as *const _ as *const u8, DIRENT_SIZE)
+            };
+
+            let bytes = [entry, name.as_bytes()].concat();
+            let len = core::cmp::min(bytes.len(), buf.len());
+            buf[..len].copy_from_slice(&bytes[..len]);
+            Ok(len)
+        }
+
+        fn path_filestat_get(
+            fd: wasip1::Fd,
+            _: wasip1::Lookupflags,
+            path: &str,
+        ) -> Result<wasip1::Filestat, wasip1::Errno> {
+            let metadata = fs::symlink_metadata(host(fd, path)).map_err(errno)?;
+            Ok(wasip1::Filestat {
+                dev: 0,
+                ino: metadata.ino(),
+                filetype: match metadata.file_type() {
+                    ty if ty.is_symlink() => wasip1::FILETYPE_SYMBOLIC_LINK,
+                    ty if ty.is_dir() => wasip1::FILETYPE_DIRECTORY,
+                    _ => wasip1::FILETYPE_REGULAR_FILE,
+                },
+                nlink: metadata.nlink(),
+                size: metadata.len(),
+                atim: metadata.atime() as u64 * 1_000_000_000 + metadata.atime_nsec() as u64,
+                mtim: metadata.mtime() as u64 * 1_000_000_000 + metadata.mtime_nsec() as u64,
+                ctim: metadata.ctime() as u64 * 1_000_000_000 + metadata.ctime_nsec() as u64,
+            })
+        }
+
+        fn path_filestat_set_times(
+            fd: wasip1::Fd,
+            _: wasip1::Lookupflags,
+            path: &str,
+            atim: wasip1::Timestamp,
+            mtim: wasip1::Timestamp,
+            fst_flags: wasip1::Fstflags,
+        ) -> Result<(), wasip1::Errno> {
+            let time = |time| std::time::UNIX_EPOCH + std::time::Duration::from_nanos(time);
+            let mut times = fs::FileTimes::new();
+            if fst_flags & wasip1::FSTFLAGS_ATIM != 0 {
+                times = times.set_accessed(time(atim));
+            }
+            if fst_flags & wasip1::FSTFLAGS_MTIM != 0 {
+                times = times.set_modified(time(mtim));
+            }
+
+            fs::File::open(host(fd, path))
+                .and_then(|file| file.set_times(times))
+                .map_err(errno)
+        }
+
+        fn path_readlink(
+            fd: wasip1::Fd,
+            path: &str,
+            buf: &mut [u8],
+        ) -> Result<wasip1::Size, wasip1::Errno> {
+            let target = fs::read_link(host(fd, path)).map_err(|_| wasip1::ERRNO_INVAL)?;
+            let target = target.to_str().unwrap().as_bytes();
+            let len = core::cmp::min(target.len(), buf.len());
+            buf[..len].copy_from_slice(&target[..len]);
+            Ok(len)
+        }
+
+        fn path_create_directory(fd: wasip1::Fd, path: &str) -> Result<(), wasip1::Errno> {
+            fs::create_dir(host(fd, path)).map_err(errno)
+        }
+
+        fn path_remove_directory(fd: wasip1::Fd, path: &str) -> Result<(), wasip1::Errno> {
+            fs::remove_dir(host(fd, path)).map_err(errno)
+        }
+
+        fn path_unlink_file(fd: wasip1::Fd, path: &str) -> Result<(), wasip1::Errno> {
+            fs::remove_file(host(fd, path)).map_err(errno)
+        }
+
+        fn path_rename(
+            old_fd: wasip1::Fd,
+            old_path: &str,
+            new_fd: wasip1::Fd,
+            new_path: &str,
+        ) -> Result<(), wasip1::Errno> {
+            fs::rename(host(old_fd, old_path), host(new_fd, new_path)).map_err(errno)
+        }
+
+        fn path_symlink(
+            old_path: &str,
+            fd: wasip1::Fd,
+            new_path: &str,
+        ) -> Result<(), wasip1::Errno> {
+            symlink(old_path, host(fd, new_path)).map_err(errno)
+        }
+    }
+
+    type Lfs = VFSHostLFS<DefaultStdIO, StdHost>;
+    type Vfs = Wasip1ConstVFS<Lfs, 8>;
+
+    /// `root/{sandbox/{hello.txt, dir/, up -> .., inner -> dir, escape -> ../secret.txt,
+    /// abs -> /etc}, secret.txt}`, pre-opened as the host fd
+    fn sandbox(name: &str) -> (PathBuf, wasip1::Fd) {
+        let root = std::env::temp_dir().join(format!(
+            "wasi_virt_layer-host-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("sandbox/dir")).unwrap();
+        fs::write(root.join("sandbox/hello.txt"), b"Hello, host!").unwrap();
+        fs::write(root.join("secret.txt"), b"secret").unwrap();
+        symlink("..", root.join("sandbox/up")).unwrap();
+        symlink("dir", root.join("sandbox/inner")).unwrap();
+        symlink("../secret.txt", root.join("sandbox/escape")).unwrap();
+        symlink("/etc", root.join("sandbox/abs")).unwrap();
+
+        let mut fds = FDS.lock().unwrap();
+        fds.push(Some(root.clone()));
+        (root, fds.len() as wasip1::Fd - 1)
+    }
+
+    #[test]
+    fn test_host_read_write() {
+        let (root, fd) = sandbox("read_write");
+        let mut vfs = Vfs::new(Lfs::with_dir("/work", fd, "sandbox"));
+
+        let rights = wasip1::RIGHTS_FD_READ | wasip1::RIGHTS_FD_WRITE | wasip1::RIGHTS_FD_SEEK;
+        let fd = MockWasm::open_with(
+            &mut vfs,
+            3,
+            wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+            "dir/../hello.txt",
+            0,
+            rights,
+            0,
+        )
+        .unwrap();
+
+        let mut buf = [0u8; 32];
+        let iovs = [wasip1::Ciovec {
+            buf: buf.as_mut_ptr(),
+            buf_len: buf.len(),
+        }];
+        let mut nread = 0;
+        assert_eq!(
+            Wasip1FileSystem::fd_pread_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                iovs.as_ptr() as *const _,
+                iovs.len(),
+                7,
+                &mut nread
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(&buf[..nread], b"host!");
+
+        // written through to the host
+        let fd = MockWasm::open_with(
+            &mut vfs,
+            3,
+            wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+            "inner/new.txt",
+            wasip1::OFLAGS_CREAT,
+            rights,
+            0,
+        )
+        .unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"new"), Ok(3));
+        assert_eq!(fs::read(root.join("sandbox/dir/new.txt")).unwrap(), b"new");
+
+        // the opened file follows a rename
+        let (old, new) = ("dir", "moved");
+        assert_eq!(
+            Wasip1FileSystem::path_rename_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                old.as_ptr(),
+                old.len(),
+                3,
+                new.as_ptr(),
+                new.len()
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        let mut filestat = core::mem::MaybeUninit::<wasip1::Filestat>::uninit();
+        assert_eq!(
+            Wasip1FileSystem::fd_filestat_get_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                filestat.as_mut_ptr()
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(unsafe { filestat.assume_init() }.size, 3);
+
+        // the times are set on the host file
+        let path = "moved/new.txt";
+        assert_eq!(
+            Wasip1FileSystem::path_filestat_set_times_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                0,
+                path.as_ptr(),
+                path.len(),
+                0,
+                1_500_000_000_000_000_000,
+                wasip1::FSTFLAGS_MTIM
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        let metadata = fs::metadata(root.join("sandbox/moved/new.txt")).unwrap();
+        assert_eq!(metadata.mtime(), 1_500_000_000);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_host_sandbox() {
+        let (root, fd) = sandbox("sandbox");
+        let mut vfs = Vfs::new(Lfs::with_dir("/work", fd, "sandbox"));
+
+        let rights = wasip1::RIGHTS_FD_READ;
+        assert_eq!(
+            MockWasm::open_with(
+                &mut vfs,
+                3,
+                wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+                "../secret.txt",
+                0,
+                rights,
+                0
+            ),
+            Err(wasip1::ERRNO_PERM)
+        );
+        assert_eq!(
+            MockWasm::open_with(
+                &mut vfs,
+                3,
+                wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+                "dir/../../secret.txt",
+                0,
+                rights,
+                0
+            ),
+            Err(wasip1::ERRNO_PERM)
+        );
+        assert_eq!(
+            MockWasm::open_with(
+                &mut vfs,
+                3,
+                wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+                "escape",
+                0,
+                rights,
+                0
+            ),
+            Err(wasip1::ERRNO_PERM)
+        );
+        assert_eq!(
+            MockWasm::open_with(
+                &mut vfs,
+                3,
+                wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+                "up/secret.txt",
+                0,
+                rights,
+                0
+            ),
+            Err(wasip1::ERRNO_PERM)
+        );
+        assert_eq!(
+            MockWasm::open_with(
+                &mut vfs,
+                3,
+                wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+                "abs/passwd",
+                0,
+                rights,
+                0
+            ),
+            Err(wasip1::ERRNO_PERM)
+        );
+
+        // inside the sandbox
+        assert!(
+            MockWasm::open_with(
+                &mut vfs,
+                3,
+                wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+                "inner/../hello.txt",
+                0,
+                rights,
+                0
+            )
+            .is_ok()
+        );
+
+        // the link itself can be read
+        let path = "escape";
+        let mut buf = [0u8; 32];
+        let mut len = 0;
+        assert_eq!(
+            Wasip1FileSystem::path_readlink_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                path.as_ptr(),
+                path.len(),
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut len
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(&buf[..len], b"../secret.txt");
+
+        // the names of the root
+        let dir = MockWasm::open_with(
+            &mut vfs,
+            3,
+            wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+            ".",
+            wasip1::OFLAGS_DIRECTORY,
+            wasip1::RIGHTS_FD_READDIR,
+            0,
+        )
+        .unwrap();
+        let mut buf = [0u8; 256];
+        let mut used = 0;
+        assert_eq!(
+            Wasip1FileSystem::fd_readdir_raw::<HostMemory>(
+                &mut vfs,
+                dir,
+                buf.as_mut_ptr(),
+                buf.len(),
+                0,
+                &mut used
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        let mut names = Vec::new();
+        let mut offset = 0;
+        while offset + DIRENT_SIZE <= used {
+            let entry = unsafe {
+                core::ptr::read_unaligned(buf[offset..].as_ptr() as *const wasip1::Dirent)
+            };
+            let start = offset + DIRENT_SIZE;
+            names.push(
+                String::from_utf8(buf[start..start + entry.d_namlen as usize].to_vec()).unwrap(),
+            );
+            offset = start + entry.d_namlen as usize;
+        }
+        assert_eq!(names, ["abs", "dir", "escape", "hello.txt", "inner", "up"]);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    fn pread(vfs: &mut Vfs, fd: wasip1::Fd) -> Result<Vec<u8>, wasip1::Errno> {
+        let (iovs, iovs_len) = MockWasm::alloc_iovs(&[32]);
+        let nread = MockWasm::alloc_value::<wasip1::Size>();
+        match Wasip1FileSystem::fd_pread_raw::<MockWasm>(vfs, fd, iovs, iovs_len, 0, nread) {
+            wasip1::ERRNO_SUCCESS => Ok(MockWasm::read_iovs(
+                iovs,
+                iovs_len,
+                MockWasm::load_le(nread),
+            )),
+            e => Err(e),
+        }
+    }
+
+    fn symlink_raw(vfs: &mut Vfs, target: &str, path: &str) -> wasip1::Errno {
+        Wasip1FileSystem::path_symlink_raw::<HostMemory>(
+            vfs,
+            target.as_ptr(),
+            target.len(),
+            3,
+            path.as_ptr(),
+            path.len(),
+        )
+    }
+
+    #[test]
+    fn test_host_swapped_dir() {
+        let (root, fd) = sandbox("swapped_dir");
+        let mut vfs = Vfs::new(Lfs::with_dir("/work", fd, "sandbox"));
+
+        fs::write(root.join("sandbox/dir/f.txt"), b"inside").unwrap();
+        fs::create_dir(root.join("outside")).unwrap();
+        fs::write(root.join("outside/f.txt"), b"secret").unwrap();
+
+        let rights = wasip1::RIGHTS_FD_READ
+            | wasip1::RIGHTS_FD_SEEK
+            | wasip1::RIGHTS_FD_FILESTAT_GET
+            | wasip1::RIGHTS_FD_FILESTAT_SET_TIMES;
+        let fd = MockWasm::open_with(
+            &mut vfs,
+            3,
+            wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+            "dir/f.txt",
+            0,
+            rights,
+            0,
+        )
+        .unwrap();
+        assert_eq!(pread(&mut vfs, fd).unwrap(), b"inside");
+
+        // the directory of the opened file becomes a link out of the sandbox
+        fs::rename(root.join("sandbox/dir"), root.join("sandbox/old")).unwrap();
+        symlink("../outside", root.join("sandbox/dir")).unwrap();
+
+        assert_eq!(pread(&mut vfs, fd), Err(wasip1::ERRNO_PERM));
+        let mut filestat = core::mem::MaybeUninit::<wasip1::Filestat>::uninit();
+        assert_eq!(
+            Wasip1FileSystem::fd_filestat_get_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                filestat.as_mut_ptr()
+            ),
+            wasip1::ERRNO_PERM
+        );
+        assert_eq!(
+            Wasip1FileSystem::fd_filestat_set_times_raw::<HostMemory>(
+                &mut vfs,
+                fd,
+                0,
+                0,
+                wasip1::FSTFLAGS_MTIM
+            ),
+            wasip1::ERRNO_PERM
+        );
+        assert_eq!(fs::read(root.join("outside/f.txt")).unwrap(), b"secret");
+
+        // links made by the guest stay inside
+        assert_eq!(symlink_raw(&mut vfs, "/etc", "a"), wasip1::ERRNO_PERM);
+        assert_eq!(
+            symlink_raw(&mut vfs, "../secret.txt", "a"),
+            wasip1::ERRNO_PERM
+        );
+        assert_eq!(
+            symlink_raw(&mut vfs, "../../secret.txt", "old/a"),
+            wasip1::ERRNO_PERM
+        );
+        assert_eq!(
+            symlink_raw(&mut vfs, "../hello.txt", "old/a"),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert!(
+            MockWasm::open_with(
+                &mut vfs,
+                3,
+                wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+                "old/a",
+                0,
+                rights,
+                0
+            )
+            .is_ok()
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_host_inode_reuse() {
+        let (root, fd) = sandbox("inode_reuse");
+        let mut vfs = Vfs::new(Lfs::with_dir("/work", fd, "sandbox"));
+
+        let rights = wasip1::RIGHTS_FD_READ | wasip1::RIGHTS_FD_SEEK;
+        let fd = MockWasm::open_with(
+            &mut vfs,
+            3,
+            wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+            "a.txt",
+            wasip1::OFLAGS_CREAT,
+            rights,
+            0,
+        )
+        .unwrap();
+        assert_eq!(vfs.lfs().path(1), Ok("a.txt"));
+
+        // the inode stays while opened, but its file is gone
+        let path = "a.txt";
+        assert_eq!(
+            Wasip1FileSystem::path_unlink_file_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                path.as_ptr(),
+                path.len()
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(pread(&mut vfs, fd), Err(wasip1::ERRNO_NOENT));
+
+        // a new file of the same name is another inode
+        let other = MockWasm::open_with(
+            &mut vfs,
+            3,
+            wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+            "a.txt",
+            wasip1::OFLAGS_CREAT,
+            rights,
+            0,
+        )
+        .unwrap();
+        assert_eq!(vfs.lfs().path(2), Ok("a.txt"));
+        assert_eq!(pread(&mut vfs, fd), Err(wasip1::ERRNO_NOENT));
+
+        assert_eq!(
+            Wasip1FileSystem::fd_close_raw::<HostMemory>(&mut vfs, fd),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(vfs.lfs().path(1), Err(wasip1::ERRNO_BADF));
+        assert_eq!(
+            Wasip1FileSystem::fd_close_raw::<HostMemory>(&mut vfs, other),
+            wasip1::ERRNO_SUCCESS
+        );
+
+        // stat numbers nothing, the freed number is taken again
+        let mut filestat = core::mem::MaybeUninit::<wasip1::Filestat>::uninit();
+        let path = "hello.txt";
+        assert_eq!(
+            Wasip1FileSystem::path_filestat_get_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                0,
+                path.as_ptr(),
+                path.len(),
+                filestat.as_mut_ptr()
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        MockWasm::open_with(
+            &mut vfs,
+            3,
+            wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+            "hello.txt",
+            0,
+            rights,
+            0,
+        )
+        .unwrap();
+        assert_eq!(vfs.lfs().path(1), Ok("hello.txt"));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,3 @@
+pub mod fs;
+pub mod lfs;
+pub mod lfs_impl;
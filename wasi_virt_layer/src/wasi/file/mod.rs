@@ -2,12 +2,39 @@
 // https://docs.rs/wasi-common/17.0.3/wasi_common/table/struct.Table.html
 
 use crate::memory::WasmAccess;
+#[cfg(feature = "alloc")]
+pub mod boxed;
+pub mod clock;
 pub mod constant;
+pub mod dev;
+pub mod dirent;
+pub mod fd_table;
+#[cfg(feature = "alloc")]
+pub mod heap;
+#[cfg(feature = "alloc")]
+pub mod host;
+pub mod mount;
+#[cfg(feature = "alloc")]
+pub mod overlay;
+#[cfg(feature = "alloc")]
+pub mod quota;
+#[cfg(feature = "alloc")]
+pub mod safe;
+#[cfg(feature = "alloc")]
+pub mod snapshot;
 pub mod stdio;
+#[cfg(feature = "alloc")]
+pub mod tar;
+#[cfg(feature = "alloc")]
+pub mod write_back;
 use crate::__private::wasip1;
 
 // no implementing dcache
 
+/// How many symbolic links are followed while resolving one path
+/// before giving up with `ERRNO_LOOP`.
+pub const SYMLINK_MAX_DEPTH: usize = 40;
+
 use crate::__private::wasip1::*;
 
 pub struct FilestatWithoutDevice {
@@ -27,16 +54,43 @@ pub struct FilestatWithoutDevice {
     pub ctim: Timestamp,
 }
 
+/// Moves `cursor` of a file with `filestat` as `fd_seek` does.
+/// Directories cannot be seeked.
+pub fn seek_cursor(
+    filestat: &FilestatWithoutDevice,
+    cursor: usize,
+    offset: Filedelta,
+    whence: Whence,
+) -> Result<usize, wasip1::Errno> {
+    if filestat.filetype == wasip1::FILETYPE_DIRECTORY {
+        return Err(wasip1::ERRNO_ISDIR);
+    }
+
+    let base = match whence {
+        wasip1::WHENCE_SET => 0,
+        wasip1::WHENCE_CUR => cursor as Filedelta,
+        wasip1::WHENCE_END => filestat.size as Filedelta,
+        _ => return Err(wasip1::ERRNO_INVAL),
+    };
+
+    let new_cursor = base.checked_add(offset).ok_or(wasip1::ERRNO_OVERFLOW)?;
+
+    usize::try_from(new_cursor).map_err(|_| wasip1::ERRNO_INVAL)
+}
+
 /// small posix like local file system
 pub trait Wasip1LFS {
     type Inode: 'static;
     const PRE_OPEN: &'static [Self::Inode];
 
-    fn fd_write_raw<Wasm: WasmAccess>(
+    /// Writes data to the file at the given offset.
+    /// The file is extended if the offset is past its end.
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
         &mut self,
         inode: Self::Inode,
         data: *const u8,
         data_len: usize,
+        offset: usize,
     ) -> Result<Size, wasip1::Errno>;
 
     fn fd_write_stdout_raw<Wasm: WasmAccess>(
@@ -53,6 +107,10 @@ pub trait Wasip1LFS {
 
     fn is_dir(&self, inode: Self::Inode) -> bool;
 
+    /// Lists the directory from the cookie with a [`dirent::DirentWriter`],
+    /// as many entries as fit with the last one cut.
+    /// Returns the bytes written and the cookie to list the rest from.
+    /// Fewer bytes than `buf_len` means the end of the directory.
     fn fd_readdir_raw<Wasm: WasmAccess>(
         &mut self,
         inode: Self::Inode,
@@ -86,6 +144,32 @@ pub trait Wasip1LFS {
         inode: Self::Inode,
     ) -> Result<FilestatWithoutDevice, wasip1::Errno>;
 
+    /// Called when a descriptor of the inode is closed,
+    /// for file systems which keep something per descriptor.
+    #[allow(unused_variables)]
+    fn fd_close_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) {}
+
+    /// Called by `fd_sync` and `fd_datasync`,
+    /// for file systems which keep the writes somewhere else.
+    #[allow(unused_variables)]
+    fn fd_sync_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) -> Result<(), wasip1::Errno> {
+        Ok(())
+    }
+
+    /// Sets the access and modification times of the inode,
+    /// as `clock::times_to_set` reads `fst_flags`.
+    /// A file system which cannot keep them returns `ERRNO_PERM`.
+    #[allow(unused_variables)]
+    fn fd_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        atim: Timestamp,
+        mtim: Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        Err(wasip1::ERRNO_PERM)
+    }
+
     fn fd_pread_raw<Wasm: WasmAccess>(
         &mut self,
         inode: Self::Inode,
@@ -94,6 +178,33 @@ pub trait Wasip1LFS {
         offset: usize,
     ) -> Result<Size, wasip1::Errno>;
 
+    /// Returns the cursor `fd_seek` moves a descriptor of the inode to,
+    /// `cursor` being where it is now.
+    /// The default seeks everything but directories,
+    /// counting `WHENCE_END` from the size [`Self::fd_filestat_get_raw`] reports.
+    /// A file system with unseekable files returns `ERRNO_SPIPE` for them.
+    fn fd_seek_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        cursor: usize,
+        offset: Filedelta,
+        whence: Whence,
+    ) -> Result<usize, wasip1::Errno> {
+        let filestat = self.fd_filestat_get_raw::<Wasm>(inode)?;
+
+        seek_cursor(&filestat, cursor, offset, whence)
+    }
+
+    /// Returns the cursor `fd_tell` reports for a descriptor of the inode.
+    #[allow(unused_variables)]
+    fn fd_tell_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        cursor: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        Ok(cursor)
+    }
+
     fn fd_read_stdin_raw<Wasm: WasmAccess>(
         &mut self,
         buf: *mut u8,
@@ -103,7 +214,7 @@ pub trait Wasip1LFS {
     fn path_open_raw<Wasm: WasmAccess>(
         &mut self,
         dir_ino: Self::Inode,
-        dir_flags: wasip1::Fdflags,
+        dir_flags: wasip1::Lookupflags,
         path_ptr: *const u8,
         path_len: usize,
         o_flags: wasip1::Oflags,
@@ -111,11 +222,84 @@ pub trait Wasip1LFS {
         fs_rights_inheriting: wasip1::Rights,
         fd_flags: wasip1::Fdflags,
     ) -> Result<Self::Inode, wasip1::Errno>;
+
+    /// Creates a directory at the path.
+    /// `ERRNO_EXIST` if something already exists there.
+    /// A read-only file system returns `ERRNO_ROFS` otherwise.
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno>;
+
+    /// Removes the empty directory at the path.
+    /// `ERRNO_NOTDIR` if it is not a directory,
+    /// `ERRNO_NOTEMPTY` if it still has entries.
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno>;
+
+    /// Removes the file at the path.
+    /// `ERRNO_ISDIR` if it is a directory.
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno>;
+
+    /// Moves the old path to the new path, replacing the target
+    /// if it is a file or an empty directory.
+    /// `ERRNO_ISDIR` / `ERRNO_NOTDIR` if the file types do not match,
+    /// `ERRNO_NOTEMPTY` if the target is a non empty directory,
+    /// `ERRNO_INVAL` if a directory is moved into itself.
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_dir_ino: Self::Inode,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_dir_ino: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno>;
+
+    /// Reads the target of the symbolic link at the path.
+    /// The target is truncated if the buffer is too small.
+    /// `ERRNO_INVAL` if it is not a symbolic link.
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<Size, wasip1::Errno>;
+
+    /// Creates a symbolic link at the new path pointing to the old path.
+    /// The old path is stored as is and resolved only when the link is followed.
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        dir_ino: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno>;
 }
 
 pub trait Wasip1FileTrait {
     fn size(&self) -> usize;
 
+    /// Modification time, in nanoseconds since the epoch.
+    /// Files which do not know it report 0.
+    fn mtim(&self) -> Timestamp {
+        0
+    }
+
     /// Reads data from the file into the provided buffer.
     /// Returns the number of bytes read.
     #[allow(unused_variables)]
@@ -199,12 +383,36 @@ pub trait Wasip1FileSystem {
 
     fn fd_close_raw<Wasm: WasmAccess>(&mut self, fd: Fd) -> wasip1::Errno;
 
+    fn fd_sync_raw<Wasm: WasmAccess>(&mut self, fd: Fd) -> wasip1::Errno;
+
+    fn fd_datasync_raw<Wasm: WasmAccess>(&mut self, fd: Fd) -> wasip1::Errno;
+
     fn fd_filestat_get_raw<Wasm: WasmAccess>(
         &mut self,
         fd: Fd,
         filestat: *mut wasip1::Filestat,
     ) -> wasip1::Errno;
 
+    fn fd_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        atim: Timestamp,
+        mtim: Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> wasip1::Errno;
+
+    #[allow(clippy::too_many_arguments)]
+    fn path_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        atim: Timestamp,
+        mtim: Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> wasip1::Errno;
+
     fn fd_read_raw<Wasm: WasmAccess>(
         &mut self,
         fd: Fd,
@@ -213,10 +421,49 @@ pub trait Wasip1FileSystem {
         nread: *mut Size,
     ) -> wasip1::Errno;
 
+    /// Reads from the file at the given offset
+    /// without changing the offset of the file descriptor.
+    fn fd_pread_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        iovs_ptr: *const Iovec,
+        iovs_len: usize,
+        offset: wasip1::Filesize,
+        nread: *mut Size,
+    ) -> wasip1::Errno;
+
+    /// Writes to the file at the given offset
+    /// without changing the offset of the file descriptor.
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        iovs_ptr: *const Ciovec,
+        iovs_len: usize,
+        offset: wasip1::Filesize,
+        nwritten: *mut Size,
+    ) -> wasip1::Errno;
+
+    /// Moves the offset of the file descriptor.
+    /// The new offset is relative to the start of the file.
+    fn fd_seek_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        offset: wasip1::Filedelta,
+        whence: wasip1::Whence,
+        newoffset: *mut wasip1::Filesize,
+    ) -> wasip1::Errno;
+
+    /// Returns the current offset of the file descriptor.
+    fn fd_tell_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        offset: *mut wasip1::Filesize,
+    ) -> wasip1::Errno;
+
     fn path_open_raw<Wasm: WasmAccess>(
         &mut self,
         dir_fd: Fd,
-        dir_flags: wasip1::Fdflags,
+        dir_flags: wasip1::Lookupflags,
         path_ptr: *const u8,
         path_len: usize,
         o_flags: wasip1::Oflags,
@@ -225,6 +472,81 @@ pub trait Wasip1FileSystem {
         fd_flags: wasip1::Fdflags,
         fd_ret: *mut wasip1::Fd,
     ) -> wasip1::Errno;
+
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> wasip1::Errno;
+
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> wasip1::Errno;
+
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> wasip1::Errno;
+
+    /// `ERRNO_XDEV` if the two directories
+    /// do not live on the same file system.
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_fd: Fd,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_fd: Fd,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> wasip1::Errno;
+
+    /// Atomically replaces `to` with `fd`, like `dup2` followed by `close`.
+    fn fd_renumber_raw<Wasm: WasmAccess>(&mut self, fd: Fd, to: Fd) -> wasip1::Errno;
+
+    fn fd_fdstat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        fdstat: *mut wasip1::Fdstat,
+    ) -> wasip1::Errno;
+
+    fn fd_fdstat_set_flags_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        flags: wasip1::Fdflags,
+    ) -> wasip1::Errno;
+
+    /// `ERRNO_NOTCAPABLE` if a right that the descriptor does not have is requested.
+    fn fd_fdstat_set_rights_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        fs_rights_base: wasip1::Rights,
+        fs_rights_inheriting: wasip1::Rights,
+    ) -> wasip1::Errno;
+
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        fd: Fd,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+        nread: *mut Size,
+    ) -> wasip1::Errno;
+
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        fd: Fd,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> wasip1::Errno;
 }
 
 #[macro_export]
@@ -241,9 +563,10 @@ macro_rules! plug_fs {
     };
 
     (@inner, @const, $state:expr, $($wasm:ident),*) => {
+        // also exported by `cargo test` on the host, to be called with `MockWasm`
         $crate::__private::paste::paste! {
             $(
-                #[cfg(target_os = "wasi")]
+                #[cfg(any(target_os = "wasi", test))]
                 #[unsafe(no_mangle)]
                 pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_write>](
                     fd: $crate::__private::wasip1::Fd,
@@ -256,7 +579,7 @@ macro_rules! plug_fs {
                     $crate::file::Wasip1FileSystem::fd_write_raw::<T>(state, fd, iovs_ptr, iovs_len, nwritten)
                 }
 
-                #[cfg(target_os = "wasi")]
+                #[cfg(any(target_os = "wasi", test))]
                 #[unsafe(no_mangle)]
                 pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_readdir>](
                     fd: $crate::__private::wasip1::Fd,
@@ -270,7 +593,7 @@ macro_rules! plug_fs {
                     $crate::file::Wasip1FileSystem::fd_readdir_raw::<T>(state, fd, buf, buf_len, cookie, nread)
                 }
 
-                #[cfg(target_os = "wasi")]
+                #[cfg(any(target_os = "wasi", test))]
                 #[unsafe(no_mangle)]
                 pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _path_filestat_get>](
                     fd: $crate::__private::wasip1::Fd,
@@ -284,7 +607,7 @@ macro_rules! plug_fs {
                     $crate::file::Wasip1FileSystem::path_filestat_get_raw::<T>(state, fd, flags, path_ptr, path_len, filestat)
                 }
 
-                #[cfg(target_os = "wasi")]
+                #[cfg(any(target_os = "wasi", test))]
                 #[unsafe(no_mangle)]
                 pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_prestat_get>](
                     fd: $crate::__private::wasip1::Fd,
@@ -295,7 +618,7 @@ macro_rules! plug_fs {
                     $crate::file::Wasip1FileSystem::fd_prestat_get_raw::<T>(state, fd, prestat)
                 }
 
-                #[cfg(target_os = "wasi")]
+                #[cfg(any(target_os = "wasi", test))]
                 #[unsafe(no_mangle)]
                 pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_prestat_dir_name>](
                     fd: $crate::__private::wasip1::Fd,
@@ -307,7 +630,7 @@ macro_rules! plug_fs {
                     $crate::file::Wasip1FileSystem::fd_prestat_dir_name_raw::<T>(state, fd, dir_path_ptr, dir_path_len)
                 }
 
-                #[cfg(target_os = "wasi")]
+                #[cfg(any(target_os = "wasi", test))]
                 #[unsafe(no_mangle)]
                 pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_close>](
                     fd: $crate::__private::wasip1::Fd,
@@ -318,10 +641,30 @@ macro_rules! plug_fs {
                 }
 
                 #[unsafe(no_mangle)]
-                #[cfg(target_os = "wasi")]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_sync>](
+                    fd: $crate::__private::wasip1::Fd,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::fd_sync_raw::<T>(state, fd)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_datasync>](
+                    fd: $crate::__private::wasip1::Fd,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::fd_datasync_raw::<T>(state, fd)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
                 pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _path_open>](
                     fd: $crate::__private::wasip1::Fd,
-                    dir_flags: $crate::__private::wasip1::Fdflags,
+                    dir_flags: $crate::__private::wasip1::Lookupflags,
                     path_ptr: *const u8,
                     path_len: usize,
                     o_flags: $crate::__private::wasip1::Oflags,
@@ -336,7 +679,7 @@ macro_rules! plug_fs {
                 }
 
                 #[unsafe(no_mangle)]
-                #[cfg(target_os = "wasi")]
+                #[cfg(any(target_os = "wasi", test))]
                 pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_read>](
                     fd: $crate::__private::wasip1::Fd,
                     iovs_ptr: *const $crate::__private::wasip1::Ciovec,
@@ -349,7 +692,59 @@ macro_rules! plug_fs {
                 }
 
                 #[unsafe(no_mangle)]
-                #[cfg(target_os = "wasi")]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_pread>](
+                    fd: $crate::__private::wasip1::Fd,
+                    iovs_ptr: *const $crate::__private::wasip1::Iovec,
+                    iovs_len: usize,
+                    offset: $crate::__private::wasip1::Filesize,
+                    nread_ret: *mut $crate::__private::wasip1::Size,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::fd_pread_raw::<T>(state, fd, iovs_ptr, iovs_len, offset, nread_ret)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_pwrite>](
+                    fd: $crate::__private::wasip1::Fd,
+                    iovs_ptr: *const $crate::__private::wasip1::Ciovec,
+                    iovs_len: usize,
+                    offset: $crate::__private::wasip1::Filesize,
+                    nwritten: *mut $crate::__private::wasip1::Size,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::fd_pwrite_raw::<T>(state, fd, iovs_ptr, iovs_len, offset, nwritten)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_seek>](
+                    fd: $crate::__private::wasip1::Fd,
+                    offset: $crate::__private::wasip1::Filedelta,
+                    whence: $crate::__private::wasip1::Whence,
+                    newoffset: *mut $crate::__private::wasip1::Filesize,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::fd_seek_raw::<T>(state, fd, offset, whence, newoffset)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_tell>](
+                    fd: $crate::__private::wasip1::Fd,
+                    offset: *mut $crate::__private::wasip1::Filesize,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::fd_tell_raw::<T>(state, fd, offset)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
                 pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_filestat_get>](
                     fd: $crate::__private::wasip1::Fd,
                     filestat: *mut $crate::__private::wasip1::Filestat,
@@ -358,6 +753,160 @@ macro_rules! plug_fs {
                     $crate::__as_t!(@as_t, $wasm);
                     $crate::file::Wasip1FileSystem::fd_filestat_get_raw::<T>(state, fd, filestat)
                 }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_filestat_set_times>](
+                    fd: $crate::__private::wasip1::Fd,
+                    atim: $crate::__private::wasip1::Timestamp,
+                    mtim: $crate::__private::wasip1::Timestamp,
+                    fst_flags: $crate::__private::wasip1::Fstflags,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::fd_filestat_set_times_raw::<T>(state, fd, atim, mtim, fst_flags)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _path_filestat_set_times>](
+                    fd: $crate::__private::wasip1::Fd,
+                    flags: $crate::__private::wasip1::Lookupflags,
+                    path_ptr: *const u8,
+                    path_len: usize,
+                    atim: $crate::__private::wasip1::Timestamp,
+                    mtim: $crate::__private::wasip1::Timestamp,
+                    fst_flags: $crate::__private::wasip1::Fstflags,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::path_filestat_set_times_raw::<T>(state, fd, flags, path_ptr, path_len, atim, mtim, fst_flags)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_renumber>](
+                    fd: $crate::__private::wasip1::Fd,
+                    to: $crate::__private::wasip1::Fd,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::fd_renumber_raw::<T>(state, fd, to)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_fdstat_get>](
+                    fd: $crate::__private::wasip1::Fd,
+                    fdstat: *mut $crate::__private::wasip1::Fdstat,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::fd_fdstat_get_raw::<T>(state, fd, fdstat)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_fdstat_set_flags>](
+                    fd: $crate::__private::wasip1::Fd,
+                    flags: $crate::__private::wasip1::Fdflags,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::fd_fdstat_set_flags_raw::<T>(state, fd, flags)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _fd_fdstat_set_rights>](
+                    fd: $crate::__private::wasip1::Fd,
+                    fs_rights_base: $crate::__private::wasip1::Rights,
+                    fs_rights_inheriting: $crate::__private::wasip1::Rights,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::fd_fdstat_set_rights_raw::<T>(state, fd, fs_rights_base, fs_rights_inheriting)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _path_create_directory>](
+                    fd: $crate::__private::wasip1::Fd,
+                    path_ptr: *const u8,
+                    path_len: usize,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::path_create_directory_raw::<T>(state, fd, path_ptr, path_len)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _path_remove_directory>](
+                    fd: $crate::__private::wasip1::Fd,
+                    path_ptr: *const u8,
+                    path_len: usize,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::path_remove_directory_raw::<T>(state, fd, path_ptr, path_len)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _path_unlink_file>](
+                    fd: $crate::__private::wasip1::Fd,
+                    path_ptr: *const u8,
+                    path_len: usize,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::path_unlink_file_raw::<T>(state, fd, path_ptr, path_len)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _path_rename>](
+                    fd: $crate::__private::wasip1::Fd,
+                    old_path_ptr: *const u8,
+                    old_path_len: usize,
+                    new_fd: $crate::__private::wasip1::Fd,
+                    new_path_ptr: *const u8,
+                    new_path_len: usize,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::path_rename_raw::<T>(state, fd, old_path_ptr, old_path_len, new_fd, new_path_ptr, new_path_len)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _path_readlink>](
+                    fd: $crate::__private::wasip1::Fd,
+                    path_ptr: *const u8,
+                    path_len: usize,
+                    buf: *mut u8,
+                    buf_len: usize,
+                    nread: *mut $crate::__private::wasip1::Size,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::path_readlink_raw::<T>(state, fd, path_ptr, path_len, buf, buf_len, nread)
+                }
+
+                #[unsafe(no_mangle)]
+                #[cfg(any(target_os = "wasi", test))]
+                pub unsafe extern "C" fn [<__wasip1_vfs_ $wasm _path_symlink>](
+                    old_path_ptr: *const u8,
+                    old_path_len: usize,
+                    fd: $crate::__private::wasip1::Fd,
+                    new_path_ptr: *const u8,
+                    new_path_len: usize,
+                ) -> $crate::__private::wasip1::Errno {
+                    let state = $state;
+                    $crate::__as_t!(@as_t, $wasm);
+                    $crate::file::Wasip1FileSystem::path_symlink_raw::<T>(state, old_path_ptr, old_path_len, fd, new_path_ptr, new_path_len)
+                }
             )*
         }
     };
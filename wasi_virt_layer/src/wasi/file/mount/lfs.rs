@@ -0,0 +1,275 @@
+use crate::{
+    __private::wasip1,
+    memory::{HostMemory, WasmAccess, WasmPathAccess, WasmPathComponent},
+    wasi::file::Wasip1LFS,
+};
+
+/// Mount table
+/// which mounts several LFS at different paths,
+/// e.g. a const tree at `/usr` and a heap LFS at `/tmp`.
+///
+/// `Mounts` is a tuple of LFS, see the [`Wasip1LFS`] impl for tuples.
+/// Each pre-opened directory of the tuple is one mount named by its path.
+/// A path opened from the root of a mount is routed
+/// to the mount owning the longest matching prefix,
+/// so `tmp/a` from `/` reaches `a` of the `/tmp` mount,
+/// and its leading `..` climb out of the mount,
+/// so `../bin` from `/usr/tmp` reaches `bin` of the `/usr` mount.
+/// `..` after other components and symbolic links never cross into another mount.
+pub struct VFSMountLFS<Mounts: Wasip1LFS<Inode = (usize, usize)>> {
+    mounts: Mounts,
+    /// mount path of each pre-opened directory of the tuple
+    paths: &'static [&'static str],
+}
+
+/// A path routed by [`VFSMountLFS::route`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MountRoute {
+    /// directory the path is relative to
+    pub inode: (usize, usize),
+    pub path_ptr: *const u8,
+    pub path_len: usize,
+    /// directory opened to reach `inode`, closed by [`VFSMountLFS::leave`]
+    pub opened: bool,
+}
+
+/// Components of a mount path, without `.` and empty ones.
+fn components(path: &str) -> impl Iterator<Item = &str> + Clone {
+    path.split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+}
+
+/// The part of `path` from its `from`-th component up to the `to`-th one.
+fn sub_path(path: &str, from: usize, to: usize) -> &str {
+    let (mut start, mut end) = (0, 0);
+    let mut offset = 0;
+    let mut index = 0;
+
+    for part in path.split('/') {
+        if !part.is_empty() && part != "." {
+            if index == from {
+                start = offset;
+            }
+            if index + 1 == to {
+                end = offset + part.len();
+            }
+            index += 1;
+        }
+        offset += part.len() + 1;
+    }
+
+    &path[start..end]
+}
+
+impl<Mounts: Wasip1LFS<Inode = (usize, usize)>> VFSMountLFS<Mounts> {
+    /// `paths` has one path per pre-opened directory of the tuple.
+    pub const fn new(mounts: Mounts, paths: &'static [&'static str]) -> Self {
+        assert!(
+            paths.len() == Mounts::PRE_OPEN.len(),
+            "one mount path is needed per pre-opened directory"
+        );
+
+        Self { mounts, paths }
+    }
+
+    #[inline]
+    pub const fn mounts(&self) -> &Mounts {
+        &self.mounts
+    }
+
+    #[inline]
+    pub const fn mounts_mut(&mut self) -> &mut Mounts {
+        &mut self.mounts
+    }
+
+    #[inline]
+    pub const fn paths(&self) -> &'static [&'static str] {
+        self.paths
+    }
+
+    /// Returns the mount rooted at `inode`, the index of it in `paths`.
+    #[inline]
+    pub fn mount_of(&self, inode: (usize, usize)) -> Option<usize> {
+        Mounts::PRE_OPEN
+            .iter()
+            .position(|pre_open| *pre_open == inode)
+    }
+
+    /// Returns the mount owning the longest prefix of `path`.
+    pub fn mount_for(&self, path: &str) -> Option<usize> {
+        let mut best = None;
+        let mut best_depth = 0;
+
+        for (mount, mount_path) in self.paths.iter().enumerate() {
+            let mut parts = components(path);
+            if !components(mount_path).all(|part| parts.next() == Some(part)) {
+                continue;
+            }
+
+            let depth = components(mount_path).count();
+            if best.is_none() || depth > best_depth {
+                best = Some(mount);
+                best_depth = depth;
+            }
+        }
+
+        best
+    }
+
+    /// Skips the leading components of the guest path equal to `prefix`.
+    /// Returns the rest of the path, `None` if the prefix does not match.
+    fn strip_prefix<'p, Wasm: WasmAccess>(
+        path_ptr: *const u8,
+        path_len: usize,
+        mut prefix: impl Iterator<Item = &'p str>,
+    ) -> Option<(*const u8, usize)> {
+        let path = WasmPathAccess::<Wasm>::new(path_ptr, path_len);
+        let mut components = path.components();
+
+        let mut next = prefix.next();
+
+        while let Some(name) = next {
+            match components.next()? {
+                WasmPathComponent::RootDir | WasmPathComponent::CurDir => {}
+                component @ WasmPathComponent::Normal(..) if component.eq_str(name) => {
+                    next = prefix.next();
+                }
+                _ => return None,
+            }
+        }
+
+        Some(components.as_raw())
+    }
+
+    /// Skips the leading `..` of the guest path, with `.` between them.
+    /// Returns how many there are and the rest of the path.
+    fn strip_parents<Wasm: WasmAccess>(
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> (usize, (*const u8, usize)) {
+        let path = WasmPathAccess::<Wasm>::new(path_ptr, path_len);
+        let mut components = path.components();
+
+        let mut parents = 0;
+        let mut rest = components.as_raw();
+
+        while let Some(component) = components.next() {
+            match component {
+                WasmPathComponent::RootDir | WasmPathComponent::CurDir => {}
+                WasmPathComponent::ParentDir => parents += 1,
+                WasmPathComponent::Normal(..) => break,
+            }
+            rest = components.as_raw();
+        }
+
+        (parents, rest)
+    }
+
+    /// Routes a path relative to `inode` to the mount owning it.
+    /// Only paths from the root of a mount are routed,
+    /// others stay in the mount of `inode`.
+    ///
+    /// When the leading `..` leave the mount for a directory
+    /// inside another mount, that directory is opened on the way,
+    /// hand the route to [`VFSMountLFS::leave`] after using it.
+    /// `ERRNO_NOENT` if no mount owns that directory.
+    pub fn route<Wasm: WasmAccess>(
+        &mut self,
+        inode: (usize, usize),
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<MountRoute, wasip1::Errno> {
+        let stay = MountRoute {
+            inode,
+            path_ptr,
+            path_len,
+            opened: false,
+        };
+
+        let Some(mount) = self.mount_of(inode) else {
+            return Ok(stay);
+        };
+
+        // `..` at `/` stays at `/`
+        let (parents, (rest_ptr, rest_len)) = Self::strip_parents::<Wasm>(path_ptr, path_len);
+        let depth = components(self.paths[mount])
+            .count()
+            .saturating_sub(parents);
+        let base = components(self.paths[mount]).take(depth);
+
+        let mut best = None;
+        let mut best_depth = 0;
+
+        // the mounts at or below the base directory
+        for (other, other_path) in self.paths.iter().enumerate() {
+            let mut rest = components(other_path);
+            if !base.clone().all(|part| rest.next() == Some(part)) {
+                continue;
+            }
+
+            let other_depth = rest.clone().count();
+            if best.is_some() && other_depth <= best_depth {
+                continue;
+            }
+
+            if let Some((ptr, len)) = Self::strip_prefix::<Wasm>(rest_ptr, rest_len, rest) {
+                best = Some(MountRoute {
+                    inode: Mounts::PRE_OPEN[other],
+                    path_ptr: ptr,
+                    path_len: len,
+                    opened: false,
+                });
+                best_depth = other_depth;
+            }
+        }
+
+        if let Some(best) = best {
+            return Ok(best);
+        }
+
+        // the base directory is inside a mount above it
+        let mut owner = None;
+        let mut owner_depth = 0;
+
+        for (other, other_path) in self.paths.iter().enumerate() {
+            let mut parts = base.clone();
+            let other_depth = components(other_path).count();
+            if other_depth < depth
+                && (owner.is_none() || other_depth > owner_depth)
+                && components(other_path).all(|part| parts.next() == Some(part))
+            {
+                owner = Some(other);
+                owner_depth = other_depth;
+            }
+        }
+
+        let owner = owner.ok_or(wasip1::ERRNO_NOENT)?;
+        let dir = sub_path(self.paths[mount], owner_depth, depth);
+
+        let inode = self.mounts.path_open_raw::<HostMemory>(
+            Mounts::PRE_OPEN[owner],
+            0,
+            dir.as_ptr(),
+            dir.len(),
+            wasip1::OFLAGS_DIRECTORY,
+            0,
+            0,
+            0,
+        )?;
+
+        Ok(MountRoute {
+            inode,
+            path_ptr: rest_ptr,
+            path_len: rest_len,
+            opened: true,
+        })
+    }
+
+    /// Closes the directory [`VFSMountLFS::route`] opened on the way.
+    #[inline]
+    pub fn leave(&mut self, route: MountRoute) {
+        if route.opened {
+            self.mounts.fd_close_raw::<HostMemory>(route.inode);
+        }
+    }
+}
@@ -0,0 +1,914 @@
+use crate::__private::wasip1;
+use crate::__private::wasip1::Dircookie;
+
+#[cfg(feature = "alloc")]
+use crate::wasi::file::snapshot::{SnapshotReader, SnapshotWriter, Wasip1Snapshot};
+use crate::{
+    memory::WasmAccess,
+    wasi::file::{FilestatWithoutDevice, Wasip1LFS, mount::lfs::VFSMountLFS},
+};
+
+impl<Mounts: Wasip1LFS<Inode = (usize, usize)>> Wasip1LFS for VFSMountLFS<Mounts> {
+    type Inode = (usize, usize);
+    const PRE_OPEN: &'static [Self::Inode] = Mounts::PRE_OPEN;
+
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        data: *const u8,
+        data_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.mounts_mut()
+            .fd_pwrite_raw::<Wasm>(inode, data, data_len, offset)
+    }
+
+    fn fd_write_stdout_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.mounts_mut()
+            .fd_write_stdout_raw::<Wasm>(data, data_len)
+    }
+
+    fn fd_write_stderr_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.mounts_mut()
+            .fd_write_stderr_raw::<Wasm>(data, data_len)
+    }
+
+    fn is_dir(&self, inode: Self::Inode) -> bool {
+        self.mounts().is_dir(inode)
+    }
+
+    fn fd_readdir_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        cookie: Dircookie,
+    ) -> Result<(wasip1::Size, Dircookie), wasip1::Errno> {
+        self.mounts_mut()
+            .fd_readdir_raw::<Wasm>(inode, buf, buf_len, cookie)
+    }
+
+    fn path_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let route = self.route::<Wasm>(inode, path_ptr, path_len)?;
+
+        let ret = self.mounts_mut().path_filestat_get_raw::<Wasm>(
+            route.inode,
+            flags,
+            route.path_ptr,
+            route.path_len,
+        );
+        self.leave(route);
+
+        ret
+    }
+
+    fn fd_prestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<wasip1::Prestat, wasip1::Errno> {
+        let mount = self.mount_of(inode).ok_or(wasip1::ERRNO_BADF)?;
+
+        Ok(wasip1::Prestat {
+            tag: 0, // prestat is enum but variant is only 0
+            // union type but we only have one variant
+            u: wasip1::PrestatU {
+                dir: wasip1::PrestatDir {
+                    pr_name_len: self.paths()[mount].len() as _,
+                },
+            },
+        })
+    }
+
+    fn fd_prestat_dir_name_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        dir_path_ptr: *mut u8,
+        dir_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let mount = self.mount_of(inode).ok_or(wasip1::ERRNO_BADF)?;
+        let name = self.paths()[mount];
+
+        Wasm::memcpy(
+            dir_path_ptr,
+            &name.as_bytes()[..core::cmp::min(name.len(), dir_path_len)],
+        );
+
+        Ok(())
+    }
+
+    fn fd_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        self.mounts_mut().fd_filestat_get_raw::<Wasm>(inode)
+    }
+
+    fn fd_close_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) {
+        self.mounts_mut().fd_close_raw::<Wasm>(inode)
+    }
+
+    fn fd_sync_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) -> Result<(), wasip1::Errno> {
+        self.mounts_mut().fd_sync_raw::<Wasm>(inode)
+    }
+
+    fn fd_seek_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        cursor: usize,
+        offset: wasip1::Filedelta,
+        whence: wasip1::Whence,
+    ) -> Result<usize, wasip1::Errno> {
+        self.mounts_mut()
+            .fd_seek_raw::<Wasm>(inode, cursor, offset, whence)
+    }
+
+    fn fd_tell_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        cursor: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        self.mounts_mut().fd_tell_raw::<Wasm>(inode, cursor)
+    }
+
+    fn fd_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        atim: wasip1::Timestamp,
+        mtim: wasip1::Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        self.mounts_mut()
+            .fd_filestat_set_times_raw::<Wasm>(inode, atim, mtim, fst_flags)
+    }
+
+    fn fd_pread_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.mounts_mut()
+            .fd_pread_raw::<Wasm>(inode, buf, buf_len, offset)
+    }
+
+    fn fd_read_stdin_raw<Wasm: WasmAccess>(
+        &mut self,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.mounts_mut().fd_read_stdin_raw::<Wasm>(buf, buf_len)
+    }
+
+    fn path_open_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        dir_flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        fs_rights_inheriting: wasip1::Rights,
+        fd_flags: wasip1::Fdflags,
+    ) -> Result<Self::Inode, wasip1::Errno> {
+        let route = self.route::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        let ret = self.mounts_mut().path_open_raw::<Wasm>(
+            route.inode,
+            dir_flags,
+            route.path_ptr,
+            route.path_len,
+            o_flags,
+            fs_rights_base,
+            fs_rights_inheriting,
+            fd_flags,
+        );
+        self.leave(route);
+
+        ret
+    }
+
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let route = self.route::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        let ret = self.mounts_mut().path_create_directory_raw::<Wasm>(
+            route.inode,
+            route.path_ptr,
+            route.path_len,
+        );
+        self.leave(route);
+
+        ret
+    }
+
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let route = self.route::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        let ret = self.mounts_mut().path_remove_directory_raw::<Wasm>(
+            route.inode,
+            route.path_ptr,
+            route.path_len,
+        );
+        self.leave(route);
+
+        ret
+    }
+
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let route = self.route::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        let ret = self.mounts_mut().path_unlink_file_raw::<Wasm>(
+            route.inode,
+            route.path_ptr,
+            route.path_len,
+        );
+        self.leave(route);
+
+        ret
+    }
+
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_dir_inode: Self::Inode,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_dir_inode: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let old = self.route::<Wasm>(old_dir_inode, old_path_ptr, old_path_len)?;
+        let new = match self.route::<Wasm>(new_dir_inode, new_path_ptr, new_path_len) {
+            Ok(new) => new,
+            Err(e) => {
+                self.leave(old);
+                return Err(e);
+            }
+        };
+
+        let ret = self.mounts_mut().path_rename_raw::<Wasm>(
+            old.inode,
+            old.path_ptr,
+            old.path_len,
+            new.inode,
+            new.path_ptr,
+            new.path_len,
+        );
+        self.leave(old);
+        self.leave(new);
+
+        ret
+    }
+
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let route = self.route::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        let ret = self.mounts_mut().path_readlink_raw::<Wasm>(
+            route.inode,
+            route.path_ptr,
+            route.path_len,
+            buf,
+            buf_len,
+        );
+        self.leave(route);
+
+        ret
+    }
+
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        dir_inode: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let route = self.route::<Wasm>(dir_inode, new_path_ptr, new_path_len)?;
+
+        let ret = self.mounts_mut().path_symlink_raw::<Wasm>(
+            old_path_ptr,
+            old_path_len,
+            route.inode,
+            route.path_ptr,
+            route.path_len,
+        );
+        self.leave(route);
+
+        ret
+    }
+}
+
+/// Max number of pre-opened directories of a tuple of LFS, all elements together.
+pub const MOUNT_PRE_OPEN_MAX: usize = 32;
+
+/// The pre-opened directories of the elements one after another.
+const fn concat_pre_open(parts: &[&[usize]]) -> ([(usize, usize); MOUNT_PRE_OPEN_MAX], usize) {
+    let mut pre_open = [(0, 0); MOUNT_PRE_OPEN_MAX];
+    let mut len = 0;
+
+    let mut mount = 0;
+    while mount < parts.len() {
+        let mut i = 0;
+        while i < parts[mount].len() {
+            assert!(
+                len < MOUNT_PRE_OPEN_MAX,
+                "too many pre-opened directories in one mount tuple"
+            );
+            pre_open[len] = (mount, parts[mount][i]);
+            len += 1;
+            i += 1;
+        }
+        mount += 1;
+    }
+
+    (pre_open, len)
+}
+
+/// Holds the pre-opened directories of a tuple,
+/// a slice of the array can be `'static` only through a const of a type.
+struct TuplePreOpen<T>(core::marker::PhantomData<T>);
+
+/// A tuple of LFS is one LFS for [`VFSMountLFS`].
+/// The inode is `(index in the tuple, inode of the element)`
+/// and the pre-opened directories are those of every element in order,
+/// at most [`MOUNT_PRE_OPEN_MAX`].
+/// The elements must number their inodes with `usize`, like the bundled ones.
+/// Stdio goes through the first element.
+macro_rules! impl_wasip1_lfs_for_tuple {
+    ($($index:tt $lfs:ident),+) => {
+        impl<$($lfs: Wasip1LFS<Inode = usize>),+> TuplePreOpen<($($lfs,)+)> {
+            const ALL: ([(usize, usize); MOUNT_PRE_OPEN_MAX], usize) =
+                concat_pre_open(&[$($lfs::PRE_OPEN),+]);
+        }
+
+        impl<$($lfs: Wasip1LFS<Inode = usize>),+> Wasip1LFS for ($($lfs,)+) {
+            type Inode = (usize, usize);
+            const PRE_OPEN: &'static [Self::Inode] =
+                TuplePreOpen::<($($lfs,)+)>::ALL.0.split_at(TuplePreOpen::<($($lfs,)+)>::ALL.1).0;
+
+            fn fd_pwrite_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, inode): Self::Inode,
+                data: *const u8,
+                data_len: usize,
+                offset: usize,
+            ) -> Result<wasip1::Size, wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.fd_pwrite_raw::<Wasm>(inode, data, data_len, offset),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn fd_write_stdout_raw<Wasm: WasmAccess>(
+                &mut self,
+                data: *const u8,
+                data_len: usize,
+            ) -> Result<wasip1::Size, wasip1::Errno> {
+                self.0.fd_write_stdout_raw::<Wasm>(data, data_len)
+            }
+
+            fn fd_write_stderr_raw<Wasm: WasmAccess>(
+                &mut self,
+                data: *const u8,
+                data_len: usize,
+            ) -> Result<wasip1::Size, wasip1::Errno> {
+                self.0.fd_write_stderr_raw::<Wasm>(data, data_len)
+            }
+
+            fn is_dir(&self, (mount, inode): Self::Inode) -> bool {
+                match mount {
+                    $($index => self.$index.is_dir(inode),)+
+                    _ => false,
+                }
+            }
+
+            fn fd_readdir_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, inode): Self::Inode,
+                buf: *mut u8,
+                buf_len: usize,
+                cookie: Dircookie,
+            ) -> Result<(wasip1::Size, Dircookie), wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.fd_readdir_raw::<Wasm>(inode, buf, buf_len, cookie),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn path_filestat_get_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, inode): Self::Inode,
+                flags: wasip1::Lookupflags,
+                path_ptr: *const u8,
+                path_len: usize,
+            ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.path_filestat_get_raw::<Wasm>(inode, flags, path_ptr, path_len),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn fd_prestat_get_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, inode): Self::Inode,
+            ) -> Result<wasip1::Prestat, wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.fd_prestat_get_raw::<Wasm>(inode),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn fd_prestat_dir_name_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, inode): Self::Inode,
+                dir_path_ptr: *mut u8,
+                dir_path_len: usize,
+            ) -> Result<(), wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.fd_prestat_dir_name_raw::<Wasm>(inode, dir_path_ptr, dir_path_len),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn fd_filestat_get_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, inode): Self::Inode,
+            ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.fd_filestat_get_raw::<Wasm>(inode),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn fd_close_raw<Wasm: WasmAccess>(&mut self, (mount, inode): Self::Inode) {
+                match mount {
+                    $($index => self.$index.fd_close_raw::<Wasm>(inode),)+
+                    _ => {}
+                }
+            }
+
+            fn fd_sync_raw<Wasm: WasmAccess>(&mut self, (mount, inode): Self::Inode) -> Result<(), wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.fd_sync_raw::<Wasm>(inode),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn fd_seek_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, inode): Self::Inode,
+                cursor: usize,
+                offset: wasip1::Filedelta,
+                whence: wasip1::Whence,
+            ) -> Result<usize, wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.fd_seek_raw::<Wasm>(inode, cursor, offset, whence),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn fd_tell_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, inode): Self::Inode,
+                cursor: usize,
+            ) -> Result<usize, wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.fd_tell_raw::<Wasm>(inode, cursor),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn fd_filestat_set_times_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, inode): Self::Inode,
+                atim: wasip1::Timestamp,
+                mtim: wasip1::Timestamp,
+                fst_flags: wasip1::Fstflags,
+            ) -> Result<(), wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.fd_filestat_set_times_raw::<Wasm>(inode, atim, mtim, fst_flags),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn fd_pread_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, inode): Self::Inode,
+                buf: *mut u8,
+                buf_len: usize,
+                offset: usize,
+            ) -> Result<wasip1::Size, wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.fd_pread_raw::<Wasm>(inode, buf, buf_len, offset),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn fd_read_stdin_raw<Wasm: WasmAccess>(
+                &mut self,
+                buf: *mut u8,
+                buf_len: usize,
+            ) -> Result<wasip1::Size, wasip1::Errno> {
+                self.0.fd_read_stdin_raw::<Wasm>(buf, buf_len)
+            }
+
+            fn path_open_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, dir_inode): Self::Inode,
+                dir_flags: wasip1::Lookupflags,
+                path_ptr: *const u8,
+                path_len: usize,
+                o_flags: wasip1::Oflags,
+                fs_rights_base: wasip1::Rights,
+                fs_rights_inheriting: wasip1::Rights,
+                fd_flags: wasip1::Fdflags,
+            ) -> Result<Self::Inode, wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.path_open_raw::<Wasm>(
+                        dir_inode,
+                        dir_flags,
+                        path_ptr,
+                        path_len,
+                        o_flags,
+                        fs_rights_base,
+                        fs_rights_inheriting,
+                        fd_flags,
+                    ).map(|inode| (mount, inode)),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn path_create_directory_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, dir_inode): Self::Inode,
+                path_ptr: *const u8,
+                path_len: usize,
+            ) -> Result<(), wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.path_create_directory_raw::<Wasm>(dir_inode, path_ptr, path_len),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn path_remove_directory_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, dir_inode): Self::Inode,
+                path_ptr: *const u8,
+                path_len: usize,
+            ) -> Result<(), wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.path_remove_directory_raw::<Wasm>(dir_inode, path_ptr, path_len),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn path_unlink_file_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, dir_inode): Self::Inode,
+                path_ptr: *const u8,
+                path_len: usize,
+            ) -> Result<(), wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.path_unlink_file_raw::<Wasm>(dir_inode, path_ptr, path_len),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            /// `ERRNO_XDEV` if the paths are in different elements.
+            fn path_rename_raw<Wasm: WasmAccess>(
+                &mut self,
+                (old_mount, old_dir_inode): Self::Inode,
+                old_path_ptr: *const u8,
+                old_path_len: usize,
+                (new_mount, new_dir_inode): Self::Inode,
+                new_path_ptr: *const u8,
+                new_path_len: usize,
+            ) -> Result<(), wasip1::Errno> {
+                if old_mount != new_mount {
+                    return Err(wasip1::ERRNO_XDEV);
+                }
+
+                match old_mount {
+                    $($index => self.$index.path_rename_raw::<Wasm>(
+                        old_dir_inode,
+                        old_path_ptr,
+                        old_path_len,
+                        new_dir_inode,
+                        new_path_ptr,
+                        new_path_len,
+                    ),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn path_readlink_raw<Wasm: WasmAccess>(
+                &mut self,
+                (mount, dir_inode): Self::Inode,
+                path_ptr: *const u8,
+                path_len: usize,
+                buf: *mut u8,
+                buf_len: usize,
+            ) -> Result<wasip1::Size, wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.path_readlink_raw::<Wasm>(dir_inode, path_ptr, path_len, buf, buf_len),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+
+            fn path_symlink_raw<Wasm: WasmAccess>(
+                &mut self,
+                old_path_ptr: *const u8,
+                old_path_len: usize,
+                (mount, dir_inode): Self::Inode,
+                new_path_ptr: *const u8,
+                new_path_len: usize,
+            ) -> Result<(), wasip1::Errno> {
+                match mount {
+                    $($index => self.$index.path_symlink_raw::<Wasm>(
+                        old_path_ptr,
+                        old_path_len,
+                        dir_inode,
+                        new_path_ptr,
+                        new_path_len,
+                    ),)+
+                    _ => Err(wasip1::ERRNO_BADF),
+                }
+            }
+        }
+
+        /// The elements one after another.
+        #[cfg(feature = "alloc")]
+        impl<$($lfs: Wasip1Snapshot),+> Wasip1Snapshot for ($($lfs,)+) {
+            fn snapshot_to(&self, out: &mut SnapshotWriter) {
+                $(self.$index.snapshot_to(out);)+
+            }
+
+            fn restore_from(&mut self, input: &mut SnapshotReader<'_>) -> Result<(), wasip1::Errno> {
+                $(self.$index.restore_from(input)?;)+
+                Ok(())
+            }
+        }
+    };
+}
+
+#[cfg(feature = "alloc")]
+impl<Mounts: Wasip1LFS<Inode = (usize, usize)> + Wasip1Snapshot> Wasip1Snapshot
+    for VFSMountLFS<Mounts>
+{
+    fn snapshot_to(&self, out: &mut SnapshotWriter) {
+        self.mounts().snapshot_to(out);
+    }
+
+    fn restore_from(&mut self, input: &mut SnapshotReader<'_>) -> Result<(), wasip1::Errno> {
+        self.mounts_mut().restore_from(input)
+    }
+}
+
+impl_wasip1_lfs_for_tuple!(0 A);
+impl_wasip1_lfs_for_tuple!(0 A, 1 B);
+impl_wasip1_lfs_for_tuple!(0 A, 1 B, 2 C);
+impl_wasip1_lfs_for_tuple!(0 A, 1 B, 2 C, 3 D);
+impl_wasip1_lfs_for_tuple!(0 A, 1 B, 2 C, 3 D, 4 E);
+impl_wasip1_lfs_for_tuple!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F);
+impl_wasip1_lfs_for_tuple!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G);
+impl_wasip1_lfs_for_tuple!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H);
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use const_struct::const_struct;
+
+    use super::*;
+    use crate::{
+        ConstFiles,
+        memory::HostMemory,
+        mock::MockWasm,
+        wasi::file::{
+            Wasip1FileSystem,
+            constant::{
+                lfs::VFSConstNormalLFS,
+                lfs_raw::{VFSConstNormalFiles, WasiConstFile},
+                vfs::Wasip1ConstVFS,
+            },
+            heap::lfs::VFSHeapLFS,
+            stdio::DefaultStdIO,
+        },
+    };
+
+    #[const_struct]
+    const FILES: VFSConstNormalFiles<WasiConstFile<&'static str>, 3> =
+        ConstFiles!([(".", [("bin", [("cc", WasiConstFile::new("cc"))])])]);
+
+    type Usr = VFSConstNormalLFS<FilesTy, WasiConstFile<&'static str>, 3, DefaultStdIO>;
+    type Tmp = VFSHeapLFS<DefaultStdIO>;
+    type Vfs = Wasip1ConstVFS<VFSMountLFS<(Usr, Tmp, Tmp)>, 8>;
+
+    #[test]
+    fn test_mount_routing() {
+        let mut vfs = Vfs::new(VFSMountLFS::new(
+            (Usr::new(), Tmp::new(""), Tmp::new("")),
+            &["/usr", "/usr/tmp", "/data"],
+        ));
+
+        assert_eq!(MockWasm::prestat_name(&mut vfs, 3).as_deref(), Ok("/usr"));
+        assert_eq!(
+            MockWasm::prestat_name(&mut vfs, 4).as_deref(),
+            Ok("/usr/tmp")
+        );
+        assert_eq!(MockWasm::prestat_name(&mut vfs, 5).as_deref(), Ok("/data"));
+        assert_eq!(MockWasm::prestat_name(&mut vfs, 6), Err(wasip1::ERRNO_BADF));
+
+        assert!(
+            MockWasm::open_with(&mut vfs, 3, 0, "bin/cc", 0, wasip1::RIGHTS_FD_READ, 0).is_ok()
+        );
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "bin/ld", wasip1::OFLAGS_CREAT),
+            Err(wasip1::ERRNO_ROFS)
+        );
+
+        // the longest prefix wins
+        assert!(MockWasm::open(&mut vfs, 3, "./tmp//a.txt", wasip1::OFLAGS_CREAT).is_ok());
+        assert!(vfs.lfs().mounts().1.get_file("a.txt").is_some());
+        assert!(MockWasm::open(&mut vfs, 4, "a.txt", 0).is_ok());
+        assert_eq!(
+            MockWasm::open(&mut vfs, 5, "a.txt", 0),
+            Err(wasip1::ERRNO_NOENT)
+        );
+
+        assert!(MockWasm::open(&mut vfs, 5, "b.txt", wasip1::OFLAGS_CREAT).is_ok());
+        assert!(vfs.lfs().mounts().2.get_file("b.txt").is_some());
+
+        let (old, new) = ("tmp/a.txt", "b.txt");
+        assert_eq!(
+            Wasip1FileSystem::path_rename_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                old.as_ptr(),
+                old.len(),
+                5,
+                new.as_ptr(),
+                new.len()
+            ),
+            wasip1::ERRNO_XDEV
+        );
+
+        assert_eq!(vfs.lfs().mount_for("/usr/tmp/a.txt"), Some(1));
+        assert_eq!(vfs.lfs().mount_for("/usr/bin"), Some(0));
+        assert_eq!(vfs.lfs().mount_for("/etc"), None);
+    }
+
+    #[const_struct]
+    const ROOTS: VFSConstNormalFiles<WasiConstFile<&'static str>, 5> = ConstFiles!([
+        (".", [("bin", [("cc", WasiConstFile::new("cc"))])]),
+        ("etc", [("hosts", WasiConstFile::new("localhost"))]),
+    ]);
+
+    type Roots = VFSConstNormalLFS<RootsTy, WasiConstFile<&'static str>, 5, DefaultStdIO>;
+
+    #[test]
+    fn test_mount_every_pre_open() {
+        let mut vfs = Wasip1ConstVFS::<VFSMountLFS<(Roots, Tmp)>, 8>::new(VFSMountLFS::new(
+            (Roots::new(), Tmp::new("")),
+            &["/usr", "/etc", "/tmp"],
+        ));
+
+        assert_eq!(<(Roots, Tmp)>::PRE_OPEN.len(), 3);
+        assert_eq!(<(Roots, Tmp)>::PRE_OPEN[2], (1, 0));
+
+        assert_eq!(MockWasm::prestat_name(&mut vfs, 3).as_deref(), Ok("/usr"));
+        assert_eq!(MockWasm::prestat_name(&mut vfs, 4).as_deref(), Ok("/etc"));
+        assert_eq!(MockWasm::prestat_name(&mut vfs, 5).as_deref(), Ok("/tmp"));
+
+        assert!(
+            MockWasm::open_with(&mut vfs, 3, 0, "bin/cc", 0, wasip1::RIGHTS_FD_READ, 0).is_ok()
+        );
+        assert!(MockWasm::open_with(&mut vfs, 4, 0, "hosts", 0, wasip1::RIGHTS_FD_READ, 0).is_ok());
+        assert!(
+            MockWasm::open_with(&mut vfs, 5, 0, "../etc/hosts", 0, wasip1::RIGHTS_FD_READ, 0)
+                .is_ok()
+        );
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "hosts", 0),
+            Err(wasip1::ERRNO_NOENT)
+        );
+    }
+
+    #[test]
+    fn test_mount_parent_dir() {
+        let mut vfs = Vfs::new(VFSMountLFS::new(
+            (Usr::new(), Tmp::new(""), Tmp::new("")),
+            &["/usr", "/usr/tmp", "/data"],
+        ));
+
+        // `..` of a mount root is the directory of the mount above
+        assert!(
+            MockWasm::open_with(&mut vfs, 4, 0, "../bin/cc", 0, wasip1::RIGHTS_FD_READ, 0).is_ok()
+        );
+        assert!(MockWasm::open(&mut vfs, 4, "./../tmp/a.txt", wasip1::OFLAGS_CREAT).is_ok());
+        assert!(vfs.lfs().mounts().1.get_file("a.txt").is_some());
+        assert!(MockWasm::open(&mut vfs, 4, "../../data/b.txt", wasip1::OFLAGS_CREAT).is_ok());
+        assert!(vfs.lfs().mounts().2.get_file("b.txt").is_some());
+        assert!(MockWasm::open(&mut vfs, 5, "../usr/tmp/a.txt", 0).is_ok());
+
+        // `..` of `/` is `/`, which no mount owns
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "../../../etc", 0),
+            Err(wasip1::ERRNO_NOENT)
+        );
+
+        let (old, new) = ("../a.txt", "a.txt");
+        assert_eq!(
+            Wasip1FileSystem::path_rename_raw::<HostMemory>(
+                &mut vfs,
+                5,
+                old.as_ptr(),
+                old.len(),
+                4,
+                new.as_ptr(),
+                new.len()
+            ),
+            wasip1::ERRNO_NOENT
+        );
+
+        let (old, new) = ("../../data/b.txt", "b.txt");
+        assert_eq!(
+            Wasip1FileSystem::path_rename_raw::<HostMemory>(
+                &mut vfs,
+                4,
+                old.as_ptr(),
+                old.len(),
+                4,
+                new.as_ptr(),
+                new.len()
+            ),
+            wasip1::ERRNO_XDEV
+        );
+    }
+
+    #[test]
+    fn test_mount_parent_dir_inside_mount() {
+        let mut vfs = Wasip1ConstVFS::<VFSMountLFS<(Tmp, Tmp)>, 8>::new(VFSMountLFS::new(
+            (Tmp::new(""), Tmp::new("")),
+            &["/", "/usr/tmp"],
+        ));
+
+        let usr = "usr";
+        assert_eq!(
+            Wasip1FileSystem::path_create_directory_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                usr.as_ptr(),
+                usr.len()
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+
+        // `/usr` is a directory of the `/` mount, opened on the way
+        let fd = MockWasm::open(&mut vfs, 4, "../lib.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert!(vfs.lfs().mounts().0.get_file("usr/lib.txt").is_some());
+        assert!(MockWasm::open(&mut vfs, 3, "usr/lib.txt", 0).is_ok());
+        assert_eq!(
+            Wasip1FileSystem::fd_close_raw::<HostMemory>(&mut vfs, fd),
+            wasip1::ERRNO_SUCCESS
+        );
+
+        assert_eq!(
+            MockWasm::open(&mut vfs, 4, "../../etc", 0),
+            Err(wasip1::ERRNO_NOENT)
+        );
+    }
+}
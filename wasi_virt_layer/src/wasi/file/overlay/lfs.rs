@@ -0,0 +1,1017 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet, btree_map::Entry},
+    string::String,
+    vec,
+    vec::Vec,
+};
+
+use crate::__private::wasip1;
+use crate::{
+    memory::{HostMemory, WasmAccess, WasmPathAccess, WasmPathComponent},
+    wasi::file::{
+        FilestatWithoutDevice, SYMLINK_MAX_DEPTH, Wasip1LFS,
+        dirent::{DIRENT_SIZE, DirentReader},
+        heap::lfs::{VFSHeapInode, VFSHeapLFS, VFSHeapNode},
+        snapshot::{SnapshotReader, SnapshotWriter, Wasip1Snapshot},
+        stdio::StdIO,
+    },
+};
+
+/// Copy-on-write file system
+/// which lays a writable heap layer over a read-only lower layer,
+/// e.g. a sysroot made with `ConstFiles!`.
+///
+/// Reads fall through to the lower layer.
+/// A file is copied to the upper layer on its first write,
+/// and removing an entry of the lower layer records a whiteout,
+/// so `fd_readdir` shows the merged listing of both layers.
+///
+/// Only the first pre-opened directory of the lower layer is overlaid.
+pub struct VFSOverlayLFS<Lower: Wasip1LFS, StdIo: StdIO + 'static>
+where
+    Lower::Inode: Copy,
+{
+    lower: Lower,
+    upper: VFSHeapLFS<StdIo>,
+    root: VFSOverlayNode<Lower::Inode>,
+    /// inode `n` is stored at `nodes[n - 1]`, root is inode 0
+    nodes: Vec<VFSOverlayNode<Lower::Inode>>,
+    /// (parent, name) -> inode
+    children: BTreeMap<(usize, String), usize>,
+    /// (parent, name) of the removed lower entries.
+    /// A whiteout is kept when the name is created again,
+    /// so the new entry does not merge with the lower one.
+    whiteouts: BTreeSet<(usize, String)>,
+    /// nodes whose lower entry was looked up,
+    /// which stays as it was since the lower layer is read-only
+    lower_known: BTreeSet<usize>,
+    /// freed node numbers, handed out again by [`VFSOverlayLFS::lookup`]
+    free_nodes: Vec<usize>,
+    /// number of descriptors of each opened node
+    opened: BTreeMap<usize, usize>,
+    /// nodes removed while opened, freed on their last close
+    unlinked: BTreeSet<usize>,
+}
+
+#[derive(Debug)]
+pub struct VFSOverlayNode<Inode> {
+    pub parent: usize,
+    pub name: String,
+    /// inode of the upper layer, once created or copied up
+    pub upper: Option<usize>,
+    pub lower: Option<VFSOverlayLower<Inode>>,
+}
+
+/// Entry of the lower layer.
+/// Symbolic links cannot be opened through [`Wasip1LFS`],
+/// so their target is kept instead.
+#[derive(Debug)]
+pub enum VFSOverlayLower<Inode> {
+    Inode(Inode),
+    Symlink(String),
+}
+
+impl<Lower: Wasip1LFS, StdIo: StdIO + 'static> VFSOverlayLFS<Lower, StdIo>
+where
+    Lower::Inode: Copy,
+{
+    pub const fn new(lower: Lower) -> Self {
+        Self {
+            lower,
+            // the lower layer answers `fd_prestat_dir_name`
+            upper: VFSHeapLFS::new(""),
+            root: VFSOverlayNode {
+                parent: 0,
+                name: String::new(),
+                upper: Some(0),
+                lower: Some(VFSOverlayLower::Inode(Lower::PRE_OPEN[0])),
+            },
+            nodes: Vec::new(),
+            children: BTreeMap::new(),
+            whiteouts: BTreeSet::new(),
+            lower_known: BTreeSet::new(),
+            free_nodes: Vec::new(),
+            opened: BTreeMap::new(),
+            unlinked: BTreeSet::new(),
+        }
+    }
+
+    #[inline]
+    pub const fn lower(&self) -> &Lower {
+        &self.lower
+    }
+
+    #[inline]
+    pub const fn lower_mut(&mut self) -> &mut Lower {
+        &mut self.lower
+    }
+
+    /// The host can read the written files back with [`VFSHeapLFS::get_file`].
+    #[inline]
+    pub const fn upper(&self) -> &VFSHeapLFS<StdIo> {
+        &self.upper
+    }
+
+    #[inline]
+    pub const fn upper_mut(&mut self) -> &mut VFSHeapLFS<StdIo> {
+        &mut self.upper
+    }
+
+    #[inline]
+    pub fn get(&self, inode: usize) -> Option<&VFSOverlayNode<Lower::Inode>> {
+        match inode {
+            0 => Some(&self.root),
+            inode => self.nodes.get(inode - 1),
+        }
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, inode: usize) -> Option<&mut VFSOverlayNode<Lower::Inode>> {
+        match inode {
+            0 => Some(&mut self.root),
+            inode => self.nodes.get_mut(inode - 1),
+        }
+    }
+
+    /// Whether the entry `name` of `dir` was removed from the lower layer.
+    #[inline]
+    pub fn is_whiteout(&self, dir: usize, name: &str) -> bool {
+        self.whiteouts.contains(&(dir, String::from(name)))
+    }
+
+    pub fn is_dir(&self, inode: usize) -> bool {
+        match self.get(inode) {
+            Some(VFSOverlayNode {
+                upper: Some(upper), ..
+            }) => self.upper.is_dir(*upper),
+            Some(VFSOverlayNode {
+                lower: Some(VFSOverlayLower::Inode(lower)),
+                ..
+            }) => self.lower.is_dir(*lower),
+            _ => false,
+        }
+    }
+
+    /// The lower directory merged into the directory,
+    /// `None` if the upper layer hides it.
+    fn lower_dir(&self, inode: usize) -> Option<Lower::Inode> {
+        match self.get(inode)? {
+            VFSOverlayNode {
+                upper,
+                lower: Some(VFSOverlayLower::Inode(lower)),
+                ..
+            } if self.lower.is_dir(*lower)
+                && upper.is_none_or(|upper| self.upper.is_dir(upper)) =>
+            {
+                Some(*lower)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn symlink_target(&self, inode: usize) -> Option<&str> {
+        match self.get(inode)? {
+            VFSOverlayNode {
+                upper: Some(upper), ..
+            } => match &self.upper.get(*upper)?.node {
+                VFSHeapNode::Symlink(target) => Some(target),
+                _ => None,
+            },
+            VFSOverlayNode {
+                lower: Some(VFSOverlayLower::Symlink(target)),
+                ..
+            } => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Whether `ancestor` is `inode` or one of its parents.
+    pub fn is_ancestor(&self, ancestor: usize, mut inode: usize) -> bool {
+        loop {
+            if inode == ancestor {
+                return true;
+            }
+            if inode == 0 {
+                return false;
+            }
+            match self.get(inode) {
+                Some(node) => inode = node.parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// Stats `name` in the lower directory.
+    /// Only an existing file or directory is opened to get its inode,
+    /// once per node since [`Self::lookup`] keeps the result.
+    fn lookup_lower(
+        &mut self,
+        dir: Lower::Inode,
+        name: &str,
+    ) -> Result<Option<VFSOverlayLower<Lower::Inode>>, wasip1::Errno> {
+        let status =
+            match self
+                .lower
+                .path_filestat_get_raw::<HostMemory>(dir, 0, name.as_ptr(), name.len())
+            {
+                Ok(status) => status,
+                Err(wasip1::ERRNO_NOENT) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+
+        if status.filetype == wasip1::FILETYPE_SYMBOLIC_LINK {
+            let mut target = vec![0; status.size as usize];
+            let len = self.lower.path_readlink_raw::<HostMemory>(
+                dir,
+                name.as_ptr(),
+                name.len(),
+                target.as_mut_ptr(),
+                target.len(),
+            )?;
+            target.truncate(len);
+
+            let target = String::from_utf8(target).map_err(|_| wasip1::ERRNO_ILSEQ)?;
+
+            return Ok(Some(VFSOverlayLower::Symlink(target)));
+        }
+
+        let inode = self.lower.path_open_raw::<HostMemory>(
+            dir,
+            0,
+            name.as_ptr(),
+            name.len(),
+            0,
+            0,
+            0,
+            0,
+        )?;
+        // only the inode is kept, no descriptor stays open on the lower layer
+        self.lower.fd_close_raw::<HostMemory>(inode);
+
+        Ok(Some(VFSOverlayLower::Inode(inode)))
+    }
+
+    /// Looks `name` up in the merged directory.
+    /// An entry of the upper layer hides the lower one,
+    /// unless both are directories.
+    pub fn lookup(&mut self, dir: usize, name: &str) -> Result<usize, wasip1::Errno> {
+        let node = self.get(dir).ok_or(wasip1::ERRNO_BADF)?;
+
+        if !self.is_dir(dir) {
+            return Err(wasip1::ERRNO_NOTDIR);
+        }
+
+        let upper = match node.upper {
+            Some(upper_dir) => match self.upper.lookup(upper_dir, name) {
+                Ok(upper) => Some(upper),
+                Err(wasip1::ERRNO_NOENT) => None,
+                Err(e) => return Err(e),
+            },
+            None => None,
+        };
+
+        let key = (dir, String::from(name));
+        let cached = self.children.get(&key).copied();
+
+        let (lower, known) = match self.lower_dir(dir) {
+            Some(_) if self.is_whiteout(dir, name) => (None, false),
+            Some(_)
+                if let Some(inode) = cached
+                    && self.lower_known.contains(&inode) =>
+            {
+                (self.get_mut(inode).unwrap().lower.take(), true)
+            }
+            Some(lower_dir) => (self.lookup_lower(lower_dir, name)?, true),
+            None => (None, false),
+        };
+
+        if upper.is_none() && lower.is_none() {
+            return Err(wasip1::ERRNO_NOENT);
+        }
+
+        let inode = match cached {
+            Some(inode) => inode,
+            None => {
+                let node = VFSOverlayNode {
+                    parent: dir,
+                    name: key.1.clone(),
+                    upper: None,
+                    lower: None,
+                };
+                let inode = match self.free_nodes.pop() {
+                    Some(inode) => {
+                        self.nodes[inode - 1] = node;
+                        inode
+                    }
+                    None => {
+                        self.nodes.push(node);
+                        self.nodes.len()
+                    }
+                };
+                self.children.insert(key, inode);
+                inode
+            }
+        };
+
+        let node = self.get_mut(inode).unwrap();
+        node.upper = upper;
+        node.lower = lower;
+        if known {
+            self.lower_known.insert(inode);
+        }
+
+        Ok(inode)
+    }
+
+    fn read_lower(&mut self, inode: Lower::Inode) -> Result<Vec<u8>, wasip1::Errno> {
+        let size = self.lower.fd_filestat_get_raw::<HostMemory>(inode)?.size as usize;
+
+        let mut data = vec![0; size];
+        let mut read = 0;
+
+        while read < size {
+            let nread = self.lower.fd_pread_raw::<HostMemory>(
+                inode,
+                data[read..].as_mut_ptr(),
+                size - read,
+                read,
+            )?;
+            if nread == 0 {
+                break;
+            }
+            read += nread;
+        }
+
+        data.truncate(read);
+
+        Ok(data)
+    }
+
+    /// Names in the lower directory, without `.` and `..`.
+    fn read_lower_dir(&mut self, dir: Lower::Inode) -> Result<Vec<String>, wasip1::Errno> {
+        let mut names = Vec::new();
+        let mut buf = vec![0u8; DIRENT_SIZE + 256];
+        let mut cookie = 0;
+
+        loop {
+            let (n, next_cookie) = self.lower.fd_readdir_raw::<HostMemory>(
+                dir,
+                buf.as_mut_ptr(),
+                buf.len(),
+                cookie,
+            )?;
+
+            let mut entries = DirentReader::new(&buf[..n]);
+            for (_, _, _, name) in entries.by_ref() {
+                if name != b"." && name != b".." {
+                    let name = core::str::from_utf8(name).map_err(|_| wasip1::ERRNO_ILSEQ)?;
+                    names.push(String::from(name));
+                }
+            }
+
+            if n < buf.len() {
+                return Ok(names);
+            }
+
+            if next_cookie == cookie {
+                // the name did not fit, read the entry again with a larger buffer
+                match entries.needed() {
+                    Some(len) if len > buf.len() => buf.resize(len, 0),
+                    _ => return Err(wasip1::ERRNO_IO),
+                }
+            }
+            cookie = next_cookie;
+        }
+    }
+
+    /// Names in the merged directory, sorted, without `.` and `..`.
+    pub fn read_dir(&mut self, dir: usize) -> Result<Vec<String>, wasip1::Errno> {
+        let node = self.get(dir).ok_or(wasip1::ERRNO_BADF)?;
+
+        if !self.is_dir(dir) {
+            return Err(wasip1::ERRNO_NOTDIR);
+        }
+
+        let mut names = BTreeSet::new();
+
+        if let Some(upper) = node.upper
+            && let Some(VFSHeapNode::Dir(entries)) = self.upper.get(upper).map(|node| &node.node)
+        {
+            names.extend(entries.keys().cloned());
+        }
+
+        if let Some(lower) = self.lower_dir(dir) {
+            for name in self.read_lower_dir(lower)? {
+                if !self.is_whiteout(dir, &name) {
+                    names.insert(name);
+                }
+            }
+        }
+
+        Ok(names.into_iter().collect())
+    }
+
+    /// Copies the entry and its parents to the upper layer
+    /// and returns its inode in the upper layer.
+    /// Directories are copied without their entries,
+    /// the lower ones stay visible through the merge.
+    pub fn copy_up(&mut self, inode: usize) -> Result<usize, wasip1::Errno> {
+        self.copy_up_with(inode, true)
+    }
+
+    /// `with_data` is false when the file is truncated anyway.
+    fn copy_up_with(&mut self, inode: usize, with_data: bool) -> Result<usize, wasip1::Errno> {
+        let node = self.get(inode).ok_or(wasip1::ERRNO_BADF)?;
+
+        if let Some(upper) = node.upper {
+            return Ok(upper);
+        }
+
+        // a removed entry kept open has no name to copy it up to
+        if self.unlinked.contains(&inode) {
+            return Err(wasip1::ERRNO_NOENT);
+        }
+
+        let (parent, name) = (node.parent, node.name.clone());
+        let mut times = None;
+
+        let upper_node = match &node.lower {
+            Some(VFSOverlayLower::Symlink(target)) => VFSHeapNode::Symlink(target.clone()),
+            Some(VFSOverlayLower::Inode(lower)) => {
+                let lower = *lower;
+                let status = self.lower.fd_filestat_get_raw::<HostMemory>(lower)?;
+                times = Some((status.atim, status.mtim, status.ctim));
+
+                if self.lower.is_dir(lower) {
+                    VFSHeapNode::Dir(BTreeMap::new())
+                } else if with_data {
+                    VFSHeapNode::File(self.read_lower(lower)?)
+                } else {
+                    VFSHeapNode::File(Vec::new())
+                }
+            }
+            None => return Err(wasip1::ERRNO_BADF),
+        };
+
+        let upper_parent = self.copy_up(parent)?;
+        let upper = self.upper.insert(upper_parent, name, upper_node)?;
+
+        // the copy keeps the times of the lower entry
+        if let (Some((atim, mtim, ctim)), Some(node)) = (times, self.upper.get_mut(upper)) {
+            node.atim = atim;
+            node.mtim = mtim;
+            node.ctim = ctim;
+        }
+
+        self.get_mut(inode).unwrap().upper = Some(upper);
+
+        // the copy is freed on the last close of the descriptors already open
+        for _ in 0..self.opened.get(&inode).copied().unwrap_or(0) {
+            self.upper.opened(upper);
+        }
+
+        Ok(upper)
+    }
+
+    /// Empties the file.
+    /// A lower file is copied up without reading its data.
+    pub fn truncate(&mut self, inode: usize) -> Result<(), wasip1::Errno> {
+        let upper = self.copy_up_with(inode, false)?;
+
+        if let Some(VFSHeapInode {
+            node: VFSHeapNode::File(file),
+            ..
+        }) = self.upper.get_mut(upper)
+        {
+            file.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Creates `name` in the upper layer and returns its inode.
+    pub fn create(
+        &mut self,
+        dir: usize,
+        name: String,
+        node: VFSHeapNode,
+    ) -> Result<usize, wasip1::Errno> {
+        match self.lookup(dir, &name) {
+            Ok(_) => return Err(wasip1::ERRNO_EXIST),
+            Err(wasip1::ERRNO_NOENT) => {}
+            Err(e) => return Err(e),
+        }
+
+        let upper_dir = self.copy_up(dir)?;
+        self.upper.insert(upper_dir, name.clone(), node)?;
+
+        self.lookup(dir, &name)
+    }
+
+    /// Removes the entry `name` of `dir`, which is `inode`.
+    /// The upper entry is freed and the lower one gets a whiteout.
+    /// Descriptors still pointing to it keep reading and writing it until closed,
+    /// except that an entry only of the lower layer is not copied up anymore.
+    fn remove(&mut self, dir: usize, name: &str, inode: usize) -> Result<(), wasip1::Errno> {
+        let node = self.get(inode).ok_or(wasip1::ERRNO_BADF)?;
+        let has_lower = node.lower.is_some();
+
+        if let Some(upper) = node.upper {
+            let upper_dir = self
+                .get(dir)
+                .and_then(|dir| dir.upper)
+                .ok_or(wasip1::ERRNO_BADF)?;
+            self.upper.unlink(upper_dir, name)?;
+            // deferred by the upper layer while the node is opened
+            self.upper.free(upper);
+        }
+
+        if has_lower {
+            self.whiteouts.insert((dir, String::from(name)));
+        }
+
+        self.children.remove(&(dir, String::from(name)));
+        self.whiteouts.retain(|(parent, _)| *parent != inode);
+        self.free(inode);
+
+        Ok(())
+    }
+
+    /// Hands the node number out again,
+    /// or keeps the node until its last close if it is opened.
+    fn free(&mut self, inode: usize) {
+        if self.opened.contains_key(&inode) {
+            self.unlinked.insert(inode);
+            return;
+        }
+
+        // entries looked up below a removed directory
+        self.children.retain(|(parent, _), _| *parent != inode);
+        self.lower_known.remove(&inode);
+
+        if let Some(node) = inode.checked_sub(1).and_then(|i| self.nodes.get_mut(i)) {
+            *node = VFSOverlayNode {
+                parent: 0,
+                name: String::new(),
+                upper: None,
+                lower: None,
+            };
+            self.free_nodes.push(inode);
+        }
+    }
+
+    /// Counts a new descriptor of the node, and of its upper entry.
+    pub fn opened(&mut self, inode: usize) {
+        *self.opened.entry(inode).or_default() += 1;
+
+        if let Some(upper) = self.get(inode).and_then(|node| node.upper) {
+            self.upper.opened(upper);
+        }
+    }
+
+    /// Counts a closed descriptor of the node,
+    /// freeing it if it was removed and this was the last one.
+    pub fn closed(&mut self, inode: usize) {
+        if let Some(upper) = self.get(inode).and_then(|node| node.upper) {
+            self.upper.closed(upper);
+        }
+
+        if let Entry::Occupied(mut count) = self.opened.entry(inode) {
+            *count.get_mut() -= 1;
+            if *count.get() == 0 {
+                count.remove();
+                if self.unlinked.remove(&inode) {
+                    self.free(inode);
+                }
+            }
+        }
+    }
+
+    pub fn create_directory(
+        &mut self,
+        dir: usize,
+        name: Option<String>,
+    ) -> Result<(), wasip1::Errno> {
+        let name = name.ok_or(wasip1::ERRNO_EXIST)?;
+
+        self.create(dir, name, VFSHeapNode::Dir(BTreeMap::new()))?;
+
+        Ok(())
+    }
+
+    pub fn remove_directory(
+        &mut self,
+        dir: usize,
+        name: Option<String>,
+    ) -> Result<(), wasip1::Errno> {
+        // `.` and `..` cannot be removed
+        let name = name.ok_or(wasip1::ERRNO_INVAL)?;
+
+        let inode = self.lookup(dir, &name)?;
+
+        if !self.is_dir(inode) {
+            return Err(wasip1::ERRNO_NOTDIR);
+        }
+
+        if !self.read_dir(inode)?.is_empty() {
+            return Err(wasip1::ERRNO_NOTEMPTY);
+        }
+
+        self.remove(dir, &name, inode)
+    }
+
+    pub fn unlink_file(&mut self, dir: usize, name: Option<String>) -> Result<(), wasip1::Errno> {
+        let name = name.ok_or(wasip1::ERRNO_ISDIR)?;
+
+        let inode = self.lookup(dir, &name)?;
+
+        if self.is_dir(inode) {
+            return Err(wasip1::ERRNO_ISDIR);
+        }
+
+        self.remove(dir, &name, inode)
+    }
+
+    /// Moves `old_dir/old_name` to `new_dir/new_name`,
+    /// replacing the target if it is a file or an empty directory.
+    /// Files are copied up first.
+    /// Like overlayfs without `redirect_dir`,
+    /// a directory coming from the lower layer cannot be moved
+    /// and `ERRNO_XDEV` is returned, so the caller falls back to copying.
+    pub fn rename(
+        &mut self,
+        old_dir: usize,
+        old_name: Option<String>,
+        new_dir: usize,
+        new_name: Option<String>,
+    ) -> Result<(), wasip1::Errno> {
+        // `.` and `..` cannot be renamed
+        let old_name = old_name.ok_or(wasip1::ERRNO_INVAL)?;
+        let new_name = new_name.ok_or(wasip1::ERRNO_INVAL)?;
+
+        let inode = self.lookup(old_dir, &old_name)?;
+
+        if self.get(new_dir).is_none() {
+            return Err(wasip1::ERRNO_BADF);
+        }
+        if !self.is_dir(new_dir) {
+            return Err(wasip1::ERRNO_NOTDIR);
+        }
+
+        let is_dir = self.is_dir(inode);
+
+        if is_dir && self.is_ancestor(inode, new_dir) {
+            // cannot move a directory into itself
+            return Err(wasip1::ERRNO_INVAL);
+        }
+
+        if is_dir && self.lower_dir(inode).is_some() {
+            return Err(wasip1::ERRNO_XDEV);
+        }
+
+        match self.lookup(new_dir, &new_name) {
+            Ok(target) if target == inode => return Ok(()),
+            Ok(target) => {
+                match (is_dir, self.is_dir(target)) {
+                    (true, false) => return Err(wasip1::ERRNO_NOTDIR),
+                    (false, true) => return Err(wasip1::ERRNO_ISDIR),
+                    (true, true) if !self.read_dir(target)?.is_empty() => {
+                        return Err(wasip1::ERRNO_NOTEMPTY);
+                    }
+                    _ => {}
+                }
+
+                self.remove(new_dir, &new_name, target)?;
+            }
+            Err(wasip1::ERRNO_NOENT) => {}
+            Err(e) => return Err(e),
+        }
+
+        let has_lower = self.get(inode).unwrap().lower.is_some();
+
+        self.copy_up(inode)?;
+        let upper_old_dir = self.copy_up(old_dir)?;
+        let upper_new_dir = self.copy_up(new_dir)?;
+
+        self.upper.rename(
+            upper_old_dir,
+            Some(old_name.clone()),
+            upper_new_dir,
+            Some(new_name.clone()),
+        )?;
+
+        if has_lower {
+            self.whiteouts.insert((old_dir, old_name.clone()));
+        }
+
+        self.children.remove(&(old_dir, old_name));
+        self.children.insert((new_dir, new_name.clone()), inode);
+
+        let node = self.get_mut(inode).unwrap();
+        node.parent = new_dir;
+        node.name = new_name;
+        node.lower = None;
+        self.lower_known.remove(&inode);
+
+        Ok(())
+    }
+
+    fn component_name<Wasm: WasmAccess>(
+        component: &WasmPathComponent<'_, Wasm>,
+    ) -> Result<String, wasip1::Errno> {
+        match component {
+            WasmPathComponent::Normal(name) => {
+                String::from_utf8(name.iter().collect()).map_err(|_| wasip1::ERRNO_ILSEQ)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn step<Wasm: WasmAccess>(
+        &mut self,
+        current: usize,
+        component: &WasmPathComponent<'_, Wasm>,
+    ) -> Result<usize, wasip1::Errno> {
+        match component {
+            // paths are resolved relative to the pre-opened directory
+            WasmPathComponent::RootDir => Ok(0),
+            WasmPathComponent::CurDir => Ok(current),
+            WasmPathComponent::ParentDir => self
+                .get(current)
+                .map(|node| node.parent)
+                .ok_or(wasip1::ERRNO_NOENT),
+            WasmPathComponent::Normal(..) => {
+                self.lookup(current, &Self::component_name::<Wasm>(component)?)
+            }
+        }
+    }
+
+    /// If the inode is a symbolic link, returns the inode it points to.
+    pub fn follow(&mut self, inode: usize, depth: &mut usize) -> Result<usize, wasip1::Errno> {
+        let Some(target) = self.symlink_target(inode).map(String::from) else {
+            return Ok(inode);
+        };
+
+        *depth += 1;
+        if *depth > SYMLINK_MAX_DEPTH {
+            return Err(wasip1::ERRNO_LOOP);
+        }
+
+        if target.is_empty() {
+            return Err(wasip1::ERRNO_NOENT);
+        }
+
+        let mut current_inode = self.get(inode).unwrap().parent;
+
+        for (i, part) in target.split('/').enumerate() {
+            current_inode = self.follow(current_inode, depth)?;
+
+            current_inode = match part {
+                "" if i == 0 => 0,
+                "" | "." => current_inode,
+                ".." => self
+                    .get(current_inode)
+                    .map(|node| node.parent)
+                    .ok_or(wasip1::ERRNO_NOENT)?,
+                name => self.lookup(current_inode, name)?,
+            };
+        }
+
+        self.follow(current_inode, depth)
+    }
+
+    fn resolve_parent<Wasm: WasmAccess>(
+        &mut self,
+        inode: usize,
+        path_ptr: *const u8,
+        path_len: usize,
+        depth: &mut usize,
+    ) -> Result<(usize, Option<String>), wasip1::Errno> {
+        let path = WasmPathAccess::<Wasm>::new(path_ptr, path_len);
+
+        let mut components = path.components().peekable();
+
+        let mut current_inode = inode;
+
+        while let Some(part) = components.next() {
+            // every component before the last one must be a directory
+            current_inode = self.follow(current_inode, depth)?;
+
+            if components.peek().is_none()
+                && let WasmPathComponent::Normal(..) = part
+            {
+                if !self.is_dir(current_inode) {
+                    return Err(wasip1::ERRNO_NOTDIR);
+                }
+                return Ok((current_inode, Some(Self::component_name(&part)?)));
+            }
+
+            current_inode = self.step(current_inode, &part)?;
+        }
+
+        Ok((current_inode, None))
+    }
+
+    /// Resolves every component except the last one.
+    /// Returns the directory the path lives in
+    /// and the name of the last component, if it is a normal one.
+    /// Symbolic links in the middle of the path are followed.
+    pub fn get_parent_for_path<Wasm: WasmAccess>(
+        &mut self,
+        inode: usize,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(usize, Option<String>), wasip1::Errno> {
+        self.resolve_parent::<Wasm>(inode, path_ptr, path_len, &mut 0)
+    }
+
+    /// The last symbolic link is followed
+    /// only when `LOOKUPFLAGS_SYMLINK_FOLLOW` is set.
+    pub fn get_inode_for_path<Wasm: WasmAccess>(
+        &mut self,
+        inode: usize,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        let mut depth = 0;
+
+        let inode = match self.resolve_parent::<Wasm>(inode, path_ptr, path_len, &mut depth)? {
+            (dir, Some(name)) => self.lookup(dir, &name)?,
+            (inode, None) => inode,
+        };
+
+        if flags & wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW == wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW {
+            self.follow(inode, &mut depth)
+        } else {
+            Ok(inode)
+        }
+    }
+
+    pub fn filestat_from_inode(
+        &mut self,
+        inode: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let node = self.get(inode).ok_or(wasip1::ERRNO_BADF)?;
+
+        let mut status = match node {
+            VFSOverlayNode {
+                upper: Some(upper), ..
+            } => self.upper.filestat_from_inode(*upper)?,
+            VFSOverlayNode {
+                lower: Some(VFSOverlayLower::Inode(lower)),
+                ..
+            } => {
+                let lower = *lower;
+                self.lower.fd_filestat_get_raw::<HostMemory>(lower)?
+            }
+            VFSOverlayNode {
+                lower: Some(VFSOverlayLower::Symlink(target)),
+                ..
+            } => FilestatWithoutDevice {
+                ino: 0,
+                filetype: wasip1::FILETYPE_SYMBOLIC_LINK,
+                nlink: 1,
+                size: target.len() as _,
+                atim: 0,
+                mtim: 0,
+                ctim: 0,
+            },
+            _ => return Err(wasip1::ERRNO_BADF),
+        };
+
+        // both layers number their inodes on their own
+        status.ino = inode as _;
+
+        Ok(status)
+    }
+}
+
+/// Both layers and the merged tree.
+/// The lower entries are looked up again on the next access,
+/// so the inodes of the lower layer are not saved.
+impl<Lower: Wasip1LFS + Wasip1Snapshot, StdIo: StdIO + 'static> Wasip1Snapshot
+    for VFSOverlayLFS<Lower, StdIo>
+where
+    Lower::Inode: Copy,
+{
+    fn snapshot_to(&self, out: &mut SnapshotWriter) {
+        self.lower.snapshot_to(out);
+        self.upper.snapshot_to(out);
+
+        // removed nodes kept for their descriptors are freed like the upper layer does
+        let free = self.free_nodes.iter().collect::<BTreeSet<_>>();
+        out.usize(self.nodes.len());
+        for (i, node) in self.nodes.iter().enumerate() {
+            if free.contains(&(i + 1)) || self.unlinked.contains(&(i + 1)) {
+                out.u8(0);
+                continue;
+            }
+            out.u8(1);
+            out.usize(node.parent);
+            out.str(&node.name);
+            out.option_u64(node.upper.map(|upper| upper as u64));
+        }
+
+        out.usize(self.whiteouts.len());
+        for (dir, name) in &self.whiteouts {
+            out.usize(*dir);
+            out.str(name);
+        }
+    }
+
+    fn restore_from(&mut self, input: &mut SnapshotReader<'_>) -> Result<(), wasip1::Errno> {
+        self.lower.restore_from(input)?;
+
+        let mut upper = VFSHeapLFS::new("");
+        upper.restore_from(input)?;
+
+        let mut nodes = Vec::new();
+        let mut children = BTreeMap::new();
+        let mut free_nodes = Vec::new();
+        for _ in 0..input.usize()? {
+            match input.u8()? {
+                0 => {
+                    nodes.push(VFSOverlayNode {
+                        parent: 0,
+                        name: String::new(),
+                        upper: None,
+                        lower: None,
+                    });
+                    free_nodes.push(nodes.len());
+                    continue;
+                }
+                1 => {}
+                _ => return Err(wasip1::ERRNO_INVAL),
+            }
+
+            let parent = input.usize()?;
+            let name = String::from(input.str()?);
+            let upper = input
+                .option_u64()?
+                .map(|upper| usize::try_from(upper).map_err(|_| wasip1::ERRNO_INVAL))
+                .transpose()?;
+
+            if children
+                .insert((parent, name.clone()), nodes.len() + 1)
+                .is_some()
+            {
+                return Err(wasip1::ERRNO_INVAL);
+            }
+            nodes.push(VFSOverlayNode {
+                parent,
+                name,
+                upper,
+                lower: None,
+            });
+        }
+
+        let mut whiteouts = BTreeSet::new();
+        for _ in 0..input.usize()? {
+            let dir = input.usize()?;
+            whiteouts.insert((dir, String::from(input.str()?)));
+        }
+
+        // the tree from the root has to reach every live node,
+        // each listed once under its parent, so lookups and `is_ancestor` end
+        let mut reached = BTreeSet::new();
+        let mut dirs = vec![0];
+        while let Some(dir) = dirs.pop() {
+            for (_, inode) in children
+                .range((dir, String::new())..)
+                .take_while(|((parent, _), _)| *parent == dir)
+            {
+                reached.insert(*inode);
+                dirs.push(*inode);
+            }
+        }
+        if reached.len() != children.len()
+            || whiteouts
+                .iter()
+                .any(|(dir, _)| *dir != 0 && !reached.contains(dir))
+        {
+            return Err(wasip1::ERRNO_INVAL);
+        }
+
+        // and every upper entry to be one of the upper layer, once
+        let mut uppers = BTreeSet::new();
+        if nodes
+            .iter()
+            .filter_map(|node| node.upper)
+            .any(|inode| inode == 0 || upper.get(inode).is_none() || !uppers.insert(inode))
+        {
+            return Err(wasip1::ERRNO_INVAL);
+        }
+
+        self.upper = upper;
+        self.nodes = nodes;
+        self.children = children;
+        self.whiteouts = whiteouts;
+        self.lower_known.clear();
+        self.free_nodes = free_nodes;
+        self.opened.clear();
+        self.unlinked.clear();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,731 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::__private::wasip1;
+use crate::__private::wasip1::Dircookie;
+
+use crate::{
+    memory::{WasmAccess, WasmArrayAccess},
+    wasi::file::{
+        FilestatWithoutDevice, Wasip1LFS,
+        dirent::{DIRCOOKIE_ENTRIES, DirentWriter},
+        heap::lfs::VFSHeapNode,
+        overlay::lfs::{VFSOverlayLFS, VFSOverlayLower, VFSOverlayNode},
+        stdio::StdIO,
+    },
+};
+
+impl<Lower: Wasip1LFS, StdIo: StdIO + 'static> Wasip1LFS for VFSOverlayLFS<Lower, StdIo>
+where
+    Lower::Inode: Copy,
+{
+    type Inode = usize;
+    const PRE_OPEN: &'static [Self::Inode] = &[0];
+
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        data: *const u8,
+        data_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        if self.get(inode).is_none() {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        if self.is_dir(inode) {
+            return Err(wasip1::ERRNO_ISDIR);
+        }
+
+        let upper = self.copy_up(inode)?;
+
+        self.upper_mut()
+            .fd_pwrite_raw::<Wasm>(upper, data, data_len, offset)
+    }
+
+    fn fd_write_stdout_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.upper_mut().fd_write_stdout_raw::<Wasm>(data, data_len)
+    }
+
+    fn fd_write_stderr_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.upper_mut().fd_write_stderr_raw::<Wasm>(data, data_len)
+    }
+
+    fn is_dir(&self, inode: Self::Inode) -> bool {
+        self.is_dir(inode)
+    }
+
+    fn fd_readdir_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        cookie: Dircookie,
+    ) -> Result<(wasip1::Size, Dircookie), wasip1::Errno> {
+        let names = self.read_dir(inode)?;
+
+        let mut writer = DirentWriter::<Wasm>::new(buf, buf_len, cookie);
+
+        if !writer.dots(inode as _, self.get(inode).unwrap().parent as _) {
+            return Ok(writer.finish());
+        }
+
+        // an entry keeps its inode while it is there, so it is a stable cookie
+        let mut entries = names
+            .into_iter()
+            .map(|name| Ok((self.lookup(inode, &name)?, name)))
+            .collect::<Result<Vec<_>, wasip1::Errno>>()?;
+        entries.sort_unstable();
+
+        for (ino, name) in entries {
+            let filetype = self.filestat_from_inode(ino)?.filetype;
+
+            if !writer.entry(
+                DIRCOOKIE_ENTRIES + ino as Dircookie,
+                ino as _,
+                filetype,
+                name.as_bytes(),
+            ) {
+                break;
+            }
+        }
+
+        Ok(writer.finish())
+    }
+
+    fn path_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let inode = self.get_inode_for_path::<Wasm>(inode, flags, path_ptr, path_len)?;
+
+        self.filestat_from_inode(inode)
+    }
+
+    fn fd_prestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<wasip1::Prestat, wasip1::Errno> {
+        if !Self::PRE_OPEN.contains(&inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        self.lower_mut()
+            .fd_prestat_get_raw::<Wasm>(Lower::PRE_OPEN[0])
+    }
+
+    fn fd_prestat_dir_name_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        dir_path_ptr: *mut u8,
+        dir_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        if !Self::PRE_OPEN.contains(&inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        self.lower_mut().fd_prestat_dir_name_raw::<Wasm>(
+            Lower::PRE_OPEN[0],
+            dir_path_ptr,
+            dir_path_len,
+        )
+    }
+
+    fn fd_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        self.filestat_from_inode(inode)
+    }
+
+    /// The lower layer is never changed,
+    /// so the entry is copied up first.
+    fn fd_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        atim: wasip1::Timestamp,
+        mtim: wasip1::Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        let upper = self.copy_up(inode)?;
+
+        self.upper_mut()
+            .fd_filestat_set_times_raw::<Wasm>(upper, atim, mtim, fst_flags)
+    }
+
+    fn fd_pread_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        match self.get(inode) {
+            Some(VFSOverlayNode {
+                upper: Some(upper), ..
+            }) => {
+                let upper = *upper;
+                self.upper_mut()
+                    .fd_pread_raw::<Wasm>(upper, buf, buf_len, offset)
+            }
+            Some(VFSOverlayNode {
+                lower: Some(VFSOverlayLower::Inode(lower)),
+                ..
+            }) => {
+                let lower = *lower;
+                self.lower_mut()
+                    .fd_pread_raw::<Wasm>(lower, buf, buf_len, offset)
+            }
+            _ => Err(wasip1::ERRNO_BADF),
+        }
+    }
+
+    fn fd_read_stdin_raw<Wasm: WasmAccess>(
+        &mut self,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.upper_mut().fd_read_stdin_raw::<Wasm>(buf, buf_len)
+    }
+
+    fn path_open_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        dir_flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        _: wasip1::Rights,
+        _: wasip1::Fdflags,
+    ) -> Result<Self::Inode, wasip1::Errno> {
+        let (dir, name) = self.get_parent_for_path::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        let inode = match name {
+            Some(name) => match self.lookup(dir, &name) {
+                Ok(inode) => {
+                    if o_flags & (wasip1::OFLAGS_CREAT | wasip1::OFLAGS_EXCL)
+                        == (wasip1::OFLAGS_CREAT | wasip1::OFLAGS_EXCL)
+                    {
+                        return Err(wasip1::ERRNO_EXIST);
+                    }
+
+                    if self.symlink_target(inode).is_none() {
+                        inode
+                    } else if dir_flags & wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW
+                        == wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW
+                    {
+                        self.follow(inode, &mut 0)?
+                    } else {
+                        return Err(wasip1::ERRNO_LOOP);
+                    }
+                }
+                Err(wasip1::ERRNO_NOENT)
+                    if o_flags & wasip1::OFLAGS_CREAT == wasip1::OFLAGS_CREAT =>
+                {
+                    if o_flags & wasip1::OFLAGS_DIRECTORY == wasip1::OFLAGS_DIRECTORY {
+                        return Err(wasip1::ERRNO_INVAL);
+                    }
+
+                    let inode = self.create(dir, name, VFSHeapNode::File(Vec::new()))?;
+                    self.opened(inode);
+                    return Ok(inode);
+                }
+                Err(e) => return Err(e),
+            },
+            None => dir,
+        };
+
+        let is_dir = self.is_dir(inode);
+
+        if o_flags & wasip1::OFLAGS_DIRECTORY == wasip1::OFLAGS_DIRECTORY && !is_dir {
+            return Err(wasip1::ERRNO_NOTDIR);
+        }
+
+        if is_dir
+            && (fs_rights_base & wasip1::RIGHTS_FD_WRITE == wasip1::RIGHTS_FD_WRITE
+                || o_flags & wasip1::OFLAGS_TRUNC == wasip1::OFLAGS_TRUNC)
+        {
+            return Err(wasip1::ERRNO_ISDIR);
+        }
+
+        if o_flags & wasip1::OFLAGS_TRUNC == wasip1::OFLAGS_TRUNC {
+            self.truncate(inode)?;
+        }
+
+        self.opened(inode);
+        Ok(inode)
+    }
+
+    fn fd_close_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) {
+        self.closed(inode);
+    }
+
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (dir, name) = self.get_parent_for_path::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        self.create_directory(dir, name)
+    }
+
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (dir, name) = self.get_parent_for_path::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        self.remove_directory(dir, name)
+    }
+
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (dir, name) = self.get_parent_for_path::<Wasm>(dir_inode, path_ptr, path_len)?;
+
+        self.unlink_file(dir, name)
+    }
+
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_dir_inode: Self::Inode,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_dir_inode: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let (old_dir, old_name) =
+            self.get_parent_for_path::<Wasm>(old_dir_inode, old_path_ptr, old_path_len)?;
+        let (new_dir, new_name) =
+            self.get_parent_for_path::<Wasm>(new_dir_inode, new_path_ptr, new_path_len)?;
+
+        self.rename(old_dir, old_name, new_dir, new_name)
+    }
+
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let inode = self.get_inode_for_path::<Wasm>(dir_inode, 0, path_ptr, path_len)?;
+
+        match self.symlink_target(inode) {
+            Some(target) => {
+                let len = core::cmp::min(target.len(), buf_len);
+                Wasm::memcpy(buf, &target.as_bytes()[..len]);
+                Ok(len)
+            }
+            None => Err(wasip1::ERRNO_INVAL),
+        }
+    }
+
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        dir_inode: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let target = String::from_utf8(
+            WasmArrayAccess::<u8, Wasm>::new(old_path_ptr, old_path_len)
+                .iter()
+                .collect(),
+        )
+        .map_err(|_| wasip1::ERRNO_ILSEQ)?;
+
+        let (dir, name) =
+            self.get_parent_for_path::<Wasm>(dir_inode, new_path_ptr, new_path_len)?;
+        let name = name.ok_or(wasip1::ERRNO_EXIST)?;
+
+        self.create(dir, name, VFSHeapNode::Symlink(target))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use const_struct::const_struct;
+
+    use super::*;
+    use crate::{
+        ConstFiles,
+        memory::HostMemory,
+        mock::MockWasm,
+        wasi::file::{
+            Wasip1FileSystem,
+            constant::{
+                lfs::VFSConstNormalLFS,
+                lfs_raw::{VFSConstNormalFiles, WasiConstFile},
+                vfs::Wasip1ConstVFS,
+            },
+            dirent::DirentReader,
+            snapshot::{SnapshotWriter, Wasip1Snapshot},
+            stdio::DefaultStdIO,
+        },
+    };
+
+    #[const_struct]
+    const FILES: VFSConstNormalFiles<WasiConstFile<&'static str>, 6> = ConstFiles!([(
+        ".",
+        [
+            ("hello.txt", WasiConstFile::new("Hello")),
+            (
+                "lib",
+                [
+                    ("a.rs", WasiConstFile::new("a")),
+                    ("b.rs", WasiConstFile::new("b")),
+                ]
+            ),
+            ("link", @symlink "lib/a.rs"),
+        ]
+    )]);
+
+    type Lower = VFSConstNormalLFS<FilesTy, WasiConstFile<&'static str>, 6, DefaultStdIO>;
+    type Vfs = Wasip1ConstVFS<VFSOverlayLFS<Lower, DefaultStdIO>, 8>;
+
+    fn new_vfs() -> Vfs {
+        Wasip1ConstVFS::new(VFSOverlayLFS::new(Lower::new()))
+    }
+
+    fn read_file(vfs: &mut Vfs, path: &str) -> Result<Vec<u8>, wasip1::Errno> {
+        let fd = MockWasm::open_with(
+            vfs,
+            3,
+            wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+            path,
+            0,
+            MockWasm::RIGHTS,
+            0,
+        )?;
+        let data = MockWasm::read(vfs, fd, 32);
+        assert_eq!(
+            Wasip1FileSystem::fd_close_raw::<HostMemory>(vfs, fd),
+            wasip1::ERRNO_SUCCESS
+        );
+        data
+    }
+
+    /// Names listed by `fd_readdir` on the path, without `.` and `..`, sorted.
+    fn list(vfs: &mut Vfs, path: &str) -> Vec<String> {
+        let mut fd = 0;
+        assert_eq!(
+            Wasip1FileSystem::path_open_raw::<HostMemory>(
+                vfs,
+                3,
+                0,
+                path.as_ptr(),
+                path.len(),
+                wasip1::OFLAGS_DIRECTORY,
+                wasip1::RIGHTS_FD_READDIR,
+                0,
+                0,
+                &mut fd,
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+
+        let mut buf = [0u8; 512];
+        let mut nread = 0;
+        assert_eq!(
+            Wasip1FileSystem::fd_readdir_raw::<HostMemory>(
+                vfs,
+                fd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                0,
+                &mut nread
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+
+        // entries are in the order of their cookies, not of their names
+        let mut names = DirentReader::new(&buf[..nread])
+            .map(|(_, _, _, name)| String::from_utf8(name.to_vec()).unwrap())
+            .filter(|name| name != "." && name != "..")
+            .collect::<Vec<_>>();
+        names.sort();
+
+        names
+    }
+
+    fn path_op(
+        vfs: &mut Vfs,
+        op: fn(&mut Vfs, wasip1::Fd, *const u8, usize) -> wasip1::Errno,
+        path: &str,
+    ) -> wasip1::Errno {
+        op(vfs, 3, path.as_ptr(), path.len())
+    }
+
+    fn rename(vfs: &mut Vfs, old: &str, new: &str) -> wasip1::Errno {
+        Wasip1FileSystem::path_rename_raw::<HostMemory>(
+            vfs,
+            3,
+            old.as_ptr(),
+            old.len(),
+            3,
+            new.as_ptr(),
+            new.len(),
+        )
+    }
+
+    #[test]
+    fn test_overlay_copy_up() {
+        let mut vfs = new_vfs();
+
+        assert_eq!(read_file(&mut vfs, "hello.txt"), Ok(b"Hello".to_vec()));
+        assert_eq!(read_file(&mut vfs, "link"), Ok(b"a".to_vec()));
+        assert!(vfs.lfs().upper().get_file("hello.txt").is_none());
+
+        // copied up on the first write
+        let fd = MockWasm::open(&mut vfs, 3, "hello.txt", 0).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"J"), Ok(1));
+        assert_eq!(vfs.lfs().upper().get_file("hello.txt"), Some(&b"Jello"[..]));
+        assert_eq!(read_file(&mut vfs, "hello.txt"), Ok(b"Jello".to_vec()));
+
+        // the link is resolved in the merged tree
+        let fd = MockWasm::open(&mut vfs, 3, "lib/a.rs", wasip1::OFLAGS_TRUNC).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"fn a() {}"), Ok(9));
+        assert_eq!(read_file(&mut vfs, "link"), Ok(b"fn a() {}".to_vec()));
+
+        MockWasm::open(&mut vfs, 3, "lib/c.rs", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(list(&mut vfs, "lib"), ["a.rs", "b.rs", "c.rs"]);
+        assert_eq!(list(&mut vfs, "."), ["hello.txt", "lib", "link"]);
+
+        assert_eq!(
+            MockWasm::open(
+                &mut vfs,
+                3,
+                "lib/b.rs",
+                wasip1::OFLAGS_CREAT | wasip1::OFLAGS_EXCL
+            ),
+            Err(wasip1::ERRNO_EXIST)
+        );
+        assert_eq!(
+            path_op(
+                &mut vfs,
+                Wasip1FileSystem::path_create_directory_raw::<HostMemory>,
+                "lib"
+            ),
+            wasip1::ERRNO_EXIST
+        );
+    }
+
+    #[test]
+    fn test_overlay_whiteout() {
+        let mut vfs = new_vfs();
+        let unlink = Wasip1FileSystem::path_unlink_file_raw::<HostMemory>;
+        let rmdir = Wasip1FileSystem::path_remove_directory_raw::<HostMemory>;
+        let mkdir = Wasip1FileSystem::path_create_directory_raw::<HostMemory>;
+
+        assert_eq!(path_op(&mut vfs, unlink, "lib/a.rs"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(read_file(&mut vfs, "lib/a.rs"), Err(wasip1::ERRNO_NOENT));
+        assert_eq!(read_file(&mut vfs, "link"), Err(wasip1::ERRNO_NOENT));
+        assert_eq!(list(&mut vfs, "lib"), ["b.rs"]);
+
+        // a new file does not bring the lower one back
+        MockWasm::open(&mut vfs, 3, "lib/a.rs", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(read_file(&mut vfs, "lib/a.rs"), Ok(Vec::new()));
+
+        assert_eq!(rename(&mut vfs, "lib", "src"), wasip1::ERRNO_XDEV);
+        assert_eq!(path_op(&mut vfs, rmdir, "lib"), wasip1::ERRNO_NOTEMPTY);
+        assert_eq!(path_op(&mut vfs, unlink, "lib/a.rs"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(path_op(&mut vfs, unlink, "lib/b.rs"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(path_op(&mut vfs, rmdir, "lib"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(list(&mut vfs, "."), ["hello.txt", "link"]);
+
+        // the new directory is opaque
+        assert_eq!(path_op(&mut vfs, mkdir, "lib"), wasip1::ERRNO_SUCCESS);
+        assert!(list(&mut vfs, "lib").is_empty());
+
+        assert_eq!(
+            rename(&mut vfs, "hello.txt", "lib/hello.txt"),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(read_file(&mut vfs, "hello.txt"), Err(wasip1::ERRNO_NOENT));
+        assert_eq!(read_file(&mut vfs, "lib/hello.txt"), Ok(b"Hello".to_vec()));
+        assert_eq!(list(&mut vfs, "."), ["lib", "link"]);
+
+        assert_eq!(rename(&mut vfs, "lib", "src"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(list(&mut vfs, "src"), ["hello.txt"]);
+    }
+
+    #[test]
+    fn test_overlay_unlink_while_open() {
+        let mut vfs = new_vfs();
+        let unlink = Wasip1FileSystem::path_unlink_file_raw::<HostMemory>;
+        let ino = |vfs: &mut Vfs, fd| {
+            let mut filestat = core::mem::MaybeUninit::<wasip1::Filestat>::uninit();
+            assert_eq!(
+                Wasip1FileSystem::fd_filestat_get_raw::<HostMemory>(vfs, fd, filestat.as_mut_ptr()),
+                wasip1::ERRNO_SUCCESS
+            );
+            unsafe { filestat.assume_init() }.ino
+        };
+        let seek_read = |vfs: &mut Vfs, fd| {
+            let mut offset = 0;
+            assert_eq!(
+                Wasip1FileSystem::fd_seek_raw::<HostMemory>(
+                    vfs,
+                    fd,
+                    0,
+                    wasip1::WHENCE_SET,
+                    &mut offset
+                ),
+                wasip1::ERRNO_SUCCESS
+            );
+            MockWasm::read(vfs, fd, 32)
+        };
+        let close = |vfs: &mut Vfs, fd| {
+            assert_eq!(
+                Wasip1FileSystem::fd_close_raw::<HostMemory>(vfs, fd),
+                wasip1::ERRNO_SUCCESS
+            );
+        };
+
+        // an upper file stays readable and writable until the last close
+        let fd = MockWasm::open(&mut vfs, 3, "out.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"kept"), Ok(4));
+        assert_eq!(path_op(&mut vfs, unlink, "out.txt"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(read_file(&mut vfs, "out.txt"), Err(wasip1::ERRNO_NOENT));
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"!"), Ok(1));
+        assert_eq!(seek_read(&mut vfs, fd), Ok(b"kept!".to_vec()));
+
+        // a snapshot has no descriptors, so not the removed file
+        let mut restored = new_vfs();
+        assert_eq!(restored.restore(&vfs.snapshot()), Ok(()));
+        assert_eq!(list(&mut restored, "."), ["hello.txt", "lib", "link"]);
+
+        let freed = ino(&mut vfs, fd);
+        close(&mut vfs, fd);
+
+        // and the node number is handed out again
+        for _ in 0..4 {
+            let fd = MockWasm::open(&mut vfs, 3, "tmp", wasip1::OFLAGS_CREAT).unwrap();
+            assert_eq!(ino(&mut vfs, fd), freed);
+            assert_eq!(seek_read(&mut vfs, fd), Ok(Vec::new()));
+            assert_eq!(path_op(&mut vfs, unlink, "tmp"), wasip1::ERRNO_SUCCESS);
+            close(&mut vfs, fd);
+        }
+
+        // a lower file can still be read, not copied up anymore
+        let fd = MockWasm::open(&mut vfs, 3, "hello.txt", 0).unwrap();
+        assert_eq!(
+            path_op(&mut vfs, unlink, "hello.txt"),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(seek_read(&mut vfs, fd), Ok(b"Hello".to_vec()));
+        assert_eq!(
+            MockWasm::write(&mut vfs, fd, b"J"),
+            Err(wasip1::ERRNO_NOENT)
+        );
+        close(&mut vfs, fd);
+        assert_eq!(list(&mut vfs, "."), ["lib", "link"]);
+    }
+
+    #[test]
+    fn test_overlay_snapshot() {
+        let mut vfs = new_vfs();
+        let unlink = Wasip1FileSystem::path_unlink_file_raw::<HostMemory>;
+        let mkdir = Wasip1FileSystem::path_create_directory_raw::<HostMemory>;
+
+        let fd = MockWasm::open(&mut vfs, 3, "hello.txt", 0).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"J"), Ok(1));
+        assert_eq!(path_op(&mut vfs, unlink, "lib/b.rs"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(path_op(&mut vfs, mkdir, "out"), wasip1::ERRNO_SUCCESS);
+        let fd = MockWasm::open(&mut vfs, 3, "out/log", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(MockWasm::write(&mut vfs, fd, b"done"), Ok(4));
+
+        let snapshot = vfs.snapshot();
+
+        let mut restored = new_vfs();
+        assert_eq!(restored.restore(&snapshot), Ok(()));
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(read_file(&mut restored, "hello.txt"), Ok(b"Jello".to_vec()));
+        assert_eq!(read_file(&mut restored, "out/log"), Ok(b"done".to_vec()));
+        assert_eq!(read_file(&mut restored, "link"), Ok(b"a".to_vec()));
+        assert_eq!(list(&mut restored, "lib"), ["a.rs"]);
+        assert_eq!(
+            list(&mut restored, "."),
+            ["hello.txt", "lib", "link", "out"]
+        );
+
+        // broken ones are refused
+        let mut fresh = new_vfs();
+        assert_eq!(
+            fresh.restore(&snapshot[..snapshot.len() - 1]),
+            Err(wasip1::ERRNO_INVAL)
+        );
+        assert_eq!(fresh.restore(b"WVFS\xff\xff"), Err(wasip1::ERRNO_NOTSUP));
+        assert_eq!(fresh.restore(b"tar"), Err(wasip1::ERRNO_INVAL));
+    }
+
+    #[test]
+    fn test_overlay_restore_tree() {
+        let mut vfs = new_vfs();
+        // both layers as they are, then the nodes, each a parent, a name and an upper inode
+        let mut restore = |nodes: &[Option<(usize, &str, Option<u64>)>]| {
+            let mut out = SnapshotWriter::new();
+            vfs.lfs().lower().snapshot_to(&mut out);
+            vfs.lfs().upper().snapshot_to(&mut out);
+            out.usize(nodes.len());
+            for node in nodes {
+                out.u8(node.is_some() as u8);
+                if let Some((parent, name, upper)) = node {
+                    out.usize(*parent);
+                    out.str(name);
+                    out.option_u64(*upper);
+                }
+            }
+            out.usize(0);
+            vfs.restore(&out.into_bytes())
+        };
+
+        assert_eq!(restore(&[Some((0, "lib", None)), None]), Ok(()));
+
+        // nodes whose parents point at each other, out of the tree
+        assert_eq!(
+            restore(&[Some((2, "a", None)), Some((1, "b", None))]),
+            Err(wasip1::ERRNO_INVAL)
+        );
+        // a node under a freed one, and one listed twice
+        assert_eq!(
+            restore(&[None, Some((1, "a", None))]),
+            Err(wasip1::ERRNO_INVAL)
+        );
+        assert_eq!(
+            restore(&[Some((0, "a", None)), Some((0, "a", None))]),
+            Err(wasip1::ERRNO_INVAL)
+        );
+        // an upper inode the upper layer does not have
+        assert_eq!(
+            restore(&[Some((0, "a", Some(7)))]),
+            Err(wasip1::ERRNO_INVAL)
+        );
+
+        assert_eq!(read_file(&mut vfs, "lib/a.rs"), Ok(b"a".to_vec()));
+    }
+}
@@ -0,0 +1,409 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec,
+    vec::Vec,
+};
+
+use crate::__private::wasip1;
+use crate::{
+    memory::{HostMemory, WasmAccess},
+    wasi::file::{
+        FilestatWithoutDevice, Wasip1LFS,
+        dirent::{DIRENT_SIZE, DirentReader},
+        snapshot::{SnapshotReader, SnapshotWriter, Wasip1Snapshot},
+    },
+};
+
+/// Limits of a [`VFSQuotaLFS`], `usize::MAX` for no limit.
+///
+/// ```ignore
+/// const LIMITS: VFSQuotaLimits = VFSQuotaLimits {
+///     total_bytes: 16 * 1024 * 1024,
+///     ..VFSQuotaLimits::UNLIMITED
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VFSQuotaLimits {
+    /// bytes of every file and symbolic link, `ERRNO_NOSPC` past it
+    pub total_bytes: usize,
+    /// bytes of one file, `ERRNO_FBIG` past it
+    pub file_bytes: usize,
+    /// files, directories and symbolic links, `ERRNO_DQUOT` past it
+    pub inodes: usize,
+    /// components of the path of an entry from the root,
+    /// `a/b/c` is 3, `ERRNO_DQUOT` past it
+    pub depth: usize,
+}
+
+impl VFSQuotaLimits {
+    pub const UNLIMITED: Self = Self {
+        total_bytes: usize::MAX,
+        file_bytes: usize::MAX,
+        inodes: usize::MAX,
+        depth: usize::MAX,
+    };
+}
+
+impl Default for VFSQuotaLimits {
+    fn default() -> Self {
+        Self::UNLIMITED
+    }
+}
+
+/// What a [`VFSQuotaLFS`] counts against its limits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VFSQuotaUsage {
+    pub bytes: usize,
+    pub inodes: usize,
+}
+
+/// Quota layer
+/// which limits what the guest can store in another writable one,
+/// e.g. a heap LFS run by an untrusted program.
+///
+/// A write which does not fit is cut to what fits,
+/// and fails only when nothing fits.
+///
+/// Only what is made through this layer is counted,
+/// the entries already in the inner file system are free,
+/// and removing them frees nothing.
+/// The depth is taken from the path as written,
+/// and a renamed directory is checked with the entries below it.
+pub struct VFSQuotaLFS<Inner: Wasip1LFS>
+where
+    Inner::Inode: Copy + Ord,
+{
+    inner: Inner,
+    limits: VFSQuotaLimits,
+    usage: VFSQuotaUsage,
+    /// depth and the number of opens of the opened inodes,
+    /// and the `ino` of a removed one, freed on its last close
+    depths: BTreeMap<Inner::Inode, (usize, usize, Option<wasip1::Inode>)>,
+    /// whether the entry is counted and the bytes counted for it,
+    /// by the `ino` of the inner file system
+    counted: BTreeMap<wasip1::Inode, (bool, usize)>,
+}
+
+impl<Inner: Wasip1LFS> VFSQuotaLFS<Inner>
+where
+    Inner::Inode: Copy + Ord,
+{
+    pub const fn new(inner: Inner, limits: VFSQuotaLimits) -> Self {
+        Self {
+            inner,
+            limits,
+            usage: VFSQuotaUsage {
+                bytes: 0,
+                inodes: 0,
+            },
+            depths: BTreeMap::new(),
+            counted: BTreeMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &Inner {
+        &self.inner
+    }
+
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut Inner {
+        &mut self.inner
+    }
+
+    #[inline]
+    pub fn limits(&self) -> VFSQuotaLimits {
+        self.limits
+    }
+
+    /// Takes effect from the next change,
+    /// what is already stored over the new limits is kept.
+    #[inline]
+    pub fn set_limits(&mut self, limits: VFSQuotaLimits) {
+        self.limits = limits;
+    }
+
+    #[inline]
+    pub fn usage(&self) -> VFSQuotaUsage {
+        self.usage
+    }
+
+    /// Depth of the path from the directory, without looking anything up.
+    /// A directory not opened through this layer is taken as the root.
+    pub fn depth<Wasm: WasmAccess>(
+        &self,
+        dir: Inner::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> usize {
+        let mut depth = self.depths.get(&dir).map_or(0, |(depth, ..)| *depth);
+
+        let path = Wasm::get_array(path_ptr, path_len);
+        if path.first() == Some(&b'/') {
+            depth = 0;
+        }
+
+        for part in path.split(|c| *c == b'/') {
+            match part {
+                b"" | b"." => {}
+                b".." => depth = depth.saturating_sub(1),
+                _ => depth += 1,
+            }
+        }
+
+        depth
+    }
+
+    /// `ERRNO_DQUOT` if one more entry at the path is over the limits.
+    pub(crate) fn check_new_entry<Wasm: WasmAccess>(
+        &self,
+        dir: Inner::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        if self.usage.inodes >= self.limits.inodes
+            || self.depth::<Wasm>(dir, path_ptr, path_len) > self.limits.depth
+        {
+            return Err(wasip1::ERRNO_DQUOT);
+        }
+
+        Ok(())
+    }
+
+    /// How many of `len` bytes written at `offset`
+    /// to a file of `size` bytes fit in the limits.
+    pub(crate) fn fit(
+        &self,
+        size: usize,
+        offset: usize,
+        len: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        let end = offset.saturating_add(len);
+        if end <= size || len == 0 {
+            return Ok(len);
+        }
+
+        let free = self.limits.total_bytes.saturating_sub(self.usage.bytes);
+        let max_end = core::cmp::min(self.limits.file_bytes, size.saturating_add(free));
+
+        if max_end <= offset {
+            return Err(match self.limits.file_bytes <= offset {
+                true => wasip1::ERRNO_FBIG,
+                false => wasip1::ERRNO_NOSPC,
+            });
+        }
+
+        Ok(core::cmp::min(end, max_end) - offset)
+    }
+
+    /// Counts what the file grew by, and frees what it shrank by
+    /// as far as it was counted.
+    pub(crate) fn grown(&mut self, ino: wasip1::Inode, before: usize, after: usize) {
+        let counted = self.counted.entry(ino).or_insert((false, 0));
+
+        if after >= before {
+            counted.1 += after - before;
+            self.usage.bytes += after - before;
+        } else {
+            let freed = core::cmp::min(before - after, counted.1);
+            counted.1 -= freed;
+            self.usage.bytes -= freed;
+        }
+
+        if *counted == (false, 0) {
+            self.counted.remove(&ino);
+        }
+    }
+
+    pub(crate) fn created(&mut self, ino: wasip1::Inode, bytes: usize) {
+        self.usage.inodes += 1;
+        self.usage.bytes += bytes;
+        self.counted.insert(ino, (true, bytes));
+    }
+
+    /// An entry of the inner file system is gone,
+    /// frees what was counted for it once its last link is.
+    /// The inner file system keeps an opened inode until its last close,
+    /// so does the count.
+    pub(crate) fn removed(&mut self, filestat: &FilestatWithoutDevice) {
+        if filestat.filetype != wasip1::FILETYPE_DIRECTORY && filestat.nlink > 1
+            || !self.counted.contains_key(&filestat.ino)
+        {
+            return;
+        }
+
+        let opened = self.depths.keys().copied().collect::<Vec<_>>();
+        let mut kept = false;
+        for inode in opened {
+            if self
+                .inner
+                .fd_filestat_get_raw::<HostMemory>(inode)
+                .is_ok_and(|opened| opened.ino == filestat.ino)
+                && let Some((.., removed)) = self.depths.get_mut(&inode)
+            {
+                *removed = Some(filestat.ino);
+                kept = true;
+            }
+        }
+
+        if !kept {
+            self.free(filestat.ino);
+        }
+    }
+
+    fn free(&mut self, ino: wasip1::Inode) {
+        if let Some((entry, bytes)) = self.counted.remove(&ino) {
+            self.usage.inodes -= entry as usize;
+            self.usage.bytes -= bytes;
+        }
+    }
+
+    /// How many levels of entries the directory has below it,
+    /// 0 for an empty one.
+    pub(crate) fn subtree_depth(&mut self, dir: Inner::Inode) -> Result<usize, wasip1::Errno> {
+        let (dirs, any) = self.read_dir(dir)?;
+        let mut depth = any as usize;
+
+        for name in dirs {
+            let sub = self.inner.path_open_raw::<HostMemory>(
+                dir,
+                0,
+                name.as_ptr(),
+                name.len(),
+                wasip1::OFLAGS_DIRECTORY,
+                0,
+                0,
+                0,
+            )?;
+            let sub_depth = self.subtree_depth(sub);
+            self.inner.fd_close_raw::<HostMemory>(sub);
+
+            depth = core::cmp::max(depth, sub_depth? + 1);
+        }
+
+        Ok(depth)
+    }
+
+    /// Names of the directories in the directory
+    /// and whether it has any entry, without `.` and `..`.
+    fn read_dir(&mut self, dir: Inner::Inode) -> Result<(Vec<String>, bool), wasip1::Errno> {
+        let mut dirs = Vec::new();
+        let mut any = false;
+        let mut buf = vec![0u8; DIRENT_SIZE + 256];
+        let mut cookie = 0;
+
+        loop {
+            let (n, next_cookie) = self.inner.fd_readdir_raw::<HostMemory>(
+                dir,
+                buf.as_mut_ptr(),
+                buf.len(),
+                cookie,
+            )?;
+
+            let mut entries = DirentReader::new(&buf[..n]);
+            for (_, _, d_type, name) in entries.by_ref() {
+                if name != b"." && name != b".." {
+                    any = true;
+                    if d_type == wasip1::FILETYPE_DIRECTORY {
+                        let name = core::str::from_utf8(name).map_err(|_| wasip1::ERRNO_ILSEQ)?;
+                        dirs.push(String::from(name));
+                    }
+                }
+            }
+
+            if n < buf.len() {
+                return Ok((dirs, any));
+            }
+
+            if next_cookie == cookie {
+                // the name did not fit, read the entry again with a larger buffer
+                match entries.needed() {
+                    Some(len) if len > buf.len() => buf.resize(len, 0),
+                    _ => return Err(wasip1::ERRNO_IO),
+                }
+            }
+            cookie = next_cookie;
+        }
+    }
+
+    pub(crate) fn opened(&mut self, inode: Inner::Inode, depth: usize) {
+        self.depths.entry(inode).or_insert((depth, 0, None)).1 += 1;
+    }
+
+    /// Frees a removed inode on its last close.
+    pub(crate) fn closed(&mut self, inode: Inner::Inode) {
+        let Some((_, count, removed)) = self.depths.get_mut(&inode) else {
+            return;
+        };
+        *count -= 1;
+        if *count > 0 {
+            return;
+        }
+
+        let removed = *removed;
+        self.depths.remove(&inode);
+        if let Some(ino) = removed
+            && !self.depths.values().any(|(.., other)| *other == Some(ino))
+        {
+            self.free(ino);
+        }
+    }
+
+    /// Removed inodes still opened, not in a snapshot of the inner file system.
+    fn pending(&self) -> impl Iterator<Item = wasip1::Inode> + '_ {
+        self.depths.values().filter_map(|(.., removed)| *removed)
+    }
+}
+
+/// What it counts, then the inner file system.
+/// The usage is summed from the counts on restore.
+impl<Inner: Wasip1LFS + Wasip1Snapshot> Wasip1Snapshot for VFSQuotaLFS<Inner>
+where
+    Inner::Inode: Copy + Ord,
+{
+    fn snapshot_to(&self, out: &mut SnapshotWriter) {
+        // removed inodes are freed on the close which the snapshot does not have
+        let pending = self.pending().collect::<BTreeSet<_>>();
+        let counted = self
+            .counted
+            .iter()
+            .filter(|(ino, _)| !pending.contains(ino))
+            .collect::<Vec<_>>();
+
+        out.usize(counted.len());
+        for (ino, (entry, bytes)) in counted {
+            out.u64(*ino);
+            out.u8(*entry as u8);
+            out.usize(*bytes);
+        }
+        self.inner.snapshot_to(out);
+    }
+
+    fn restore_from(&mut self, input: &mut SnapshotReader<'_>) -> Result<(), wasip1::Errno> {
+        let mut usage = VFSQuotaUsage::default();
+        let mut counted = BTreeMap::new();
+        for _ in 0..input.usize()? {
+            let ino = input.u64()?;
+            let entry = match input.u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(wasip1::ERRNO_INVAL),
+            };
+            let bytes = input.usize()?;
+
+            usage.inodes += entry as usize;
+            usage.bytes = usage.bytes.checked_add(bytes).ok_or(wasip1::ERRNO_INVAL)?;
+            if counted.insert(ino, (entry, bytes)).is_some() {
+                return Err(wasip1::ERRNO_INVAL);
+            }
+        }
+        self.inner.restore_from(input)?;
+
+        self.usage = usage;
+        self.counted = counted;
+        self.depths.clear();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,652 @@
+use crate::__private::wasip1;
+use crate::__private::wasip1::Dircookie;
+
+use crate::{
+    memory::WasmAccess,
+    wasi::file::{FilestatWithoutDevice, Wasip1LFS, quota::lfs::VFSQuotaLFS},
+};
+
+impl<Inner: Wasip1LFS> Wasip1LFS for VFSQuotaLFS<Inner>
+where
+    Inner::Inode: Copy + Ord,
+{
+    type Inode = Inner::Inode;
+    const PRE_OPEN: &'static [Self::Inode] = Inner::PRE_OPEN;
+
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        data: *const u8,
+        data_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let before = self.inner_mut().fd_filestat_get_raw::<Wasm>(inode)?;
+        let data_len = self.fit(before.size as usize, offset, data_len)?;
+
+        let written = self
+            .inner_mut()
+            .fd_pwrite_raw::<Wasm>(inode, data, data_len, offset)?;
+
+        let after = self.inner_mut().fd_filestat_get_raw::<Wasm>(inode)?.size as usize;
+        self.grown(before.ino, before.size as usize, after);
+
+        Ok(written)
+    }
+
+    fn fd_write_stdout_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.inner_mut().fd_write_stdout_raw::<Wasm>(data, data_len)
+    }
+
+    fn fd_write_stderr_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.inner_mut().fd_write_stderr_raw::<Wasm>(data, data_len)
+    }
+
+    fn is_dir(&self, inode: Self::Inode) -> bool {
+        self.inner().is_dir(inode)
+    }
+
+    fn fd_readdir_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        cookie: Dircookie,
+    ) -> Result<(wasip1::Size, Dircookie), wasip1::Errno> {
+        self.inner_mut()
+            .fd_readdir_raw::<Wasm>(inode, buf, buf_len, cookie)
+    }
+
+    fn path_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        self.inner_mut()
+            .path_filestat_get_raw::<Wasm>(inode, flags, path_ptr, path_len)
+    }
+
+    fn fd_prestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<wasip1::Prestat, wasip1::Errno> {
+        self.inner_mut().fd_prestat_get_raw::<Wasm>(inode)
+    }
+
+    fn fd_prestat_dir_name_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        dir_path_ptr: *mut u8,
+        dir_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.inner_mut()
+            .fd_prestat_dir_name_raw::<Wasm>(inode, dir_path_ptr, dir_path_len)
+    }
+
+    fn fd_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        self.inner_mut().fd_filestat_get_raw::<Wasm>(inode)
+    }
+
+    fn fd_close_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) {
+        self.closed(inode);
+        self.inner_mut().fd_close_raw::<Wasm>(inode);
+    }
+
+    fn fd_sync_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) -> Result<(), wasip1::Errno> {
+        self.inner_mut().fd_sync_raw::<Wasm>(inode)
+    }
+
+    fn fd_seek_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        cursor: usize,
+        offset: wasip1::Filedelta,
+        whence: wasip1::Whence,
+    ) -> Result<usize, wasip1::Errno> {
+        self.inner_mut()
+            .fd_seek_raw::<Wasm>(inode, cursor, offset, whence)
+    }
+
+    fn fd_tell_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        cursor: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        self.inner_mut().fd_tell_raw::<Wasm>(inode, cursor)
+    }
+
+    fn fd_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        atim: wasip1::Timestamp,
+        mtim: wasip1::Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        self.inner_mut()
+            .fd_filestat_set_times_raw::<Wasm>(inode, atim, mtim, fst_flags)
+    }
+
+    fn fd_pread_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.inner_mut()
+            .fd_pread_raw::<Wasm>(inode, buf, buf_len, offset)
+    }
+
+    fn fd_read_stdin_raw<Wasm: WasmAccess>(
+        &mut self,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.inner_mut().fd_read_stdin_raw::<Wasm>(buf, buf_len)
+    }
+
+    fn path_open_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        dir_flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        fs_rights_inheriting: wasip1::Rights,
+        fd_flags: wasip1::Fdflags,
+    ) -> Result<Self::Inode, wasip1::Errno> {
+        let before = match o_flags & (wasip1::OFLAGS_CREAT | wasip1::OFLAGS_TRUNC) {
+            0 => None,
+            _ => self
+                .inner_mut()
+                .path_filestat_get_raw::<Wasm>(dir_ino, dir_flags, path_ptr, path_len)
+                .ok(),
+        };
+
+        let creating = o_flags & wasip1::OFLAGS_CREAT != 0 && before.is_none();
+        if creating {
+            self.check_new_entry::<Wasm>(dir_ino, path_ptr, path_len)?;
+        }
+
+        let inode = self.inner_mut().path_open_raw::<Wasm>(
+            dir_ino,
+            dir_flags,
+            path_ptr,
+            path_len,
+            o_flags,
+            fs_rights_base,
+            fs_rights_inheriting,
+            fd_flags,
+        )?;
+
+        if creating {
+            match self.inner_mut().fd_filestat_get_raw::<Wasm>(inode) {
+                Ok(filestat) => self.created(filestat.ino, 0),
+                Err(e) => {
+                    self.inner_mut().fd_close_raw::<Wasm>(inode);
+                    return Err(e);
+                }
+            }
+        }
+
+        if o_flags & wasip1::OFLAGS_TRUNC != 0
+            && let Some(before) = before
+            && before.filetype != wasip1::FILETYPE_DIRECTORY
+        {
+            self.grown(before.ino, before.size as usize, 0);
+        }
+
+        // counted for every open, every open is paired with a close
+        let depth = self.depth::<Wasm>(dir_ino, path_ptr, path_len);
+        self.opened(inode, depth);
+
+        Ok(inode)
+    }
+
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.check_new_entry::<Wasm>(dir_ino, path_ptr, path_len)?;
+
+        self.inner_mut()
+            .path_create_directory_raw::<Wasm>(dir_ino, path_ptr, path_len)?;
+
+        let filestat = self
+            .inner_mut()
+            .path_filestat_get_raw::<Wasm>(dir_ino, 0, path_ptr, path_len)?;
+        self.created(filestat.ino, 0);
+
+        Ok(())
+    }
+
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let before = self
+            .inner_mut()
+            .path_filestat_get_raw::<Wasm>(dir_ino, 0, path_ptr, path_len)?;
+
+        self.inner_mut()
+            .path_remove_directory_raw::<Wasm>(dir_ino, path_ptr, path_len)?;
+        self.removed(&before);
+
+        Ok(())
+    }
+
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let before = self
+            .inner_mut()
+            .path_filestat_get_raw::<Wasm>(dir_ino, 0, path_ptr, path_len)?;
+
+        self.inner_mut()
+            .path_unlink_file_raw::<Wasm>(dir_ino, path_ptr, path_len)?;
+        self.removed(&before);
+
+        Ok(())
+    }
+
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_dir_ino: Self::Inode,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_dir_ino: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let moved = self.inner_mut().path_filestat_get_raw::<Wasm>(
+            old_dir_ino,
+            0,
+            old_path_ptr,
+            old_path_len,
+        )?;
+
+        // the entries below a directory move with it
+        let below = match moved.filetype {
+            wasip1::FILETYPE_DIRECTORY => {
+                let dir = self.inner_mut().path_open_raw::<Wasm>(
+                    old_dir_ino,
+                    0,
+                    old_path_ptr,
+                    old_path_len,
+                    wasip1::OFLAGS_DIRECTORY,
+                    0,
+                    0,
+                    0,
+                )?;
+                let below = self.subtree_depth(dir);
+                self.inner_mut().fd_close_raw::<Wasm>(dir);
+                below?
+            }
+            _ => 0,
+        };
+
+        let depth = self.depth::<Wasm>(new_dir_ino, new_path_ptr, new_path_len);
+        if depth.saturating_add(below) > self.limits().depth {
+            return Err(wasip1::ERRNO_DQUOT);
+        }
+
+        // the target is replaced
+        let replaced = self
+            .inner_mut()
+            .path_filestat_get_raw::<Wasm>(new_dir_ino, 0, new_path_ptr, new_path_len)
+            .ok();
+
+        self.inner_mut().path_rename_raw::<Wasm>(
+            old_dir_ino,
+            old_path_ptr,
+            old_path_len,
+            new_dir_ino,
+            new_path_ptr,
+            new_path_len,
+        )?;
+
+        if let Some(replaced) = replaced {
+            self.removed(&replaced);
+        }
+
+        Ok(())
+    }
+
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.inner_mut()
+            .path_readlink_raw::<Wasm>(dir_ino, path_ptr, path_len, buf, buf_len)
+    }
+
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        dir_ino: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.check_new_entry::<Wasm>(dir_ino, new_path_ptr, new_path_len)?;
+
+        // the target is stored as the contents
+        if self.limits().total_bytes.saturating_sub(self.usage().bytes) < old_path_len {
+            return Err(wasip1::ERRNO_NOSPC);
+        }
+
+        self.inner_mut().path_symlink_raw::<Wasm>(
+            old_path_ptr,
+            old_path_len,
+            dir_ino,
+            new_path_ptr,
+            new_path_len,
+        )?;
+
+        let filestat = self.inner_mut().path_filestat_get_raw::<Wasm>(
+            dir_ino,
+            0,
+            new_path_ptr,
+            new_path_len,
+        )?;
+        self.created(filestat.ino, filestat.size as usize);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        memory::{HostMemory, WasmAccess},
+        mock::MockWasm,
+        wasi::file::{
+            Wasip1FileSystem,
+            constant::vfs::Wasip1ConstVFS,
+            heap::lfs::VFSHeapLFS,
+            quota::lfs::{VFSQuotaLimits, VFSQuotaUsage},
+            snapshot::SnapshotWriter,
+            stdio::DefaultStdIO,
+        },
+    };
+
+    type Vfs = Wasip1ConstVFS<VFSQuotaLFS<VFSHeapLFS<DefaultStdIO>>, 8>;
+
+    fn pwrite(
+        vfs: &mut Vfs,
+        fd: wasip1::Fd,
+        data: &[u8],
+        offset: wasip1::Filesize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let (iovs, iovs_len) = MockWasm::alloc_ciovs(&[data]);
+        let nwritten = MockWasm::alloc_value::<wasip1::Size>();
+        match Wasip1FileSystem::fd_pwrite_raw::<MockWasm>(vfs, fd, iovs, iovs_len, offset, nwritten)
+        {
+            wasip1::ERRNO_SUCCESS => Ok(MockWasm::load_le(nwritten)),
+            e => Err(e),
+        }
+    }
+
+    fn mkdir(vfs: &mut Vfs, path: &str) -> wasip1::Errno {
+        Wasip1FileSystem::path_create_directory_raw::<HostMemory>(vfs, 3, path.as_ptr(), path.len())
+    }
+
+    #[test]
+    fn test_quota() {
+        let mut vfs = Vfs::new(VFSQuotaLFS::new(
+            VFSHeapLFS::new("."),
+            VFSQuotaLimits {
+                total_bytes: 10,
+                file_bytes: 8,
+                inodes: 3,
+                depth: 2,
+            },
+        ));
+
+        // a write past the limit of the file is cut
+        let a = MockWasm::open(&mut vfs, 3, "a.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(pwrite(&mut vfs, a, b"Hello!", 0), Ok(6));
+        assert_eq!(pwrite(&mut vfs, a, b"World", 6), Ok(2));
+        assert_eq!(pwrite(&mut vfs, a, b"!", 8), Err(wasip1::ERRNO_FBIG));
+        // overwriting takes nothing
+        assert_eq!(pwrite(&mut vfs, a, b"J", 0), Ok(1));
+
+        // and past the limit of every file
+        let b = MockWasm::open(&mut vfs, 3, "b.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(pwrite(&mut vfs, b, b"abcdef", 0), Ok(2));
+        assert_eq!(pwrite(&mut vfs, b, b"c", 2), Err(wasip1::ERRNO_NOSPC));
+        assert_eq!(
+            vfs.lfs().usage(),
+            VFSQuotaUsage {
+                bytes: 10,
+                inodes: 2
+            }
+        );
+
+        assert_eq!(mkdir(&mut vfs, "d"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(mkdir(&mut vfs, "e"), wasip1::ERRNO_DQUOT);
+
+        assert_eq!(
+            Wasip1FileSystem::fd_close_raw::<HostMemory>(&mut vfs, b),
+            wasip1::ERRNO_SUCCESS
+        );
+        let removed = "b.txt";
+        assert_eq!(
+            Wasip1FileSystem::path_unlink_file_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                removed.as_ptr(),
+                removed.len()
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(
+            vfs.lfs().usage(),
+            VFSQuotaUsage {
+                bytes: 8,
+                inodes: 2
+            }
+        );
+
+        // too deep, then one more entry fits
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "d/x/y.txt", wasip1::OFLAGS_CREAT),
+            Err(wasip1::ERRNO_DQUOT)
+        );
+        assert!(MockWasm::open(&mut vfs, 3, "d/../d/y.txt", wasip1::OFLAGS_CREAT).is_ok());
+        assert_eq!(
+            MockWasm::open(&mut vfs, 3, "c.txt", wasip1::OFLAGS_CREAT),
+            Err(wasip1::ERRNO_DQUOT)
+        );
+
+        // truncating frees the bytes
+        assert!(MockWasm::open(&mut vfs, 3, "a.txt", wasip1::OFLAGS_TRUNC).is_ok());
+        assert_eq!(
+            vfs.lfs().usage(),
+            VFSQuotaUsage {
+                bytes: 0,
+                inodes: 3
+            }
+        );
+    }
+
+    fn path_op(
+        vfs: &mut Vfs,
+        op: fn(&mut Vfs, wasip1::Fd, *const u8, usize) -> wasip1::Errno,
+        path: &str,
+    ) -> wasip1::Errno {
+        op(vfs, 3, path.as_ptr(), path.len())
+    }
+
+    fn rename(vfs: &mut Vfs, old: &str, new: &str) -> wasip1::Errno {
+        Wasip1FileSystem::path_rename_raw::<HostMemory>(
+            vfs,
+            3,
+            old.as_ptr(),
+            old.len(),
+            3,
+            new.as_ptr(),
+            new.len(),
+        )
+    }
+
+    /// Writes a file in the inner file system, past the quota.
+    fn inner_file(vfs: &mut Vfs, path: &str, data: &[u8]) {
+        let inner = vfs.lfs_mut().inner_mut();
+        let inode = MockWasm::lfs_open(
+            inner,
+            VFSHeapLFS::<DefaultStdIO>::PRE_OPEN[0],
+            path,
+            wasip1::OFLAGS_CREAT,
+        )
+        .unwrap();
+        assert_eq!(MockWasm::lfs_write(inner, inode, data), Ok(data.len()));
+        inner.fd_close_raw::<MockWasm>(inode);
+    }
+
+    #[test]
+    fn test_quota_inner_entries() {
+        let mut vfs = Vfs::new(VFSQuotaLFS::new(
+            VFSHeapLFS::new("."),
+            VFSQuotaLimits::UNLIMITED,
+        ));
+        let unlink = Wasip1FileSystem::path_unlink_file_raw::<HostMemory>;
+
+        inner_file(&mut vfs, "a.txt", b"Hello");
+        inner_file(&mut vfs, "b.txt", b"World");
+        inner_file(&mut vfs, "c.txt", b"!");
+
+        // removing or shrinking what was there frees nothing
+        assert_eq!(path_op(&mut vfs, unlink, "a.txt"), wasip1::ERRNO_SUCCESS);
+        assert!(MockWasm::open(&mut vfs, 3, "b.txt", wasip1::OFLAGS_TRUNC).is_ok());
+        assert_eq!(vfs.lfs().usage(), VFSQuotaUsage::default());
+
+        // what it grew by is counted and freed
+        let c = MockWasm::open(&mut vfs, 3, "c.txt", 0).unwrap();
+        assert_eq!(pwrite(&mut vfs, c, b"abc", 1), Ok(3));
+        assert_eq!(
+            vfs.lfs().usage(),
+            VFSQuotaUsage {
+                bytes: 3,
+                inodes: 0
+            }
+        );
+        assert_eq!(
+            Wasip1FileSystem::fd_close_raw::<HostMemory>(&mut vfs, c),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(rename(&mut vfs, "b.txt", "c.txt"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(vfs.lfs().usage(), VFSQuotaUsage::default());
+    }
+
+    #[test]
+    fn test_quota_unlink_while_open() {
+        let mut vfs = Vfs::new(VFSQuotaLFS::new(
+            VFSHeapLFS::new("."),
+            VFSQuotaLimits {
+                total_bytes: 8,
+                ..VFSQuotaLimits::UNLIMITED
+            },
+        ));
+        let unlink = Wasip1FileSystem::path_unlink_file_raw::<HostMemory>;
+        let usage = |bytes, inodes| VFSQuotaUsage { bytes, inodes };
+
+        // the inner file system keeps the data until the last close, so does the quota
+        let a = MockWasm::open(&mut vfs, 3, "a.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(pwrite(&mut vfs, a, b"abcd", 0), Ok(4));
+        assert_eq!(path_op(&mut vfs, unlink, "a.txt"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(vfs.lfs().usage(), usage(4, 1));
+        assert_eq!(pwrite(&mut vfs, a, b"efgh", 4), Ok(4));
+        assert_eq!(vfs.lfs().usage(), usage(8, 1));
+
+        let b = MockWasm::open(&mut vfs, 3, "b.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(pwrite(&mut vfs, b, b"x", 0), Err(wasip1::ERRNO_NOSPC));
+
+        // a snapshot has no descriptors, so not the removed file
+        let snapshot = vfs.snapshot();
+        let mut restored = Vfs::new(VFSQuotaLFS::new(
+            VFSHeapLFS::new("."),
+            VFSQuotaLimits::UNLIMITED,
+        ));
+        assert_eq!(restored.restore(&snapshot), Ok(()));
+        assert_eq!(restored.lfs().usage(), usage(0, 1));
+
+        assert_eq!(
+            Wasip1FileSystem::fd_close_raw::<HostMemory>(&mut vfs, a),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(vfs.lfs().usage(), usage(0, 1));
+
+        // the freed number handed out again is counted from scratch
+        let c = MockWasm::open(&mut vfs, 3, "c.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(pwrite(&mut vfs, c, b"abc", 0), Ok(3));
+        assert_eq!(path_op(&mut vfs, unlink, "c.txt"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(
+            Wasip1FileSystem::fd_close_raw::<HostMemory>(&mut vfs, c),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(vfs.lfs().usage(), usage(0, 1));
+    }
+
+    #[test]
+    fn test_quota_restore_counted_twice() {
+        let mut out = SnapshotWriter::new();
+        out.usize(2);
+        for _ in 0..2 {
+            out.u64(1);
+            out.u8(1);
+            out.usize(usize::MAX);
+        }
+
+        let mut vfs = Vfs::new(VFSQuotaLFS::new(
+            VFSHeapLFS::new("."),
+            VFSQuotaLimits::UNLIMITED,
+        ));
+        assert_eq!(vfs.restore(&out.into_bytes()), Err(wasip1::ERRNO_INVAL));
+        assert_eq!(vfs.lfs().usage(), VFSQuotaUsage::default());
+    }
+
+    #[test]
+    fn test_quota_rename_depth() {
+        let mut vfs = Vfs::new(VFSQuotaLFS::new(
+            VFSHeapLFS::new("."),
+            VFSQuotaLimits {
+                depth: 3,
+                ..VFSQuotaLimits::UNLIMITED
+            },
+        ));
+
+        for dir in ["a", "a/b", "a/b/c", "x"] {
+            assert_eq!(mkdir(&mut vfs, dir), wasip1::ERRNO_SUCCESS);
+        }
+
+        // `a/b/c` would end up at `x/a/b/c`
+        assert_eq!(rename(&mut vfs, "a", "x/a"), wasip1::ERRNO_DQUOT);
+        assert_eq!(rename(&mut vfs, "a/b", "x/b"), wasip1::ERRNO_SUCCESS);
+    }
+}
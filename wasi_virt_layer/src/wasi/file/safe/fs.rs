@@ -0,0 +1,169 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::__private::wasip1;
+use crate::wasi::file::FilestatWithoutDevice;
+
+/// How [`Wasip1SafeFS::open`] opens the path,
+/// read from the flags and rights of `path_open`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Wasip1OpenOptions {
+    /// follow a symbolic link at the end of the path
+    pub follow: bool,
+    /// create a file if nothing is there
+    pub create: bool,
+    /// with `create`, `ERRNO_EXIST` if something is already there
+    pub exclusive: bool,
+    /// `ERRNO_NOTDIR` if it is not a directory
+    pub directory: bool,
+    /// empty the file
+    pub truncate: bool,
+    /// the guest may write to it, `ERRNO_ISDIR` for a directory
+    pub write: bool,
+}
+
+impl Wasip1OpenOptions {
+    pub const fn from_raw(
+        dir_flags: wasip1::Lookupflags,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+    ) -> Self {
+        Self {
+            follow: dir_flags & wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW
+                == wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+            create: o_flags & wasip1::OFLAGS_CREAT == wasip1::OFLAGS_CREAT,
+            exclusive: o_flags & wasip1::OFLAGS_EXCL == wasip1::OFLAGS_EXCL,
+            directory: o_flags & wasip1::OFLAGS_DIRECTORY == wasip1::OFLAGS_DIRECTORY,
+            truncate: o_flags & wasip1::OFLAGS_TRUNC == wasip1::OFLAGS_TRUNC,
+            write: fs_rights_base & wasip1::RIGHTS_FD_WRITE == wasip1::RIGHTS_FD_WRITE,
+        }
+    }
+}
+
+/// Entry of a directory listed by [`Wasip1SafeFS::readdir`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wasip1DirEntry {
+    pub ino: wasip1::Inode,
+    pub filetype: wasip1::Filetype,
+    pub name: String,
+}
+
+/// File system trait
+/// written with paths and slices instead of pointers into the guest,
+/// served to the guest by [`VFSSafeLFS`](super::lfs::VFSSafeLFS).
+///
+/// Paths are relative to the directory and resolved by the file system,
+/// including `..` and symbolic links.
+///
+/// ```ignore
+/// struct Notes(BTreeMap<String, Vec<u8>>);
+///
+/// impl Wasip1SafeFS for Notes {
+///     type Inode = usize;
+///     const PRE_OPEN: &'static [usize] = &[0];
+///
+///     fn prestat_name(&self, _: usize) -> &str {
+///         "/notes"
+///     }
+///     // ...
+/// }
+///
+/// static mut VIRTUAL_FILE_SYSTEM: Wasip1ConstVFS<VFSSafeLFS<Notes, DefaultStdIO>, FLAT_LEN> =
+///     Wasip1ConstVFS::new(VFSSafeLFS::new(Notes(BTreeMap::new())));
+/// ```
+pub trait Wasip1SafeFS {
+    type Inode: Copy + Eq + 'static;
+    const PRE_OPEN: &'static [Self::Inode];
+
+    /// Guest path of the pre-opened directory.
+    fn prestat_name(&self, inode: Self::Inode) -> &str;
+
+    /// Looks the path up from the directory,
+    /// and creates or truncates the file as the options say.
+    /// Opening `..` of a directory gives its parent,
+    /// a root may refuse it or give itself.
+    fn open(
+        &mut self,
+        dir: Self::Inode,
+        path: &str,
+        options: Wasip1OpenOptions,
+    ) -> Result<Self::Inode, wasip1::Errno>;
+
+    /// Called when a descriptor of the inode is closed.
+    #[allow(unused_variables)]
+    fn close(&mut self, inode: Self::Inode) {}
+
+    /// Reads from the offset, 0 past the end of the file.
+    fn read_at(
+        &mut self,
+        inode: Self::Inode,
+        buf: &mut [u8],
+        offset: usize,
+    ) -> Result<usize, wasip1::Errno>;
+
+    /// Writes at the offset, extending the file if it is past its end.
+    fn write_at(
+        &mut self,
+        inode: Self::Inode,
+        data: &[u8],
+        offset: usize,
+    ) -> Result<usize, wasip1::Errno>;
+
+    fn stat(&self, inode: Self::Inode) -> Result<FilestatWithoutDevice, wasip1::Errno>;
+
+    fn is_dir(&self, inode: Self::Inode) -> bool {
+        self.stat(inode)
+            .is_ok_and(|filestat| filestat.filetype == wasip1::FILETYPE_DIRECTORY)
+    }
+
+    /// Entries of the directory without `.` and `..`, in any order.
+    /// Each entry needs its own `ino`,
+    /// which is its cookie so the listing can be resumed.
+    fn readdir(&self, dir: Self::Inode) -> Result<Vec<Wasip1DirEntry>, wasip1::Errno>;
+
+    /// Creates a directory at the path.
+    /// `ERRNO_EXIST` if something already exists there.
+    fn mkdir(&mut self, dir: Self::Inode, path: &str) -> Result<(), wasip1::Errno>;
+
+    /// Removes the file at the path.
+    /// `ERRNO_ISDIR` if it is a directory.
+    fn unlink(&mut self, dir: Self::Inode, path: &str) -> Result<(), wasip1::Errno>;
+
+    /// Removes the empty directory at the path.
+    #[allow(unused_variables)]
+    fn rmdir(&mut self, dir: Self::Inode, path: &str) -> Result<(), wasip1::Errno> {
+        Err(wasip1::ERRNO_PERM)
+    }
+
+    #[allow(unused_variables)]
+    fn rename(
+        &mut self,
+        old_dir: Self::Inode,
+        old_path: &str,
+        new_dir: Self::Inode,
+        new_path: &str,
+    ) -> Result<(), wasip1::Errno> {
+        Err(wasip1::ERRNO_PERM)
+    }
+
+    /// Reads the target of the symbolic link at the path, cut to the buffer.
+    #[allow(unused_variables)]
+    fn readlink(
+        &mut self,
+        dir: Self::Inode,
+        path: &str,
+        buf: &mut [u8],
+    ) -> Result<usize, wasip1::Errno> {
+        Err(wasip1::ERRNO_INVAL)
+    }
+
+    #[allow(unused_variables)]
+    fn symlink(&mut self, target: &str, dir: Self::Inode, path: &str) -> Result<(), wasip1::Errno> {
+        Err(wasip1::ERRNO_PERM)
+    }
+
+    /// Called by `fd_sync` and `fd_datasync`.
+    #[allow(unused_variables)]
+    fn sync(&mut self, inode: Self::Inode) -> Result<(), wasip1::Errno> {
+        Ok(())
+    }
+}
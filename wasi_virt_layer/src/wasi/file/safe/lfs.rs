@@ -0,0 +1,108 @@
+use alloc::string::String;
+
+use crate::__private::wasip1;
+use crate::{
+    memory::WasmAccess,
+    wasi::file::{
+        safe::fs::{Wasip1OpenOptions, Wasip1SafeFS},
+        stdio::StdIO,
+    },
+};
+
+/// Adapter
+/// which serves any [`Wasip1SafeFS`] to the guest.
+///
+/// Paths, buffers and directory entries are copied
+/// between the memory of the guest and the file system here,
+/// so the file system never sees a pointer.
+pub struct VFSSafeLFS<FS: Wasip1SafeFS, StdIo: StdIO + 'static> {
+    fs: FS,
+    __marker: core::marker::PhantomData<StdIo>,
+}
+
+impl<FS: Wasip1SafeFS, StdIo: StdIO + 'static> VFSSafeLFS<FS, StdIo> {
+    pub const fn new(fs: FS) -> Self {
+        Self {
+            fs,
+            __marker: core::marker::PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &FS {
+        &self.fs
+    }
+
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut FS {
+        &mut self.fs
+    }
+
+    /// `ino` of the parent of the directory, looked up through `..`.
+    pub(crate) fn parent_ino(&mut self, dir: FS::Inode) -> Option<wasip1::Inode> {
+        let options = Wasip1OpenOptions {
+            directory: true,
+            ..Wasip1OpenOptions::default()
+        };
+
+        let parent = self.fs.open(dir, "..", options).ok()?;
+        let filestat = self.fs.stat(parent);
+        self.fs.close(parent);
+
+        filestat.ok().map(|filestat| filestat.ino)
+    }
+}
+
+/// The path in the memory of the guest, `ERRNO_ILSEQ` if it is not UTF-8.
+pub(crate) fn guest_path<Wasm: WasmAccess>(
+    path_ptr: *const u8,
+    path_len: usize,
+) -> Result<String, wasip1::Errno> {
+    let path = Wasm::get_array(path_ptr, path_len);
+    let path = core::str::from_utf8(&path).map_err(|_| wasip1::ERRNO_ILSEQ)?;
+
+    Ok(String::from(path))
+}
+
+/// Lends the data in the memory of the guest as a slice.
+pub(crate) fn with_guest_data<Wasm: WasmAccess, R>(
+    data: *const u8,
+    data_len: usize,
+    f: impl FnOnce(&[u8]) -> R,
+) -> R {
+    #[cfg(not(feature = "multi_memory"))]
+    {
+        let data =
+            unsafe { core::slice::from_raw_parts(Wasm::memory_director(data, data_len), data_len) };
+        f(data)
+    }
+    #[cfg(feature = "multi_memory")]
+    {
+        f(&Wasm::get_array(data, data_len))
+    }
+}
+
+/// Lends the buffer in the memory of the guest as a slice,
+/// `f` returns how many bytes it filled.
+pub(crate) fn with_guest_buf<Wasm: WasmAccess>(
+    buf: *mut u8,
+    buf_len: usize,
+    f: impl FnOnce(&mut [u8]) -> Result<usize, wasip1::Errno>,
+) -> Result<usize, wasip1::Errno> {
+    #[cfg(not(feature = "multi_memory"))]
+    {
+        let buf = unsafe {
+            core::slice::from_raw_parts_mut(Wasm::memory_director_mut(buf, buf_len), buf_len)
+        };
+        f(buf)
+    }
+    #[cfg(feature = "multi_memory")]
+    {
+        use crate::__private::utils;
+
+        let (buf_vec, read) = unsafe { utils::alloc_buff(buf_len, f) };
+        let read = read?;
+        Wasm::memcpy(buf, &buf_vec[..read]);
+        Ok(read)
+    }
+}
@@ -0,0 +1,528 @@
+use crate::__private::wasip1;
+use crate::__private::wasip1::Dircookie;
+
+use crate::{
+    memory::WasmAccess,
+    wasi::file::{
+        FilestatWithoutDevice, Wasip1LFS,
+        dirent::{DIRCOOKIE_DOTDOT, DIRCOOKIE_ENTRIES, DirentWriter},
+        safe::{
+            fs::{Wasip1OpenOptions, Wasip1SafeFS},
+            lfs::{VFSSafeLFS, guest_path, with_guest_buf, with_guest_data},
+        },
+        stdio::{self, StdIO},
+    },
+};
+
+impl<FS: Wasip1SafeFS, StdIo: StdIO + 'static> Wasip1LFS for VFSSafeLFS<FS, StdIo> {
+    type Inode = FS::Inode;
+    const PRE_OPEN: &'static [Self::Inode] = FS::PRE_OPEN;
+
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        data: *const u8,
+        data_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let fs = self.inner_mut();
+        with_guest_data::<Wasm, _>(data, data_len, |data| fs.write_at(inode, data, offset))
+    }
+
+    fn fd_write_stdout_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::write_stdout_raw::<StdIo, Wasm>(data, data_len)
+    }
+
+    fn fd_write_stderr_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::write_stderr_raw::<StdIo, Wasm>(data, data_len)
+    }
+
+    fn is_dir(&self, inode: Self::Inode) -> bool {
+        self.inner().is_dir(inode)
+    }
+
+    fn fd_readdir_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        cookie: Dircookie,
+    ) -> Result<(wasip1::Size, Dircookie), wasip1::Errno> {
+        let mut entries = self.inner().readdir(inode)?;
+        entries.sort_unstable_by_key(|entry| entry.ino);
+
+        let ino = self.inner().stat(inode)?.ino;
+
+        // only looked up while `..` is still to be listed
+        let parent = match cookie <= DIRCOOKIE_DOTDOT {
+            true => self.parent_ino(inode).unwrap_or(ino),
+            false => ino,
+        };
+
+        let mut writer = DirentWriter::<Wasm>::new(buf, buf_len, cookie);
+
+        if !writer.dots(ino, parent) {
+            return Ok(writer.finish());
+        }
+
+        for entry in entries {
+            if !writer.entry(
+                DIRCOOKIE_ENTRIES + entry.ino,
+                entry.ino,
+                entry.filetype,
+                entry.name.as_bytes(),
+            ) {
+                break;
+            }
+        }
+
+        Ok(writer.finish())
+    }
+
+    fn path_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let path = guest_path::<Wasm>(path_ptr, path_len)?;
+        let options = Wasip1OpenOptions::from_raw(flags, 0, 0);
+
+        let fs = self.inner_mut();
+        let inode = fs.open(inode, &path, options)?;
+        let filestat = fs.stat(inode);
+        fs.close(inode);
+
+        filestat
+    }
+
+    fn fd_prestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<wasip1::Prestat, wasip1::Errno> {
+        if !Self::PRE_OPEN.contains(&inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        Ok(wasip1::Prestat {
+            tag: 0, // prestat is enum but variant is only 0
+            // union type but we only have one variant
+            u: wasip1::PrestatU {
+                dir: wasip1::PrestatDir {
+                    pr_name_len: self.inner().prestat_name(inode).len() as _,
+                },
+            },
+        })
+    }
+
+    fn fd_prestat_dir_name_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        dir_path_ptr: *mut u8,
+        dir_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        if !Self::PRE_OPEN.contains(&inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        let name = self.inner().prestat_name(inode);
+
+        Wasm::memcpy(
+            dir_path_ptr,
+            &name.as_bytes()[..core::cmp::min(name.len(), dir_path_len)],
+        );
+
+        Ok(())
+    }
+
+    fn fd_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        self.inner().stat(inode)
+    }
+
+    fn fd_close_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) {
+        self.inner_mut().close(inode);
+    }
+
+    fn fd_sync_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) -> Result<(), wasip1::Errno> {
+        self.inner_mut().sync(inode)
+    }
+
+    fn fd_pread_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let fs = self.inner_mut();
+        with_guest_buf::<Wasm>(buf, buf_len, |buf| fs.read_at(inode, buf, offset))
+    }
+
+    fn fd_read_stdin_raw<Wasm: WasmAccess>(
+        &mut self,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::read_stdin_raw::<StdIo, Wasm>(buf, buf_len)
+    }
+
+    fn path_open_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        dir_flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        _: wasip1::Rights,
+        _: wasip1::Fdflags,
+    ) -> Result<Self::Inode, wasip1::Errno> {
+        let path = guest_path::<Wasm>(path_ptr, path_len)?;
+        let options = Wasip1OpenOptions::from_raw(dir_flags, o_flags, fs_rights_base);
+
+        self.inner_mut().open(dir_inode, &path, options)
+    }
+
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let path = guest_path::<Wasm>(path_ptr, path_len)?;
+
+        self.inner_mut().mkdir(dir_inode, &path)
+    }
+
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let path = guest_path::<Wasm>(path_ptr, path_len)?;
+
+        self.inner_mut().rmdir(dir_inode, &path)
+    }
+
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let path = guest_path::<Wasm>(path_ptr, path_len)?;
+
+        self.inner_mut().unlink(dir_inode, &path)
+    }
+
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_dir_inode: Self::Inode,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_dir_inode: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let old_path = guest_path::<Wasm>(old_path_ptr, old_path_len)?;
+        let new_path = guest_path::<Wasm>(new_path_ptr, new_path_len)?;
+
+        self.inner_mut()
+            .rename(old_dir_inode, &old_path, new_dir_inode, &new_path)
+    }
+
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let path = guest_path::<Wasm>(path_ptr, path_len)?;
+
+        let fs = self.inner_mut();
+        with_guest_buf::<Wasm>(buf, buf_len, |buf| fs.readlink(dir_inode, &path, buf))
+    }
+
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        dir_inode: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let target = guest_path::<Wasm>(old_path_ptr, old_path_len)?;
+        let path = guest_path::<Wasm>(new_path_ptr, new_path_len)?;
+
+        self.inner_mut().symlink(&target, dir_inode, &path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use super::*;
+    use crate::{
+        memory::HostMemory,
+        mock::MockWasm,
+        wasi::file::{
+            dirent::{DIRENT_SIZE, DirentReader},
+            safe::fs::Wasip1DirEntry,
+            stdio::DefaultStdIO,
+        },
+    };
+
+    /// One directory of files and empty directories,
+    /// inode `n` is `nodes[n - 1]`, `None` once removed.
+    #[derive(Default)]
+    struct Flat {
+        nodes: Vec<Option<(String, Option<Vec<u8>>)>>,
+    }
+
+    impl Flat {
+        fn lookup(&self, path: &str) -> Option<usize> {
+            self.nodes
+                .iter()
+                .position(|node| matches!(node, Some((name, _)) if name == path))
+                .map(|index| index + 1)
+        }
+    }
+
+    impl Wasip1SafeFS for Flat {
+        type Inode = usize;
+        const PRE_OPEN: &'static [usize] = &[0];
+
+        fn prestat_name(&self, _: usize) -> &str {
+            "/flat"
+        }
+
+        fn open(
+            &mut self,
+            dir: usize,
+            path: &str,
+            options: Wasip1OpenOptions,
+        ) -> Result<usize, wasip1::Errno> {
+            if dir != 0 {
+                return Err(wasip1::ERRNO_NOTDIR);
+            }
+
+            let inode = match path {
+                "." | ".." => 0,
+                _ => match self.lookup(path) {
+                    Some(_) if options.create && options.exclusive => {
+                        return Err(wasip1::ERRNO_EXIST);
+                    }
+                    Some(inode) => inode,
+                    None if options.create => {
+                        self.nodes.push(Some((path.into(), Some(Vec::new()))));
+                        return Ok(self.nodes.len());
+                    }
+                    None => return Err(wasip1::ERRNO_NOENT),
+                },
+            };
+
+            if options.directory && !self.is_dir(inode) {
+                return Err(wasip1::ERRNO_NOTDIR);
+            }
+            if options.truncate
+                && let Some(Some((_, Some(data)))) = self.nodes.get_mut(inode.wrapping_sub(1))
+            {
+                data.clear();
+            }
+
+            Ok(inode)
+        }
+
+        fn read_at(
+            &mut self,
+            inode: usize,
+            buf: &mut [u8],
+            offset: usize,
+        ) -> Result<usize, wasip1::Errno> {
+            match self.nodes.get(inode.wrapping_sub(1)) {
+                Some(Some((_, Some(data)))) => {
+                    let data = data.get(offset..).unwrap_or_default();
+                    let len = core::cmp::min(buf.len(), data.len());
+                    buf[..len].copy_from_slice(&data[..len]);
+                    Ok(len)
+                }
+                _ => Err(wasip1::ERRNO_ISDIR),
+            }
+        }
+
+        fn write_at(
+            &mut self,
+            inode: usize,
+            data: &[u8],
+            offset: usize,
+        ) -> Result<usize, wasip1::Errno> {
+            match self.nodes.get_mut(inode.wrapping_sub(1)) {
+                Some(Some((_, Some(file)))) => {
+                    if file.len() < offset + data.len() {
+                        file.resize(offset + data.len(), 0);
+                    }
+                    file[offset..offset + data.len()].copy_from_slice(data);
+                    Ok(data.len())
+                }
+                _ => Err(wasip1::ERRNO_ISDIR),
+            }
+        }
+
+        fn stat(&self, inode: usize) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+            let (filetype, size) = match inode {
+                0 => (wasip1::FILETYPE_DIRECTORY, 0),
+                _ => match self.nodes.get(inode - 1) {
+                    Some(Some((_, Some(data)))) => (wasip1::FILETYPE_REGULAR_FILE, data.len()),
+                    Some(Some((_, None))) => (wasip1::FILETYPE_DIRECTORY, 0),
+                    _ => return Err(wasip1::ERRNO_BADF),
+                },
+            };
+
+            Ok(FilestatWithoutDevice {
+                ino: inode as _,
+                filetype,
+                nlink: 1,
+                size: size as _,
+                atim: 0,
+                mtim: 0,
+                ctim: 0,
+            })
+        }
+
+        fn readdir(&self, dir: usize) -> Result<Vec<Wasip1DirEntry>, wasip1::Errno> {
+            if dir != 0 {
+                return Err(wasip1::ERRNO_NOTDIR);
+            }
+
+            // newest first, the adapter puts them in the order of their cookies
+            Ok(self
+                .nodes
+                .iter()
+                .enumerate()
+                .rev()
+                .filter_map(|(index, node)| {
+                    let (name, data) = node.as_ref()?;
+                    Some(Wasip1DirEntry {
+                        ino: index as u64 + 1,
+                        filetype: match data {
+                            Some(_) => wasip1::FILETYPE_REGULAR_FILE,
+                            None => wasip1::FILETYPE_DIRECTORY,
+                        },
+                        name: name.clone(),
+                    })
+                })
+                .collect())
+        }
+
+        fn mkdir(&mut self, _: usize, path: &str) -> Result<(), wasip1::Errno> {
+            if self.lookup(path).is_some() {
+                return Err(wasip1::ERRNO_EXIST);
+            }
+
+            self.nodes.push(Some((path.into(), None)));
+            Ok(())
+        }
+
+        fn unlink(&mut self, _: usize, path: &str) -> Result<(), wasip1::Errno> {
+            let inode = self.lookup(path).ok_or(wasip1::ERRNO_NOENT)?;
+            if self.is_dir(inode) {
+                return Err(wasip1::ERRNO_ISDIR);
+            }
+
+            self.nodes[inode - 1] = None;
+            Ok(())
+        }
+    }
+
+    type Lfs = VFSSafeLFS<Flat, DefaultStdIO>;
+
+    fn list(lfs: &mut Lfs, buf_len: usize, cookie: Dircookie) -> (Vec<String>, Dircookie) {
+        let mut buf = vec![0u8; buf_len];
+        let (used, next) = lfs
+            .fd_readdir_raw::<HostMemory>(0, buf.as_mut_ptr(), buf.len(), cookie)
+            .unwrap();
+
+        let names = DirentReader::new(&buf[..used])
+            .map(|(_, _, _, name)| String::from_utf8(name.to_vec()).unwrap())
+            .collect();
+        (names, next)
+    }
+
+    #[test]
+    fn test_safe_lfs() {
+        let mut lfs = Lfs::new(Flat::default());
+
+        assert_eq!(
+            MockWasm::lfs_prestat_name(&mut lfs, 0).as_deref(),
+            Ok("/flat")
+        );
+
+        let inode = MockWasm::lfs_open(&mut lfs, 0, "a.txt", wasip1::OFLAGS_CREAT).unwrap();
+        let data = MockWasm::alloc_bytes(b"Hello");
+        assert_eq!(lfs.fd_pwrite_raw::<MockWasm>(inode, data, 5, 2), Ok(5));
+        assert_eq!(
+            MockWasm::lfs_read(&mut lfs, inode, 16).as_deref(),
+            Ok(&b"\0\0Hello"[..])
+        );
+
+        let path = "a.txt";
+        let filestat = lfs
+            .path_filestat_get_raw::<HostMemory>(0, 0, path.as_ptr(), path.len())
+            .unwrap();
+        assert_eq!(filestat.size, 7);
+        assert_eq!(
+            MockWasm::lfs_open(
+                &mut lfs,
+                0,
+                "a.txt",
+                wasip1::OFLAGS_CREAT | wasip1::OFLAGS_EXCL
+            ),
+            Err(wasip1::ERRNO_EXIST)
+        );
+
+        let path = "dir";
+        lfs.path_create_directory_raw::<HostMemory>(0, path.as_ptr(), path.len())
+            .unwrap();
+        assert_eq!(
+            MockWasm::lfs_open(&mut lfs, 0, "dir", wasip1::OFLAGS_DIRECTORY)
+                .map(|inode| lfs.is_dir(inode)),
+            Ok(true)
+        );
+        assert_eq!(
+            lfs.path_remove_directory_raw::<HostMemory>(0, path.as_ptr(), path.len()),
+            Err(wasip1::ERRNO_PERM)
+        );
+
+        // entries in the order of their inodes, resumed from the cookie
+        assert_eq!(list(&mut lfs, 256, 0).0, [".", "..", "a.txt", "dir"]);
+        let (names, next) = list(&mut lfs, 2 * DIRENT_SIZE + 3 + DIRENT_SIZE + 1, 0);
+        assert_eq!(names, [".", ".."]);
+        MockWasm::lfs_open(&mut lfs, 0, "b.txt", wasip1::OFLAGS_CREAT).unwrap();
+        assert_eq!(list(&mut lfs, 256, next).0, ["a.txt", "dir", "b.txt"]);
+
+        let path = "a.txt";
+        lfs.path_unlink_file_raw::<HostMemory>(0, path.as_ptr(), path.len())
+            .unwrap();
+        assert_eq!(list(&mut lfs, 256, next).0, ["dir", "b.txt"]);
+        assert_eq!(
+            MockWasm::lfs_open(&mut lfs, 0, "a.txt", 0),
+            Err(wasip1::ERRNO_NOENT)
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use alloc::vec::Vec;
+
+use crate::__private::wasip1;
+
+/// The first bytes of every snapshot.
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"WVFS";
+
+/// Bumped whenever a file system changes what it writes,
+/// a snapshot of another version is refused with `ERRNO_NOTSUP`.
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+/// A file system whose state can be saved to bytes
+/// and loaded again into a fresh instance,
+/// see [`Wasip1VFS::snapshot`](crate::file::Wasip1VFS::snapshot).
+///
+/// Only what the guest can change is saved,
+/// e.g. the files of a heap LFS, not the files of a const tree.
+pub trait Wasip1Snapshot {
+    /// Appends the state to the snapshot.
+    fn snapshot_to(&self, out: &mut SnapshotWriter);
+
+    /// Replaces the state with the one [`Wasip1Snapshot::snapshot_to`] wrote.
+    /// `ERRNO_INVAL` if the snapshot is broken,
+    /// a file system made of others may be left partly restored then.
+    fn restore_from(&mut self, input: &mut SnapshotReader<'_>) -> Result<(), wasip1::Errno>;
+}
+
+/// Writes the snapshot format, a header followed by what each file system writes.
+/// Numbers are LEB128, so small ones take one byte.
+pub struct SnapshotWriter {
+    buf: Vec<u8>,
+}
+
+impl SnapshotWriter {
+    /// Starts a snapshot with the header.
+    pub fn new() -> Self {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&SNAPSHOT_MAGIC);
+        buf.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+
+        Self { buf }
+    }
+
+    #[inline]
+    pub fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub fn u64(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+
+            if value == 0 {
+                self.buf.push(byte);
+                return;
+            }
+            self.buf.push(byte | 0x80);
+        }
+    }
+
+    #[inline]
+    pub fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    pub fn option_u64(&mut self, value: Option<u64>) {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                self.u64(value);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    /// The length, then the bytes.
+    pub fn bytes(&mut self, value: &[u8]) {
+        self.usize(value.len());
+        self.buf.extend_from_slice(value);
+    }
+
+    #[inline]
+    pub fn str(&mut self, value: &str) {
+        self.bytes(value.as_bytes());
+    }
+
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+impl Default for SnapshotWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads what [`SnapshotWriter`] wrote.
+/// Every read fails with `ERRNO_INVAL` past the end of the snapshot.
+pub struct SnapshotReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> SnapshotReader<'a> {
+    /// Checks the header.
+    /// `ERRNO_INVAL` if it is not a snapshot,
+    /// `ERRNO_NOTSUP` if it was written by another version.
+    pub fn new(data: &'a [u8]) -> Result<Self, wasip1::Errno> {
+        let mut reader = Self { data };
+
+        if reader.take(SNAPSHOT_MAGIC.len())? != SNAPSHOT_MAGIC {
+            return Err(wasip1::ERRNO_INVAL);
+        }
+
+        let version = reader.take(2)?;
+        if u16::from_le_bytes([version[0], version[1]]) != SNAPSHOT_VERSION {
+            return Err(wasip1::ERRNO_NOTSUP);
+        }
+
+        Ok(reader)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], wasip1::Errno> {
+        if self.data.len() < len {
+            return Err(wasip1::ERRNO_INVAL);
+        }
+
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    #[inline]
+    pub fn u8(&mut self) -> Result<u8, wasip1::Errno> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u64(&mut self) -> Result<u64, wasip1::Errno> {
+        let mut value = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            let bits = (byte & 0x7f) as u64;
+
+            if bits << shift >> shift != bits {
+                return Err(wasip1::ERRNO_INVAL);
+            }
+            value |= bits << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(wasip1::ERRNO_INVAL)
+    }
+
+    #[inline]
+    pub fn usize(&mut self) -> Result<usize, wasip1::Errno> {
+        usize::try_from(self.u64()?).map_err(|_| wasip1::ERRNO_INVAL)
+    }
+
+    pub fn option_u64(&mut self) -> Result<Option<u64>, wasip1::Errno> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.u64()?)),
+            _ => Err(wasip1::ERRNO_INVAL),
+        }
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], wasip1::Errno> {
+        let len = self.usize()?;
+        self.take(len)
+    }
+
+    pub fn str(&mut self) -> Result<&'a str, wasip1::Errno> {
+        core::str::from_utf8(self.bytes()?).map_err(|_| wasip1::ERRNO_INVAL)
+    }
+
+    /// `ERRNO_INVAL` if something is left after the state.
+    pub fn finish(self) -> Result<(), wasip1::Errno> {
+        if self.data.is_empty() {
+            Ok(())
+        } else {
+            Err(wasip1::ERRNO_INVAL)
+        }
+    }
+}
+
+/// Exports the `virtual-file-system-snapshot` interface of `wit/snapshot.wit`
+/// on the type given to `export!`, for the VFS state.
+/// The world of the crate has to include it,
+/// with the `wit` directory of this crate as the `deps/wasip1-vfs` dependency
+/// and `generate_all` given to `wit_bindgen::generate!`:
+///
+/// ```ignore
+/// // wit/main.wit
+/// world main {
+///   include wasip1-vfs:host/snapshot;
+///   export main: func();
+/// }
+/// ```
+///
+/// ```ignore
+/// plug_snapshot!(Main, {
+///     #[allow(static_mut_refs)]
+///     unsafe { &mut VIRTUAL_FILE_SYSTEM }
+/// });
+/// ```
+#[macro_export]
+macro_rules! plug_snapshot {
+    ($guest:ty, $state:expr) => {
+        impl exports::wasip1_vfs::host::virtual_file_system_snapshot::Guest for $guest {
+            fn snapshot() -> $crate::__private::Vec<u8> {
+                let state = $state;
+                state.snapshot()
+            }
+
+            fn restore(data: $crate::__private::Vec<u8>) -> Result<(), $crate::__private::String> {
+                let state = $state;
+                state
+                    .restore(&data)
+                    .map_err(|errno| $crate::__private::String::from(errno.message()))
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        memory::HostMemory,
+        wasi::file::{
+            Wasip1FileSystem, constant::vfs::Wasip1ConstVFS, heap::lfs::VFSHeapLFS,
+            stdio::DefaultStdIO,
+        },
+        wit::snapshot::exports::{self, wasip1_vfs::host::virtual_file_system_snapshot::Guest},
+    };
+
+    static mut VIRTUAL_FILE_SYSTEM: Wasip1ConstVFS<VFSHeapLFS<DefaultStdIO>, 8> =
+        Wasip1ConstVFS::new(VFSHeapLFS::new("."));
+
+    struct Main;
+
+    crate::plug_snapshot!(Main, {
+        #[allow(static_mut_refs)]
+        unsafe {
+            &mut VIRTUAL_FILE_SYSTEM
+        }
+    });
+
+    #[test]
+    #[allow(static_mut_refs)]
+    fn test_plug_snapshot() {
+        let path = "out";
+        assert_eq!(
+            Wasip1FileSystem::path_create_directory_raw::<HostMemory>(
+                unsafe { &mut VIRTUAL_FILE_SYSTEM },
+                3,
+                path.as_ptr(),
+                path.len(),
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        let snapshot = Main::snapshot();
+
+        // a fresh instance gets the state of the snapshot
+        unsafe { VIRTUAL_FILE_SYSTEM = Wasip1ConstVFS::new(VFSHeapLFS::new(".")) };
+        assert_eq!(Main::restore(snapshot.clone()), Ok(()));
+        assert_eq!(Main::snapshot(), snapshot);
+
+        assert_eq!(
+            Main::restore(b"tar".to_vec()),
+            Err(alloc::string::String::from(wasip1::ERRNO_INVAL.message()))
+        );
+    }
+}
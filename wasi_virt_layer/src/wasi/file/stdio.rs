@@ -1,10 +1,70 @@
 use crate::__private::wasip1;
 use crate::__private::wasip1::Size;
 
-#[allow(unused_imports)]
 use crate::memory::WasmAccess;
 use crate::transporter::Wasip1Transporter;
 
+/// Writes `data_len` bytes of the guest at `data` to stdout through `StdIo`,
+/// for [`Wasip1LFS::fd_write_stdout_raw`](crate::wasi::file::Wasip1LFS::fd_write_stdout_raw).
+/// With `multi_memory` the bytes are copied out of the guest first.
+pub fn write_stdout_raw<StdIo: StdIO, Wasm: WasmAccess>(
+    data: *const u8,
+    data_len: usize,
+) -> Result<Size, wasip1::Errno> {
+    #[cfg(not(feature = "multi_memory"))]
+    {
+        StdIo::write_direct::<Wasm>(data, data_len)
+    }
+    #[cfg(feature = "multi_memory")]
+    {
+        StdIo::write(&guest_bytes::<Wasm>(data, data_len))
+    }
+}
+
+/// [`write_stdout_raw`] for stderr.
+pub fn write_stderr_raw<StdIo: StdIO, Wasm: WasmAccess>(
+    data: *const u8,
+    data_len: usize,
+) -> Result<Size, wasip1::Errno> {
+    #[cfg(not(feature = "multi_memory"))]
+    {
+        StdIo::ewrite_direct::<Wasm>(data, data_len)
+    }
+    #[cfg(feature = "multi_memory")]
+    {
+        StdIo::ewrite(&guest_bytes::<Wasm>(data, data_len))
+    }
+}
+
+/// Reads stdin through `StdIo` into `buf_len` bytes of the guest at `buf`,
+/// for [`Wasip1LFS::fd_read_stdin_raw`](crate::wasi::file::Wasip1LFS::fd_read_stdin_raw).
+pub fn read_stdin_raw<StdIo: StdIO, Wasm: WasmAccess>(
+    buf: *mut u8,
+    buf_len: usize,
+) -> Result<Size, wasip1::Errno> {
+    #[cfg(not(feature = "multi_memory"))]
+    {
+        StdIo::read_direct::<Wasm>(buf, buf_len)
+    }
+    #[cfg(feature = "multi_memory")]
+    {
+        use crate::utils::alloc_buff;
+
+        let (buf_vec, read) = unsafe { alloc_buff(buf_len, |b| StdIo::read(b)) };
+        let read = read?;
+        Wasm::memcpy(buf, &buf_vec[..read]);
+        Ok(read)
+    }
+}
+
+#[cfg(feature = "multi_memory")]
+fn guest_bytes<Wasm: WasmAccess>(data: *const u8, data_len: usize) -> alloc::boxed::Box<[u8]> {
+    use crate::utils::alloc_buff;
+
+    let (buf, _) = unsafe { alloc_buff(data_len, |buf| Wasm::memcpy_to(buf, data)) };
+    buf
+}
+
 pub struct DefaultStdIO;
 
 impl StdIO for DefaultStdIO {
@@ -0,0 +1,622 @@
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+use crate::__private::wasip1;
+use crate::{
+    memory::{WasmAccess, WasmPathAccess, WasmPathComponent},
+    wasi::file::{
+        FilestatWithoutDevice, SYMLINK_MAX_DEPTH,
+        snapshot::{SnapshotReader, SnapshotWriter, Wasip1Snapshot},
+        stdio::StdIO,
+    },
+};
+
+const BLOCK: usize = 512;
+
+/// Read-only file system
+/// which serves a tar archive (ustar, pax and GNU long names)
+/// embedded with `include_bytes!`.
+/// The archive is indexed once, on the first access or by [`VFSTarLFS::index`],
+/// and files are read straight from the archive bytes.
+/// The file system is read-only.
+pub struct VFSTarLFS<StdIo: StdIO + 'static> {
+    name: &'static str,
+    archive: &'static [u8],
+    /// inode `n` is stored at `inodes[n]`, root is inode 0,
+    /// empty until the archive is indexed
+    inodes: Vec<VFSTarInode>,
+    /// `Err` if the archive is broken
+    indexed: Option<Result<(), wasip1::Errno>>,
+    __marker: core::marker::PhantomData<StdIo>,
+}
+
+#[derive(Debug)]
+pub struct VFSTarInode {
+    pub parent: usize,
+    /// names of the inode, more than one with hard links
+    pub nlink: wasip1::Linkcount,
+    /// modification time in nanoseconds
+    pub mtim: wasip1::Timestamp,
+    pub node: VFSTarNode,
+}
+
+#[derive(Debug)]
+pub enum VFSTarNode {
+    /// contents in the archive
+    File(&'static [u8]),
+    /// name, inode
+    Dir(BTreeMap<&'static str, usize>),
+    /// target path
+    Symlink(&'static str),
+}
+
+impl VFSTarNode {
+    pub const fn filetype(&self) -> wasip1::Filetype {
+        match self {
+            Self::File(..) => wasip1::FILETYPE_REGULAR_FILE,
+            Self::Dir(..) => wasip1::FILETYPE_DIRECTORY,
+            Self::Symlink(..) => wasip1::FILETYPE_SYMBOLIC_LINK,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Self::File(data) => data.len(),
+            Self::Dir(entries) => entries.len(),
+            Self::Symlink(target) => target.len(),
+        }
+    }
+}
+
+/// One member of the archive, after applying pax and GNU long name headers.
+struct TarEntry {
+    /// ustar prefix of the path
+    prefix: &'static str,
+    path: &'static str,
+    link: &'static str,
+    typeflag: u8,
+    mtime: u64,
+    data: &'static [u8],
+}
+
+/// Extended attributes which apply to the next member.
+#[derive(Default)]
+struct TarExtension {
+    path: Option<&'static str>,
+    link: Option<&'static str>,
+    size: Option<usize>,
+    mtime: Option<u64>,
+}
+
+fn str_from(bytes: &'static [u8]) -> Result<&'static str, wasip1::Errno> {
+    core::str::from_utf8(bytes).map_err(|_| wasip1::ERRNO_ILSEQ)
+}
+
+/// A NUL terminated field of a header.
+fn field(header: &'static [u8]) -> &'static [u8] {
+    let end = header.iter().position(|&b| b == 0).unwrap_or(header.len());
+    &header[..end]
+}
+
+/// An octal number, or a base-256 one if the high bit is set.
+fn number(field: &[u8]) -> Result<u64, wasip1::Errno> {
+    if field.first().is_some_and(|b| b & 0x80 != 0) {
+        return Ok(field[1..]
+            .iter()
+            .fold((field[0] & 0x7f) as u64, |n, &b| (n << 8) | b as u64));
+    }
+
+    let mut n = 0u64;
+    for &b in field {
+        match b {
+            b'0'..=b'7' => n = n.checked_mul(8).ok_or(wasip1::ERRNO_IO)? + (b - b'0') as u64,
+            b' ' | 0 if n == 0 => {}
+            b' ' | 0 => break,
+            _ => return Err(wasip1::ERRNO_IO),
+        }
+    }
+
+    Ok(n)
+}
+
+fn decimal(value: &str) -> Result<u64, wasip1::Errno> {
+    value.parse().map_err(|_| wasip1::ERRNO_IO)
+}
+
+/// Reads the `length key=value\n` records of a pax header.
+fn pax_records(mut data: &'static [u8], extension: &mut TarExtension) -> Result<(), wasip1::Errno> {
+    while !data.is_empty() {
+        let space = data
+            .iter()
+            .position(|&b| b == b' ')
+            .ok_or(wasip1::ERRNO_IO)?;
+        let len = decimal(str_from(&data[..space])?)? as usize;
+        if len <= space + 1 || len > data.len() || data[len - 1] != b'\n' {
+            return Err(wasip1::ERRNO_IO);
+        }
+
+        let record = &data[space + 1..len - 1];
+        let equal = record
+            .iter()
+            .position(|&b| b == b'=')
+            .ok_or(wasip1::ERRNO_IO)?;
+        let value = str_from(&record[equal + 1..])?;
+
+        match &record[..equal] {
+            b"path" => extension.path = Some(value),
+            b"linkpath" => extension.link = Some(value),
+            b"size" => extension.size = Some(decimal(value)? as usize),
+            // seconds with an optional fraction
+            b"mtime" => extension.mtime = Some(decimal(value.split('.').next().unwrap())?),
+            _ => {}
+        }
+
+        data = &data[len..];
+    }
+
+    Ok(())
+}
+
+/// Iterates over the members of the archive.
+struct TarEntries {
+    archive: &'static [u8],
+    offset: usize,
+}
+
+impl TarEntries {
+    fn next_entry(&mut self) -> Result<Option<TarEntry>, wasip1::Errno> {
+        let mut extension = TarExtension::default();
+
+        loop {
+            let Some(header) = self
+                .archive
+                .get(self.offset..)
+                .and_then(|rest| rest.get(..BLOCK))
+            else {
+                return Ok(None);
+            };
+
+            // the end of the archive
+            if header.iter().all(|&b| b == 0) {
+                return Ok(None);
+            }
+
+            let checksum = number(&header[148..156])?;
+            let sum = header
+                .iter()
+                .enumerate()
+                .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as u64)
+                .sum::<u64>();
+            if checksum != sum {
+                return Err(wasip1::ERRNO_IO);
+            }
+
+            let typeflag = header[156];
+            let size = match extension.size {
+                Some(size) => size,
+                None => number(&header[124..136])? as usize,
+            };
+            // sizes come from the archive, a broken one may overflow
+            let start = self.offset + BLOCK;
+            let end = start.checked_add(size).ok_or(wasip1::ERRNO_IO)?;
+            let data = self.archive.get(start..end).ok_or(wasip1::ERRNO_IO)?;
+            self.offset = size
+                .div_ceil(BLOCK)
+                .checked_mul(BLOCK)
+                .and_then(|padded| start.checked_add(padded))
+                .ok_or(wasip1::ERRNO_IO)?;
+
+            match typeflag {
+                // pax header of the next member
+                b'x' => pax_records(data, &mut extension)?,
+                // global pax header
+                b'g' => {}
+                // GNU long name and long link of the next member
+                b'L' => extension.path = Some(str_from(field(data))?),
+                b'K' => extension.link = Some(str_from(field(data))?),
+                _ => {
+                    let (prefix, path) = match extension.path {
+                        Some(path) => ("", path),
+                        None => (
+                            match &header[257..263] {
+                                b"ustar\0" => str_from(field(&header[345..500]))?,
+                                _ => "",
+                            },
+                            str_from(field(&header[..100]))?,
+                        ),
+                    };
+
+                    return Ok(Some(TarEntry {
+                        prefix,
+                        path,
+                        link: match extension.link {
+                            Some(link) => link,
+                            None => str_from(field(&header[157..257]))?,
+                        },
+                        typeflag,
+                        mtime: match extension.mtime {
+                            Some(mtime) => mtime,
+                            None => number(&header[136..148])?,
+                        },
+                        data,
+                    }));
+                }
+            }
+        }
+    }
+}
+
+impl<StdIo: StdIO + 'static> VFSTarLFS<StdIo> {
+    /// `name` is the name of the pre-opened root directory,
+    /// e.g. "." or "/usr/lib/rustlib".
+    pub const fn new(name: &'static str, archive: &'static [u8]) -> Self {
+        Self {
+            name,
+            archive,
+            inodes: Vec::new(),
+            indexed: None,
+            __marker: core::marker::PhantomData,
+        }
+    }
+
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    pub const fn archive(&self) -> &'static [u8] {
+        self.archive
+    }
+
+    /// Indexes the archive if it is not yet.
+    /// `ERRNO_IO` if the archive is broken,
+    /// every access fails with it then.
+    pub fn index(&mut self) -> Result<(), wasip1::Errno> {
+        if let Some(indexed) = self.indexed {
+            return indexed;
+        }
+
+        self.inodes.push(VFSTarInode {
+            parent: 0,
+            nlink: 1,
+            mtim: 0,
+            node: VFSTarNode::Dir(BTreeMap::new()),
+        });
+
+        let mut entries = TarEntries {
+            archive: self.archive,
+            offset: 0,
+        };
+
+        let indexed = loop {
+            match entries.next_entry() {
+                Ok(Some(entry)) => self.add(entry),
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+
+        if indexed.is_err() {
+            self.inodes.clear();
+        }
+        self.indexed = Some(indexed);
+
+        indexed
+    }
+
+    /// Components of a path in the archive,
+    /// `None` for members outside of the root, with `..`.
+    fn components(paths: &[&'static str]) -> Option<Vec<&'static str>> {
+        let mut components = Vec::new();
+        for part in paths.iter().flat_map(|path| path.split('/')) {
+            match part {
+                "" | "." => {}
+                ".." => return None,
+                name => components.push(name),
+            }
+        }
+
+        Some(components)
+    }
+
+    /// Returns the directory at `components`, creating missing ones.
+    /// `None` if something else is in the way.
+    fn make_dirs(&mut self, components: &[&'static str]) -> Option<usize> {
+        let mut dir = 0;
+
+        for name in components {
+            dir = match self.lookup(dir, name) {
+                Ok(inode) if self.is_dir(inode) => inode,
+                Ok(_) => return None,
+                Err(_) => self.insert(
+                    dir,
+                    name,
+                    VFSTarInode {
+                        parent: dir,
+                        nlink: 1,
+                        mtim: 0,
+                        node: VFSTarNode::Dir(BTreeMap::new()),
+                    },
+                ),
+            };
+        }
+
+        Some(dir)
+    }
+
+    /// Adds `inode` as `name` into the directory `dir`.
+    fn insert(&mut self, dir: usize, name: &'static str, inode: VFSTarInode) -> usize {
+        let ino = self.inodes.len();
+
+        if let VFSTarNode::Dir(entries) = &mut self.inodes[dir].node {
+            entries.insert(name, ino);
+        }
+        self.inodes.push(inode);
+
+        ino
+    }
+
+    /// Adds a member to the index.
+    /// Members which cannot be placed are skipped,
+    /// e.g. a file under a path which is a file, or a device.
+    fn add(&mut self, entry: TarEntry) {
+        let mtim = entry.mtime.saturating_mul(1_000_000_000);
+
+        let Some(components) = Self::components(&[entry.prefix, entry.path]) else {
+            return;
+        };
+        let Some((name, parents)) = components.split_last() else {
+            // the root itself, e.g. `./`
+            self.inodes[0].mtim = mtim;
+            return;
+        };
+        let Some(dir) = self.make_dirs(parents) else {
+            return;
+        };
+
+        let node = match entry.typeflag {
+            b'0' | 0 | b'7' => VFSTarNode::File(entry.data),
+            b'2' => VFSTarNode::Symlink(entry.link),
+            b'5' => {
+                if let Some(inode) = self.make_dirs(&components) {
+                    self.inodes[inode].mtim = mtim;
+                }
+                return;
+            }
+            // hard link to a former member
+            b'1' => {
+                let target = Self::components(&[entry.link]).and_then(|components| {
+                    components
+                        .iter()
+                        .try_fold(0, |dir, part| self.lookup(dir, part).ok())
+                });
+
+                if let Some(target) = target
+                    && !self.is_dir(target)
+                    && let VFSTarNode::Dir(entries) = &mut self.inodes[dir].node
+                {
+                    let former = entries.insert(name, target);
+                    if former != Some(target) {
+                        self.inodes[target].nlink += 1;
+                        if let Some(former) = former {
+                            self.inodes[former].nlink -= 1;
+                        }
+                    }
+                }
+                return;
+            }
+            // devices and fifos cannot be served
+            _ => return,
+        };
+
+        // a later member replaces the name of a former one,
+        // other hard links to the former keep its contents
+        match self.lookup(dir, name) {
+            Ok(inode) if self.is_dir(inode) => return,
+            Ok(inode) => self.inodes[inode].nlink -= 1,
+            Err(_) => {}
+        }
+        self.insert(
+            dir,
+            name,
+            VFSTarInode {
+                parent: dir,
+                nlink: 1,
+                mtim,
+                node,
+            },
+        );
+    }
+
+    #[inline]
+    pub fn get(&self, inode: usize) -> Option<&VFSTarInode> {
+        self.inodes.get(inode)
+    }
+
+    #[inline]
+    pub fn is_dir(&self, inode: usize) -> bool {
+        // the root is a directory even before the archive is indexed
+        inode == 0
+            || matches!(
+                self.get(inode),
+                Some(VFSTarInode {
+                    node: VFSTarNode::Dir(..),
+                    ..
+                })
+            )
+    }
+
+    #[inline]
+    pub fn parent_inode(&self, inode: usize) -> Option<usize> {
+        self.get(inode).map(|inode| inode.parent)
+    }
+
+    pub fn lookup(&self, dir: usize, name: &str) -> Result<usize, wasip1::Errno> {
+        match self.get(dir) {
+            Some(VFSTarInode {
+                node: VFSTarNode::Dir(entries),
+                ..
+            }) => entries.get(name).copied().ok_or(wasip1::ERRNO_NOENT),
+            Some(_) => Err(wasip1::ERRNO_NOTDIR),
+            None => Err(wasip1::ERRNO_BADF),
+        }
+    }
+
+    fn component_name<Wasm: WasmAccess>(
+        component: &WasmPathComponent<'_, Wasm>,
+    ) -> Result<String, wasip1::Errno> {
+        match component {
+            WasmPathComponent::Normal(name) => {
+                String::from_utf8(name.iter().collect()).map_err(|_| wasip1::ERRNO_ILSEQ)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn step<Wasm: WasmAccess>(
+        &self,
+        current: usize,
+        component: &WasmPathComponent<'_, Wasm>,
+    ) -> Result<usize, wasip1::Errno> {
+        match component {
+            // paths are resolved relative to the pre-opened directory
+            WasmPathComponent::RootDir => Ok(0),
+            WasmPathComponent::CurDir => Ok(current),
+            WasmPathComponent::ParentDir => self.parent_inode(current).ok_or(wasip1::ERRNO_NOENT),
+            WasmPathComponent::Normal(..) => {
+                self.lookup(current, &Self::component_name::<Wasm>(component)?)
+            }
+        }
+    }
+
+    /// If the inode is a symbolic link, returns the inode it points to.
+    pub fn follow(&self, inode: usize, depth: &mut usize) -> Result<usize, wasip1::Errno> {
+        match self.get(inode) {
+            Some(VFSTarInode {
+                parent,
+                node: VFSTarNode::Symlink(target),
+                ..
+            }) => {
+                *depth += 1;
+                if *depth > SYMLINK_MAX_DEPTH {
+                    return Err(wasip1::ERRNO_LOOP);
+                }
+
+                if target.is_empty() {
+                    return Err(wasip1::ERRNO_NOENT);
+                }
+
+                let mut current_inode = *parent;
+
+                for (i, part) in target.split('/').enumerate() {
+                    current_inode = self.follow(current_inode, depth)?;
+
+                    current_inode = match part {
+                        "" if i == 0 => 0,
+                        "" | "." => current_inode,
+                        ".." => self
+                            .parent_inode(current_inode)
+                            .ok_or(wasip1::ERRNO_NOENT)?,
+                        name => self.lookup(current_inode, name)?,
+                    };
+                }
+
+                self.follow(current_inode, depth)
+            }
+            _ => Ok(inode),
+        }
+    }
+
+    fn resolve_parent<Wasm: WasmAccess>(
+        &self,
+        inode: usize,
+        path_ptr: *const u8,
+        path_len: usize,
+        depth: &mut usize,
+    ) -> Result<(usize, Option<String>), wasip1::Errno> {
+        let path = WasmPathAccess::<Wasm>::new(path_ptr, path_len);
+
+        let mut components = path.components().peekable();
+
+        let mut current_inode = inode;
+
+        while let Some(part) = components.next() {
+            // every component before the last one must be a directory
+            current_inode = self.follow(current_inode, depth)?;
+
+            if components.peek().is_none()
+                && let WasmPathComponent::Normal(..) = part
+            {
+                if !self.is_dir(current_inode) {
+                    return Err(wasip1::ERRNO_NOTDIR);
+                }
+                return Ok((current_inode, Some(Self::component_name(&part)?)));
+            }
+
+            current_inode = self.step(current_inode, &part)?;
+        }
+
+        Ok((current_inode, None))
+    }
+
+    /// Resolves every component except the last one.
+    /// Returns the directory the path lives in
+    /// and the name of the last component, if it is a normal one.
+    pub fn get_parent_for_path<Wasm: WasmAccess>(
+        &self,
+        inode: usize,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(usize, Option<String>), wasip1::Errno> {
+        self.resolve_parent::<Wasm>(inode, path_ptr, path_len, &mut 0)
+    }
+
+    /// The last symbolic link is followed
+    /// only when `LOOKUPFLAGS_SYMLINK_FOLLOW` is set.
+    pub fn get_inode_for_path<Wasm: WasmAccess>(
+        &self,
+        inode: usize,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        let mut depth = 0;
+
+        let inode = match self.resolve_parent::<Wasm>(inode, path_ptr, path_len, &mut depth)? {
+            (dir, Some(name)) => self.lookup(dir, &name)?,
+            (inode, None) => inode,
+        };
+
+        if flags & wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW == wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW {
+            self.follow(inode, &mut depth)
+        } else {
+            Ok(inode)
+        }
+    }
+
+    pub fn filestat_from_inode(
+        &self,
+        inode: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        let inode_data = self.get(inode).ok_or(wasip1::ERRNO_BADF)?;
+
+        Ok(FilestatWithoutDevice {
+            ino: inode as _,
+            filetype: inode_data.node.filetype(),
+            nlink: inode_data.nlink,
+            size: inode_data.node.size() as _,
+            atim: inode_data.mtim,
+            mtim: inode_data.mtim,
+            ctim: inode_data.mtim,
+        })
+    }
+}
+
+/// Nothing to save, the archive cannot change.
+impl<StdIo: StdIO + 'static> Wasip1Snapshot for VFSTarLFS<StdIo> {
+    fn snapshot_to(&self, _: &mut SnapshotWriter) {}
+
+    fn restore_from(&mut self, _: &mut SnapshotReader<'_>) -> Result<(), wasip1::Errno> {
+        Ok(())
+    }
+}
@@ -0,0 +1,539 @@
+use crate::__private::wasip1;
+use crate::__private::wasip1::Dircookie;
+
+use crate::{
+    memory::WasmAccess,
+    wasi::file::{
+        FilestatWithoutDevice, Wasip1LFS,
+        dirent::{DIRCOOKIE_ENTRIES, DirentWriter},
+        stdio::{self, StdIO},
+        tar::lfs::{VFSTarInode, VFSTarLFS, VFSTarNode},
+    },
+};
+
+impl<StdIo: StdIO + 'static> Wasip1LFS for VFSTarLFS<StdIo> {
+    type Inode = usize;
+    const PRE_OPEN: &'static [Self::Inode] = &[0];
+
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
+        &mut self,
+        _: Self::Inode,
+        _: *const u8,
+        _: usize,
+        _: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        Err(wasip1::ERRNO_ROFS)
+    }
+
+    fn fd_write_stdout_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::write_stdout_raw::<StdIo, Wasm>(data, data_len)
+    }
+
+    fn fd_write_stderr_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::write_stderr_raw::<StdIo, Wasm>(data, data_len)
+    }
+
+    fn is_dir(&self, inode: Self::Inode) -> bool {
+        self.is_dir(inode)
+    }
+
+    fn fd_readdir_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        cookie: Dircookie,
+    ) -> Result<(wasip1::Size, Dircookie), wasip1::Errno> {
+        self.index()?;
+
+        let entries = match self.get(inode) {
+            Some(VFSTarInode {
+                node: VFSTarNode::Dir(entries),
+                ..
+            }) => entries,
+            Some(_) => return Err(wasip1::ERRNO_NOTDIR),
+            None => return Err(wasip1::ERRNO_BADF),
+        };
+
+        let mut writer = DirentWriter::<Wasm>::new(buf, buf_len, cookie);
+
+        if !writer.dots(inode as _, self.parent_inode(inode).unwrap() as _) {
+            return Ok(writer.finish());
+        }
+
+        // the archive never changes, so the position is a stable cookie
+        let skipped = cookie.saturating_sub(DIRCOOKIE_ENTRIES) as usize;
+        for (index, (name, ino)) in entries.iter().enumerate().skip(skipped) {
+            let filetype = self.get(*ino).unwrap().node.filetype();
+
+            if !writer.entry(
+                DIRCOOKIE_ENTRIES + index as Dircookie,
+                *ino as _,
+                filetype,
+                name.as_bytes(),
+            ) {
+                break;
+            }
+        }
+
+        Ok(writer.finish())
+    }
+
+    fn path_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        self.index()?;
+
+        let inode = self.get_inode_for_path::<Wasm>(inode, flags, path_ptr, path_len)?;
+
+        self.filestat_from_inode(inode)
+    }
+
+    fn fd_prestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<wasip1::Prestat, wasip1::Errno> {
+        if !Self::PRE_OPEN.contains(&inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        Ok(wasip1::Prestat {
+            tag: 0, // prestat is enum but variant is only 0
+            // union type but we only have one variant
+            u: wasip1::PrestatU {
+                dir: wasip1::PrestatDir {
+                    pr_name_len: self.name().len() as _,
+                },
+            },
+        })
+    }
+
+    fn fd_prestat_dir_name_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        dir_path_ptr: *mut u8,
+        dir_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        if !Self::PRE_OPEN.contains(&inode) {
+            return Err(wasip1::ERRNO_BADF);
+        }
+
+        let name = self.name();
+
+        Wasm::memcpy(
+            dir_path_ptr,
+            &name.as_bytes()[..core::cmp::min(name.len(), dir_path_len)],
+        );
+
+        Ok(())
+    }
+
+    fn fd_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        self.index()?;
+
+        self.filestat_from_inode(inode)
+    }
+
+    fn fd_pread_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.index()?;
+
+        match self.get(inode) {
+            Some(VFSTarInode {
+                node: VFSTarNode::File(file),
+                ..
+            }) => {
+                if offset >= file.len() {
+                    return Ok(0); // No data to read
+                }
+
+                let buf_len = core::cmp::min(buf_len, file.len() - offset);
+                Wasm::memcpy(buf, &file[offset..offset + buf_len]);
+
+                Ok(buf_len)
+            }
+            Some(_) => Err(wasip1::ERRNO_ISDIR),
+            None => Err(wasip1::ERRNO_BADF),
+        }
+    }
+
+    fn fd_read_stdin_raw<Wasm: WasmAccess>(
+        &mut self,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        stdio::read_stdin_raw::<StdIo, Wasm>(buf, buf_len)
+    }
+
+    fn path_open_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        dir_flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        _: wasip1::Rights,
+        _: wasip1::Fdflags,
+    ) -> Result<Self::Inode, wasip1::Errno> {
+        self.index()?;
+
+        match self.get_inode_for_path::<Wasm>(dir_inode, dir_flags, path_ptr, path_len) {
+            Ok(inode) => {
+                if o_flags & wasip1::OFLAGS_EXCL == wasip1::OFLAGS_EXCL {
+                    return Err(wasip1::ERRNO_EXIST);
+                }
+
+                // the link was not followed
+                if let Some(VFSTarInode {
+                    node: VFSTarNode::Symlink(..),
+                    ..
+                }) = self.get(inode)
+                {
+                    return Err(wasip1::ERRNO_LOOP);
+                }
+
+                if o_flags & wasip1::OFLAGS_DIRECTORY == wasip1::OFLAGS_DIRECTORY
+                    && !self.is_dir(inode)
+                {
+                    return Err(wasip1::ERRNO_NOTDIR);
+                }
+
+                if fs_rights_base & wasip1::RIGHTS_FD_WRITE == wasip1::RIGHTS_FD_WRITE {
+                    return Err(wasip1::ERRNO_ROFS);
+                }
+
+                if o_flags & wasip1::OFLAGS_TRUNC == wasip1::OFLAGS_TRUNC {
+                    return Err(wasip1::ERRNO_ROFS);
+                }
+
+                Ok(inode)
+            }
+            Err(wasip1::ERRNO_NOENT) if o_flags & wasip1::OFLAGS_CREAT == wasip1::OFLAGS_CREAT => {
+                Err(wasip1::ERRNO_ROFS)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.index()?;
+
+        match self.get_inode_for_path::<Wasm>(dir_inode, 0, path_ptr, path_len) {
+            Ok(_) => Err(wasip1::ERRNO_EXIST),
+            Err(wasip1::ERRNO_NOENT) => Err(wasip1::ERRNO_ROFS),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.index()?;
+
+        let inode = self.get_inode_for_path::<Wasm>(dir_inode, 0, path_ptr, path_len)?;
+
+        if !self.is_dir(inode) {
+            return Err(wasip1::ERRNO_NOTDIR);
+        }
+
+        Err(wasip1::ERRNO_ROFS)
+    }
+
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.index()?;
+
+        let inode = self.get_inode_for_path::<Wasm>(dir_inode, 0, path_ptr, path_len)?;
+
+        if self.is_dir(inode) {
+            return Err(wasip1::ERRNO_ISDIR);
+        }
+
+        Err(wasip1::ERRNO_ROFS)
+    }
+
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_dir_inode: Self::Inode,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        _: Self::Inode,
+        _: *const u8,
+        _: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.index()?;
+
+        self.get_inode_for_path::<Wasm>(old_dir_inode, 0, old_path_ptr, old_path_len)?;
+
+        Err(wasip1::ERRNO_ROFS)
+    }
+
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_inode: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.index()?;
+
+        let inode = self.get_inode_for_path::<Wasm>(dir_inode, 0, path_ptr, path_len)?;
+
+        match self.get(inode) {
+            Some(VFSTarInode {
+                node: VFSTarNode::Symlink(target),
+                ..
+            }) => {
+                let len = core::cmp::min(target.len(), buf_len);
+                Wasm::memcpy(buf, &target.as_bytes()[..len]);
+                Ok(len)
+            }
+            _ => Err(wasip1::ERRNO_INVAL),
+        }
+    }
+
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        _: *const u8,
+        _: usize,
+        dir_inode: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.index()?;
+
+        match self.get_inode_for_path::<Wasm>(dir_inode, 0, new_path_ptr, new_path_len) {
+            Ok(_) => Err(wasip1::ERRNO_EXIST),
+            Err(wasip1::ERRNO_NOENT) => Err(wasip1::ERRNO_ROFS),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::String, vec::Vec};
+
+    use super::*;
+    use crate::{
+        memory::HostMemory,
+        mock::MockWasm,
+        wasi::file::{Wasip1FileSystem, constant::vfs::Wasip1ConstVFS, stdio::DefaultStdIO},
+    };
+
+    /// a pax archive with a long path, a symbolic link and a hard link
+    const ARCHIVE: &[u8] = include_bytes!("../../../../test_assets/archive.tar");
+
+    type Vfs = Wasip1ConstVFS<VFSTarLFS<DefaultStdIO>, 8>;
+
+    fn read_file(vfs: &mut Vfs, path: &str) -> Result<String, wasip1::Errno> {
+        let fd = MockWasm::open_with(
+            vfs,
+            3,
+            wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+            path,
+            0,
+            wasip1::RIGHTS_FD_READ | wasip1::RIGHTS_FD_SEEK,
+            0,
+        )?;
+        let data = MockWasm::read(vfs, fd, 256)?;
+        assert_eq!(
+            Wasip1FileSystem::fd_close_raw::<HostMemory>(vfs, fd),
+            wasip1::ERRNO_SUCCESS
+        );
+
+        Ok(String::from_utf8(data).unwrap())
+    }
+
+    fn stat(vfs: &mut Vfs, path: &str, flags: wasip1::Lookupflags) -> wasip1::Filestat {
+        let mut filestat = core::mem::MaybeUninit::<wasip1::Filestat>::uninit();
+        assert_eq!(
+            Wasip1FileSystem::path_filestat_get_raw::<HostMemory>(
+                vfs,
+                3,
+                flags,
+                path.as_ptr(),
+                path.len(),
+                filestat.as_mut_ptr(),
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        unsafe { filestat.assume_init() }
+    }
+
+    #[test]
+    fn test_tar_read() {
+        let mut vfs = Vfs::new(VFSTarLFS::new("/sysroot", ARCHIVE));
+
+        assert_eq!(read_file(&mut vfs, "hello.txt").unwrap(), "Hello, tar!\n");
+        assert_eq!(
+            read_file(&mut vfs, "./lib/../hello.txt").unwrap(),
+            "Hello, tar!\n"
+        );
+
+        let long = "lib/a_directory_name_which_is_long_enough/to_need_a_pax_header_because_ustar_names_are_limited_to_one_hundred_bytes.txt";
+        assert_eq!(read_file(&mut vfs, long).unwrap(), "long\n");
+
+        // symbolic link and hard link
+        assert_eq!(
+            read_file(&mut vfs, "lib/link.txt").unwrap(),
+            "Hello, tar!\n"
+        );
+        assert_eq!(read_file(&mut vfs, "hard.txt").unwrap(), "Hello, tar!\n");
+
+        let link = stat(&mut vfs, "lib/link.txt", 0);
+        assert_eq!(link.filetype, wasip1::FILETYPE_SYMBOLIC_LINK);
+
+        let hello = stat(&mut vfs, "hello.txt", 0);
+        assert_eq!(hello.filetype, wasip1::FILETYPE_REGULAR_FILE);
+        assert_eq!(hello.size, 12);
+        assert_eq!(hello.nlink, 2);
+        assert_eq!(stat(&mut vfs, "hard.txt", 0).ino, hello.ino);
+        assert_eq!(hello.mtim, 1_700_000_000 * 1_000_000_000);
+
+        let lib = stat(&mut vfs, "lib", 0);
+        assert_eq!(lib.filetype, wasip1::FILETYPE_DIRECTORY);
+        assert_eq!(lib.mtim, 1_600_000_000 * 1_000_000_000);
+    }
+
+    #[test]
+    fn test_tar_read_only() {
+        let mut vfs = Vfs::new(VFSTarLFS::new("/sysroot", ARCHIVE));
+
+        assert_eq!(
+            MockWasm::open_with(
+                &mut vfs,
+                3,
+                wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+                "hello.txt",
+                0,
+                wasip1::RIGHTS_FD_WRITE,
+                0
+            ),
+            Err(wasip1::ERRNO_ROFS)
+        );
+        assert_eq!(
+            MockWasm::open_with(
+                &mut vfs,
+                3,
+                wasip1::LOOKUPFLAGS_SYMLINK_FOLLOW,
+                "missing.txt",
+                0,
+                wasip1::RIGHTS_FD_READ,
+                0
+            ),
+            Err(wasip1::ERRNO_NOENT)
+        );
+
+        let path = "lib";
+        assert_eq!(
+            Wasip1FileSystem::path_create_directory_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                path.as_ptr(),
+                path.len()
+            ),
+            wasip1::ERRNO_EXIST
+        );
+
+        // a broken archive
+        let mut broken = Vfs::new(VFSTarLFS::new("/sysroot", &ARCHIVE[1..]));
+        assert_eq!(read_file(&mut broken, "hello.txt"), Err(wasip1::ERRNO_IO));
+    }
+
+    /// A v7 header of the member and its data, padded to blocks.
+    fn member(name: &str, typeflag: u8, link: &str, data: &[u8]) -> Vec<u8> {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+        header[156] = typeflag;
+        header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        header[148..156].fill(b' ');
+        let sum = header.iter().map(|&b| b as u32).sum::<u32>();
+        header[148..155].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+
+        let mut member = header.to_vec();
+        member.extend_from_slice(data);
+        member.resize(member.len().next_multiple_of(512), 0);
+        member
+    }
+
+    #[test]
+    fn test_tar_replaced_member() {
+        // `b` links the first `a`, which a later `a` replaces
+        let mut archive = [
+            member("a", b'0', "", b"old"),
+            member("b", b'1', "a", b""),
+            member("a", b'0', "", b"new"),
+        ]
+        .concat();
+        archive.resize(archive.len() + 512 * 2, 0);
+        let mut vfs = Vfs::new(VFSTarLFS::new("/sysroot", archive.leak()));
+
+        assert_eq!(read_file(&mut vfs, "a").unwrap(), "new");
+        assert_eq!(read_file(&mut vfs, "b").unwrap(), "old");
+
+        let (a, b) = (stat(&mut vfs, "a", 0), stat(&mut vfs, "b", 0));
+        assert_ne!(a.ino, b.ino);
+        assert_eq!((a.nlink, b.nlink), (1, 1));
+    }
+
+    #[test]
+    fn test_tar_size_overflow() {
+        // one member `a` with a base-256 size
+        let archive = |size: u8| {
+            let mut header = [0u8; 512];
+            header[0] = b'a';
+            header[156] = b'0';
+            header[124] = 0x80;
+            header[128..136].fill(size);
+            header[148..156].fill(b' ');
+            let sum = header.iter().map(|&b| b as u32).sum::<u32>();
+            header[148..155].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+
+            let mut archive = header.to_vec();
+            archive.resize(512 * 4, 0);
+            &*archive.leak()
+        };
+
+        let mut lfs = VFSTarLFS::<DefaultStdIO>::new("/sysroot", archive(0));
+        assert_eq!(lfs.index(), Ok(()));
+
+        let mut lfs = VFSTarLFS::<DefaultStdIO>::new("/sysroot", archive(0xff));
+        assert_eq!(lfs.index(), Err(wasip1::ERRNO_IO));
+    }
+}
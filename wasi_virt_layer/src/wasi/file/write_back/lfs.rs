@@ -0,0 +1,488 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec,
+    vec::Vec,
+};
+
+use crate::__private::wasip1;
+use crate::{
+    memory::{HostMemory, WasmAccess},
+    transporter::Wasip1Transporter,
+    wasi::file::{SYMLINK_MAX_DEPTH, Wasip1LFS, host::fs::HostFS},
+};
+
+/// Write-back cache
+/// which mirrors the first pre-opened directory of another one
+/// to a directory pre-opened by the host,
+/// e.g. a heap LFS written back to the host preopen fd 3.
+///
+/// Writes stay in the inner file system and only mark the file dirty.
+/// Dirty files are pushed to the host in one batch
+/// when the guest calls `fd_sync`, `fd_datasync` or `fd_close` on them,
+/// or when the host calls [`VFSWriteBackLFS::flush`], see [`plug_flush!`](crate::plug_flush).
+/// Created directories, removes, renames and symbolic links are replayed
+/// on the host before the files.
+///
+/// Paths are resolved in the inner file system,
+/// so a file reached through a linked directory is written back under its real path,
+/// while a file opened through a link to it is written back under the path of the link.
+/// Symbolic links whose target is absolute or climbs out of the mirrored directory
+/// are refused with `ERRNO_PERM`, the host would follow them outside.
+/// What the host does not have or already has is skipped
+/// and kept in [`VFSWriteBackLFS::skipped`].
+/// Times are not written back.
+pub struct VFSWriteBackLFS<Inner: Wasip1LFS, Host: HostFS = Wasip1Transporter>
+where
+    Inner::Inode: Copy + Ord,
+{
+    inner: Inner,
+    /// pre-opened directory of the host
+    host_fd: wasip1::Fd,
+    /// path relative to the root and the number of opens of the opened inodes,
+    /// no path for the ones not opened from the mirrored directory
+    opened: BTreeMap<Inner::Inode, (Option<String>, usize)>,
+    /// paths of the files written since they were last pushed
+    dirty: BTreeSet<String>,
+    /// changes of the tree not replayed on the host yet, in order
+    pending: Vec<VFSWriteBackOp>,
+    /// what flushing skipped and why
+    skipped: Vec<(VFSWriteBackOp, wasip1::Errno)>,
+    __marker: core::marker::PhantomData<Host>,
+}
+
+/// Change of the tree replayed on the host,
+/// paths are relative to the root.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VFSWriteBackOp {
+    CreateDirectory(String),
+    RemoveDirectory(String),
+    UnlinkFile(String),
+    Rename(String, String),
+    Symlink(String, String),
+    /// contents of a dirty file, never pending, only in [`VFSWriteBackLFS::skipped`]
+    WriteFile(String),
+}
+
+impl<Inner: Wasip1LFS, Host: HostFS> VFSWriteBackLFS<Inner, Host>
+where
+    Inner::Inode: Copy + Ord,
+{
+    pub const fn new(inner: Inner, host_fd: wasip1::Fd) -> Self {
+        Self {
+            inner,
+            host_fd,
+            opened: BTreeMap::new(),
+            dirty: BTreeSet::new(),
+            pending: Vec::new(),
+            skipped: Vec::new(),
+            __marker: core::marker::PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &Inner {
+        &self.inner
+    }
+
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut Inner {
+        &mut self.inner
+    }
+
+    #[inline]
+    pub fn host_fd(&self) -> wasip1::Fd {
+        self.host_fd
+    }
+
+    /// Paths of the files not pushed to the host yet.
+    pub fn dirty(&self) -> impl Iterator<Item = &str> {
+        self.dirty.iter().map(String::as_str)
+    }
+
+    /// Changes of the tree not replayed on the host yet.
+    #[inline]
+    pub fn pending(&self) -> &[VFSWriteBackOp] {
+        &self.pending
+    }
+
+    /// What flushing skipped since the last [`VFSWriteBackLFS::take_skipped`],
+    /// with the errno of the host or the inner file system:
+    /// `ERRNO_NOENT` for a change of an entry the host does not have,
+    /// `ERRNO_EXIST` for an entry the host already has
+    /// and `ERRNO_NOENT` or `ERRNO_ISDIR` for a file
+    /// removed or replaced through a path which was not mirrored.
+    #[inline]
+    pub fn skipped(&self) -> &[(VFSWriteBackOp, wasip1::Errno)] {
+        &self.skipped
+    }
+
+    #[inline]
+    pub fn take_skipped(&mut self) -> Vec<(VFSWriteBackOp, wasip1::Errno)> {
+        core::mem::take(&mut self.skipped)
+    }
+
+    /// Path of the inode relative to the root,
+    /// `None` if it was not opened from the mirrored directory.
+    pub fn path(&self, inode: Inner::Inode) -> Option<&str> {
+        if inode == Inner::PRE_OPEN[0] {
+            return Some("");
+        }
+
+        self.opened.get(&inode)?.0.as_deref()
+    }
+
+    /// Resolves the path against the directory in the inner file system,
+    /// following the symbolic links before the last component,
+    /// `None` if the directory is not mirrored or the path leaves it.
+    pub fn join<Wasm: WasmAccess>(
+        &mut self,
+        dir: Inner::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Option<String> {
+        let path = Wasm::get_array(path_ptr, path_len);
+        let path = core::str::from_utf8(&path).ok()?;
+
+        if path.starts_with('/') {
+            return None;
+        }
+
+        let mut joined = self
+            .path(dir)?
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        // components left, the next one last
+        let mut rest = path.split('/').rev().map(String::from).collect::<Vec<_>>();
+        let mut links = 0;
+
+        while let Some(part) = rest.pop() {
+            match part.as_str() {
+                "" | "." => {}
+                ".." => {
+                    joined.pop()?;
+                }
+                _ => {
+                    joined.push(part);
+
+                    if rest.iter().all(|part| part.is_empty() || part == ".") {
+                        continue;
+                    }
+
+                    if let Some(target) = self.link_target(&joined.join("/")) {
+                        links += 1;
+                        if links > SYMLINK_MAX_DEPTH || target.starts_with('/') {
+                            return None;
+                        }
+
+                        joined.pop();
+                        rest.extend(target.split('/').rev().map(String::from));
+                    }
+                }
+            }
+        }
+
+        Some(joined.join("/"))
+    }
+
+    /// Target of the symbolic link at the path in the inner file system,
+    /// `None` if there is no symbolic link there.
+    fn link_target(&mut self, path: &str) -> Option<String> {
+        let filestat = self
+            .inner
+            .path_filestat_get_raw::<HostMemory>(Inner::PRE_OPEN[0], 0, path.as_ptr(), path.len())
+            .ok()?;
+
+        if filestat.filetype != wasip1::FILETYPE_SYMBOLIC_LINK {
+            return None;
+        }
+
+        let mut buf = vec![0; filestat.size as usize];
+        let len = self
+            .inner
+            .path_readlink_raw::<HostMemory>(
+                Inner::PRE_OPEN[0],
+                path.as_ptr(),
+                path.len(),
+                buf.as_mut_ptr(),
+                buf.len(),
+            )
+            .ok()?;
+        buf.truncate(len);
+
+        String::from_utf8(buf).ok()
+    }
+
+    pub(crate) fn opened(&mut self, inode: Inner::Inode, path: Option<String>) {
+        self.opened.entry(inode).or_insert((path, 0)).1 += 1;
+    }
+
+    /// Forgets the path of the inode once every descriptor of it is closed.
+    pub(crate) fn closed(&mut self, inode: Inner::Inode) {
+        if let Some((_, count)) = self.opened.get_mut(&inode) {
+            *count -= 1;
+            if *count == 0 {
+                self.opened.remove(&inode);
+            }
+        }
+    }
+
+    pub(crate) fn mark_dirty(&mut self, inode: Inner::Inode) {
+        if let Some((Some(path), _)) = self.opened.get(&inode) {
+            self.dirty.insert(path.clone());
+        }
+    }
+
+    pub(crate) fn push_op(&mut self, op: VFSWriteBackOp) {
+        match &op {
+            VFSWriteBackOp::RemoveDirectory(path) | VFSWriteBackOp::UnlinkFile(path) => {
+                self.dirty.retain(|dirty| !is_under(dirty, path));
+            }
+            VFSWriteBackOp::Rename(old, new) => {
+                // the target is replaced by what is moved there
+                self.dirty.retain(|dirty| !is_under(dirty, new));
+                self.dirty = core::mem::take(&mut self.dirty)
+                    .into_iter()
+                    .map(|dirty| moved(dirty, old, new))
+                    .collect();
+
+                for (path, _) in self.opened.values_mut() {
+                    *path = path.take().map(|path| moved(path, old, new));
+                }
+            }
+            _ => {}
+        }
+
+        self.pending.push(op);
+    }
+
+    /// Pushes every dirty file and pending change to the host.
+    /// What fails is kept and tried again on the next flush.
+    pub fn flush(&mut self) -> Result<(), wasip1::Errno> {
+        self.flush_pending()?;
+
+        let dirty = core::mem::take(&mut self.dirty)
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        for (i, path) in dirty.iter().enumerate() {
+            if let Err(e) = self.write_back(path) {
+                self.dirty.extend(dirty[i..].iter().cloned());
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pushes the pending changes and the file of the inode to the host.
+    pub fn flush_inode(&mut self, inode: Inner::Inode) -> Result<(), wasip1::Errno> {
+        self.flush_pending()?;
+
+        let Some((Some(path), _)) = self.opened.get(&inode) else {
+            return Ok(());
+        };
+
+        if let Some(path) = self.dirty.take(path)
+            && let Err(e) = self.write_back(&path)
+        {
+            self.dirty.insert(path);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    fn flush_pending(&mut self) -> Result<(), wasip1::Errno> {
+        let pending = core::mem::take(&mut self.pending);
+
+        for (i, op) in pending.iter().enumerate() {
+            if let Err(e) = self.replay(op) {
+                self.pending = pending[i..].to_vec();
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Missing entries and entries already there are skipped,
+    /// the host may have never seen them or already have them.
+    fn replay(&mut self, op: &VFSWriteBackOp) -> Result<(), wasip1::Errno> {
+        let result = match op {
+            VFSWriteBackOp::CreateDirectory(path) => {
+                Host::path_create_directory(self.host_fd, path)
+            }
+            VFSWriteBackOp::RemoveDirectory(path) => {
+                Host::path_remove_directory(self.host_fd, path)
+            }
+            VFSWriteBackOp::UnlinkFile(path) => Host::path_unlink_file(self.host_fd, path),
+            VFSWriteBackOp::Rename(old, new) => {
+                Host::path_rename(self.host_fd, old, self.host_fd, new)
+            }
+            VFSWriteBackOp::Symlink(target, path) => Host::path_symlink(target, self.host_fd, path),
+            VFSWriteBackOp::WriteFile(path) => self.write_back(path),
+        };
+
+        match result {
+            Err(e @ (wasip1::ERRNO_NOENT | wasip1::ERRNO_EXIST)) => {
+                self.skipped.push((op.clone(), e));
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Replaces the host file with the contents in the inner file system.
+    fn write_back(&mut self, path: &str) -> Result<(), wasip1::Errno> {
+        let data = match self.read_inner(path) {
+            Ok(data) => data,
+            // removed through a path which was not mirrored
+            Err(e @ (wasip1::ERRNO_NOENT | wasip1::ERRNO_ISDIR)) => {
+                self.skipped
+                    .push((VFSWriteBackOp::WriteFile(String::from(path)), e));
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+
+        // directories which were there from the start
+        let mut parent = 0;
+        while let Some(end) = path[parent..].find('/') {
+            parent += end;
+            match Host::path_create_directory(self.host_fd, &path[..parent]) {
+                Ok(()) => {}
+                Err(wasip1::ERRNO_EXIST) => {
+                    let filestat = Host::path_filestat_get(self.host_fd, 0, &path[..parent])?;
+                    if filestat.filetype != wasip1::FILETYPE_DIRECTORY {
+                        return Err(wasip1::ERRNO_NOTDIR);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+            parent += 1;
+        }
+
+        let fd = Host::path_open(
+            self.host_fd,
+            0,
+            path,
+            wasip1::OFLAGS_CREAT | wasip1::OFLAGS_TRUNC,
+            wasip1::RIGHTS_FD_WRITE,
+            0,
+            0,
+        )?;
+
+        let mut written = 0;
+        let result = loop {
+            if written == data.len() {
+                break Ok(());
+            }
+            match Host::fd_pwrite(fd, &data[written..], written as _) {
+                Ok(0) => break Err(wasip1::ERRNO_IO),
+                Ok(n) => written += n,
+                Err(e) => break Err(e),
+            }
+        };
+
+        Host::fd_close(fd)?;
+        result
+    }
+
+    fn read_inner(&mut self, path: &str) -> Result<Vec<u8>, wasip1::Errno> {
+        let inode = self.inner.path_open_raw::<HostMemory>(
+            Inner::PRE_OPEN[0],
+            0,
+            path.as_ptr(),
+            path.len(),
+            0,
+            0,
+            0,
+            0,
+        )?;
+
+        let data = self.read_inode(inode);
+        self.inner.fd_close_raw::<HostMemory>(inode);
+        data
+    }
+
+    fn read_inode(&mut self, inode: Inner::Inode) -> Result<Vec<u8>, wasip1::Errno> {
+        if self.inner.is_dir(inode) {
+            return Err(wasip1::ERRNO_ISDIR);
+        }
+
+        let size = self.inner.fd_filestat_get_raw::<HostMemory>(inode)?.size as usize;
+
+        let mut data = vec![0; size];
+        let mut read = 0;
+
+        while read < size {
+            let nread = self.inner.fd_pread_raw::<HostMemory>(
+                inode,
+                data[read..].as_mut_ptr(),
+                size - read,
+                read,
+            )?;
+            if nread == 0 {
+                break;
+            }
+            read += nread;
+        }
+
+        data.truncate(read);
+
+        Ok(data)
+    }
+}
+
+/// Whether the path is the other path or inside it.
+fn is_under(path: &str, dir: &str) -> bool {
+    match path.strip_prefix(dir) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+fn moved(path: String, old: &str, new: &str) -> String {
+    if is_under(&path, old) {
+        alloc::format!("{new}{}", &path[old.len()..])
+    } else {
+        path
+    }
+}
+
+/// Exports the `virtual-file-system-flush` interface of `wit/flush.wit`
+/// on the type given to `export!`, for a [`VFSWriteBackLFS`].
+/// The world of the crate has to include it
+/// the same way as for [`plug_snapshot!`](crate::plug_snapshot):
+///
+/// ```ignore
+/// // wit/main.wit
+/// world main {
+///   include wasip1-vfs:host/flush;
+///   export main: func();
+/// }
+/// ```
+///
+/// ```ignore
+/// plug_flush!(Main, {
+///     #[allow(static_mut_refs)]
+///     unsafe { VIRTUAL_FILE_SYSTEM.lfs_mut() }
+/// });
+/// ```
+#[macro_export]
+macro_rules! plug_flush {
+    ($guest:ty, $state:expr) => {
+        impl exports::wasip1_vfs::host::virtual_file_system_flush::Guest for $guest {
+            fn flush() -> Result<(), $crate::__private::String> {
+                let state = $state;
+                state
+                    .flush()
+                    .map_err(|errno| $crate::__private::String::from(errno.message()))
+            }
+        }
+    };
+}
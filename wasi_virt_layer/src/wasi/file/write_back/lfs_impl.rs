@@ -0,0 +1,723 @@
+use alloc::string::String;
+
+use crate::__private::wasip1;
+use crate::__private::wasip1::Dircookie;
+
+use crate::{
+    memory::WasmAccess,
+    wasi::file::{
+        FilestatWithoutDevice, Wasip1LFS,
+        host::{fs::HostFS, lfs::VFSHostLFS},
+        stdio::DefaultStdIO,
+        write_back::lfs::{VFSWriteBackLFS, VFSWriteBackOp},
+    },
+};
+
+impl<Inner: Wasip1LFS, Host: HostFS> Wasip1LFS for VFSWriteBackLFS<Inner, Host>
+where
+    Inner::Inode: Copy + Ord,
+{
+    type Inode = Inner::Inode;
+    const PRE_OPEN: &'static [Self::Inode] = Inner::PRE_OPEN;
+
+    fn fd_pwrite_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        data: *const u8,
+        data_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        let written = self
+            .inner_mut()
+            .fd_pwrite_raw::<Wasm>(inode, data, data_len, offset)?;
+        self.mark_dirty(inode);
+
+        Ok(written)
+    }
+
+    fn fd_write_stdout_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.inner_mut().fd_write_stdout_raw::<Wasm>(data, data_len)
+    }
+
+    fn fd_write_stderr_raw<Wasm: WasmAccess>(
+        &mut self,
+        data: *const u8,
+        data_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.inner_mut().fd_write_stderr_raw::<Wasm>(data, data_len)
+    }
+
+    fn is_dir(&self, inode: Self::Inode) -> bool {
+        self.inner().is_dir(inode)
+    }
+
+    fn fd_readdir_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        cookie: Dircookie,
+    ) -> Result<(wasip1::Size, Dircookie), wasip1::Errno> {
+        self.inner_mut()
+            .fd_readdir_raw::<Wasm>(inode, buf, buf_len, cookie)
+    }
+
+    fn path_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        self.inner_mut()
+            .path_filestat_get_raw::<Wasm>(inode, flags, path_ptr, path_len)
+    }
+
+    fn fd_prestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<wasip1::Prestat, wasip1::Errno> {
+        self.inner_mut().fd_prestat_get_raw::<Wasm>(inode)
+    }
+
+    fn fd_prestat_dir_name_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        dir_path_ptr: *mut u8,
+        dir_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.inner_mut()
+            .fd_prestat_dir_name_raw::<Wasm>(inode, dir_path_ptr, dir_path_len)
+    }
+
+    fn fd_filestat_get_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+    ) -> Result<FilestatWithoutDevice, wasip1::Errno> {
+        self.inner_mut().fd_filestat_get_raw::<Wasm>(inode)
+    }
+
+    /// The error of writing back is dropped,
+    /// the file stays dirty for the next flush.
+    fn fd_close_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) {
+        let _ = self.flush_inode(inode);
+        self.closed(inode);
+        self.inner_mut().fd_close_raw::<Wasm>(inode);
+    }
+
+    fn fd_sync_raw<Wasm: WasmAccess>(&mut self, inode: Self::Inode) -> Result<(), wasip1::Errno> {
+        self.inner_mut().fd_sync_raw::<Wasm>(inode)?;
+        self.flush_inode(inode)
+    }
+
+    fn fd_seek_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        cursor: usize,
+        offset: wasip1::Filedelta,
+        whence: wasip1::Whence,
+    ) -> Result<usize, wasip1::Errno> {
+        self.inner_mut()
+            .fd_seek_raw::<Wasm>(inode, cursor, offset, whence)
+    }
+
+    fn fd_tell_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        cursor: usize,
+    ) -> Result<usize, wasip1::Errno> {
+        self.inner_mut().fd_tell_raw::<Wasm>(inode, cursor)
+    }
+
+    fn fd_filestat_set_times_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        atim: wasip1::Timestamp,
+        mtim: wasip1::Timestamp,
+        fst_flags: wasip1::Fstflags,
+    ) -> Result<(), wasip1::Errno> {
+        self.inner_mut()
+            .fd_filestat_set_times_raw::<Wasm>(inode, atim, mtim, fst_flags)
+    }
+
+    fn fd_pread_raw<Wasm: WasmAccess>(
+        &mut self,
+        inode: Self::Inode,
+        buf: *mut u8,
+        buf_len: usize,
+        offset: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.inner_mut()
+            .fd_pread_raw::<Wasm>(inode, buf, buf_len, offset)
+    }
+
+    fn fd_read_stdin_raw<Wasm: WasmAccess>(
+        &mut self,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.inner_mut().fd_read_stdin_raw::<Wasm>(buf, buf_len)
+    }
+
+    fn path_open_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        dir_flags: wasip1::Lookupflags,
+        path_ptr: *const u8,
+        path_len: usize,
+        o_flags: wasip1::Oflags,
+        fs_rights_base: wasip1::Rights,
+        fs_rights_inheriting: wasip1::Rights,
+        fd_flags: wasip1::Fdflags,
+    ) -> Result<Self::Inode, wasip1::Errno> {
+        let inode = self.inner_mut().path_open_raw::<Wasm>(
+            dir_ino,
+            dir_flags,
+            path_ptr,
+            path_len,
+            o_flags,
+            fs_rights_base,
+            fs_rights_inheriting,
+            fd_flags,
+        )?;
+
+        // counted even when the path is not mirrored, every open is paired with a close
+        let path = self.join::<Wasm>(dir_ino, path_ptr, path_len);
+        self.opened(inode, path);
+
+        if o_flags & (wasip1::OFLAGS_CREAT | wasip1::OFLAGS_TRUNC) != 0 && !self.is_dir(inode) {
+            self.mark_dirty(inode);
+        }
+
+        Ok(inode)
+    }
+
+    fn path_create_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.inner_mut()
+            .path_create_directory_raw::<Wasm>(dir_ino, path_ptr, path_len)?;
+
+        if let Some(path) = self.join::<Wasm>(dir_ino, path_ptr, path_len) {
+            self.push_op(VFSWriteBackOp::CreateDirectory(path));
+        }
+
+        Ok(())
+    }
+
+    fn path_remove_directory_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.inner_mut()
+            .path_remove_directory_raw::<Wasm>(dir_ino, path_ptr, path_len)?;
+
+        if let Some(path) = self.join::<Wasm>(dir_ino, path_ptr, path_len) {
+            self.push_op(VFSWriteBackOp::RemoveDirectory(path));
+        }
+
+        Ok(())
+    }
+
+    fn path_unlink_file_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.inner_mut()
+            .path_unlink_file_raw::<Wasm>(dir_ino, path_ptr, path_len)?;
+
+        if let Some(path) = self.join::<Wasm>(dir_ino, path_ptr, path_len) {
+            self.push_op(VFSWriteBackOp::UnlinkFile(path));
+        }
+
+        Ok(())
+    }
+
+    fn path_rename_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_dir_ino: Self::Inode,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        new_dir_ino: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        self.inner_mut().path_rename_raw::<Wasm>(
+            old_dir_ino,
+            old_path_ptr,
+            old_path_len,
+            new_dir_ino,
+            new_path_ptr,
+            new_path_len,
+        )?;
+
+        if let (Some(old), Some(new)) = (
+            self.join::<Wasm>(old_dir_ino, old_path_ptr, old_path_len),
+            self.join::<Wasm>(new_dir_ino, new_path_ptr, new_path_len),
+        ) {
+            self.push_op(VFSWriteBackOp::Rename(old, new));
+        }
+
+        Ok(())
+    }
+
+    fn path_readlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        dir_ino: Self::Inode,
+        path_ptr: *const u8,
+        path_len: usize,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> Result<wasip1::Size, wasip1::Errno> {
+        self.inner_mut()
+            .path_readlink_raw::<Wasm>(dir_ino, path_ptr, path_len, buf, buf_len)
+    }
+
+    fn path_symlink_raw<Wasm: WasmAccess>(
+        &mut self,
+        old_path_ptr: *const u8,
+        old_path_len: usize,
+        dir_ino: Self::Inode,
+        new_path_ptr: *const u8,
+        new_path_len: usize,
+    ) -> Result<(), wasip1::Errno> {
+        let target = Wasm::get_array(old_path_ptr, old_path_len);
+        let target = String::from_utf8(target.into_vec()).map_err(|_| wasip1::ERRNO_ILSEQ)?;
+
+        // the host would follow it out of the mirrored directory
+        let path = self.join::<Wasm>(dir_ino, new_path_ptr, new_path_len);
+        if let Some(path) = &path {
+            VFSHostLFS::<DefaultStdIO, Host>::check_link_target(path, &target)?;
+        }
+
+        self.inner_mut().path_symlink_raw::<Wasm>(
+            old_path_ptr,
+            old_path_len,
+            dir_ino,
+            new_path_ptr,
+            new_path_len,
+        )?;
+
+        if let Some(path) = path {
+            self.push_op(VFSWriteBackOp::Symlink(target, path));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use std::{collections::BTreeMap, sync::Mutex};
+
+    use super::*;
+    use crate::{
+        memory::HostMemory,
+        wasi::file::{
+            Wasip1FileSystem, constant::vfs::Wasip1ConstVFS, heap::lfs::VFSHeapLFS,
+            stdio::DefaultStdIO,
+        },
+        wit::flush::exports::{self, wasip1_vfs::host::virtual_file_system_flush::Guest},
+    };
+
+    /// files of the host, `None` for a directory
+    static FILES: Mutex<BTreeMap<String, Option<Vec<u8>>>> = Mutex::new(BTreeMap::new());
+    /// symbolic links of the host and their targets
+    static LINKS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+    /// opened host paths, the index is the fd minus 1, fd 0 is the root
+    static FDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    /// [`HostFS`] in memory, for the tests.
+    /// Only what writing back calls is implemented.
+    struct MemHost;
+
+    impl HostFS for MemHost {
+        fn path_open(
+            fd: wasip1::Fd,
+            _: wasip1::Lookupflags,
+            path: &str,
+            o_flags: wasip1::Oflags,
+            _: wasip1::Rights,
+            _: wasip1::Rights,
+            _: wasip1::Fdflags,
+        ) -> Result<wasip1::Fd, wasip1::Errno> {
+            assert_eq!(fd, 0);
+            assert_eq!(o_flags, wasip1::OFLAGS_CREAT | wasip1::OFLAGS_TRUNC);
+
+            let mut files = FILES.lock().unwrap();
+            if let Some((parent, _)) = path.rsplit_once('/') {
+                files.get(parent).ok_or(wasip1::ERRNO_NOENT)?;
+            }
+            files.insert(String::from(path), Some(Vec::new()));
+
+            let mut fds = FDS.lock().unwrap();
+            fds.push(String::from(path));
+            Ok(fds.len() as wasip1::Fd)
+        }
+
+        fn fd_close(_: wasip1::Fd) -> Result<(), wasip1::Errno> {
+            Ok(())
+        }
+
+        fn fd_pread(_: wasip1::Fd, _: &mut [u8], _: u64) -> Result<wasip1::Size, wasip1::Errno> {
+            unimplemented!()
+        }
+
+        fn fd_pwrite(
+            fd: wasip1::Fd,
+            data: &[u8],
+            offset: wasip1::Filesize,
+        ) -> Result<wasip1::Size, wasip1::Errno> {
+            let path = FDS.lock().unwrap()[fd as usize - 1].clone();
+            let mut files = FILES.lock().unwrap();
+            let file = files.get_mut(&path).unwrap().as_mut().unwrap();
+            file.truncate(offset as usize);
+            file.extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn fd_readdir(
+            _: wasip1::Fd,
+            _: &mut [u8],
+            _: wasip1::Dircookie,
+        ) -> Result<wasip1::Size, wasip1::Errno> {
+            unimplemented!()
+        }
+
+        fn path_filestat_get(
+            _: wasip1::Fd,
+            _: wasip1::Lookupflags,
+            path: &str,
+        ) -> Result<wasip1::Filestat, wasip1::Errno> {
+            let (filetype, size) = match FILES.lock().unwrap().get(path) {
+                Some(None) => (wasip1::FILETYPE_DIRECTORY, 0),
+                Some(Some(data)) => (wasip1::FILETYPE_REGULAR_FILE, data.len()),
+                None => return Err(wasip1::ERRNO_NOENT),
+            };
+
+            Ok(wasip1::Filestat {
+                dev: 0,
+                ino: 0,
+                filetype,
+                nlink: 1,
+                size: size as _,
+                atim: 0,
+                mtim: 0,
+                ctim: 0,
+            })
+        }
+
+        fn path_filestat_set_times(
+            _: wasip1::Fd,
+            _: wasip1::Lookupflags,
+            _: &str,
+            _: wasip1::Timestamp,
+            _: wasip1::Timestamp,
+            _: wasip1::Fstflags,
+        ) -> Result<(), wasip1::Errno> {
+            unimplemented!()
+        }
+
+        fn path_readlink(
+            _: wasip1::Fd,
+            _: &str,
+            _: &mut [u8],
+        ) -> Result<wasip1::Size, wasip1::Errno> {
+            unimplemented!()
+        }
+
+        fn path_create_directory(_: wasip1::Fd, path: &str) -> Result<(), wasip1::Errno> {
+            let mut files = FILES.lock().unwrap();
+            if files.contains_key(path) {
+                return Err(wasip1::ERRNO_EXIST);
+            }
+            files.insert(String::from(path), None);
+            Ok(())
+        }
+
+        fn path_remove_directory(_: wasip1::Fd, path: &str) -> Result<(), wasip1::Errno> {
+            match FILES.lock().unwrap().remove(path) {
+                Some(None) => Ok(()),
+                _ => Err(wasip1::ERRNO_NOENT),
+            }
+        }
+
+        fn path_unlink_file(_: wasip1::Fd, path: &str) -> Result<(), wasip1::Errno> {
+            match FILES.lock().unwrap().remove(path) {
+                Some(Some(_)) => Ok(()),
+                _ => Err(wasip1::ERRNO_NOENT),
+            }
+        }
+
+        fn path_rename(
+            _: wasip1::Fd,
+            old_path: &str,
+            _: wasip1::Fd,
+            new_path: &str,
+        ) -> Result<(), wasip1::Errno> {
+            let mut files = FILES.lock().unwrap();
+            let file = files.remove(old_path).ok_or(wasip1::ERRNO_NOENT)?;
+            files.insert(String::from(new_path), file);
+            Ok(())
+        }
+
+        fn path_symlink(target: &str, _: wasip1::Fd, path: &str) -> Result<(), wasip1::Errno> {
+            let mut links = LINKS.lock().unwrap();
+            if links.contains_key(path) || FILES.lock().unwrap().contains_key(path) {
+                return Err(wasip1::ERRNO_EXIST);
+            }
+            links.insert(String::from(path), String::from(target));
+            Ok(())
+        }
+    }
+
+    type Vfs = Wasip1ConstVFS<VFSWriteBackLFS<VFSHeapLFS<DefaultStdIO>, MemHost>, 8>;
+
+    fn host_file(path: &str) -> Option<Option<Vec<u8>>> {
+        FILES.lock().unwrap().get(path).cloned()
+    }
+
+    fn open(vfs: &mut Vfs, path: &str, o_flags: wasip1::Oflags) -> wasip1::Fd {
+        let mut fd = 0;
+        assert_eq!(
+            Wasip1FileSystem::path_open_raw::<HostMemory>(
+                vfs,
+                3,
+                0,
+                path.as_ptr(),
+                path.len(),
+                o_flags,
+                wasip1::RIGHTS_FD_WRITE | wasip1::RIGHTS_FD_SYNC | wasip1::RIGHTS_FD_DATASYNC,
+                0,
+                0,
+                &mut fd,
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        fd
+    }
+
+    fn write(vfs: &mut Vfs, fd: wasip1::Fd, data: &[u8]) {
+        let iovs = [wasip1::Ciovec {
+            buf: data.as_ptr(),
+            buf_len: data.len(),
+        }];
+        let mut nwritten = 0;
+        assert_eq!(
+            Wasip1FileSystem::fd_write_raw::<HostMemory>(
+                vfs,
+                fd,
+                iovs.as_ptr(),
+                iovs.len(),
+                &mut nwritten,
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+    }
+
+    #[test]
+    fn test_write_back() {
+        let mut vfs = Vfs::new(VFSWriteBackLFS::new(VFSHeapLFS::new("."), 0));
+
+        // written on fd_sync only
+        let fd = open(&mut vfs, "a.txt", wasip1::OFLAGS_CREAT);
+        write(&mut vfs, fd, b"Hello");
+        assert_eq!(host_file("a.txt"), None);
+        assert_eq!(vfs.lfs().dirty().collect::<Vec<_>>(), ["a.txt"]);
+
+        assert_eq!(
+            Wasip1FileSystem::fd_sync_raw::<HostMemory>(&mut vfs, fd),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(host_file("a.txt"), Some(Some(b"Hello".to_vec())));
+
+        write(&mut vfs, fd, b", host!");
+        assert_eq!(
+            Wasip1FileSystem::fd_datasync_raw::<HostMemory>(&mut vfs, fd),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(host_file("a.txt"), Some(Some(b"Hello, host!".to_vec())));
+        assert_eq!(vfs.lfs().dirty().count(), 0);
+
+        // the directory is created before the file, on fd_close
+        let dir = "dir";
+        assert_eq!(
+            Wasip1FileSystem::path_create_directory_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                dir.as_ptr(),
+                dir.len()
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        let fd_b = open(&mut vfs, "dir/../dir/b.txt", wasip1::OFLAGS_CREAT);
+        write(&mut vfs, fd_b, b"b");
+        assert_eq!(host_file("dir"), None);
+        assert_eq!(
+            Wasip1FileSystem::fd_close_raw::<HostMemory>(&mut vfs, fd_b),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(host_file("dir"), Some(None));
+        assert_eq!(host_file("dir/b.txt"), Some(Some(b"b".to_vec())));
+
+        // removes and renames are replayed by the flush of the host
+        let (old, new, removed) = ("a.txt", "dir/c.txt", "dir/b.txt");
+        assert_eq!(
+            Wasip1FileSystem::path_rename_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                old.as_ptr(),
+                old.len(),
+                3,
+                new.as_ptr(),
+                new.len()
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(
+            Wasip1FileSystem::path_unlink_file_raw::<HostMemory>(
+                &mut vfs,
+                3,
+                removed.as_ptr(),
+                removed.len()
+            ),
+            wasip1::ERRNO_SUCCESS
+        );
+        write(&mut vfs, fd, b"!");
+        assert_eq!(vfs.lfs().pending().len(), 2);
+        assert_eq!(vfs.lfs().dirty().collect::<Vec<_>>(), ["dir/c.txt"]);
+
+        vfs.lfs_mut().flush().unwrap();
+        assert_eq!(host_file("a.txt"), None);
+        assert_eq!(host_file("dir/b.txt"), None);
+        assert_eq!(
+            host_file("dir/c.txt"),
+            Some(Some(b"Hello, host!!".to_vec()))
+        );
+        assert!(vfs.lfs().pending().is_empty());
+
+        // stdio is not a file to sync
+        assert_eq!(
+            Wasip1FileSystem::fd_sync_raw::<HostMemory>(&mut vfs, 1),
+            wasip1::ERRNO_INVAL
+        );
+    }
+
+    fn path_op(
+        vfs: &mut Vfs,
+        op: fn(&mut Vfs, wasip1::Fd, *const u8, usize) -> wasip1::Errno,
+        path: &str,
+    ) -> wasip1::Errno {
+        op(vfs, 3, path.as_ptr(), path.len())
+    }
+
+    fn symlink(vfs: &mut Vfs, target: &str, path: &str) -> wasip1::Errno {
+        Wasip1FileSystem::path_symlink_raw::<HostMemory>(
+            vfs,
+            target.as_ptr(),
+            target.len(),
+            3,
+            path.as_ptr(),
+            path.len(),
+        )
+    }
+
+    #[test]
+    fn test_write_back_links() {
+        let mut vfs = Vfs::new(VFSWriteBackLFS::new(VFSHeapLFS::new("."), 0));
+        let mkdir = Wasip1FileSystem::path_create_directory_raw::<HostMemory>;
+
+        assert_eq!(path_op(&mut vfs, mkdir, "links"), wasip1::ERRNO_SUCCESS);
+        assert_eq!(
+            path_op(&mut vfs, mkdir, "links/real"),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(
+            path_op(&mut vfs, mkdir, "links/real/deep"),
+            wasip1::ERRNO_SUCCESS
+        );
+        assert_eq!(path_op(&mut vfs, mkdir, "links/sub"), wasip1::ERRNO_SUCCESS);
+
+        // targets leaving the mirrored directory are refused
+        assert_eq!(
+            symlink(&mut vfs, "/etc/passwd", "links/abs"),
+            wasip1::ERRNO_PERM
+        );
+        assert_eq!(
+            symlink(&mut vfs, "../../../x", "links/sub/esc"),
+            wasip1::ERRNO_PERM
+        );
+        assert_eq!(
+            symlink(&mut vfs, "../real/deep", "links/sub/link"),
+            wasip1::ERRNO_SUCCESS
+        );
+
+        // `..` after a symbolic link leaves its target, not the link
+        let fd = open(&mut vfs, "links/sub/link/../f.txt", wasip1::OFLAGS_CREAT);
+        write(&mut vfs, fd, b"f");
+        assert_eq!(vfs.lfs().dirty().collect::<Vec<_>>(), ["links/real/f.txt"]);
+
+        // the file is removed before the host ever saw it
+        let unlink = Wasip1FileSystem::path_unlink_file_raw::<HostMemory>;
+        assert_eq!(
+            path_op(&mut vfs, unlink, "links/real/f.txt"),
+            wasip1::ERRNO_SUCCESS
+        );
+
+        vfs.lfs_mut().flush().unwrap();
+        assert_eq!(host_file("links/real/deep"), Some(None));
+        assert_eq!(
+            LINKS
+                .lock()
+                .unwrap()
+                .get("links/sub/link")
+                .map(String::as_str),
+            Some("../real/deep")
+        );
+        assert_eq!(host_file("links/real/f.txt"), None);
+        assert_eq!(
+            vfs.lfs_mut().take_skipped(),
+            [(
+                VFSWriteBackOp::UnlinkFile(String::from("links/real/f.txt")),
+                wasip1::ERRNO_NOENT
+            )]
+        );
+        assert!(vfs.lfs().skipped().is_empty());
+    }
+
+    static mut VIRTUAL_FILE_SYSTEM: Vfs = Vfs::new(VFSWriteBackLFS::new(VFSHeapLFS::new("."), 0));
+
+    struct Main;
+
+    crate::plug_flush!(Main, {
+        #[allow(static_mut_refs)]
+        unsafe {
+            VIRTUAL_FILE_SYSTEM.lfs_mut()
+        }
+    });
+
+    #[test]
+    #[allow(static_mut_refs)]
+    fn test_plug_flush() {
+        let vfs = unsafe { &mut VIRTUAL_FILE_SYSTEM };
+
+        let fd = open(vfs, "flushed.txt", wasip1::OFLAGS_CREAT);
+        write(vfs, fd, b"flushed");
+        assert_eq!(host_file("flushed.txt"), None);
+
+        assert_eq!(Main::flush(), Ok(()));
+        assert_eq!(host_file("flushed.txt"), Some(Some(b"flushed".to_vec())));
+    }
+}
@@ -0,0 +1,2 @@
+pub mod lfs;
+pub mod lfs_impl;
@@ -0,0 +1,166 @@
+// Generated by `wit-bindgen` 0.43.0. DO NOT EDIT!
+// Options used:
+#[allow(dead_code, clippy::all)]
+pub mod exports {
+    pub mod wasip1_vfs {
+        pub mod host {
+            /// * wit/flush.wit
+            ///    wit is only kebab-case */
+            ///  for the host to write back what the guest wrote without waiting for fd_sync,
+            ///  implemented by `plug_flush!`
+            #[allow(dead_code, async_fn_in_trait, unused_imports, clippy::all)]
+            pub mod virtual_file_system_flush {
+                #[used]
+                #[doc(hidden)]
+                static __FORCE_SECTION_REF: fn() =
+                    super::super::super::super::__link_custom_section_describing_imports;
+
+                use super::super::super::super::_rt;
+                #[doc(hidden)]
+                #[allow(non_snake_case, unused_unsafe)]
+                pub unsafe fn _export_flush_cabi<T: Guest>() -> *mut u8 {
+                    unsafe {
+                        #[cfg(target_arch = "wasm32")]
+                        _rt::run_ctors_once();
+                        let result0 = { T::flush() };
+                        let ptr1 = (&raw mut _RET_AREA.0).cast::<u8>();
+                        match result0 {
+                            Ok(_) => {
+                                *ptr1.add(0).cast::<u8>() = (0i32) as u8;
+                            }
+                            Err(e) => {
+                                *ptr1.add(0).cast::<u8>() = (1i32) as u8;
+                                let vec2 = (e.into_bytes()).into_boxed_slice();
+                                let ptr2 = vec2.as_ptr().cast::<u8>();
+                                let len2 = vec2.len();
+                                ::core::mem::forget(vec2);
+                                *ptr1
+                                    .add(2 * ::core::mem::size_of::<*const u8>())
+                                    .cast::<usize>() = len2;
+                                *ptr1
+                                    .add(::core::mem::size_of::<*const u8>())
+                                    .cast::<*mut u8>() = ptr2.cast_mut();
+                            }
+                        };
+                        ptr1
+                    }
+                }
+                #[doc(hidden)]
+                #[allow(non_snake_case)]
+                pub unsafe fn __post_return_flush<T: Guest>(arg0: *mut u8) {
+                    unsafe {
+                        let l0 = i32::from(*arg0.add(0).cast::<u8>());
+                        match l0 {
+                            0 => (),
+                            _ => {
+                                let l1 = *arg0
+                                    .add(::core::mem::size_of::<*const u8>())
+                                    .cast::<*mut u8>();
+                                let l2 = *arg0
+                                    .add(2 * ::core::mem::size_of::<*const u8>())
+                                    .cast::<usize>();
+                                _rt::cabi_dealloc(l1, l2, 1);
+                            }
+                        }
+                    }
+                }
+                pub trait Guest {
+                    /// pushes every dirty file to the host directory, the message of the errno if it fails
+                    #[allow(async_fn_in_trait)]
+                    fn flush() -> Result<(), _rt::String>;
+                }
+                #[doc(hidden)]
+
+                macro_rules! __export_wasip1_vfs_host_virtual_file_system_flush_cabi{
+        ($ty:ident with_types_in $($path_to_types:tt)*) => (const _: () = {
+
+          #[unsafe(export_name = "wasip1-vfs:host/virtual-file-system-flush#flush")]
+          unsafe extern "C" fn export_flush() -> *mut u8 {
+            unsafe { $($path_to_types)*::_export_flush_cabi::<$ty>() }
+          }
+          #[unsafe(export_name = "cabi_post_wasip1-vfs:host/virtual-file-system-flush#flush")]
+          unsafe extern "C" fn _post_return_flush(arg0: *mut u8,) {
+            unsafe { $($path_to_types)*::__post_return_flush::<$ty>(arg0) }
+          }
+        };);
+      }
+                #[doc(hidden)]
+                pub(crate) use __export_wasip1_vfs_host_virtual_file_system_flush_cabi;
+
+                #[cfg_attr(target_pointer_width = "64", repr(align(8)))]
+                #[cfg_attr(target_pointer_width = "32", repr(align(4)))]
+                struct _RetArea(
+                    [::core::mem::MaybeUninit<u8>; 3 * ::core::mem::size_of::<*const u8>()],
+                );
+                static mut _RET_AREA: _RetArea = _RetArea(
+                    [::core::mem::MaybeUninit::uninit(); 3 * ::core::mem::size_of::<*const u8>()],
+                );
+            }
+        }
+    }
+}
+mod _rt {
+    #![allow(dead_code, clippy::all)]
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn run_ctors_once() {
+        wit_bindgen::rt::run_ctors_once();
+    }
+    pub unsafe fn cabi_dealloc(ptr: *mut u8, size: usize, align: usize) {
+        if size == 0 {
+            return;
+        }
+        unsafe {
+            let layout = alloc::Layout::from_size_align_unchecked(size, align);
+            alloc::dealloc(ptr, layout);
+        }
+    }
+    pub use alloc_crate::alloc;
+    pub use alloc_crate::string::String;
+    extern crate alloc as alloc_crate;
+}
+
+/// Generates `#[unsafe(no_mangle)]` functions to export the specified type as
+/// the root implementation of all generated traits.
+///
+/// For more information see the documentation of `wit_bindgen::generate!`.
+///
+/// ```rust
+/// # macro_rules! export{ ($($t:tt)*) => (); }
+/// # trait Guest {}
+/// struct MyType;
+///
+/// impl Guest for MyType {
+///     // ...
+/// }
+///
+/// export!(MyType);
+/// ```
+#[allow(unused_macros)]
+#[doc(hidden)]
+
+macro_rules! __export_flush_impl {
+  ($ty:ident) => (self::export!($ty with_types_in self););
+  ($ty:ident with_types_in $($path_to_types_root:tt)*) => (
+  $($path_to_types_root)*::exports::wasip1_vfs::host::virtual_file_system_flush::__export_wasip1_vfs_host_virtual_file_system_flush_cabi!($ty with_types_in $($path_to_types_root)*::exports::wasip1_vfs::host::virtual_file_system_flush);
+  )
+}
+#[doc(inline)]
+pub(crate) use __export_flush_impl as export;
+
+#[cfg(target_arch = "wasm32")]
+#[unsafe(link_section = "component-type:wit-bindgen:0.43.0:wasip1-vfs:host:flush:encoded world")]
+#[doc(hidden)]
+#[allow(clippy::octal_escapes)]
+pub static __WIT_BINDGEN_COMPONENT_TYPE: [u8; 224] = *b"\
+\0asm\x0d\0\x01\0\0\x19\x16wit-component-encoding\x04\0\x07e\x01A\x02\x01A\x02\x01\
+B\x03\x01j\0\x01s\x01@\0\0\0\x04\0\x05flush\x01\x01\x04\0)wasip1-vfs:host/virtua\
+l-file-system-flush\x05\0\x04\0\x15wasip1-vfs:host/flush\x04\0\x0b\x0b\x01\0\x05\
+flush\x03\0\0\0G\x09producers\x01\x0cprocessed-by\x02\x0dwit-component\x070.235.\
+0\x10wit-bindgen-rust\x060.43.0";
+
+#[inline(never)]
+#[doc(hidden)]
+pub fn __link_custom_section_describing_imports() {
+    wit_bindgen::rt::maybe_link_cabi_realloc();
+}
@@ -8,6 +8,16 @@ pub use virtual_file_system_threads as virtual_file_system;
 #[cfg(not(feature = "threads"))]
 pub mod virtual_file_system;
 
+// `export!` is left to the crates plugging it
+#[allow(dead_code, unused_imports, clippy::all)]
+#[cfg(feature = "alloc")]
+pub mod snapshot;
+
+// `export!` is left to the crates plugging it
+#[allow(dead_code, unused_imports, clippy::all)]
+#[cfg(feature = "alloc")]
+pub mod flush;
+
 // Define a custom type and implement the generated `Guest` trait for it which
 // represents implementing all the necessary exported interfaces for this
 // component.
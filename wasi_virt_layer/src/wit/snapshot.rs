@@ -0,0 +1,217 @@
+// Generated by `wit-bindgen` 0.43.0. DO NOT EDIT!
+// Options used:
+#[allow(dead_code, clippy::all)]
+pub mod exports {
+    pub mod wasip1_vfs {
+        pub mod host {
+            /// * wit/snapshot.wit
+            ///    wit is only kebab-case */
+            ///  for the host to save what the guest wrote and load it into a fresh instance,
+            ///  implemented by `plug_snapshot!`
+            #[allow(dead_code, async_fn_in_trait, unused_imports, clippy::all)]
+            pub mod virtual_file_system_snapshot {
+                #[used]
+                #[doc(hidden)]
+                static __FORCE_SECTION_REF: fn() =
+                    super::super::super::super::__link_custom_section_describing_imports;
+
+                use super::super::super::super::_rt;
+                #[doc(hidden)]
+                #[allow(non_snake_case, unused_unsafe)]
+                pub unsafe fn _export_snapshot_cabi<T: Guest>() -> *mut u8 {
+                    unsafe {
+                        #[cfg(target_arch = "wasm32")]
+                        _rt::run_ctors_once();
+                        let result0 = { T::snapshot() };
+                        let ptr1 = (&raw mut _RET_AREA.0).cast::<u8>();
+                        let vec2 = (result0).into_boxed_slice();
+                        let ptr2 = vec2.as_ptr().cast::<u8>();
+                        let len2 = vec2.len();
+                        ::core::mem::forget(vec2);
+                        *ptr1
+                            .add(::core::mem::size_of::<*const u8>())
+                            .cast::<usize>() = len2;
+                        *ptr1.add(0).cast::<*mut u8>() = ptr2.cast_mut();
+                        ptr1
+                    }
+                }
+                #[doc(hidden)]
+                #[allow(non_snake_case)]
+                pub unsafe fn __post_return_snapshot<T: Guest>(arg0: *mut u8) {
+                    unsafe {
+                        let l0 = *arg0.add(0).cast::<*mut u8>();
+                        let l1 = *arg0
+                            .add(::core::mem::size_of::<*const u8>())
+                            .cast::<usize>();
+                        let base2 = l0;
+                        let len2 = l1;
+                        _rt::cabi_dealloc(base2, len2 * 1, 1);
+                    }
+                }
+                #[doc(hidden)]
+                #[allow(non_snake_case, unused_unsafe)]
+                pub unsafe fn _export_restore_cabi<T: Guest>(
+                    arg0: *mut u8,
+                    arg1: usize,
+                ) -> *mut u8 {
+                    unsafe {
+                        #[cfg(target_arch = "wasm32")]
+                        _rt::run_ctors_once();
+                        let result1 = {
+                            let len0 = arg1;
+                            T::restore(_rt::Vec::from_raw_parts(arg0.cast(), len0, len0))
+                        };
+                        let ptr2 = (&raw mut _RET_AREA.0).cast::<u8>();
+                        match result1 {
+                            Ok(_) => {
+                                *ptr2.add(0).cast::<u8>() = (0i32) as u8;
+                            }
+                            Err(e) => {
+                                *ptr2.add(0).cast::<u8>() = (1i32) as u8;
+                                let vec3 = (e.into_bytes()).into_boxed_slice();
+                                let ptr3 = vec3.as_ptr().cast::<u8>();
+                                let len3 = vec3.len();
+                                ::core::mem::forget(vec3);
+                                *ptr2
+                                    .add(2 * ::core::mem::size_of::<*const u8>())
+                                    .cast::<usize>() = len3;
+                                *ptr2
+                                    .add(::core::mem::size_of::<*const u8>())
+                                    .cast::<*mut u8>() = ptr3.cast_mut();
+                            }
+                        };
+                        ptr2
+                    }
+                }
+                #[doc(hidden)]
+                #[allow(non_snake_case)]
+                pub unsafe fn __post_return_restore<T: Guest>(arg0: *mut u8) {
+                    unsafe {
+                        let l0 = i32::from(*arg0.add(0).cast::<u8>());
+                        match l0 {
+                            0 => (),
+                            _ => {
+                                let l1 = *arg0
+                                    .add(::core::mem::size_of::<*const u8>())
+                                    .cast::<*mut u8>();
+                                let l2 = *arg0
+                                    .add(2 * ::core::mem::size_of::<*const u8>())
+                                    .cast::<usize>();
+                                _rt::cabi_dealloc(l1, l2, 1);
+                            }
+                        }
+                    }
+                }
+                pub trait Guest {
+                    /// the file systems in the versioned snapshot format
+                    #[allow(async_fn_in_trait)]
+                    fn snapshot() -> _rt::Vec<u8>;
+                    /// loads a snapshot of the same file systems, the message of the errno if it fails
+                    #[allow(async_fn_in_trait)]
+                    fn restore(data: _rt::Vec<u8>) -> Result<(), _rt::String>;
+                }
+                #[doc(hidden)]
+
+                macro_rules! __export_wasip1_vfs_host_virtual_file_system_snapshot_cabi{
+      ($ty:ident with_types_in $($path_to_types:tt)*) => (const _: () = {
+
+        #[unsafe(export_name = "wasip1-vfs:host/virtual-file-system-snapshot#snapshot")]
+        unsafe extern "C" fn export_snapshot() -> *mut u8 {
+          unsafe { $($path_to_types)*::_export_snapshot_cabi::<$ty>() }
+        }
+        #[unsafe(export_name = "cabi_post_wasip1-vfs:host/virtual-file-system-snapshot#snapshot")]
+        unsafe extern "C" fn _post_return_snapshot(arg0: *mut u8,) {
+          unsafe { $($path_to_types)*::__post_return_snapshot::<$ty>(arg0) }
+        }
+        #[unsafe(export_name = "wasip1-vfs:host/virtual-file-system-snapshot#restore")]
+        unsafe extern "C" fn export_restore(arg0: *mut u8,arg1: usize,) -> *mut u8 {
+          unsafe { $($path_to_types)*::_export_restore_cabi::<$ty>(arg0, arg1) }
+        }
+        #[unsafe(export_name = "cabi_post_wasip1-vfs:host/virtual-file-system-snapshot#restore")]
+        unsafe extern "C" fn _post_return_restore(arg0: *mut u8,) {
+          unsafe { $($path_to_types)*::__post_return_restore::<$ty>(arg0) }
+        }
+      };);
+    }
+                #[doc(hidden)]
+                pub(crate) use __export_wasip1_vfs_host_virtual_file_system_snapshot_cabi;
+
+                #[cfg_attr(target_pointer_width = "64", repr(align(8)))]
+                #[cfg_attr(target_pointer_width = "32", repr(align(4)))]
+                struct _RetArea(
+                    [::core::mem::MaybeUninit<u8>; 3 * ::core::mem::size_of::<*const u8>()],
+                );
+                static mut _RET_AREA: _RetArea = _RetArea(
+                    [::core::mem::MaybeUninit::uninit(); 3 * ::core::mem::size_of::<*const u8>()],
+                );
+            }
+        }
+    }
+}
+mod _rt {
+    #![allow(dead_code, clippy::all)]
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn run_ctors_once() {
+        wit_bindgen::rt::run_ctors_once();
+    }
+    pub unsafe fn cabi_dealloc(ptr: *mut u8, size: usize, align: usize) {
+        if size == 0 {
+            return;
+        }
+        unsafe {
+            let layout = alloc::Layout::from_size_align_unchecked(size, align);
+            alloc::dealloc(ptr, layout);
+        }
+    }
+    pub use alloc_crate::alloc;
+    pub use alloc_crate::string::String;
+    pub use alloc_crate::vec::Vec;
+    extern crate alloc as alloc_crate;
+}
+
+/// Generates `#[unsafe(no_mangle)]` functions to export the specified type as
+/// the root implementation of all generated traits.
+///
+/// For more information see the documentation of `wit_bindgen::generate!`.
+///
+/// ```rust
+/// # macro_rules! export{ ($($t:tt)*) => (); }
+/// # trait Guest {}
+/// struct MyType;
+///
+/// impl Guest for MyType {
+///     // ...
+/// }
+///
+/// export!(MyType);
+/// ```
+#[allow(unused_macros)]
+#[doc(hidden)]
+
+macro_rules! __export_snapshot_impl {
+  ($ty:ident) => (self::export!($ty with_types_in self););
+  ($ty:ident with_types_in $($path_to_types_root:tt)*) => (
+  $($path_to_types_root)*::exports::wasip1_vfs::host::virtual_file_system_snapshot::__export_wasip1_vfs_host_virtual_file_system_snapshot_cabi!($ty with_types_in $($path_to_types_root)*::exports::wasip1_vfs::host::virtual_file_system_snapshot);
+  )
+}
+#[doc(inline)]
+pub(crate) use __export_snapshot_impl as export;
+
+#[cfg(target_arch = "wasm32")]
+#[unsafe(link_section = "component-type:wit-bindgen:0.43.0:wasip1-vfs:host:snapshot:encoded world")]
+#[doc(hidden)]
+#[allow(clippy::octal_escapes)]
+pub static __WIT_BINDGEN_COMPONENT_TYPE: [u8; 263] = *b"\
+\0asm\x0d\0\x01\0\0\x19\x16wit-component-encoding\x04\0\x07\x88\x01\x01A\x02\x01\
+A\x02\x01B\x06\x01p}\x01@\0\0\0\x04\0\x08snapshot\x01\x01\x01j\0\x01s\x01@\x01\x04\
+data\0\0\x02\x04\0\x07restore\x01\x03\x04\0,wasip1-vfs:host/virtual-file-system-\
+snapshot\x05\0\x04\0\x18wasip1-vfs:host/snapshot\x04\0\x0b\x0e\x01\0\x08snapshot\
+\x03\0\0\0G\x09producers\x01\x0cprocessed-by\x02\x0dwit-component\x070.235.0\x10\
+wit-bindgen-rust\x060.43.0";
+
+#[inline(never)]
+#[doc(hidden)]
+pub fn __link_custom_section_describing_imports() {
+    wit_bindgen::rt::maybe_link_cabi_realloc();
+}